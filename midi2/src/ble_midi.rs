@@ -0,0 +1,540 @@
+//! The packet format of the MIDI over Bluetooth Low Energy specification.
+//!
+//! Each BLE-MIDI packet starts with a header byte carrying the high six bits
+//! of a 13 bit millisecond timestamp. Every message within the packet is
+//! preceded by a timestamp byte carrying the low seven bits.
+//! Running status may be used between the messages of a packet
+//! and system exclusive messages continue across packets.
+//!
+//! - [BleMidiEncoder] packs timestamped messages into packets.
+//! - [BleMidiDecoder] recovers timestamped messages from packets.
+//!
+//! Only the data format is handled here; the Bluetooth stack is left to the caller.
+//!
+//! ```rust
+//! use midi2::{prelude::*, ble_midi::{BleMidiDecoder, BleMidiEncoder}};
+//!
+//! let mut encoder = BleMidiEncoder::<20>::new();
+//! let mut packets = Vec::new();
+//! let note_on = BytesMessage::try_from(&[0x90, 0x3C, 0x40][..]).unwrap();
+//! let note_off = BytesMessage::try_from(&[0x90, 0x3C, 0x00][..]).unwrap();
+//! encoder.push(0x100, &note_on, |p| packets.push(p.to_vec()));
+//! encoder.push(0x105, &note_off, |p| packets.push(p.to_vec()));
+//! encoder.flush(|p| packets.push(p.to_vec()));
+//!
+//! assert_eq!(packets, [[0x82, 0x80, 0x90, 0x3C, 0x40, 0x85, 0x3C, 0x00]]);
+//!
+//! let mut decoder = BleMidiDecoder::<16>::new();
+//! let mut packet = &packets[0][..];
+//! let (time, message) = decoder.decode(&mut packet).unwrap().unwrap();
+//! assert_eq!(time, 0x100);
+//! assert_eq!(message.data(), &[0x90, 0x3C, 0x40]);
+//! let (time, message) = decoder.decode(&mut packet).unwrap().unwrap();
+//! assert_eq!(time, 0x105);
+//! assert_eq!(message.data(), &[0x90, 0x3C, 0x00]);
+//! assert!(decoder.decode(&mut packet).unwrap().is_none());
+//! ```
+
+use crate::{
+    error::{Error, InvalidData},
+    io::BytesDecoder,
+    message::BytesMessage,
+    traits::Data,
+};
+
+const ERR_INVALID_HEADER: &str = "BLE-MIDI packets should begin with a header byte";
+
+// header, timestamp and a three byte message
+const MIN_PACKET_SIZE: usize = 5;
+const TIMESTAMP_MASK: u16 = 0x1FFF;
+
+/// A decoded message with its timestamp in milliseconds.
+pub type TimestampedMessage<'a> = (u32, BytesMessage<&'a [u8]>);
+
+/// Packs timestamped MIDI 1.0 messages into BLE-MIDI packets without allocating.
+///
+/// Messages are collected into a packet of at most `SIZE` bytes,
+/// which must be at least five.
+/// The packet is handed to the `send` callback once the next message
+/// does not fit, or when its timestamp cannot be expressed relative
+/// to the messages already in the packet.
+/// System exclusive messages longer than a packet are split over several.
+/// Call [flush](Self::flush) to send a partially filled packet,
+/// typically once per connection interval.
+///
+/// A smaller `SIZE` is rejected at compile time.
+///
+/// ```rust,compile_fail
+/// let encoder = midi2::ble_midi::BleMidiEncoder::<4>::new();
+/// ```
+#[derive(Clone, Debug)]
+pub struct BleMidiEncoder<const SIZE: usize = 20> {
+    packet: [u8; SIZE],
+    len: usize,
+    packet_size: usize,
+    running_status: Option<u8>,
+    time: u16,
+}
+
+impl<const SIZE: usize> Default for BleMidiEncoder<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> BleMidiEncoder<SIZE> {
+    pub fn new() -> Self {
+        const {
+            assert!(
+                SIZE >= MIN_PACKET_SIZE,
+                "BLE-MIDI packets need at least five bytes"
+            )
+        };
+        BleMidiEncoder {
+            packet: [0x0; SIZE],
+            len: 0,
+            packet_size: SIZE,
+            running_status: None,
+            time: 0,
+        }
+    }
+
+    /// Limit the packets to a size below `SIZE` bytes.
+    ///
+    /// The size of a packet is the negotiated ATT MTU less three bytes.
+    /// Takes effect from the next packet.
+    pub fn set_packet_size(&mut self, size: usize) {
+        self.packet_size = size.clamp(MIN_PACKET_SIZE, SIZE);
+    }
+
+    /// Add a message to the packet.
+    ///
+    /// The timestamp is in milliseconds and only its low 13 bits are sent.
+    pub fn push<B: crate::buffer::Bytes, M: Data<B>, F: FnMut(&[u8])>(
+        &mut self,
+        timestamp: u32,
+        message: &M,
+        mut send: F,
+    ) {
+        let time = timestamp as u16 & TIMESTAMP_MASK;
+        let data = message.data();
+        let Some(&status) = data.first() else {
+            return;
+        };
+        if self.len != 0 && (time.wrapping_sub(self.time) & TIMESTAMP_MASK) >= 0x80 {
+            self.flush(&mut send);
+        }
+
+        if status == 0xF0 {
+            let (body, end) = match data.split_last() {
+                Some((&0xF7, body)) => (body, true),
+                _ => (data, false),
+            };
+            self.reserve(time, 2, &mut send);
+            self.push_timestamp(time);
+            for &byte in body {
+                self.reserve(time, 1, &mut send);
+                self.push_byte(byte);
+            }
+            if end {
+                self.reserve(time, 2, &mut send);
+                self.push_timestamp(time);
+                self.push_byte(0xF7);
+            }
+            self.running_status = None;
+            return;
+        }
+
+        let running = status < 0xF0 && self.running_status == Some(status);
+        let size = 1 + data.len() - usize::from(running);
+        if self.len + size > self.packet_size {
+            self.flush(&mut send);
+        }
+        // the status is always sent at the start of a packet
+        let running = running && self.len != 0;
+        self.reserve(time, 1 + data.len() - usize::from(running), &mut send);
+        self.push_timestamp(time);
+        for &byte in &data[usize::from(running)..] {
+            self.push_byte(byte);
+        }
+        self.running_status = match status {
+            0x80..=0xEF => Some(status),
+            0xF0..=0xF7 => None,
+            _ => self.running_status,
+        };
+    }
+
+    /// Send the partially filled packet, if there is one.
+    pub fn flush<F: FnMut(&[u8])>(&mut self, mut send: F) {
+        if self.len != 0 {
+            send(&self.packet[..self.len]);
+        }
+        self.len = 0;
+        self.running_status = None;
+    }
+
+    /// Make room for `size` bytes, starting a new packet when needed.
+    fn reserve<F: FnMut(&[u8])>(&mut self, time: u16, size: usize, send: &mut F) {
+        if self.len != 0 && self.len + size > self.packet_size {
+            self.flush(&mut *send);
+        }
+        if self.len == 0 {
+            self.push_byte(0x80 | (time >> 7) as u8);
+            self.time = time;
+        }
+    }
+
+    fn push_timestamp(&mut self, time: u16) {
+        self.push_byte(0x80 | (time & 0x7F) as u8);
+        self.time = time;
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.packet[self.len] = byte;
+        self.len += 1;
+    }
+}
+
+/// Recovers timestamped MIDI 1.0 messages from BLE-MIDI packets without allocating.
+///
+/// Pass each packet to [decode](Self::decode) until it returns `Ok(None)`,
+/// at which point the packet has been used up.
+/// System exclusive messages are collected into an internal buffer of `SIZE` bytes
+/// and may continue across packets.
+///
+/// The 13 bit timestamps of the packets roll over every 8192 milliseconds.
+/// The decoder unwraps them into a 32 bit millisecond count
+/// which starts from the first timestamp received.
+/// Timestamps up to half the 13 bit range earlier than the previous one
+/// are taken to be slightly out of order rather than to have rolled over.
+#[derive(Clone, Debug)]
+pub struct BleMidiDecoder<const SIZE: usize = 64> {
+    bytes: BytesDecoder<SIZE>,
+    in_packet: bool,
+    after_timestamp: bool,
+    high: u16,
+    low: Option<u16>,
+    // the most recent 13 bit timestamp and its unwrapped value
+    previous: Option<(u16, u32)>,
+}
+
+impl<const SIZE: usize> Default for BleMidiDecoder<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> BleMidiDecoder<SIZE> {
+    pub fn new() -> Self {
+        BleMidiDecoder {
+            bytes: BytesDecoder::new(),
+            in_packet: false,
+            after_timestamp: false,
+            high: 0,
+            low: None,
+            previous: None,
+        }
+    }
+
+    /// Consume bytes from the front of the packet until a message is complete.
+    ///
+    /// Returns `Ok(None)` once the packet is used up.
+    /// The next call then expects the header of a new packet.
+    ///
+    /// Errors are reported as soon as they're found and the offending data is dropped,
+    /// so decoding can carry on with the next call.
+    /// A packet without a valid header is dropped entirely.
+    pub fn decode(&mut self, packet: &mut &[u8]) -> Result<Option<TimestampedMessage<'_>>, Error> {
+        if !self.in_packet {
+            let Some((&header, rest)) = packet.split_first() else {
+                return Ok(None);
+            };
+            if header & 0xC0 != 0x80 {
+                *packet = &[];
+                return Err(InvalidData(ERR_INVALID_HEADER).into());
+            }
+            *packet = rest;
+            self.in_packet = true;
+            self.after_timestamp = false;
+            self.high = u16::from(header & 0x3F);
+            self.low = None;
+        }
+
+        while let Some((&byte, rest)) = packet.split_first() {
+            *packet = rest;
+            if byte & 0x80 != 0 && !self.after_timestamp {
+                let low = u16::from(byte & 0x7F);
+                if self.low.is_some_and(|previous| low < previous) {
+                    self.high = (self.high + 1) & 0x3F;
+                }
+                self.low = Some(low);
+                self.after_timestamp = true;
+                continue;
+            }
+            self.after_timestamp = false;
+            if self.bytes.frame(&mut core::slice::from_ref(&byte))? {
+                let time = self.unwrap_time();
+                return Ok(Some((time, self.bytes.framed()?)));
+            }
+        }
+
+        self.in_packet = false;
+        Ok(None)
+    }
+
+    /// Drop any partially received data and forget the previous timestamp.
+    pub fn reset(&mut self) {
+        self.bytes.reset();
+        self.in_packet = false;
+        self.previous = None;
+    }
+
+    fn unwrap_time(&mut self) -> u32 {
+        let time = self.high << 7 | self.low.unwrap_or(0);
+        let unwrapped = match self.previous {
+            None => u32::from(time),
+            Some((previous, unwrapped)) => {
+                let mut delta = i32::from(time.wrapping_sub(previous) & TIMESTAMP_MASK);
+                if delta > i32::from(TIMESTAMP_MASK / 2) {
+                    delta -= i32::from(TIMESTAMP_MASK) + 1;
+                }
+                unwrapped.wrapping_add_signed(delta)
+            }
+        };
+        self.previous = Some((time, unwrapped));
+        unwrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::vec::Vec;
+
+    fn encode<const SIZE: usize>(
+        encoder: &mut BleMidiEncoder<SIZE>,
+        messages: &[(u32, &[u8])],
+    ) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        for (time, data) in messages {
+            let message = BytesMessage::try_from(*data).unwrap();
+            encoder.push(*time, &message, |p| packets.push(p.to_vec()));
+        }
+        encoder.flush(|p| packets.push(p.to_vec()));
+        packets
+    }
+
+    fn decode_all<const SIZE: usize>(
+        decoder: &mut BleMidiDecoder<SIZE>,
+        packets: &[Vec<u8>],
+    ) -> Vec<Result<(u32, Vec<u8>), Error>> {
+        let mut messages = Vec::new();
+        for packet in packets {
+            let mut packet = &packet[..];
+            loop {
+                match decoder.decode(&mut packet) {
+                    Ok(Some((time, message))) => messages.push(Ok((time, message.data().to_vec()))),
+                    Ok(None) => break,
+                    Err(e) => messages.push(Err(e)),
+                }
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn encode_running_status() {
+        let mut encoder = BleMidiEncoder::<20>::new();
+        assert_eq!(
+            encode(
+                &mut encoder,
+                &[
+                    (0x3FF, &[0x90, 0x3C, 0x40]),
+                    (0x400, &[0x90, 0x3E, 0x40]),
+                    (0x400, &[0xF8]),
+                    (0x401, &[0x90, 0x40, 0x40]),
+                    (0x401, &[0xC0, 0x01]),
+                ]
+            ),
+            [std::vec![
+                0x87, 0xFF, 0x90, 0x3C, 0x40, 0x80, 0x3E, 0x40, 0x80, 0xF8, 0x81, 0x40, 0x40, 0x81,
+                0xC0, 0x01,
+            ]]
+        );
+    }
+
+    #[test]
+    fn encode_new_packet_when_full() {
+        let mut encoder = BleMidiEncoder::<8>::new();
+        assert_eq!(
+            encode(
+                &mut encoder,
+                &[
+                    (0x0, &[0x90, 0x3C, 0x40]),
+                    (0x1, &[0x80, 0x3C, 0x40]),
+                    (0x2, &[0x80, 0x3E, 0x40]),
+                ]
+            ),
+            [
+                std::vec![0x80, 0x80, 0x90, 0x3C, 0x40],
+                std::vec![0x80, 0x81, 0x80, 0x3C, 0x40, 0x82, 0x3E, 0x40],
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_new_packet_when_time_jumps() {
+        let mut encoder = BleMidiEncoder::<20>::new();
+        assert_eq!(
+            encode(
+                &mut encoder,
+                &[
+                    (0x10, &[0xC0, 0x01]),
+                    (0x90, &[0xC0, 0x02]),
+                    (0x8, &[0xC0, 0x03])
+                ]
+            ),
+            [
+                std::vec![0x80, 0x90, 0xC0, 0x01],
+                std::vec![0x81, 0x90, 0xC0, 0x02],
+                std::vec![0x80, 0x88, 0xC0, 0x03],
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_sysex_across_packets() {
+        let mut encoder = BleMidiEncoder::<6>::new();
+        assert_eq!(
+            encode(
+                &mut encoder,
+                &[(0x5, &[0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0xF7])]
+            ),
+            [
+                std::vec![0x80, 0x85, 0xF0, 0x01, 0x02, 0x03],
+                std::vec![0x80, 0x04, 0x05, 0x85, 0xF7],
+            ]
+        );
+    }
+
+    #[test]
+    fn set_packet_size() {
+        let mut encoder = BleMidiEncoder::<20>::new();
+        encoder.set_packet_size(6);
+        assert_eq!(
+            encode(&mut encoder, &[(0x0, &[0xC0, 0x01]), (0x0, &[0xC0, 0x02])]),
+            [std::vec![0x80, 0x80, 0xC0, 0x01, 0x80, 0x02],]
+        );
+        encoder.set_packet_size(0);
+        assert_eq!(encoder.packet_size, MIN_PACKET_SIZE);
+    }
+
+    #[test]
+    fn decode_timestamp_rollover_within_packet() {
+        let mut decoder = BleMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[std::vec![0x81, 0xFE, 0x90, 0x3C, 0x40, 0x82, 0x3E, 0x40]]
+            ),
+            [
+                Ok((0xFE, std::vec![0x90, 0x3C, 0x40])),
+                Ok((0x102, std::vec![0x90, 0x3E, 0x40])),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_running_status_without_timestamp() {
+        let mut decoder = BleMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[std::vec![0x80, 0x81, 0xB0, 0x07, 0x64, 0x0A, 0x40]]
+            ),
+            [
+                Ok((0x1, std::vec![0xB0, 0x07, 0x64])),
+                Ok((0x1, std::vec![0xB0, 0x0A, 0x40])),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_sysex_across_packets_with_real_time() {
+        let mut decoder = BleMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    std::vec![0x80, 0x81, 0xF0, 0x01, 0x02],
+                    std::vec![0x80, 0x03, 0x82, 0xF8, 0x04, 0x83, 0xF7],
+                ]
+            ),
+            [
+                Ok((0x2, std::vec![0xF8])),
+                Ok((0x3, std::vec![0xF0, 0x01, 0x02, 0x03, 0x04, 0xF7])),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_unwraps_13_bit_rollover() {
+        let mut decoder = BleMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    std::vec![0xBF, 0xFF, 0xF8],
+                    std::vec![0x80, 0x81, 0xF8],
+                    std::vec![0xBF, 0xFE, 0xF8],
+                ]
+            ),
+            [
+                Ok((0x1FFF, std::vec![0xF8])),
+                Ok((0x2001, std::vec![0xF8])),
+                Ok((0x1FFE, std::vec![0xF8])),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut decoder = BleMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[std::vec![0x3C, 0x40], std::vec![0x80, 0x80, 0xF8]]
+            ),
+            [
+                Err(InvalidData(ERR_INVALID_HEADER).into()),
+                Ok((0x0, std::vec![0xF8])),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let messages: [(u32, &[u8]); 6] = [
+            (0x1FF0, &[0x90, 0x3C, 0x40]),
+            (0x1FFA, &[0x90, 0x3E, 0x40]),
+            (
+                0x2005,
+                &[0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0xF7],
+            ),
+            (0x2010, &[0xF2, 0x01, 0x02]),
+            (0x2200, &[0xE0, 0x00, 0x40]),
+            (0x2201, &[0xFA]),
+        ];
+        let mut encoder = BleMidiEncoder::<8>::new();
+        let packets = encode(&mut encoder, &messages);
+        let mut decoder = BleMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &packets),
+            messages
+                .iter()
+                .map(|(time, data)| Ok((*time, data.to_vec())))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -79,7 +79,8 @@
 //! let mut message = sysex8::Sysex8::<[u32; 64]>::new();
 //! assert_eq!(message.try_set_payload(0..20), Ok(()));
 //! ```
-//! `Vec<U>` implements [BufferMut] and [BufferResize].
+//! `Vec<U>` implements [BufferMut] and [BufferResize],
+//! as well as [BufferTryResize] which never fails.
 //! Messages backed with with such buffers have the most powerful api.
 //!
 //! ```rust
@@ -332,6 +333,14 @@ impl<U: Unit> BufferResize for std::vec::Vec<U> {
     }
 }
 
+#[cfg(any(feature = "std", test))]
+impl<U: Unit> BufferTryResize for std::vec::Vec<U> {
+    fn try_resize(&mut self, size: usize) -> Result<(), BufferOverflow> {
+        self.resize(size, U::zero());
+        Ok(())
+    }
+}
+
 #[cfg(any(feature = "std", test))]
 impl<U: Unit> BufferDefault for std::vec::Vec<U> {
     fn default() -> Self {
@@ -426,6 +435,13 @@ impl<U: Unit> FromBuffer<&mut [U]> for std::vec::Vec<U> {
     }
 }
 
+#[cfg(any(feature = "std", test))]
+impl<U: Unit> TryFromBuffer<&[U]> for std::vec::Vec<U> {
+    fn try_from_buffer(value: &[U]) -> Result<Self, BufferOverflow> {
+        Ok(value.to_vec())
+    }
+}
+
 //
 // private
 //
@@ -35,6 +35,9 @@ pub(crate) const UMP_MESSAGE_TYPE: u8 = 0x2;
     midi2_proc::RebufferFrom,
     midi2_proc::RebufferFromArray,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -18,6 +18,7 @@ mod registered_controller;
 mod registered_per_note_controller;
 mod relative_assignable_controller;
 mod relative_registered_controller;
+mod unknown;
 
 pub use assignable_controller::*;
 pub use assignable_per_note_controller::*;
@@ -37,6 +38,7 @@ pub use registered_controller::*;
 pub use registered_per_note_controller::*;
 pub use relative_assignable_controller::*;
 pub use relative_registered_controller::*;
+pub use unknown::*;
 
 pub(crate) const UMP_MESSAGE_TYPE: u8 = 0x4;
 
@@ -49,6 +51,9 @@ pub(crate) const UMP_MESSAGE_TYPE: u8 = 0x4;
     midi2_proc::RebufferFrom,
     midi2_proc::RebufferFromArray,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -71,6 +76,7 @@ pub enum ChannelVoice2<B: crate::buffer::Ump> {
     RegisteredPerNoteController(registered_per_note_controller::RegisteredPerNoteController<B>),
     RelativeAssignableController(relative_assignable_controller::RelativeAssignableController<B>),
     RelativeRegisteredController(relative_registered_controller::RelativeRegisteredController<B>),
+    Unknown(unknown::UnknownChannelVoice2<B>),
 }
 
 impl<'a> TryFrom<&'a [u32]> for ChannelVoice2<&'a [u32]> {
@@ -129,6 +135,31 @@ impl<'a> TryFrom<&'a [u32]> for ChannelVoice2<&'a [u32]> {
     }
 }
 
+impl<'a> ChannelVoice2<&'a [u32]> {
+    /// Parses the message at the front of the buffer without rejecting
+    /// messages which don't strictly follow the specification.
+    ///
+    /// Reserved bits which have been set are reported through the returned warnings.
+    /// Messages with unassigned statuses or invalid fields come back unmodified
+    /// as [ChannelVoice2::Unknown].
+    ///
+    /// ```rust
+    /// use midi2::{prelude::*, channel_voice2::ChannelVoice2};
+    ///
+    /// let (message, mut warnings) = ChannelVoice2::try_from_lenient(&[0x4070_1234, 0x0]).unwrap();
+    /// assert!(matches!(message, ChannelVoice2::Unknown(_)));
+    /// assert_eq!(warnings.next(), None);
+    /// ```
+    pub fn try_from_lenient(
+        buffer: &'a [u32],
+    ) -> Result<(Self, crate::error::Warnings<'a>), crate::error::Diagnostic> {
+        crate::detail::diagnose::lenient(
+            buffer,
+            Some(|packet| Ok(unknown::UnknownChannelVoice2::try_from(packet)?.into())),
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
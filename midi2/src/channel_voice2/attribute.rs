@@ -1,6 +1,8 @@
 use crate::detail::{property, BitOps};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum Attribute {
     ManufacturerSpecific(u16),
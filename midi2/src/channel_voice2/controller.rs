@@ -4,6 +4,8 @@ use crate::{
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum Controller {
     Modulation(u32),
@@ -29,6 +31,8 @@ pub enum Controller {
     EffectDepth { index: u8, data: u32 },
 }
 
+const ERR_INVALID_CONTROLLER_INDEX: &str = "Couldn't interpret controller index";
+
 pub fn validate_index(index: u8) -> Result<(), crate::error::InvalidData> {
     match index {
         1 => Ok(()),
@@ -53,7 +57,7 @@ pub fn validate_index(index: u8) -> Result<(), crate::error::InvalidData> {
         93 => Ok(()),
         94 => Ok(()),
         95 => Ok(()),
-        _ => Err(InvalidData("Couldn't interpret controller index")),
+        _ => Err(InvalidData(ERR_INVALID_CONTROLLER_INDEX)),
     }
 }
 
@@ -144,8 +148,16 @@ impl<'a, B: crate::buffer::Ump> property::ReadProperty<'a, B> for ControllerProp
 impl<B: crate::buffer::Ump + crate::buffer::BufferMut> property::WriteProperty<B>
     for ControllerProperty
 {
-    fn validate(_v: &Self::Type) -> Result<(), crate::error::InvalidData> {
-        Ok(())
+    fn validate(v: &Self::Type) -> Result<(), crate::error::InvalidData> {
+        match *v {
+            Controller::SoundController { index, .. } if !(1..=10).contains(&index) => {
+                Err(InvalidData(ERR_INVALID_CONTROLLER_INDEX))
+            }
+            Controller::EffectDepth { index, .. } if !(1..=4).contains(&index) => {
+                Err(InvalidData(ERR_INVALID_CONTROLLER_INDEX))
+            }
+            _ => Ok(()),
+        }
     }
     fn default() -> Self::Type {
         Default::default()
@@ -0,0 +1,50 @@
+use crate::{
+    channel_voice2::UMP_MESSAGE_TYPE,
+    detail::{common_properties, schema},
+    ux::u4,
+};
+
+/// MIDI 2.0 Channel Voice Message with an unassigned status
+///
+/// Only produced by the lenient parsing path, which uses it
+/// to carry messages it can't interpret without modifying them.
+/// The status, index and data bits are kept as they were found in `raw_data`.
+///
+/// See the [module docs](crate::channel_voice2) for more info.
+#[midi2_proc::generate_message(Via(crate::channel_voice2::ChannelVoice2), FixedSize, MinSizeUmp(2))]
+struct UnknownChannelVoice2 {
+    #[property(common_properties::UmpMessageTypeProperty<UMP_MESSAGE_TYPE>)]
+    ump_type: (),
+    #[property(common_properties::UmpSchemaProperty<u4, schema::Ump<0x000F_0000, 0x0, 0x0, 0x0>>)]
+    channel: u4,
+    #[property(common_properties::GroupProperty)]
+    group: u4,
+    #[property(common_properties::UnknownDataProperty<2, 0x00F0_FFFF>)]
+    raw_data: [u32; 2],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn from_data() {
+        let message = UnknownChannelVoice2::try_from(&[0x4B78_1234, 0x5678_9ABC][..]).unwrap();
+        assert_eq!(message.raw_data(), [0x0070_1234, 0x5678_9ABC]);
+    }
+
+    #[test]
+    fn setters() {
+        use crate::traits::{Channeled, Grouped};
+
+        let mut message = UnknownChannelVoice2::<[u32; 4]>::new();
+        message.set_group(u4::new(0xB));
+        message.set_channel(u4::new(0x8));
+        message.set_raw_data([0x0070_1234, 0x5678_9ABC]);
+        assert_eq!(
+            message,
+            UnknownChannelVoice2([0x4B78_1234, 0x5678_9ABC, 0x0, 0x0])
+        );
+    }
+}
@@ -0,0 +1,276 @@
+#![doc = include_str!("clip/README.md")]
+
+use crate::{
+    error::InvalidData,
+    message::{UmpMessage, UmpMessageIterator},
+    traits::{Data, RebufferInto},
+    ump_stream::{EndOfClip, StartOfClip, UmpStream},
+    utility::{delta_clockstamps, DeltaClockstampTpq, Utility},
+};
+
+#[cfg(feature = "smf")]
+mod smf;
+
+#[cfg(feature = "smf")]
+pub use smf::{Assignment, Conversion, Protocol};
+
+const FILE_ID: &[u8; 8] = b"SMF2CLIP";
+
+const ERR_NO_FILE_ID: &str = "Midi clip files should begin with SMF2CLIP";
+const ERR_TRUNCATED_WORD: &str = "Midi clip file data should be a whole number of words";
+const ERR_NO_TICKS_PER_QUARTER_NOTE: &str =
+    "Midi clip files should declare ticks per quarter note before the Start of Clip";
+const ERR_NO_START_OF_CLIP: &str = "Midi clip file has no Start of Clip message";
+const ERR_NO_END_OF_CLIP: &str = "Midi clip file has no End of Clip message";
+
+/// A MIDI Clip File.
+///
+/// See the [module docs](crate::clip) for more info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clip {
+    pub ticks_per_quarter_note: u16,
+    /// Messages of the clip header, between the ticks per quarter note
+    /// declaration and the Start of Clip message.
+    pub header: std::vec::Vec<UmpMessage<std::vec::Vec<u32>>>,
+    pub events: std::vec::Vec<ClipEvent>,
+    /// Ticks between the last event and the End of Clip message.
+    pub end_delta: u32,
+}
+
+/// A message in the clip sequence along with its delta time in ticks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClipEvent {
+    pub delta: u32,
+    pub message: UmpMessage<std::vec::Vec<u32>>,
+}
+
+impl ClipEvent {
+    pub fn new(delta: u32, message: UmpMessage<std::vec::Vec<u32>>) -> Self {
+        ClipEvent { delta, message }
+    }
+}
+
+impl Clip {
+    pub fn new(ticks_per_quarter_note: u16) -> Self {
+        Clip {
+            ticks_per_quarter_note,
+            header: std::vec::Vec::new(),
+            events: std::vec::Vec::new(),
+            end_delta: 0,
+        }
+    }
+
+    pub fn to_bytes(&self) -> std::vec::Vec<u8> {
+        let mut data = std::vec::Vec::from(&FILE_ID[..]);
+        for word in self.to_words() {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        data
+    }
+
+    fn to_words(&self) -> std::vec::Vec<u32> {
+        let mut words = std::vec::Vec::new();
+
+        let mut tpq = DeltaClockstampTpq::<[u32; 4]>::new();
+        tpq.set_time_data(self.ticks_per_quarter_note);
+        write_message(&mut words, tpq.data());
+
+        for message in self.header.iter() {
+            write_delta(&mut words, 0);
+            write_message(&mut words, message.data());
+        }
+
+        write_delta(&mut words, 0);
+        write_message(&mut words, StartOfClip::<[u32; 4]>::new().data());
+
+        for event in self.events.iter() {
+            write_delta(&mut words, event.delta);
+            write_message(&mut words, event.message.data());
+        }
+
+        write_delta(&mut words, self.end_delta);
+        write_message(&mut words, EndOfClip::<[u32; 4]>::new().data());
+
+        words
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for Clip {
+    type Error = InvalidData;
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let Some(words) = data.strip_prefix(&FILE_ID[..]) else {
+            return Err(InvalidData(ERR_NO_FILE_ID));
+        };
+        if words.len() % 4 != 0 {
+            return Err(InvalidData(ERR_TRUNCATED_WORD));
+        }
+        let words: std::vec::Vec<u32> = words
+            .chunks_exact(4)
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+
+        let mut ticks_per_quarter_note = None;
+        let mut header = std::vec::Vec::new();
+        let mut events = std::vec::Vec::new();
+        let mut started = false;
+        let mut delta = 0_u32;
+
+        for message in UmpMessageIterator::new(&words) {
+            match message? {
+                UmpMessage::Utility(Utility::DeltaClockstampTpq(m)) => {
+                    ticks_per_quarter_note = Some(m.time_data());
+                }
+                UmpMessage::Utility(Utility::DeltaClockstamp(m)) => {
+                    delta = delta.saturating_add(u32::from(m.time_data()));
+                }
+                UmpMessage::Utility(Utility::NoOp(_)) => {}
+                UmpMessage::UmpStream(UmpStream::StartOfClip(_)) => {
+                    if ticks_per_quarter_note.is_none() {
+                        return Err(InvalidData(ERR_NO_TICKS_PER_QUARTER_NOTE));
+                    }
+                    started = true;
+                    delta = 0;
+                }
+                UmpMessage::UmpStream(UmpStream::EndOfClip(_)) if started => {
+                    return Ok(Clip {
+                        ticks_per_quarter_note: ticks_per_quarter_note.unwrap(),
+                        header,
+                        events,
+                        end_delta: delta,
+                    });
+                }
+                message if started => {
+                    events.push(ClipEvent::new(delta, message.rebuffer_into()));
+                    delta = 0;
+                }
+                message => {
+                    header.push(message.rebuffer_into());
+                    delta = 0;
+                }
+            }
+        }
+
+        if started {
+            Err(InvalidData(ERR_NO_END_OF_CLIP))
+        } else {
+            Err(InvalidData(ERR_NO_START_OF_CLIP))
+        }
+    }
+}
+
+fn write_delta(words: &mut std::vec::Vec<u32>, delta: u32) {
+    for message in delta_clockstamps(u64::from(delta)) {
+        write_message(words, message.data());
+    }
+}
+
+// messages may hold less data than their packets,
+// e.g. a Start of Clip message is represented with a single word.
+// The file always contains whole packets.
+fn write_message(words: &mut std::vec::Vec<u32>, data: &[u32]) {
+    use crate::detail::BitOps;
+
+    let mut data = data;
+    while let Some(first) = data.first() {
+        let size = crate::packet::size(first.nibble(0).into());
+        let packet = &data[..size.min(data.len())];
+        words.extend_from_slice(packet);
+        words.extend(core::iter::repeat_n(0x0, size - packet.len()));
+        data = &data[packet.len()..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const CLIP: [u8; 72] = [
+        0x53, 0x4D, 0x46, 0x32, 0x43, 0x4C, 0x49, 0x50, // SMF2CLIP
+        0x00, 0x30, 0x01, 0xE0, // dctpq 480
+        0x00, 0x40, 0x00, 0x00, // dcs 0
+        0xF0, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // start of clip
+        0x00, 0x40, 0x00, 0x60, // dcs 96
+        0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00, // note on
+        0x00, 0x40, 0x01, 0xE0, // dcs 480
+        0xF0, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // end of clip
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // trailing no ops
+    ];
+
+    #[test]
+    fn read() {
+        let clip = Clip::try_from(&CLIP[..]).unwrap();
+        assert_eq!(clip.ticks_per_quarter_note, 480);
+        assert_eq!(clip.header, std::vec::Vec::new());
+        assert_eq!(clip.events.len(), 1);
+        assert_eq!(clip.events[0].delta, 96);
+        assert_eq!(clip.events[0].message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+        assert_eq!(clip.end_delta, 480);
+    }
+
+    #[test]
+    fn write() {
+        let clip = Clip::try_from(&CLIP[..]).unwrap();
+        assert_eq!(clip.to_bytes(), &CLIP[..64]);
+    }
+
+    #[test]
+    fn write_large_delta() {
+        let mut clip = Clip::new(96);
+        clip.end_delta = 0x12_3456;
+        let clip = Clip::try_from(&clip.to_bytes()[..]).unwrap();
+        assert_eq!(clip.end_delta, 0x12_3456);
+    }
+
+    #[test]
+    fn header_messages() {
+        let mut clip = Clip::new(96);
+        clip.header.push(
+            UmpMessage::try_from(&[0xF006_0200, 0x0, 0x0, 0x0][..])
+                .unwrap()
+                .rebuffer_into(),
+        );
+        assert_eq!(Clip::try_from(&clip.to_bytes()[..]), Ok(clip));
+    }
+
+    #[test]
+    fn no_file_id() {
+        assert_eq!(Clip::try_from(&CLIP[8..]), Err(InvalidData(ERR_NO_FILE_ID)));
+    }
+
+    #[test]
+    fn truncated_word() {
+        assert_eq!(
+            Clip::try_from(&CLIP[..63]),
+            Err(InvalidData(ERR_TRUNCATED_WORD))
+        );
+    }
+
+    #[test]
+    fn no_end_of_clip() {
+        assert_eq!(
+            Clip::try_from(&CLIP[..44]),
+            Err(InvalidData(ERR_NO_END_OF_CLIP))
+        );
+    }
+
+    #[test]
+    fn no_start_of_clip() {
+        assert_eq!(
+            Clip::try_from(&CLIP[..16]),
+            Err(InvalidData(ERR_NO_START_OF_CLIP))
+        );
+    }
+
+    #[test]
+    fn no_ticks_per_quarter_note() {
+        let mut data = std::vec::Vec::from(&CLIP[..8]);
+        data.extend_from_slice(&CLIP[12..]);
+        assert_eq!(
+            Clip::try_from(&data[..]),
+            Err(InvalidData(ERR_NO_TICKS_PER_QUARTER_NOTE))
+        );
+    }
+}
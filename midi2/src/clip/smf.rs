@@ -0,0 +1,580 @@
+use super::{Clip, ClipEvent};
+use crate::{
+    channel_voice1::ChannelVoice1,
+    detail::translate::{Downgrader, Upgrader},
+    error::InvalidData,
+    flex_data::{self, FlexData, SetKeySignatureSharpsFlats, Tonic},
+    message::UmpMessage,
+    smf::{self, Event, Format, KeySignature, Meta, Smf, TextKind, Timing},
+    sysex7::Sysex7,
+    traits::{Channeled, Grouped, IntoBytes, IntoUmp},
+    ux::{u15, u24, u3, u4},
+};
+
+const ERR_TIMECODE_TIMING: &str = "Files with timecode timing can not be converted to a clip";
+const ERR_TOO_MANY_TRACKS: &str = "Too many tracks to assign a group or channel to each";
+const ERR_TICKS_PER_QUARTER_NOTE_OUT_OF_RANGE: &str =
+    "Standard midi files can't hold more than 0x7FFF ticks per quarter note";
+
+// the tonic of the major key with no sharps or flats is at index 1.
+// The tonic of the relative minor is three steps further round.
+const CIRCLE_OF_FIFTHS: [Tonic; 7] = [
+    Tonic::F,
+    Tonic::C,
+    Tonic::G,
+    Tonic::D,
+    Tonic::A,
+    Tonic::E,
+    Tonic::B,
+];
+
+/// How channel events of a Standard MIDI File are represented in a clip.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// MIDI 1.0 channel voice messages in UMP.
+    Midi1,
+    /// Translated to MIDI 2.0 channel voice messages.
+    #[default]
+    Midi2,
+}
+
+/// How the tracks of a Standard MIDI File are assigned to groups
+/// and channels when merged into the single sequence of a clip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assignment {
+    /// Every track is sent on the given group.
+    /// Events keep the channel they have in the file.
+    Group(u4),
+    /// Each track is sent on its own group, counting up from group 0 in track order.
+    /// Events keep the channel they have in the file.
+    GroupPerTrack,
+    /// Each track is sent on its own channel of group 0, counting up from channel 0 in track order.
+    /// The channels of the events in the file are replaced.
+    ChannelPerTrack,
+}
+
+impl Default for Assignment {
+    fn default() -> Self {
+        Assignment::Group(u4::new(0))
+    }
+}
+
+/// Options for converting a Standard MIDI File into a clip.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Conversion {
+    pub protocol: Protocol,
+    pub assignment: Assignment,
+}
+
+impl Clip {
+    /// Convert a Standard MIDI File into a clip.
+    ///
+    /// The tracks of the file are merged into a single sequence.
+    /// The tracks of a format 2 file are played one after another.
+    /// Events with no clip equivalent, such as sysex escapes
+    /// and most meta events, are dropped.
+    pub fn try_from_smf(smf: &Smf, conversion: Conversion) -> Result<Clip, InvalidData> {
+        let Timing::Metrical(ticks_per_quarter_note) = smf.timing else {
+            return Err(InvalidData(ERR_TIMECODE_TIMING));
+        };
+        if matches!(
+            conversion.assignment,
+            Assignment::GroupPerTrack | Assignment::ChannelPerTrack
+        ) && smf.tracks.len() > 16
+        {
+            return Err(InvalidData(ERR_TOO_MANY_TRACKS));
+        }
+
+        let mut timed = std::vec::Vec::new();
+        let mut track_start = 0_u64;
+        let mut end = 0_u64;
+
+        for (index, track) in smf.tracks.iter().enumerate() {
+            let (group, channel) = match conversion.assignment {
+                Assignment::Group(group) => (group, None),
+                Assignment::GroupPerTrack => (u4::new(index as u8), None),
+                Assignment::ChannelPerTrack => (u4::new(0), Some(u4::new(index as u8))),
+            };
+            let mut upgrader = Upgrader::default();
+            let mut tick = track_start;
+
+            for track_event in track.events.iter() {
+                tick += u64::from(track_event.delta);
+                let message: Option<UmpMessage<std::vec::Vec<u32>>> = match &track_event.event {
+                    Event::Midi(message) => {
+                        let mut message = *message;
+                        if let Some(channel) = channel {
+                            message.set_channel(channel);
+                        }
+                        match conversion.protocol {
+                            Protocol::Midi1 => {
+                                let mut message: ChannelVoice1<std::vec::Vec<u32>> =
+                                    message.into_ump();
+                                message.set_group(group);
+                                Some(message.into())
+                            }
+                            Protocol::Midi2 => upgrader.upgrade(message).map(|mut message| {
+                                message.set_group(group);
+                                message.into()
+                            }),
+                        }
+                    }
+                    Event::Sysex(sysex) => {
+                        let mut message: Sysex7<std::vec::Vec<u32>> =
+                            sysex.message.clone().into_ump();
+                        message.set_group(group);
+                        tick += sysex
+                            .continuations
+                            .iter()
+                            .map(|c| u64::from(c.delta))
+                            .sum::<u64>();
+                        Some(message.into())
+                    }
+                    Event::Meta(meta) => meta_to_flex_data(meta).map(|mut message| {
+                        message.set_group(group);
+                        message.into()
+                    }),
+                    Event::Escape(_) => None,
+                };
+                if let Some(message) = message {
+                    timed.push((tick, message));
+                }
+            }
+
+            end = end.max(tick);
+            if smf.format == Format::Sequential {
+                track_start = tick;
+            }
+        }
+
+        // stable: simultaneous events keep their track order
+        timed.sort_by_key(|(tick, _)| *tick);
+
+        let mut clip = Clip::new(u16::from(ticks_per_quarter_note));
+        let mut previous = 0_u64;
+        for (tick, message) in timed {
+            clip.events
+                .push(ClipEvent::new(clamp_delta(tick - previous), message));
+            previous = tick;
+        }
+        clip.end_delta = clamp_delta(end - previous);
+
+        Ok(clip)
+    }
+
+    /// Convert the clip into a single track Standard MIDI File.
+    ///
+    /// MIDI 2.0 channel voice messages are translated to MIDI 1.0.
+    /// Groups are not represented in the file.
+    /// Messages with no file equivalent are dropped.
+    ///
+    /// Fails if the clip has more ticks per quarter note
+    /// than the 15 bits a file can hold.
+    pub fn try_to_smf(&self) -> Result<Smf, InvalidData> {
+        if self.ticks_per_quarter_note > 0x7FFF {
+            return Err(InvalidData(ERR_TICKS_PER_QUARTER_NOTE_OUT_OF_RANGE));
+        }
+
+        let mut track = smf::Track::default();
+        let mut downgrader = Downgrader::default();
+        let mut delta = 0_u32;
+
+        for clip_event in self.events.iter() {
+            delta = delta.saturating_add(clip_event.delta);
+            for event in message_to_events(&clip_event.message, &mut downgrader) {
+                track.events.push(smf::TrackEvent::new(delta, event));
+                delta = 0;
+            }
+        }
+        track.events.push(smf::TrackEvent::new(
+            delta.saturating_add(self.end_delta),
+            Event::Meta(Meta::EndOfTrack),
+        ));
+
+        let mut smf = Smf::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(self.ticks_per_quarter_note)),
+        );
+        smf.tracks.push(track);
+        Ok(smf)
+    }
+}
+
+fn clamp_delta(delta: u64) -> u32 {
+    delta.min(u64::from(u32::MAX)) as u32
+}
+
+fn meta_to_flex_data(meta: &Meta) -> Option<FlexData<std::vec::Vec<u32>>> {
+    Some(match meta {
+        Meta::Tempo(tempo) => {
+            let mut message = flex_data::SetTempo::<std::vec::Vec<u32>>::new();
+            message.set_number_of_10_nanosecond_units_per_quarter_note(
+                u32::from(tempo.microseconds_per_quarter_note) * 100,
+            );
+            message.into()
+        }
+        Meta::TimeSignature(signature) => {
+            let mut message = flex_data::SetTimeSignature::<std::vec::Vec<u32>>::new();
+            message.set_numerator(signature.numerator);
+            message.set_denominator(signature.denominator);
+            message.set_number_of_32nd_notes(signature.number_of_32nd_notes);
+            message.into()
+        }
+        Meta::KeySignature(signature) if (-7..=7).contains(&signature.sharps_flats) => {
+            let mut message = flex_data::SetKeySignature::<std::vec::Vec<u32>>::new();
+            let count = u3::new(signature.sharps_flats.unsigned_abs());
+            message.set_sharps_flats(if signature.sharps_flats < 0 {
+                SetKeySignatureSharpsFlats::Flats(count)
+            } else {
+                SetKeySignatureSharpsFlats::Sharps(count)
+            });
+            message.set_tonic(tonic(signature.sharps_flats, signature.minor));
+            message.into()
+        }
+        Meta::Text(kind, text) => {
+            let text = std::string::String::from_utf8_lossy(text);
+            match kind {
+                TextKind::Text => {
+                    let mut message = flex_data::UnknownMetadataText::<std::vec::Vec<u32>>::new();
+                    message.set_text(&text);
+                    message.into()
+                }
+                TextKind::Copyright => {
+                    let mut message = flex_data::CopyrightNotice::<std::vec::Vec<u32>>::new();
+                    message.set_text(&text);
+                    message.into()
+                }
+                TextKind::Lyric => {
+                    let mut message = flex_data::Lyrics::<std::vec::Vec<u32>>::new();
+                    message.set_text(&text);
+                    message.into()
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    })
+}
+
+fn message_to_events(
+    message: &UmpMessage<std::vec::Vec<u32>>,
+    downgrader: &mut Downgrader,
+) -> std::vec::Vec<Event> {
+    match message {
+        UmpMessage::ChannelVoice1(message) => {
+            let message: ChannelVoice1<std::vec::Vec<u8>> = message.clone().into_bytes();
+            std::vec![message.into()]
+        }
+        UmpMessage::ChannelVoice2(message) => {
+            let mut messages = std::vec::Vec::new();
+            downgrader.downgrade(message, &mut messages);
+            messages.into_iter().map(Event::from).collect()
+        }
+        UmpMessage::Sysex7(message) => {
+            let message: Sysex7<std::vec::Vec<u8>> = message.clone().into_bytes();
+            std::vec![message.into()]
+        }
+        UmpMessage::FlexData(message) => flex_data_to_meta(message)
+            .map(|meta| std::vec![meta.into()])
+            .unwrap_or_default(),
+        _ => std::vec::Vec::new(),
+    }
+}
+
+fn flex_data_to_meta(message: &FlexData<std::vec::Vec<u32>>) -> Option<Meta> {
+    Some(match message {
+        FlexData::SetTempo(m) => Meta::Tempo(smf::Tempo {
+            microseconds_per_quarter_note: u24::new(
+                (m.number_of_10_nanosecond_units_per_quarter_note() / 100).min(u32::from(u24::MAX)),
+            ),
+        }),
+        FlexData::SetTimeSignature(m) => Meta::TimeSignature(smf::TimeSignature {
+            numerator: m.numerator(),
+            denominator: m.denominator(),
+            // one click per quarter note
+            clocks_per_click: 24,
+            number_of_32nd_notes: m.number_of_32nd_notes(),
+        }),
+        FlexData::SetKeySignature(m) => {
+            let sharps_flats = match m.sharps_flats() {
+                SetKeySignatureSharpsFlats::Sharps(n) => u8::from(n) as i8,
+                SetKeySignatureSharpsFlats::Flats(n) => -(u8::from(n) as i8),
+                SetKeySignatureSharpsFlats::NonStandard => return None,
+            };
+            Meta::KeySignature(KeySignature {
+                sharps_flats,
+                minor: m.tonic() != tonic(sharps_flats, false),
+            })
+        }
+        FlexData::UnknownMetadataText(m) => Meta::Text(TextKind::Text, m.text().into_bytes()),
+        FlexData::CopyrightNotice(m) => Meta::Text(TextKind::Copyright, m.text().into_bytes()),
+        FlexData::Lyrics(m) => Meta::Text(TextKind::Lyric, m.text().into_bytes()),
+        _ => return None,
+    })
+}
+
+fn tonic(sharps_flats: i8, minor: bool) -> Tonic {
+    let offset = if minor { 4 } else { 1 };
+    CIRCLE_OF_FIFTHS[(sharps_flats + offset).rem_euclid(7) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        channel_voice2::ChannelVoice2,
+        smf::{Track, TrackEvent},
+        traits::Data,
+        ux::u7,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn note_on(channel: u8, note: u8, velocity: u8) -> Event {
+        let mut message = crate::channel_voice1::NoteOn::<[u8; 3]>::new();
+        message.set_channel(u4::new(channel));
+        message.set_note_number(u7::new(note));
+        message.set_velocity(u7::new(velocity));
+        ChannelVoice1::from(message).into()
+    }
+
+    fn format_1() -> Smf {
+        let mut smf = Smf::new(Format::Simultaneous, Timing::Metrical(u15::new(96)));
+        smf.tracks.push(Track {
+            events: std::vec![
+                TrackEvent::new(
+                    0,
+                    Meta::Tempo(smf::Tempo {
+                        microseconds_per_quarter_note: u24::new(500_000),
+                    })
+                    .into()
+                ),
+                TrackEvent::new(
+                    0,
+                    Meta::KeySignature(KeySignature {
+                        sharps_flats: -3,
+                        minor: true,
+                    })
+                    .into()
+                ),
+                TrackEvent::new(0, Meta::Text(TextKind::Copyright, b"Me".to_vec()).into()),
+                TrackEvent::new(384, Meta::EndOfTrack.into()),
+            ],
+        });
+        smf.tracks.push(Track {
+            events: std::vec![
+                TrackEvent::new(0, note_on(0, 0x3C, 0x7F)),
+                TrackEvent::new(96, note_on(0, 0x3C, 0x00)),
+                TrackEvent::new(0, Meta::EndOfTrack.into()),
+            ],
+        });
+        smf.tracks.push(Track {
+            events: std::vec![
+                TrackEvent::new(48, note_on(5, 0x40, 0x40)),
+                TrackEvent::new(0, Meta::EndOfTrack.into()),
+            ],
+        });
+        smf
+    }
+
+    #[test]
+    fn merge_tracks() {
+        let clip = Clip::try_from_smf(&format_1(), Conversion::default()).unwrap();
+        assert_eq!(clip.ticks_per_quarter_note, 96);
+        assert_eq!(
+            clip.events
+                .iter()
+                .map(|e| e.delta)
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![0, 0, 0, 0, 48, 48],
+        );
+        assert_eq!(clip.end_delta, 288);
+        assert_eq!(clip.events[0].message.data(), &[0xD010_0000, 0x02FA_F080]);
+        assert_eq!(clip.events[3].message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+        assert_eq!(clip.events[4].message.data(), &[0x4095_4000, 0x8000_0000]);
+        assert_eq!(clip.events[5].message.data(), &[0x4080_3C00, 0x8000_0000]);
+    }
+
+    #[test]
+    fn key_signature() {
+        let clip = Clip::try_from_smf(&format_1(), Conversion::default()).unwrap();
+        let UmpMessage::FlexData(FlexData::SetKeySignature(message)) = &clip.events[1].message
+        else {
+            panic!();
+        };
+        assert_eq!(message.tonic(), Tonic::C);
+        assert_eq!(
+            message.sharps_flats(),
+            SetKeySignatureSharpsFlats::Flats(u3::new(3))
+        );
+    }
+
+    #[test]
+    fn copyright() {
+        let clip = Clip::try_from_smf(&format_1(), Conversion::default()).unwrap();
+        let UmpMessage::FlexData(FlexData::CopyrightNotice(message)) = &clip.events[2].message
+        else {
+            panic!();
+        };
+        assert_eq!(message.text(), "Me");
+    }
+
+    #[test]
+    fn midi1_protocol() {
+        let clip = Clip::try_from_smf(
+            &format_1(),
+            Conversion {
+                protocol: Protocol::Midi1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(clip.events[3].message.data(), &[0x2090_3C7F]);
+        assert_eq!(clip.events[5].message.data(), &[0x2090_3C00]);
+    }
+
+    #[test]
+    fn group_per_track() {
+        let clip = Clip::try_from_smf(
+            &format_1(),
+            Conversion {
+                assignment: Assignment::GroupPerTrack,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(clip.events[0].message.data()[0], 0xD010_0000);
+        assert_eq!(clip.events[3].message.data()[0], 0x4190_3C00);
+        assert_eq!(clip.events[4].message.data()[0], 0x4295_4000);
+    }
+
+    #[test]
+    fn channel_per_track() {
+        let clip = Clip::try_from_smf(
+            &format_1(),
+            Conversion {
+                assignment: Assignment::ChannelPerTrack,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(clip.events[3].message.data()[0], 0x4091_3C00);
+        assert_eq!(clip.events[4].message.data()[0], 0x4092_4000);
+    }
+
+    #[test]
+    fn sequential_tracks() {
+        let mut smf = format_1();
+        smf.format = Format::Sequential;
+        let clip = Clip::try_from_smf(&smf, Conversion::default()).unwrap();
+        assert_eq!(
+            clip.events
+                .iter()
+                .map(|e| e.delta)
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![0, 0, 0, 384, 96, 48],
+        );
+        assert_eq!(clip.end_delta, 0);
+    }
+
+    #[test]
+    fn too_many_tracks() {
+        let mut smf = format_1();
+        smf.tracks.resize(17, Track::default());
+        assert_eq!(
+            Clip::try_from_smf(
+                &smf,
+                Conversion {
+                    assignment: Assignment::GroupPerTrack,
+                    ..Default::default()
+                }
+            ),
+            Err(InvalidData(ERR_TOO_MANY_TRACKS)),
+        );
+    }
+
+    #[test]
+    fn timecode_timing() {
+        let smf = Smf::new(
+            Format::SingleTrack,
+            Timing::Timecode {
+                frames_per_second: 25,
+                ticks_per_frame: 40,
+            },
+        );
+        assert_eq!(
+            Clip::try_from_smf(&smf, Conversion::default()),
+            Err(InvalidData(ERR_TIMECODE_TIMING)),
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let clip = Clip::try_from_smf(&format_1(), Conversion::default()).unwrap();
+        let smf = clip.try_to_smf().unwrap();
+        assert_eq!(smf.format, Format::SingleTrack);
+        assert_eq!(smf.timing, Timing::Metrical(u15::new(96)));
+        assert_eq!(
+            smf.tracks[0]
+                .events
+                .iter()
+                .map(|e| (e.delta, e.event.clone()))
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![
+                (
+                    0,
+                    Meta::Tempo(smf::Tempo {
+                        microseconds_per_quarter_note: u24::new(500_000),
+                    })
+                    .into()
+                ),
+                (
+                    0,
+                    Meta::KeySignature(KeySignature {
+                        sharps_flats: -3,
+                        minor: true,
+                    })
+                    .into()
+                ),
+                (0, Meta::Text(TextKind::Copyright, b"Me".to_vec()).into()),
+                (0, note_on(0, 0x3C, 0x7F)),
+                (48, note_on(5, 0x40, 0x40)),
+                (48, {
+                    let mut message = crate::channel_voice1::NoteOff::<[u8; 3]>::new();
+                    message.set_note_number(u7::new(0x3C));
+                    message.set_velocity(u7::new(0x40));
+                    ChannelVoice1::from(message).into()
+                }),
+                (288, Meta::EndOfTrack.into()),
+            ],
+        );
+    }
+
+    #[test]
+    fn downgrade_controller() {
+        let mut clip = Clip::new(96);
+        let mut controller = crate::channel_voice2::ControlChange::<std::vec::Vec<u32>>::new();
+        controller.set_control(u7::new(0x07));
+        controller.set_control_change_data(0xFFFF_FFFF);
+        clip.events
+            .push(ClipEvent::new(10, ChannelVoice2::from(controller).into()));
+        let smf = clip.try_to_smf().unwrap();
+        let Event::Midi(ChannelVoice1::ControlChange(message)) = &smf.tracks[0].events[0].event
+        else {
+            panic!();
+        };
+        assert_eq!(smf.tracks[0].events[0].delta, 10);
+        assert_eq!(message.control_data(), u7::new(0x7F));
+    }
+
+    #[test]
+    fn ticks_per_quarter_note_out_of_range() {
+        assert_eq!(
+            Clip::new(0x8000).try_to_smf(),
+            Err(InvalidData(ERR_TICKS_PER_QUARTER_NOTE_OUT_OF_RANGE)),
+        );
+        assert_eq!(
+            Clip::new(0x7FFF).try_to_smf().unwrap().timing,
+            Timing::Metrical(u15::new(0x7FFF))
+        );
+    }
+}
@@ -3,9 +3,23 @@ mod encode_7bit;
 
 pub mod common_err_strings;
 pub mod common_properties;
+pub mod diagnose;
 pub mod helpers;
 pub mod property;
 pub mod schema;
+pub mod text;
+
+#[cfg(feature = "defmt")]
+pub mod defmt;
+
+#[cfg(feature = "serde")]
+pub mod serde;
+
+#[cfg(any(all(feature = "clip", feature = "smf"), feature = "tracker"))]
+pub mod translate;
+
+#[cfg(any(feature = "smf", feature = "rtp-midi"))]
+pub mod vlq;
 
 #[cfg(test)]
 pub mod test_support;
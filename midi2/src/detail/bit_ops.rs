@@ -3,7 +3,9 @@ use ux::*;
 pub trait BitOps {
     fn bit(&self, index: usize) -> bool;
     fn set_bit(&mut self, index: usize, v: bool) -> &mut Self;
+    #[allow(dead_code)]
     fn crumb(&self, index: usize) -> u2;
+    #[allow(dead_code)]
     fn set_crumb(&mut self, index: usize, v: u2) -> &mut Self;
     fn nibble(&self, index: usize) -> u4;
     fn set_nibble(&mut self, index: usize, v: u4) -> &mut Self;
@@ -269,3 +269,76 @@ impl<
         Default::default()
     }
 }
+
+/// The raw words of a message which couldn't be interpreted.
+///
+/// The bits of the first word not covered by `MASK` belong to the other
+/// properties of the message (message type, group, channel etc.)
+/// and read back as zero.
+#[cfg(any(
+    feature = "channel-voice2",
+    feature = "flex-data",
+    feature = "ump-stream",
+    feature = "utility"
+))]
+pub struct UnknownDataProperty<const SIZE: usize, const MASK: u32>;
+
+#[cfg(any(
+    feature = "channel-voice2",
+    feature = "flex-data",
+    feature = "ump-stream",
+    feature = "utility"
+))]
+impl<const SIZE: usize, const MASK: u32, B: crate::buffer::Ump> Property<B>
+    for UnknownDataProperty<SIZE, MASK>
+{
+    type Type = [u32; SIZE];
+}
+
+#[cfg(any(
+    feature = "channel-voice2",
+    feature = "flex-data",
+    feature = "ump-stream",
+    feature = "utility"
+))]
+impl<'a, const SIZE: usize, const MASK: u32, B: crate::buffer::Ump> ReadProperty<'a, B>
+    for UnknownDataProperty<SIZE, MASK>
+{
+    fn read(buffer: &'a B) -> Self::Type {
+        let mut data = [0x0; SIZE];
+        data.copy_from_slice(&buffer.buffer()[..SIZE]);
+        data[0] &= MASK;
+        data
+    }
+    fn validate(_buffer: &B) -> Result<(), crate::error::InvalidData> {
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice2",
+    feature = "flex-data",
+    feature = "ump-stream",
+    feature = "utility"
+))]
+impl<const SIZE: usize, const MASK: u32, B: crate::buffer::Ump + BufferMut> WriteProperty<B>
+    for UnknownDataProperty<SIZE, MASK>
+{
+    fn write(buffer: &mut B, v: Self::Type) {
+        let buffer = &mut buffer.buffer_mut()[..SIZE];
+        let header = buffer[0] & !MASK;
+        buffer.copy_from_slice(&v);
+        buffer[0] = header | (v[0] & MASK);
+    }
+    fn validate(v: &Self::Type) -> Result<(), crate::error::InvalidData> {
+        if v[0] & !MASK != 0 {
+            return Err(crate::error::InvalidData(
+                "Unknown message data overlaps the message header",
+            ));
+        }
+        Ok(())
+    }
+    fn default() -> Self::Type {
+        [0x0; SIZE]
+    }
+}
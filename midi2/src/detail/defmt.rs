@@ -0,0 +1,301 @@
+use crate::{buffer::Buffer, detail::property::ReadProperty};
+use ::defmt::{write, Format, Formatter};
+
+/// A value which appears in the logged form of a message.
+///
+/// The `ux` integer types have no `defmt` support of their own
+/// so they are logged as the smallest primitive which can hold them.
+pub trait Field {
+    type Repr: Format;
+    fn to_repr(self) -> Self::Repr;
+}
+
+macro_rules! identity_field {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Field for $ty {
+                type Repr = Self;
+                fn to_repr(self) -> Self::Repr {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! ux_field {
+    ($($ty:ty => $repr:ty),* $(,)?) => {
+        $(
+            impl Field for $ty {
+                type Repr = $repr;
+                fn to_repr(self) -> Self::Repr {
+                    self.into()
+                }
+            }
+        )*
+    };
+}
+
+identity_field!(bool, u8, u16, u32);
+
+#[cfg(feature = "channel-voice2")]
+identity_field!(
+    crate::channel_voice2::Controller,
+    crate::channel_voice2::NoteAttribute,
+);
+
+#[cfg(feature = "flex-data")]
+identity_field!(
+    crate::flex_data::Alteration,
+    crate::flex_data::ChordType,
+    crate::flex_data::SetChordNameSharpsFlats,
+    crate::flex_data::SetKeySignatureSharpsFlats,
+    crate::flex_data::Tonic,
+);
+
+#[cfg(feature = "ump-stream")]
+identity_field!(
+    crate::ump_stream::Direction,
+    crate::ump_stream::Midi1Port,
+    crate::ump_stream::UiHint,
+);
+
+ux_field!(
+    crate::ux::u3 => u8,
+    crate::ux::u4 => u8,
+    crate::ux::u7 => u8,
+    crate::ux::u14 => u16,
+    crate::ux::u20 => u32,
+    crate::ux::u28 => u32,
+);
+
+impl<T: Field> Field for Option<T> {
+    type Repr = Option<T::Repr>;
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+}
+
+impl<T: Field, const SIZE: usize> Field for [T; SIZE] {
+    type Repr = [T::Repr; SIZE];
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+}
+
+pub fn field<'a, P, B>(buffer: &'a B) -> <P::Type as Field>::Repr
+where
+    B: Buffer,
+    P: ReadProperty<'a, B>,
+    P::Type: Field,
+{
+    P::read(buffer).to_repr()
+}
+
+/// Logs sysex payload bytes as space separated hex.
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+pub fn write_payload<T: Into<u8>>(payload: impl core::iter::Iterator<Item = T>, fmt: Formatter) {
+    write!(fmt, "[");
+    for (i, b) in payload.enumerate() {
+        if i != 0 {
+            write!(fmt, " ");
+        }
+        write!(fmt, "{=u8:02X}", b.into());
+    }
+    write!(fmt, "]");
+}
+
+// the supporting enums holding `ux` values can't derive `Format`
+
+#[cfg(feature = "flex-data")]
+impl Format for crate::flex_data::Alteration {
+    fn format(&self, fmt: Formatter) {
+        use crate::flex_data::Alteration::*;
+        match *self {
+            Add(v) => write!(fmt, "Add({=u8})", u8::from(v)),
+            Subtract(v) => write!(fmt, "Subtract({=u8})", u8::from(v)),
+            Raise(v) => write!(fmt, "Raise({=u8})", u8::from(v)),
+            Lower(v) => write!(fmt, "Lower({=u8})", u8::from(v)),
+        }
+    }
+}
+
+#[cfg(feature = "flex-data")]
+impl Format for crate::flex_data::SetKeySignatureSharpsFlats {
+    fn format(&self, fmt: Formatter) {
+        use crate::flex_data::SetKeySignatureSharpsFlats::*;
+        match *self {
+            Flats(v) => write!(fmt, "Flats({=u8})", u8::from(v)),
+            Sharps(v) => write!(fmt, "Sharps({=u8})", u8::from(v)),
+            NonStandard => write!(fmt, "NonStandard"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        detail::common_properties::{ChannelProperty, GroupProperty},
+        prelude::*,
+    };
+    use pretty_assertions::assert_eq;
+
+    static_assertions::assert_impl_all!(error::Error: Format);
+    static_assertions::assert_impl_all!(error::BufferOverflow: Format);
+    static_assertions::assert_impl_all!(error::InvalidData: Format);
+    static_assertions::assert_impl_all!(error::Diagnostic: Format);
+    static_assertions::assert_impl_all!(error::ErrorKind: Format);
+    static_assertions::assert_impl_all!(error::Offset: Format);
+    static_assertions::assert_impl_all!(UmpMessage<&[u32]>: Format);
+    static_assertions::assert_impl_all!(UmpMessage<std::vec::Vec<u32>>: Format);
+    static_assertions::assert_impl_all!(UnknownMessage<[u32; 4]>: Format);
+
+    #[test]
+    fn ux_fields_are_logged_as_primitives() {
+        assert_eq!(u3::new(0x5).to_repr(), 0x5_u8);
+        assert_eq!(u4::new(0xA).to_repr(), 0xA_u8);
+        assert_eq!(u7::new(0x7F).to_repr(), 0x7F_u8);
+        assert_eq!(u14::new(0x3FFF).to_repr(), 0x3FFF_u16);
+        assert_eq!(u20::new(0xF_FFFF).to_repr(), 0xF_FFFF_u32);
+        assert_eq!(u28::new(0xFFF_FFFF).to_repr(), 0xFFF_FFFF_u32);
+    }
+
+    #[test]
+    fn primitive_fields_are_logged_as_they_are() {
+        assert!(true.to_repr());
+        assert_eq!(0x42_u8.to_repr(), 0x42);
+        assert_eq!(0x4242_u16.to_repr(), 0x4242);
+        assert_eq!(0x4242_4242_u32.to_repr(), 0x4242_4242);
+    }
+
+    #[test]
+    fn wrapped_fields_are_logged_element_wise() {
+        assert_eq!(Some(u7::new(0x3C)).to_repr(), Some(0x3C_u8));
+        assert_eq!(Option::<u7>::None.to_repr(), None);
+        assert_eq!([u4::new(0x1), u4::new(0x2)].to_repr(), [0x1_u8, 0x2]);
+    }
+
+    #[test]
+    fn unknown_message_fields() {
+        let message = UnknownMessage::try_from(&[0xB312_3456, 0x1, 0x2][..]).unwrap();
+        assert_eq!(field::<GroupProperty, _>(&message.data()), 0x3);
+        assert_eq!(message.raw_data().to_repr(), [0x0012_3456, 0x1, 0x2, 0x0]);
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn channel_voice1_fields() {
+        static_assertions::assert_impl_all!(channel_voice1::NoteOn<[u8; 3]>: Format);
+        static_assertions::assert_impl_all!(BytesMessage<[u8; 3]>: Format);
+
+        let mut message = channel_voice1::NoteOn::<[u32; 4]>::new();
+        message.set_group(u4::new(0x3));
+        message.set_channel(u4::new(0x5));
+        message.set_note_number(u7::new(0x3C));
+        let data = message.data();
+        assert_eq!(field::<GroupProperty, _>(&data), 0x3);
+        assert_eq!(field::<ChannelProperty, _>(&data), 0x5);
+        assert_eq!(message.note_number().to_repr(), 0x3C);
+
+        let mut message = channel_voice1::NoteOn::<[u8; 3]>::new();
+        message.set_channel(u4::new(0x9));
+        assert_eq!(field::<ChannelProperty, _>(&message.data()), 0x9);
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice2")]
+    fn channel_voice2_fields() {
+        static_assertions::assert_impl_all!(channel_voice2::ChannelVoice2<&[u32]>: Format);
+
+        let mut message = channel_voice2::NoteOn::<[u32; 4]>::new();
+        message.set_group(u4::new(0x2));
+        message.set_channel(u4::new(0xF));
+        message.set_velocity(0xFFFF);
+        message.set_attribute(Some(channel_voice2::NoteAttribute::ManufacturerSpecific(
+            0x1234,
+        )));
+        let data = message.data();
+        assert_eq!(field::<GroupProperty, _>(&data), 0x2);
+        assert_eq!(field::<ChannelProperty, _>(&data), 0xF);
+        assert_eq!(message.velocity().to_repr(), 0xFFFF);
+        assert_eq!(
+            message.attribute().to_repr(),
+            Some(channel_voice2::NoteAttribute::ManufacturerSpecific(0x1234))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex7_fields() {
+        static_assertions::assert_impl_all!(sysex7::Sysex7<std::vec::Vec<u32>>: Format);
+        static_assertions::assert_impl_all!(sysex7::Sysex7<std::vec::Vec<u8>>: Format);
+
+        let mut message = sysex7::Sysex7::<std::vec::Vec<u32>>::new();
+        message.set_group(u4::new(0x4));
+        assert_eq!(field::<GroupProperty, _>(&message.data()), 0x4);
+    }
+
+    #[test]
+    #[cfg(feature = "sysex8")]
+    fn sysex8_fields() {
+        static_assertions::assert_impl_all!(sysex8::Sysex8<std::vec::Vec<u32>>: Format);
+
+        let message = sysex8::Sysex8::try_from(&[0x5604_AB00, 0x0102_0000, 0x0, 0x0][..]).unwrap();
+        assert_eq!(field::<GroupProperty, _>(&message.data()), 0x6);
+        assert_eq!(message.stream_id().to_repr(), 0xAB);
+    }
+
+    #[test]
+    #[cfg(feature = "system-common")]
+    fn system_common_fields() {
+        static_assertions::assert_impl_all!(system_common::SystemCommon<&[u8]>: Format);
+
+        let mut message = system_common::SongPositionPointer::<[u32; 4]>::new();
+        message.set_group(u4::new(0x1));
+        message.set_position(u14::new(0x1234));
+        assert_eq!(field::<GroupProperty, _>(&message.data()), 0x1);
+        assert_eq!(message.position().to_repr(), 0x1234_u16);
+    }
+
+    #[test]
+    #[cfg(feature = "utility")]
+    fn utility_fields() {
+        static_assertions::assert_impl_all!(utility::Utility<&[u32]>: Format);
+
+        let mut message = utility::DeltaClockstamp::<[u32; 4]>::new();
+        message.set_time_data(u20::new(0xA_BCDE));
+        assert_eq!(message.time_data().to_repr(), 0xA_BCDE_u32);
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn flex_data_fields() {
+        static_assertions::assert_impl_all!(flex_data::FlexData<std::vec::Vec<u32>>: Format);
+
+        let mut message = flex_data::SetKeySignature::<[u32; 4]>::new();
+        message.set_group(u4::new(0x7));
+        message.set_tonic(flex_data::Tonic::D);
+        message.set_sharps_flats(flex_data::SetKeySignatureSharpsFlats::Sharps(u3::new(0x2)));
+        assert_eq!(field::<GroupProperty, _>(&message.data()), 0x7);
+        assert_eq!(message.tonic().to_repr(), flex_data::Tonic::D);
+        assert_eq!(
+            message.sharps_flats().to_repr(),
+            flex_data::SetKeySignatureSharpsFlats::Sharps(u3::new(0x2))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn ump_stream_fields() {
+        static_assertions::assert_impl_all!(ump_stream::UmpStream<std::vec::Vec<u32>>: Format);
+
+        let mut message = ump_stream::FunctionBlockInfo::<[u32; 4]>::new();
+        message.set_function_block_number(u7::new(0x12));
+        message.set_ui_hint(ump_stream::UiHint::Receiver);
+        message.set_direction(ump_stream::Direction::Output);
+        assert_eq!(message.function_block_number().to_repr(), 0x12);
+        assert_eq!(message.ui_hint().to_repr(), ump_stream::UiHint::Receiver);
+        assert_eq!(message.direction().to_repr(), ump_stream::Direction::Output);
+    }
+}
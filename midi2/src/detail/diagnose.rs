@@ -0,0 +1,603 @@
+use crate::{
+    detail::BitOps,
+    error::{Diagnostic, ErrorKind, InvalidData, Offset, Warnings},
+    packet::{self, Position},
+};
+
+fn diagnostic(
+    kind: ErrorKind,
+    offset: Offset,
+    expected: Option<u32>,
+    found: Option<u32>,
+) -> Diagnostic {
+    Diagnostic {
+        expected,
+        found,
+        ..Diagnostic::new(kind, offset)
+    }
+}
+
+/// Faults found by the message validation which runs after the structural checks
+/// are reported against the start of the message.
+pub fn from_invalid_data(error: InvalidData, offset: Offset) -> Diagnostic {
+    Diagnostic {
+        description: error.0,
+        ..Diagnostic::new(ErrorKind::OutOfRange, offset)
+    }
+}
+
+/// Checks the structure of the (possibly multi-packet) ump message
+/// at the front of the data, returning its size in words.
+///
+/// Checks the message type, the status
+/// and the packet format sequence of each packet.
+pub fn ump(data: &[u32]) -> Result<usize, Diagnostic> {
+    structure(data, false)
+}
+
+/// As [ump], also checking that the reserved bits of each packet are clear.
+pub fn ump_strict(data: &[u32]) -> Result<usize, Diagnostic> {
+    structure(data, true)
+}
+
+fn structure(data: &[u32], check_reserved_bits: bool) -> Result<usize, Diagnostic> {
+    use ErrorKind::*;
+
+    let Some(first) = data.first() else {
+        return Err(diagnostic(Truncated, Offset::Word(0), Some(1), Some(0)));
+    };
+    let message_type = u8::from(first.nibble(0));
+
+    let packet_size = packet::size(message_type);
+    let mut offset = 0;
+    loop {
+        let Some(packet) = data.get(offset..offset + packet_size) else {
+            return Err(diagnostic(
+                Truncated,
+                Offset::Word(data.len()),
+                Some((offset + packet_size) as u32),
+                Some(data.len() as u32),
+            ));
+        };
+        let packet_type = u8::from(packet[0].nibble(0));
+        if packet_type != message_type {
+            return Err(diagnostic(
+                UnexpectedMessageType,
+                Offset::Word(offset),
+                Some(message_type.into()),
+                Some(packet_type.into()),
+            ));
+        }
+        let masks = reserved_bits(packet, offset)?;
+        if check_reserved_bits {
+            if let Some(diagnostic) = reserved_bits_set(packet, offset, masks).next() {
+                return Err(diagnostic);
+            }
+        }
+
+        let position = packet::position(packet[0]);
+        let valid_sequence = match offset {
+            0 => matches!(position, Position::Complete | Position::Start),
+            _ => matches!(position, Position::Continue | Position::End),
+        };
+        if !valid_sequence {
+            return Err(diagnostic(
+                InvalidPacketFormat,
+                Offset::Word(offset),
+                None,
+                Some(packet::format(packet[0]).into()),
+            ));
+        }
+
+        offset += packet_size;
+        if matches!(position, Position::Complete | Position::End) {
+            return Ok(offset);
+        }
+    }
+}
+
+/// Checks the status of the packet, returning the masks of its reserved bits
+/// for each word.
+pub(crate) fn reserved_bits(packet: &[u32], offset: usize) -> Result<&'static [u32], Diagnostic> {
+    let word = packet[0];
+    let invalid_status = |status: u32| {
+        Err(diagnostic(
+            ErrorKind::InvalidStatus,
+            Offset::Word(offset),
+            None,
+            Some(status),
+        ))
+    };
+    let out_of_range = |max: u32, found: u32| {
+        Err(diagnostic(
+            ErrorKind::OutOfRange,
+            Offset::Word(offset),
+            Some(max),
+            Some(found),
+        ))
+    };
+
+    // uninterpreted packets are carried as they are
+    if !packet::is_interpreted(word.nibble(0).into()) {
+        return Ok(&[]);
+    }
+
+    let reserved: &[u32] = match u8::from(word.nibble(0)) {
+        // utility
+        0x0 => match u8::from(word.nibble(2)) {
+            0x0 => &[0x000F_FFFF],
+            0x1..=0x3 => &[0x000F_0000],
+            0x4 => &[0x0],
+            status => return invalid_status(status.into()),
+        },
+        // system common and real time
+        0x1 => match word.octet(1) {
+            0xF1 | 0xF3 => &[0x0000_80FF],
+            0xF2 => &[0x0000_8080],
+            0xF6 | 0xF8 | 0xFA..=0xFC | 0xFE | 0xFF => &[0x0000_FFFF],
+            status => return invalid_status(status.into()),
+        },
+        // midi 1.0 channel voice
+        0x2 => match u8::from(word.nibble(2)) {
+            0x8..=0xB | 0xE => &[0x0000_8080],
+            0xC | 0xD => &[0x0000_80FF],
+            status => return invalid_status(status.into()),
+        },
+        // sysex7
+        0x3 => {
+            let status = u8::from(word.nibble(2));
+            if status > 0x3 {
+                return invalid_status(status.into());
+            }
+            let number_of_bytes = u8::from(word.nibble(3));
+            if number_of_bytes > 6 {
+                return out_of_range(6, number_of_bytes.into());
+            }
+            &[0x0000_8080, 0x8080_8080]
+        }
+        // midi 2.0 channel voice
+        0x4 => match u8::from(word.nibble(2)) {
+            0x0 | 0x1 | 0x8 | 0x9 => &[0x0000_8000, 0x0],
+            0x2..=0x5 => &[0x0000_8080, 0x0],
+            0x6 | 0xA | 0xB => &[0x0000_80FF, 0x0],
+            0xC => &[0x0000_FFFE, 0x80FF_8080],
+            0xD | 0xE => &[0x0000_FFFF, 0x0],
+            0xF => &[0x0000_80FC, 0xFFFF_FFFF],
+            status => return invalid_status(status.into()),
+        },
+        // sysex8
+        0x5 => {
+            let status = u8::from(word.nibble(2));
+            if status > 0x3 {
+                return invalid_status(status.into());
+            }
+            let number_of_bytes = u8::from(word.nibble(3));
+            if number_of_bytes > 14 {
+                return out_of_range(14, number_of_bytes.into());
+            }
+            &[]
+        }
+        // ump stream
+        0xF => match (word >> 16) & 0x3FF {
+            0x00..=0x06 | 0x10..=0x12 | 0x20 | 0x21 => &[],
+            status => return invalid_status(status),
+        },
+        _ => &[],
+    };
+
+    Ok(reserved)
+}
+
+pub(crate) fn reserved_bits_set<'a>(
+    packet: &'a [u32],
+    offset: usize,
+    masks: &'a [u32],
+) -> impl Iterator<Item = Diagnostic> + 'a {
+    packet
+        .iter()
+        .zip(masks)
+        .enumerate()
+        .filter(|(_, (word, mask))| *word & *mask != 0)
+        .map(move |(index, (word, mask))| {
+            diagnostic(
+                ErrorKind::ReservedBitsSet,
+                Offset::Word(offset + index),
+                Some(0x0),
+                Some(word & mask),
+            )
+        })
+}
+
+/// Interprets a single packet as the unknown variant of a message enum.
+pub type Unknown<'a, M> = fn(&'a [u32]) -> Result<M, InvalidData>;
+
+/// Parses the message at the front of the data without rejecting
+/// reserved bits which have been set.
+///
+/// Those are reported through the returned warnings instead.
+/// When `unknown` is provided, any single packet which can't be interpreted
+/// (unknown statuses, invalid fields, broken packet sequences)
+/// is handed to it so that it can be carried unmodified.
+/// Truncated data and unexpected message types are always errors.
+pub fn lenient<'a, M>(
+    data: &'a [u32],
+    unknown: Option<Unknown<'a, M>>,
+) -> Result<(M, Warnings<'a>), Diagnostic>
+where
+    M: TryFrom<&'a [u32], Error = InvalidData>,
+{
+    let error = match structure(data, false) {
+        Ok(size) => match M::try_from(&data[..size]) {
+            Ok(message) => return Ok((message, Warnings::new(&data[..size]))),
+            Err(error) => from_invalid_data(error, Offset::Word(0)),
+        },
+        Err(error) => error,
+    };
+
+    let fatal = match error.kind {
+        ErrorKind::Truncated => true,
+        ErrorKind::UnexpectedMessageType => error.offset == Offset::Word(0),
+        _ => false,
+    };
+    match unknown {
+        Some(unknown) if !fatal => {
+            // the structural checks always cover the first packet
+            let packet = &data[..packet::size(data[0].nibble(0).into())];
+            let message = unknown(packet).map_err(|e| from_invalid_data(e, Offset::Word(0)))?;
+            Ok((message, Warnings::new(packet)))
+        }
+        _ => Err(error),
+    }
+}
+
+/// Checks the structure of the midi 1.0 bytes message
+/// at the front of the data, returning its size in bytes.
+///
+/// Checks the status byte, the length
+/// and that the data bytes have their top bit clear.
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+pub fn bytes(data: &[u8]) -> Result<usize, Diagnostic> {
+    use ErrorKind::*;
+
+    let Some(&status) = data.first() else {
+        return Err(diagnostic(Truncated, Offset::Byte(0), Some(1), Some(0)));
+    };
+    let invalid_status = || {
+        Err(diagnostic(
+            InvalidStatus,
+            Offset::Byte(0),
+            None,
+            Some(status.into()),
+        ))
+    };
+
+    let (size, data_end) = match status {
+        0x00..=0x7F | 0xF4 | 0xF5 | 0xF7 | 0xF9 | 0xFD => return invalid_status(),
+        #[cfg(feature = "channel-voice1")]
+        0xC0..=0xDF => (2, 2),
+        #[cfg(feature = "channel-voice1")]
+        0x80..=0xEF => (3, 3),
+        #[cfg(feature = "sysex7")]
+        0xF0 => match data.iter().position(|&b| b == 0xF7) {
+            Some(end) => (end + 1, end),
+            None => {
+                return Err(diagnostic(
+                    Truncated,
+                    Offset::Byte(data.len()),
+                    None,
+                    Some(data.len() as u32),
+                ))
+            }
+        },
+        #[cfg(feature = "system-common")]
+        0xF1 | 0xF3 => (2, 2),
+        #[cfg(feature = "system-common")]
+        0xF2 => (3, 3),
+        #[cfg(feature = "system-common")]
+        0xF6 | 0xF8 | 0xFA..=0xFC | 0xFE | 0xFF => (1, 1),
+        // reachable when some of the message features are disabled
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(diagnostic(
+                UnexpectedMessageType,
+                Offset::Byte(0),
+                None,
+                Some(status.into()),
+            ))
+        }
+    };
+
+    if data.len() < size {
+        return Err(diagnostic(
+            Truncated,
+            Offset::Byte(data.len()),
+            Some(size as u32),
+            Some(data.len() as u32),
+        ));
+    }
+    if let Some(index) = (1..data_end).find(|&i| data[i] > 0x7F) {
+        return Err(diagnostic(
+            OutOfRange,
+            Offset::Byte(index),
+            Some(0x7F),
+            Some(data[index].into()),
+        ));
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            ump(&[]),
+            Err(diagnostic(
+                ErrorKind::Truncated,
+                Offset::Word(0),
+                Some(1),
+                Some(0)
+            ))
+        );
+    }
+
+    #[test]
+    fn reserved_message_type() {
+        assert_eq!(ump(&[0x6000_0000]), Ok(1));
+        assert_eq!(ump(&[0xE000_0000, 0x0, 0x0, 0x0]), Ok(4));
+        assert_eq!(
+            ump(&[0xB000_0000, 0x0]),
+            Err(diagnostic(
+                ErrorKind::Truncated,
+                Offset::Word(2),
+                Some(3),
+                Some(2)
+            ))
+        );
+    }
+
+    #[test]
+    fn channel_voice2() {
+        assert_eq!(ump(&[0x4898_5E03, 0x6A14_E98A]), Ok(2));
+    }
+
+    #[test]
+    fn channel_voice2_truncated() {
+        assert_eq!(
+            ump(&[0x4898_5E03]),
+            Err(diagnostic(
+                ErrorKind::Truncated,
+                Offset::Word(1),
+                Some(2),
+                Some(1)
+            ))
+        );
+    }
+
+    #[test]
+    fn channel_voice2_reserved_status() {
+        assert_eq!(
+            ump(&[0x4078_5E03, 0x0]),
+            Err(diagnostic(
+                ErrorKind::InvalidStatus,
+                Offset::Word(0),
+                None,
+                Some(0x7)
+            ))
+        );
+    }
+
+    #[test]
+    fn channel_voice2_reserved_bits() {
+        assert_eq!(
+            ump_strict(&[0x40F0_0000, 0x0000_0001]),
+            Err(diagnostic(
+                ErrorKind::ReservedBitsSet,
+                Offset::Word(1),
+                Some(0x0),
+                Some(0x1)
+            ))
+        );
+    }
+
+    #[test]
+    fn reserved_bits_are_only_checked_when_strict() {
+        assert_eq!(ump(&[0x40F0_0000, 0x0000_0001]), Ok(2));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn display() {
+        use std::string::ToString;
+
+        assert_eq!(
+            diagnostic(ErrorKind::Truncated, Offset::Word(1), Some(2), Some(1)).to_string(),
+            "Slice is too short at word 1 (expected 2, found 1)",
+        );
+        assert_eq!(
+            diagnostic(ErrorKind::InvalidStatus, Offset::Byte(0), None, Some(0xF4)).to_string(),
+            "Incorrect message status at byte 0 (found 0xF4)",
+        );
+        assert_eq!(
+            from_invalid_data(
+                InvalidData("Couldn't interpret controller index"),
+                Offset::Word(0)
+            )
+            .to_string(),
+            "Value out of range at word 0: Couldn't interpret controller index",
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn display_errors() {
+        use crate::error::{BufferOverflow, Error};
+        use std::string::ToString;
+
+        assert_eq!(BufferOverflow.to_string(), "Buffer overflow");
+        assert_eq!(Error::BufferOverflow.to_string(), "Buffer overflow");
+        assert_eq!(
+            InvalidData("Incorrect message status").to_string(),
+            "Incorrect message status"
+        );
+        assert_eq!(
+            Error::InvalidData(InvalidData("Incorrect message status")).to_string(),
+            "Incorrect message status"
+        );
+    }
+
+    #[test]
+    fn diagnostic_converts_to_invalid_data() {
+        let diagnostic = diagnostic(ErrorKind::InvalidStatus, Offset::Word(0), None, Some(0x7));
+        assert_eq!(
+            InvalidData::from(diagnostic.clone()),
+            InvalidData("Incorrect message status")
+        );
+        assert_eq!(
+            crate::error::Error::from(diagnostic),
+            crate::error::Error::InvalidData(InvalidData("Incorrect message status"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex7_sequence() {
+        assert_eq!(
+            ump(&[
+                0x3016_0001,
+                0x0203_0405,
+                0x3026_0607,
+                0x0809_0A0B,
+                0x3034_0C0D,
+                0x0E0F_0000,
+            ]),
+            Ok(6)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex7_bad_sequence() {
+        assert_eq!(
+            ump(&[0x3016_0001, 0x0203_0405, 0x3016_0607, 0x0809_0A0B]),
+            Err(diagnostic(
+                ErrorKind::InvalidPacketFormat,
+                Offset::Word(2),
+                None,
+                Some(0x1)
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex7_missing_end() {
+        assert_eq!(
+            ump(&[0x3016_0001, 0x0203_0405]),
+            Err(diagnostic(
+                ErrorKind::Truncated,
+                Offset::Word(2),
+                Some(4),
+                Some(2)
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex7_payload_reserved_bits() {
+        assert_eq!(
+            ump_strict(&[0x3006_0001, 0x0283_0405]),
+            Err(diagnostic(
+                ErrorKind::ReservedBitsSet,
+                Offset::Word(1),
+                Some(0x0),
+                Some(0x0080_0000)
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn lenient_warns_of_each_reserved_bits_violation() {
+        let buffer = [0x3016_0081, 0x0203_0405, 0x3036_0607, 0x0809_8A0B];
+        let (_, warnings) = lenient::<crate::sysex7::Sysex7<&[u32]>>(&buffer, None).unwrap();
+        assert_eq!(
+            warnings.collect::<std::vec::Vec<_>>(),
+            [
+                diagnostic(
+                    ErrorKind::ReservedBitsSet,
+                    Offset::Word(0),
+                    Some(0x0),
+                    Some(0x0000_0080)
+                ),
+                diagnostic(
+                    ErrorKind::ReservedBitsSet,
+                    Offset::Word(3),
+                    Some(0x0),
+                    Some(0x0000_8000)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn bytes_channel_voice1() {
+        assert_eq!(bytes(&[0x93, 0x3C, 0x40]), Ok(3));
+        assert_eq!(
+            bytes(&[0x93, 0x3C]),
+            Err(diagnostic(
+                ErrorKind::Truncated,
+                Offset::Byte(2),
+                Some(3),
+                Some(2)
+            ))
+        );
+        assert_eq!(
+            bytes(&[0x93, 0x3C, 0xC0]),
+            Err(diagnostic(
+                ErrorKind::OutOfRange,
+                Offset::Byte(2),
+                Some(0x7F),
+                Some(0xC0)
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn bytes_running_status() {
+        assert_eq!(
+            bytes(&[0x3C, 0x40]),
+            Err(diagnostic(
+                ErrorKind::InvalidStatus,
+                Offset::Byte(0),
+                None,
+                Some(0x3C)
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn bytes_sysex7_missing_end() {
+        assert_eq!(
+            bytes(&[0xF0, 0x01, 0x02]),
+            Err(diagnostic(
+                ErrorKind::Truncated,
+                Offset::Byte(3),
+                None,
+                Some(3)
+            ))
+        );
+    }
+}
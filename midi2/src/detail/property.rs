@@ -14,8 +14,8 @@ pub trait WriteProperty<B: crate::buffer::Buffer + crate::buffer::BufferMut>: Pr
     // ideally the type system should do this for us so this will
     // most often just trivially return Ok
     //
-    // This function is currently unused, but we'll keep it hangin' around
-    // in case we need it sometime down the line.
+    // Only deserialization currently makes use of this function,
+    // so it goes unused without the serde feature.
     #[allow(dead_code)]
     fn validate(v: &Self::Type) -> Result<(), crate::error::InvalidData>;
     fn default() -> Self::Type;
@@ -23,6 +23,7 @@ pub trait WriteProperty<B: crate::buffer::Buffer + crate::buffer::BufferMut>: Pr
 
 // properties which may require resizing the underlying buffer
 // before writing the value
+#[allow(dead_code)]
 pub trait ResizeProperty<B: crate::buffer::Buffer + crate::buffer::BufferMut>:
     WriteProperty<B>
 {
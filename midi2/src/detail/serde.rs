@@ -0,0 +1,363 @@
+use crate::{
+    buffer::{Buffer, BufferDefault, BufferMut, BufferTryResize, Unit, UNIT_ID_U32, UNIT_ID_U8},
+    detail::property::{ReadProperty, WriteProperty},
+    error::InvalidData,
+};
+use ::serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, SeqAccess},
+    ser::{self, SerializeStruct},
+    Deserializer, Serialize, Serializer,
+};
+
+const ERR_OUT_OF_RANGE: &str = "Value out of range";
+const ERR_BUFFER_OVERFLOW: &str = "Message does not fit in the buffer";
+const ERR_UNKNOWN_VARIANT: &str = "Unknown message variant";
+
+/// A value which appears in the structured form of a message.
+///
+/// The `ux` integer types have no serde support of their own
+/// so they are represented by the smallest primitive which can hold them
+/// and range checked on the way back in.
+pub trait Field: Sized {
+    type Repr: Serialize + DeserializeOwned;
+    fn to_repr(self) -> Self::Repr;
+    fn from_repr(repr: Self::Repr) -> Result<Self, InvalidData>;
+}
+
+macro_rules! identity_field {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Field for $ty {
+                type Repr = Self;
+                fn to_repr(self) -> Self::Repr {
+                    self
+                }
+                fn from_repr(repr: Self::Repr) -> Result<Self, InvalidData> {
+                    Ok(repr)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! ux_field {
+    ($($ty:ty => $repr:ty),* $(,)?) => {
+        $(
+            impl Field for $ty {
+                type Repr = $repr;
+                fn to_repr(self) -> Self::Repr {
+                    self.into()
+                }
+                fn from_repr(repr: Self::Repr) -> Result<Self, InvalidData> {
+                    <$ty>::try_from(repr).map_err(|_| InvalidData(ERR_OUT_OF_RANGE))
+                }
+            }
+        )*
+    };
+}
+
+identity_field!(bool, u8, u16, u32);
+
+#[cfg(feature = "std")]
+identity_field!(std::string::String);
+
+#[cfg(feature = "channel-voice2")]
+identity_field!(
+    crate::channel_voice2::Controller,
+    crate::channel_voice2::NoteAttribute,
+);
+
+#[cfg(feature = "flex-data")]
+identity_field!(
+    crate::flex_data::Alteration,
+    crate::flex_data::ChordType,
+    crate::flex_data::SetChordNameSharpsFlats,
+    crate::flex_data::SetKeySignatureSharpsFlats,
+    crate::flex_data::Tonic,
+);
+
+#[cfg(feature = "ump-stream")]
+identity_field!(
+    crate::ump_stream::Direction,
+    crate::ump_stream::Midi1Port,
+    crate::ump_stream::UiHint,
+);
+
+ux_field!(
+    crate::ux::u3 => u8,
+    crate::ux::u4 => u8,
+    crate::ux::u7 => u8,
+    crate::ux::u14 => u16,
+    crate::ux::u20 => u32,
+    crate::ux::u28 => u32,
+);
+
+impl<T: Field> Field for Option<T> {
+    type Repr = Option<T::Repr>;
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+    fn from_repr(repr: Self::Repr) -> Result<Self, InvalidData> {
+        repr.map(T::from_repr).transpose()
+    }
+}
+
+impl<T: Field + Copy> Field for [T; 1] {
+    type Repr = [T::Repr; 1];
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+    fn from_repr([a]: Self::Repr) -> Result<Self, InvalidData> {
+        Ok([T::from_repr(a)?])
+    }
+}
+
+impl<T: Field + Copy> Field for [T; 2] {
+    type Repr = [T::Repr; 2];
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+    fn from_repr([a, b]: Self::Repr) -> Result<Self, InvalidData> {
+        Ok([T::from_repr(a)?, T::from_repr(b)?])
+    }
+}
+
+impl<T: Field + Copy> Field for [T; 3] {
+    type Repr = [T::Repr; 3];
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+    fn from_repr([a, b, c]: Self::Repr) -> Result<Self, InvalidData> {
+        Ok([T::from_repr(a)?, T::from_repr(b)?, T::from_repr(c)?])
+    }
+}
+
+impl<T: Field + Copy> Field for [T; 4] {
+    type Repr = [T::Repr; 4];
+    fn to_repr(self) -> Self::Repr {
+        self.map(T::to_repr)
+    }
+    fn from_repr([a, b, c, d]: Self::Repr) -> Result<Self, InvalidData> {
+        Ok([
+            T::from_repr(a)?,
+            T::from_repr(b)?,
+            T::from_repr(c)?,
+            T::from_repr(d)?,
+        ])
+    }
+}
+
+/// For use with `#[serde(with = "...")]` on the `ux` fields
+/// of the supporting enums.
+#[cfg(feature = "flex-data")]
+pub mod field {
+    use super::*;
+    use ::serde::Deserialize;
+
+    pub fn serialize<T: Field + Copy, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.to_repr().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Field, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        T::from_repr(T::Repr::deserialize(deserializer)?).map_err(invalid_data)
+    }
+}
+
+pub fn invalid_data<E: de::Error>(error: InvalidData) -> E {
+    E::custom(error.0)
+}
+
+pub fn buffer_overflow<E: de::Error, T>(_error: T) -> E {
+    E::custom(ERR_BUFFER_OVERFLOW)
+}
+
+pub fn unknown_variant<E: de::Error>() -> E {
+    E::custom(ERR_UNKNOWN_VARIANT)
+}
+
+pub fn serialize_field<'a, P, B, S>(
+    state: &mut S,
+    key: &'static str,
+    buffer: &'a B,
+) -> Result<(), S::Error>
+where
+    B: Buffer,
+    P: ReadProperty<'a, B>,
+    P::Type: Field,
+    S: SerializeStruct,
+{
+    state.serialize_field(key, &P::read(buffer).to_repr())
+}
+
+pub fn deserialize_field<'de, P, B, A>(map: &mut A, buffer: &mut B) -> Result<(), A::Error>
+where
+    B: Buffer + BufferMut,
+    P: WriteProperty<B>,
+    P::Type: Field,
+    A: de::MapAccess<'de>,
+{
+    let repr = map.next_value::<<P::Type as Field>::Repr>()?;
+    let value = <P::Type as Field>::from_repr(repr).map_err(invalid_data)?;
+    P::validate(&value).map_err(invalid_data)?;
+    P::write(buffer, value);
+    Ok(())
+}
+
+/// Looks up the index of a field or variant name.
+/// Unknown names map to `None`.
+pub struct FieldIndex(pub &'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for FieldIndex {
+    type Value = Option<usize>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl de::Visitor<'_> for FieldIndex {
+    type Value = Option<usize>;
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a field identifier")
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(usize::try_from(v).ok().filter(|&i| i < self.0.len()))
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(self.0.iter().position(|&name| name == v))
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(self.0.iter().position(|&name| name.as_bytes() == v))
+    }
+}
+
+/// The visitor of the structured form of messages and aggregate enums.
+/// The `Visitor` impls are generated alongside the types.
+pub struct MessageVisitor<T>(core::marker::PhantomData<T>);
+
+impl<T> MessageVisitor<T> {
+    pub fn new() -> Self {
+        MessageVisitor(core::marker::PhantomData)
+    }
+}
+
+/// Serializes the payload of a sysex message as a sequence.
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+pub struct Payload<'a, M, B>(pub &'a M, pub core::marker::PhantomData<B>);
+
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+impl<M, B> Serialize for Payload<'_, M, B>
+where
+    B: Buffer,
+    M: crate::traits::Sysex<B>,
+    M::Byte: Field + Copy,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.payload().map(Field::to_repr))
+    }
+}
+
+/// Appends the elements of a sequence to the payload of a sysex message.
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+pub struct PayloadSeed<'a, M, B>(pub &'a mut M, pub core::marker::PhantomData<B>);
+
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+impl<'de, M, B> DeserializeSeed<'de> for PayloadSeed<'_, M, B>
+where
+    B: Buffer + BufferMut + BufferTryResize,
+    M: crate::traits::Sysex<B>,
+    M::Byte: Field,
+{
+    type Value = ();
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+impl<'de, M, B> de::Visitor<'de> for PayloadSeed<'_, M, B>
+where
+    B: Buffer + BufferMut + BufferTryResize,
+    M: crate::traits::Sysex<B>,
+    M::Byte: Field,
+{
+    type Value = ();
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a sequence of payload bytes")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        self.0
+            .try_set_payload(core::iter::empty())
+            .map_err(buffer_overflow)?;
+        while let Some(repr) = seq.next_element::<<M::Byte as Field>::Repr>()? {
+            let byte = <M::Byte as Field>::from_repr(repr).map_err(invalid_data)?;
+            self.0.try_append_byte(byte).map_err(buffer_overflow)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes message data as a sequence of its raw units.
+pub fn serialize_units<U: Unit, S: Serializer>(
+    data: &[U],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match U::UNIT_ID {
+        UNIT_ID_U8 => serializer.collect_seq(U::specialise_buffer_u8(data)),
+        UNIT_ID_U32 => serializer.collect_seq(U::specialise_buffer_u32(data)),
+        _ => Err(ser::Error::custom("Unknown unit type")),
+    }
+}
+
+/// Deserializes a sequence of raw units into a new buffer.
+/// Returns the buffer together with the number of units read.
+pub fn deserialize_units<'de, B, D>(deserializer: D) -> Result<(B, usize), D::Error>
+where
+    B: Buffer + BufferMut + BufferDefault + BufferTryResize,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_seq(UnitsVisitor(core::marker::PhantomData))
+}
+
+struct UnitsVisitor<B>(core::marker::PhantomData<B>);
+
+impl<'de, B> de::Visitor<'de> for UnitsVisitor<B>
+where
+    B: Buffer + BufferMut + BufferDefault + BufferTryResize,
+{
+    type Value = (B, usize);
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a sequence of message data")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        use crate::buffer::{SpecialiseU32, SpecialiseU8};
+
+        let mut buffer = B::default();
+        let mut size = 0;
+        loop {
+            match <B::Unit as crate::buffer::UnitPrivate>::UNIT_ID {
+                UNIT_ID_U8 => {
+                    let Some(unit) = seq.next_element::<u8>()? else {
+                        break;
+                    };
+                    buffer.try_resize(size + 1).map_err(buffer_overflow)?;
+                    buffer.specialise_u8_mut()[size] = unit;
+                }
+                UNIT_ID_U32 => {
+                    let Some(unit) = seq.next_element::<u32>()? else {
+                        break;
+                    };
+                    buffer.try_resize(size + 1).map_err(buffer_overflow)?;
+                    buffer.specialise_u32_mut()[size] = unit;
+                }
+                _ => unreachable!(),
+            }
+            size += 1;
+        }
+        Ok((buffer, size))
+    }
+}
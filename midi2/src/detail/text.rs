@@ -0,0 +1,817 @@
+use crate::{
+    buffer::{Buffer, BufferMut},
+    detail::property::{ReadProperty, WriteProperty},
+    error::InvalidData,
+};
+use core::fmt::{Formatter, Result as FmtResult, Write};
+
+pub const ERR_UNEXPECTED_NAME: &str = "Unexpected message name";
+pub const ERR_UNKNOWN_MESSAGE: &str = "Couldn't interpret message name";
+const ERR_BUFFER_OVERFLOW: &str = "Message does not fit in the buffer";
+const ERR_EMPTY: &str = "Empty message text";
+const ERR_INVALID_VALUE: &str = "Couldn't interpret value";
+const ERR_UNKNOWN_FIELD: &str = "Unknown field";
+const ERR_UNTERMINATED: &str = "Unterminated quote or bracket";
+
+/// A value which appears in the text form of a message.
+///
+/// Small integers are written in decimal and wide data values in hex.
+/// Either base is accepted when parsing.
+pub trait Text: Sized {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult;
+    fn parse_text(s: &str) -> Result<Self, InvalidData>;
+}
+
+fn parse_integer(s: &str) -> Result<u32, InvalidData> {
+    let result = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse::<u32>(),
+    };
+    result.map_err(|_| InvalidData(ERR_INVALID_VALUE))
+}
+
+macro_rules! decimal_text {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Text for $ty {
+                fn write_text(&self, f: &mut Formatter) -> FmtResult {
+                    write!(f, "{}", u32::from(*self))
+                }
+                fn parse_text(s: &str) -> Result<Self, InvalidData> {
+                    <$ty>::try_from(parse_integer(s)?).map_err(|_| InvalidData(ERR_INVALID_VALUE))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! hex_text {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Text for $ty {
+                fn write_text(&self, f: &mut Formatter) -> FmtResult {
+                    write!(f, "{:#X}", u32::from(*self))
+                }
+                fn parse_text(s: &str) -> Result<Self, InvalidData> {
+                    <$ty>::try_from(parse_integer(s)?).map_err(|_| InvalidData(ERR_INVALID_VALUE))
+                }
+            }
+        )*
+    };
+}
+
+decimal_text!(u8, crate::ux::u3, crate::ux::u4, crate::ux::u7);
+hex_text!(u16, u32, crate::ux::u14, crate::ux::u20, crate::ux::u28);
+
+impl Text for bool {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{self}")
+    }
+    fn parse_text(s: &str) -> Result<Self, InvalidData> {
+        s.parse().map_err(|_| InvalidData(ERR_INVALID_VALUE))
+    }
+}
+
+impl Text for crate::num::Fixed7_9 {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{self}")
+    }
+    fn parse_text(s: &str) -> Result<Self, InvalidData> {
+        s.parse().map_err(|_| InvalidData(ERR_INVALID_VALUE))
+    }
+}
+
+impl Text for crate::num::Fixed7_25 {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{self}")
+    }
+    fn parse_text(s: &str) -> Result<Self, InvalidData> {
+        s.parse().map_err(|_| InvalidData(ERR_INVALID_VALUE))
+    }
+}
+
+impl<T: Text> Text for Option<T> {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Some(v) => v.write_text(f),
+            None => f.write_str("None"),
+        }
+    }
+    fn parse_text(s: &str) -> Result<Self, InvalidData> {
+        match s {
+            "None" => Ok(None),
+            _ => T::parse_text(s).map(Some),
+        }
+    }
+}
+
+impl<T: Text + Copy + Default, const SIZE: usize> Text for [T; SIZE] {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult {
+        f.write_char('[')?;
+        write_list(self.iter(), f)?;
+        f.write_char(']')
+    }
+    fn parse_text(s: &str) -> Result<Self, InvalidData> {
+        let list = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or(InvalidData(ERR_INVALID_VALUE))?;
+        let mut ret = [T::default(); SIZE];
+        parse_list(list, &mut ret)?;
+        Ok(ret)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Text for std::string::String {
+    fn write_text(&self, f: &mut Formatter) -> FmtResult {
+        f.write_char('"')?;
+        for c in self.chars() {
+            if c == '"' || c == '\\' {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        f.write_char('"')
+    }
+    fn parse_text(s: &str) -> Result<Self, InvalidData> {
+        let s = s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .ok_or(InvalidData(ERR_INVALID_VALUE))?;
+        let mut ret = std::string::String::with_capacity(s.len());
+        let mut escaped = false;
+        for c in s.chars() {
+            if c == '\\' && !escaped {
+                escaped = true;
+                continue;
+            }
+            escaped = false;
+            ret.push(c);
+        }
+        Ok(ret)
+    }
+}
+
+/// Implements [Text] for the supporting enums.
+///
+/// Unit variants are written as their name,
+/// and variants holding data as `Name(a)` or `Name(a,b)`.
+#[cfg(any(
+    feature = "channel-voice2",
+    feature = "flex-data",
+    feature = "ump-stream"
+))]
+macro_rules! variants_text {
+    (
+        $ty:ty {
+            $($unit:ident),* ;
+            $($newtype:ident),* ;
+            $($pair:ident { $a:ident, $b:ident }),* $(;)?
+        }
+    ) => {
+        impl Text for $ty {
+            fn write_text(&self, f: &mut Formatter) -> FmtResult {
+                match self {
+                    $(Self::$unit => f.write_str(stringify!($unit)),)*
+                    $(Self::$newtype(v) => {
+                        f.write_str(concat!(stringify!($newtype), "("))?;
+                        v.write_text(f)?;
+                        f.write_char(')')
+                    })*
+                    $(Self::$pair { $a, $b } => {
+                        f.write_str(concat!(stringify!($pair), "("))?;
+                        $a.write_text(f)?;
+                        f.write_char(',')?;
+                        $b.write_text(f)?;
+                        f.write_char(')')
+                    })*
+                }
+            }
+            fn parse_text(s: &str) -> Result<Self, InvalidData> {
+                $(if s == stringify!($unit) {
+                    return Ok(Self::$unit);
+                })*
+                $(if let Some(arguments) = variant_arguments(s, stringify!($newtype)) {
+                    let mut arguments = arguments.split(',');
+                    let v = variant_argument(&mut arguments)?;
+                    end_of_arguments(&mut arguments)?;
+                    return Ok(Self::$newtype(v));
+                })*
+                $(if let Some(arguments) = variant_arguments(s, stringify!($pair)) {
+                    let mut arguments = arguments.split(',');
+                    let $a = variant_argument(&mut arguments)?;
+                    let $b = variant_argument(&mut arguments)?;
+                    end_of_arguments(&mut arguments)?;
+                    return Ok(Self::$pair { $a, $b });
+                })*
+                Err(InvalidData(ERR_INVALID_VALUE))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "channel-voice2")]
+variants_text!(crate::channel_voice2::Controller {
+    ;
+    Modulation,
+    Breath,
+    Pitch7_25,
+    Volume,
+    Balance,
+    Pan,
+    Expression,
+    SoundVariation,
+    Timbre,
+    ReleaseTime,
+    AttackTime,
+    Brightness,
+    DecayTime,
+    VebratoRate,
+    VebratoDepth,
+    VebratoDelay,
+    Undefined,
+    ReverbSendLevel,
+    ChorusSendLevel;
+    SoundController { index, data },
+    EffectDepth { index, data }
+});
+
+#[cfg(feature = "channel-voice2")]
+variants_text!(crate::channel_voice2::NoteAttribute {
+    ;
+    ManufacturerSpecific,
+    ProfileSpecific,
+    Pitch7_9;
+});
+
+#[cfg(feature = "flex-data")]
+variants_text!(crate::flex_data::Alteration {
+    ;
+    Add,
+    Subtract,
+    Raise,
+    Lower;
+});
+
+#[cfg(feature = "flex-data")]
+variants_text!(crate::flex_data::ChordType {
+    ClearChord,
+    Major,
+    Major6th,
+    Major7th,
+    Major9th,
+    Major11th,
+    Major13th,
+    Minor,
+    Minor6th,
+    Minor7th,
+    Minor9th,
+    Minor11th,
+    Minor13th,
+    Dominant,
+    Dominant9th,
+    Dominant11th,
+    Dominant13th,
+    Augmented,
+    Augmented7th,
+    Diminished,
+    Diminished7th,
+    HalfDiminished,
+    MajorMinor,
+    Pedal,
+    Power,
+    Suspended2nd,
+    Suspended4th;
+    ;
+});
+
+#[cfg(feature = "flex-data")]
+variants_text!(crate::flex_data::SetChordNameSharpsFlats {
+    DoubleSharp,
+    Sharp,
+    Natural,
+    Flat,
+    DoubleFlat;
+    ;
+});
+
+#[cfg(feature = "flex-data")]
+variants_text!(crate::flex_data::SetKeySignatureSharpsFlats {
+    NonStandard;
+    Flats,
+    Sharps;
+});
+
+#[cfg(feature = "flex-data")]
+variants_text!(crate::flex_data::Tonic {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    NonStandard;
+    ;
+});
+
+#[cfg(feature = "ump-stream")]
+variants_text!(crate::ump_stream::Direction {
+    Input,
+    Output,
+    Bidirectional;
+    ;
+});
+
+#[cfg(feature = "ump-stream")]
+variants_text!(crate::ump_stream::Midi1Port {
+    RestrictBandwidth,
+    DontRestrictBandwidth;
+    ;
+});
+
+#[cfg(feature = "ump-stream")]
+variants_text!(crate::ump_stream::UiHint {
+    Undeclared,
+    Sender,
+    Receiver,
+    SenderReciever;
+    ;
+});
+
+/// Writes values separated by commas.
+pub fn write_list<'a, T: Text + 'a>(
+    values: impl core::iter::Iterator<Item = &'a T>,
+    f: &mut Formatter,
+) -> FmtResult {
+    for (i, v) in values.enumerate() {
+        if i != 0 {
+            f.write_char(',')?;
+        }
+        v.write_text(f)?;
+    }
+    Ok(())
+}
+
+/// Parses a comma separated list which should hold exactly one value for each slot.
+pub fn parse_list<T: Text>(s: &str, slots: &mut [T]) -> Result<(), InvalidData> {
+    let mut values = s.split(',');
+    for slot in slots.iter_mut() {
+        *slot = T::parse_text(values.next().ok_or(InvalidData(ERR_INVALID_VALUE))?.trim())?;
+    }
+    if values.next().is_some() {
+        return Err(InvalidData(ERR_INVALID_VALUE));
+    }
+    Ok(())
+}
+
+/// Splits the arguments from an enum variant of the form `Name(a,b)`.
+#[cfg(any(feature = "channel-voice2", feature = "flex-data"))]
+pub fn variant_arguments<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+#[cfg(any(feature = "channel-voice2", feature = "flex-data"))]
+pub fn variant_argument<T: Text>(arguments: &mut core::str::Split<char>) -> Result<T, InvalidData> {
+    T::parse_text(
+        arguments
+            .next()
+            .ok_or(InvalidData(ERR_INVALID_VALUE))?
+            .trim(),
+    )
+}
+
+#[cfg(any(feature = "channel-voice2", feature = "flex-data"))]
+pub fn end_of_arguments(arguments: &mut core::str::Split<char>) -> Result<(), InvalidData> {
+    match arguments.next() {
+        None => Ok(()),
+        Some(_) => Err(InvalidData(ERR_INVALID_VALUE)),
+    }
+}
+
+pub fn write_property<'a, P, B>(buffer: &'a B, f: &mut Formatter) -> FmtResult
+where
+    B: Buffer,
+    P: ReadProperty<'a, B>,
+    P::Type: Text,
+{
+    P::read(buffer).write_text(f)
+}
+
+pub fn parse_property<P, B>(value: &str, buffer: &mut B) -> Result<(), InvalidData>
+where
+    B: Buffer + BufferMut,
+    P: WriteProperty<B>,
+    P::Type: Text,
+{
+    let value = <P::Type as Text>::parse_text(value)?;
+    P::validate(&value)?;
+    P::write(buffer, value);
+    Ok(())
+}
+
+/// Writes sysex payload bytes as space separated hex.
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+pub fn write_payload<T: Into<u8>>(
+    payload: impl core::iter::Iterator<Item = T>,
+    f: &mut Formatter,
+) -> FmtResult {
+    f.write_char('[')?;
+    for (i, b) in payload.enumerate() {
+        if i != 0 {
+            f.write_char(' ')?;
+        }
+        write!(f, "{:02X}", b.into())?;
+    }
+    f.write_char(']')
+}
+
+#[cfg(any(feature = "sysex7", feature = "sysex8"))]
+pub fn parse_payload<T: TryFrom<u8>>(
+    s: &str,
+) -> impl core::iter::Iterator<Item = Result<T, InvalidData>> + '_ {
+    s.split_whitespace().map(|b| {
+        u8::from_str_radix(b, 16)
+            .ok()
+            .and_then(|b| T::try_from(b).ok())
+            .ok_or(InvalidData(ERR_INVALID_VALUE))
+    })
+}
+
+/// A whitespace separated element of the text form of a message.
+pub enum Token<'a> {
+    Group(&'a str),
+    #[cfg(any(feature = "channel-voice1", feature = "channel-voice2"))]
+    Channel(&'a str),
+    Field(&'a str, &'a str),
+    #[cfg(any(feature = "sysex7", feature = "sysex8"))]
+    Payload(&'a str),
+}
+
+impl<'a> Token<'a> {
+    fn new(s: &'a str) -> Result<Self, InvalidData> {
+        #[cfg(any(feature = "sysex7", feature = "sysex8"))]
+        if let Some(payload) = s.strip_prefix('[') {
+            return payload
+                .strip_suffix(']')
+                .map(Token::Payload)
+                .ok_or(InvalidData(ERR_UNTERMINATED));
+        }
+        if let Some((key, value)) = s.split_once('=') {
+            return Ok(Token::Field(key, value));
+        }
+        #[cfg(any(feature = "channel-voice1", feature = "channel-voice2"))]
+        if let Some(channel) = s.strip_prefix("ch") {
+            return Ok(Token::Channel(channel));
+        }
+        if let Some(group) = s.strip_prefix('g') {
+            return Ok(Token::Group(group));
+        }
+        Err(InvalidData(ERR_UNKNOWN_FIELD))
+    }
+}
+
+pub fn unknown_field<T>() -> Result<T, InvalidData> {
+    Err(InvalidData(ERR_UNKNOWN_FIELD))
+}
+
+pub fn buffer_overflow<T>(_error: T) -> InvalidData {
+    InvalidData(ERR_BUFFER_OVERFLOW)
+}
+
+/// Splits message text into its name and the following tokens.
+///
+/// Tokens are separated by whitespace,
+/// except within double quotes or square brackets.
+pub fn tokens<'a>(
+    s: &'a str,
+    name: &str,
+) -> Result<impl core::iter::Iterator<Item = Result<Token<'a>, InvalidData>>, InvalidData> {
+    let mut rest = s;
+    if next_token(&mut rest).ok_or(InvalidData(ERR_EMPTY))?? != name {
+        return Err(InvalidData(ERR_UNEXPECTED_NAME));
+    }
+    Ok(core::iter::from_fn(move || {
+        Some(next_token(&mut rest)?.and_then(Token::new))
+    }))
+}
+
+fn next_token<'a>(rest: &mut &'a str) -> Option<Result<&'a str, InvalidData>> {
+    let s = rest.trim_start();
+    if s.is_empty() {
+        *rest = s;
+        return None;
+    }
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut brackets = 0_usize;
+    let mut end = s.len();
+    for (i, c) in s.char_indices() {
+        if in_quotes {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            '[' => brackets += 1,
+            ']' => brackets = brackets.saturating_sub(1),
+            c if c.is_whitespace() && brackets == 0 => {
+                end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    if in_quotes || brackets != 0 {
+        *rest = "";
+        return Some(Err(InvalidData(ERR_UNTERMINATED)));
+    }
+    let (token, remainder) = s.split_at(end);
+    *rest = remainder;
+    Some(Ok(token))
+}
+
+/// Tries to parse one variant of an aggregate message enum.
+///
+/// Errors from variants which don't recognise the message name are ignored,
+/// so that the error returned for unparsable text comes from the message
+/// it was meant to describe.
+pub fn parse_variant<T>(s: &str, error: &mut InvalidData) -> Option<T>
+where
+    T: core::str::FromStr<Err = InvalidData>,
+{
+    let is_unmatched = |e: &InvalidData| e.0 == ERR_UNEXPECTED_NAME || e.0 == ERR_UNKNOWN_MESSAGE;
+    match s.parse::<T>() {
+        Ok(message) => Some(message),
+        Err(e) => {
+            if is_unmatched(error) && !is_unmatched(&e) {
+                *error = e;
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+
+    fn collect(s: &str) -> Result<std::vec::Vec<std::string::String>, InvalidData> {
+        let mut rest = s;
+        let mut ret = std::vec::Vec::new();
+        while let Some(token) = next_token(&mut rest) {
+            ret.push(std::string::String::from(token?));
+        }
+        Ok(ret)
+    }
+
+    #[test]
+    fn tokens_split_on_whitespace() {
+        assert_eq!(
+            collect("  NoteOn g0\tch1   note=60 ").unwrap(),
+            ["NoteOn", "g0", "ch1", "note=60"],
+        );
+    }
+
+    #[test]
+    fn tokens_keep_quotes_and_brackets_together() {
+        assert_eq!(
+            collect(r#"Name text="a \"b\" c" [01 02 03]"#).unwrap(),
+            ["Name", r#"text="a \"b\" c""#, "[01 02 03]"],
+        );
+    }
+
+    #[test]
+    fn tokens_unterminated() {
+        assert_eq!(
+            collect(r#"Name text="abc"#),
+            Err(InvalidData(ERR_UNTERMINATED))
+        );
+        assert_eq!(collect("Name [01 02"), Err(InvalidData(ERR_UNTERMINATED)));
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(crate::ux::u7::parse_text("60"), Ok(crate::ux::u7::new(60)));
+        assert_eq!(
+            crate::ux::u7::parse_text("0x3C"),
+            Ok(crate::ux::u7::new(60))
+        );
+        assert_eq!(
+            crate::ux::u7::parse_text("128"),
+            Err(InvalidData(ERR_INVALID_VALUE))
+        );
+        assert_eq!(u16::parse_text("0x8000"), Ok(0x8000));
+        assert_eq!(u16::parse_text("-1"), Err(InvalidData(ERR_INVALID_VALUE)));
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            std::string::String::parse_text(r#""a \"b\" \\ c""#),
+            Ok(std::string::String::from(r#"a "b" \ c"#)),
+        );
+    }
+
+    #[test]
+    fn display() {
+        let message =
+            crate::channel_voice2::NoteOn::try_from(&[0x4898_5E03, 0x6A14_E98A][..]).unwrap();
+        assert_eq!(
+            std::format!("{message}"),
+            "NoteOn g8 ch8 note=94 vel=0x6A14 attr=Pitch7_9(116.77)",
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let message =
+            crate::channel_voice2::NoteOn::try_from(&[0x4898_5E03, 0x6A14_E98A][..]).unwrap();
+        let parsed: crate::channel_voice2::NoteOn<[u32; 4]> =
+            std::format!("{message}").parse().unwrap();
+        assert_eq!(parsed.data(), message.data());
+    }
+
+    #[test]
+    fn parse_takes_defaults_for_missing_fields() {
+        let message: crate::channel_voice2::NoteOn<[u32; 4]> =
+            "NoteOn  note=0x3C\tch1".parse().unwrap();
+        assert_eq!(message.data(), &[0x4091_3C00, 0x0]);
+    }
+
+    #[test]
+    fn parse_full_field_names() {
+        let message: crate::channel_voice2::NoteOn<[u32; 4]> =
+            "NoteOn note_number=60 velocity=0x8000 attribute=None"
+                .parse()
+                .unwrap();
+        assert_eq!(message.data(), &[0x4090_3C00, 0x8000_0000]);
+    }
+
+    #[test]
+    fn parse_controller() {
+        let message: crate::channel_voice2::RegisteredPerNoteController<[u32; 4]> =
+            "RegisteredPerNoteController note=60 controller=SoundController(2,0x10)"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            std::format!("{message}"),
+            "RegisteredPerNoteController g0 ch0 note=60 controller=SoundController(2,0x10)",
+        );
+        assert_eq!(
+            "RegisteredPerNoteController controller=SoundController(11,0x10)"
+                .parse::<crate::channel_voice2::RegisteredPerNoteController<[u32; 4]>>(),
+            Err(InvalidData("Couldn't interpret controller index")),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        type NoteOn = crate::channel_voice2::NoteOn<[u32; 4]>;
+        assert_eq!("".parse::<NoteOn>(), Err(InvalidData(ERR_EMPTY)));
+        assert_eq!(
+            "NoteOff g0".parse::<NoteOn>(),
+            Err(InvalidData(ERR_UNEXPECTED_NAME))
+        );
+        assert_eq!(
+            "NoteOn colour=blue".parse::<NoteOn>(),
+            Err(InvalidData(ERR_UNKNOWN_FIELD))
+        );
+        assert_eq!(
+            "NoteOn note=128".parse::<NoteOn>(),
+            Err(InvalidData(ERR_INVALID_VALUE))
+        );
+        assert_eq!(
+            "NoteOn".parse::<crate::channel_voice2::NoteOn<[u32; 1]>>(),
+            Err(InvalidData(ERR_BUFFER_OVERFLOW))
+        );
+    }
+
+    #[test]
+    fn aggregate() {
+        let message = crate::UmpMessage::try_from(&[0x4898_5E00, 0x6A14_0000][..]).unwrap();
+        let text = std::format!("{message}");
+        assert_eq!(text, "NoteOn g8 ch8 note=94 vel=0x6A14 attr=None");
+        assert_eq!(
+            text.parse::<crate::UmpMessage<[u32; 4]>>().unwrap().data(),
+            message.data()
+        );
+    }
+
+    #[test]
+    fn aggregate_errors() {
+        type UmpMessage = crate::UmpMessage<[u32; 4]>;
+        assert_eq!(
+            "Sysex9 g0".parse::<UmpMessage>(),
+            Err(InvalidData(ERR_UNKNOWN_MESSAGE))
+        );
+        assert_eq!(
+            "NoteOn vel=0x10000".parse::<UmpMessage>(),
+            Err(InvalidData(ERR_INVALID_VALUE))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn aggregate_errors_with_vec() {
+        type UmpMessage = crate::UmpMessage<std::vec::Vec<u32>>;
+        assert_eq!(
+            "Garbage".parse::<UmpMessage>(),
+            Err(InvalidData(ERR_UNKNOWN_MESSAGE))
+        );
+        assert_eq!("".parse::<UmpMessage>(), Err(InvalidData(ERR_EMPTY)));
+        assert_eq!(
+            "EndOfClip".parse::<UmpMessage>().unwrap().data(),
+            &[0xF021_0000]
+        );
+    }
+
+    #[test]
+    fn reserved_message_type_round_trip() {
+        let message = crate::UmpMessage::try_from(&[0x9312_3456, 0x789A_BCDE][..]).unwrap();
+        let text = std::format!("{message}");
+        assert_eq!(
+            text,
+            "UnknownMessage g3 message_type=9 raw_data=[0x123456,0x789ABCDE,0x0,0x0]"
+        );
+        let parsed = text.parse::<crate::UmpMessage<[u32; 4]>>().unwrap();
+        assert_eq!(parsed.data(), message.data());
+    }
+
+    #[test]
+    fn unknown_round_trip() {
+        let (message, _) =
+            crate::UmpMessage::try_from_lenient(&[0x4378_1234, 0x5678_9ABC][..]).unwrap();
+        let text = std::format!("{message}");
+        assert_eq!(
+            text,
+            "UnknownChannelVoice2 g3 ch8 raw_data=[0x701234,0x56789ABC]"
+        );
+        assert_eq!(
+            text.parse::<crate::UmpMessage<[u32; 4]>>().unwrap().data(),
+            message.data()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn bytes_message_has_no_group() {
+        let message = crate::channel_voice1::NoteOn::try_from(&[0x93_u8, 0x3C, 0x40][..]).unwrap();
+        assert_eq!(std::format!("{message}"), "NoteOn ch3 note=60 vel=64");
+        assert_eq!(
+            "NoteOn ch3 note=60 vel=64"
+                .parse::<crate::channel_voice1::NoteOn<[u8; 3]>>()
+                .unwrap()
+                .data(),
+            &[0x93, 0x3C, 0x40],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex7() {
+        use crate::traits::{Grouped, Sysex};
+
+        let mut message = crate::sysex7::Sysex7::<std::vec::Vec<u32>>::new();
+        message.set_group(crate::ux::u4::new(0x2));
+        message.set_payload((0..3).map(crate::ux::u7::new));
+        assert_eq!(std::format!("{message}"), "Sysex7 g2 [00 01 02]");
+        assert_eq!(
+            "Sysex7 g2 [00 01 02]"
+                .parse::<crate::sysex7::Sysex7<[u32; 8]>>()
+                .unwrap()
+                .data(),
+            message.data(),
+        );
+        assert_eq!(
+            "Sysex7 [00 80]".parse::<crate::sysex7::Sysex7<[u32; 8]>>(),
+            Err(InvalidData(ERR_INVALID_VALUE))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn flex_data_text() {
+        let mut message = crate::flex_data::ProjectName::<std::vec::Vec<u32>>::new();
+        message.set_text("The \"Moon\"");
+        let text = std::format!("{message}");
+        assert_eq!(
+            text,
+            r#"ProjectName g0 optional_channel=None text="The \"Moon\"""#
+        );
+        assert_eq!(
+            text.parse::<crate::flex_data::ProjectName<std::vec::Vec<u32>>>()
+                .unwrap()
+                .data(),
+            message.data(),
+        );
+    }
+}
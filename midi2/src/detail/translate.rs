@@ -0,0 +1,535 @@
+// Translation of channel voice messages between the MIDI 1.0
+// and MIDI 2.0 protocols, following the default translation
+// described in the UMP specification.
+
+use crate::{
+    channel_voice1::{self as cv1, ChannelVoice1},
+    channel_voice2::{self as cv2, ChannelVoice2},
+    traits::Channeled,
+    ux::{u14, u4, u7},
+};
+
+const BANK_SELECT_MSB: u8 = 0;
+const BANK_SELECT_LSB: u8 = 32;
+const DATA_ENTRY_MSB: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+const NRPN_LSB: u8 = 98;
+const NRPN_MSB: u8 = 99;
+const RPN_LSB: u8 = 100;
+const RPN_MSB: u8 = 101;
+
+// a note on with zero velocity is translated to
+// a note off with the default velocity
+const NOTE_OFF_VELOCITY: u16 = 0x8000;
+
+/// Scale a value up to a greater bit depth with the min-center-max algorithm.
+pub fn scale_up(value: u32, src_bits: u32, dst_bits: u32) -> u32 {
+    let scale_bits = dst_bits - src_bits;
+    let bit_shifted = value << scale_bits;
+    let src_center = 1 << (src_bits - 1);
+    if value <= src_center {
+        return bit_shifted;
+    }
+
+    let repeat_bits = src_bits - 1;
+    let repeat_mask = (1 << repeat_bits) - 1;
+    let mut repeat_value = value & repeat_mask;
+    if scale_bits > repeat_bits {
+        repeat_value <<= scale_bits - repeat_bits;
+    } else {
+        repeat_value >>= repeat_bits - scale_bits;
+    }
+
+    let mut result = bit_shifted;
+    while repeat_value != 0 {
+        result |= repeat_value;
+        repeat_value >>= repeat_bits;
+    }
+    result
+}
+
+pub fn scale_down(value: u32, src_bits: u32, dst_bits: u32) -> u32 {
+    value >> (src_bits - dst_bits)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ParameterKind {
+    #[default]
+    None,
+    Registered,
+    Assignable,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Parameter {
+    kind: ParameterKind,
+    bank: u7,
+    index: u7,
+}
+
+impl Parameter {
+    fn is_selected(&self) -> bool {
+        self.kind != ParameterKind::None
+            && !(self.bank == u7::max_value() && self.index == u7::max_value())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct UpgradeState {
+    bank_msb: Option<u7>,
+    bank_lsb: Option<u7>,
+    parameter: Parameter,
+    data_msb: u7,
+}
+
+/// Translates MIDI 1.0 channel voice messages to MIDI 2.0.
+///
+/// Bank select and (N)RPN control changes are consumed and
+/// applied to the following program change and data entry messages.
+#[derive(Clone, Debug, Default)]
+pub struct Upgrader {
+    channels: [UpgradeState; 16],
+}
+
+impl Upgrader {
+    pub fn upgrade(
+        &mut self,
+        message: ChannelVoice1<[u8; 3]>,
+    ) -> Option<ChannelVoice2<std::vec::Vec<u32>>> {
+        let channel = message.channel();
+        let state = &mut self.channels[usize::from(u8::from(channel))];
+
+        let mut upgraded: ChannelVoice2<std::vec::Vec<u32>> = match message {
+            ChannelVoice1::NoteOn(m) if m.velocity() == u7::new(0) => {
+                let mut note_off = cv2::NoteOff::<std::vec::Vec<u32>>::new();
+                note_off.set_note_number(m.note_number());
+                note_off.set_velocity(NOTE_OFF_VELOCITY);
+                note_off.into()
+            }
+            ChannelVoice1::NoteOn(m) => {
+                let mut note_on = cv2::NoteOn::<std::vec::Vec<u32>>::new();
+                note_on.set_note_number(m.note_number());
+                note_on.set_velocity(scale_up(u32::from(m.velocity()), 7, 16) as u16);
+                note_on.into()
+            }
+            ChannelVoice1::NoteOff(m) => {
+                let mut note_off = cv2::NoteOff::<std::vec::Vec<u32>>::new();
+                note_off.set_note_number(m.note_number());
+                note_off.set_velocity(scale_up(u32::from(m.velocity()), 7, 16) as u16);
+                note_off.into()
+            }
+            ChannelVoice1::KeyPressure(m) => {
+                let mut key_pressure = cv2::KeyPressure::<std::vec::Vec<u32>>::new();
+                key_pressure.set_note_number(m.note_number());
+                key_pressure.set_key_pressure_data(scale_up(u32::from(m.pressure()), 7, 32));
+                key_pressure.into()
+            }
+            ChannelVoice1::ChannelPressure(m) => {
+                let mut channel_pressure = cv2::ChannelPressure::<std::vec::Vec<u32>>::new();
+                channel_pressure.set_channel_pressure_data(scale_up(
+                    u32::from(m.pressure()),
+                    7,
+                    32,
+                ));
+                channel_pressure.into()
+            }
+            ChannelVoice1::PitchBend(m) => {
+                let mut pitch_bend = cv2::ChannelPitchBend::<std::vec::Vec<u32>>::new();
+                pitch_bend.set_pitch_bend_data(scale_up(u32::from(m.bend()), 14, 32));
+                pitch_bend.into()
+            }
+            ChannelVoice1::ProgramChange(m) => {
+                let mut program_change = cv2::ProgramChange::<std::vec::Vec<u32>>::new();
+                program_change.set_program(m.program());
+                if state.bank_msb.is_some() || state.bank_lsb.is_some() {
+                    let msb = u16::from(state.bank_msb.unwrap_or_default());
+                    let lsb = u16::from(state.bank_lsb.unwrap_or_default());
+                    program_change.set_bank(Some(u14::new((msb << 7) | lsb)));
+                }
+                program_change.into()
+            }
+            ChannelVoice1::ControlChange(m) => upgrade_control_change(state, m)?,
+        };
+
+        upgraded.set_channel(channel);
+        Some(upgraded)
+    }
+
+    /// The relative controller message which changes the registered
+    /// or assignable controller selected on the channel by `delta`.
+    #[cfg(feature = "tracker")]
+    pub fn relative_parameter(
+        &self,
+        channel: u4,
+        delta: i32,
+    ) -> Option<ChannelVoice2<std::vec::Vec<u32>>> {
+        let parameter = self.channels[usize::from(u8::from(channel))].parameter;
+        if !parameter.is_selected() {
+            return None;
+        }
+
+        let mut message: ChannelVoice2<std::vec::Vec<u32>> =
+            if parameter.kind == ParameterKind::Registered {
+                let mut message = cv2::RelativeRegisteredController::<std::vec::Vec<u32>>::new();
+                message.set_bank(parameter.bank);
+                message.set_index(parameter.index);
+                message.set_controller_data(delta as u32);
+                message.into()
+            } else {
+                let mut message = cv2::RelativeAssignableController::<std::vec::Vec<u32>>::new();
+                message.set_bank(parameter.bank);
+                message.set_index(parameter.index);
+                message.set_controller_data(delta as u32);
+                message.into()
+            };
+        message.set_channel(channel);
+        Some(message)
+    }
+}
+
+fn upgrade_control_change(
+    state: &mut UpgradeState,
+    message: cv1::ControlChange<[u8; 3]>,
+) -> Option<ChannelVoice2<std::vec::Vec<u32>>> {
+    let value = message.control_data();
+    let select = |state: &mut UpgradeState, kind| {
+        if state.parameter.kind != kind {
+            state.parameter = Parameter {
+                kind,
+                ..Default::default()
+            };
+        }
+    };
+
+    match u8::from(message.control()) {
+        BANK_SELECT_MSB => state.bank_msb = Some(value),
+        BANK_SELECT_LSB => state.bank_lsb = Some(value),
+        RPN_MSB => {
+            select(state, ParameterKind::Registered);
+            state.parameter.bank = value;
+        }
+        RPN_LSB => {
+            select(state, ParameterKind::Registered);
+            state.parameter.index = value;
+        }
+        NRPN_MSB => {
+            select(state, ParameterKind::Assignable);
+            state.parameter.bank = value;
+        }
+        NRPN_LSB => {
+            select(state, ParameterKind::Assignable);
+            state.parameter.index = value;
+        }
+        DATA_ENTRY_MSB if state.parameter.is_selected() => {
+            state.data_msb = value;
+            return Some(parameter_message(state.parameter, value, u7::new(0)));
+        }
+        DATA_ENTRY_LSB if state.parameter.is_selected() => {
+            return Some(parameter_message(state.parameter, state.data_msb, value));
+        }
+        _ => {
+            let mut control_change = cv2::ControlChange::<std::vec::Vec<u32>>::new();
+            control_change.set_control(message.control());
+            control_change.set_control_change_data(scale_up(u32::from(value), 7, 32));
+            return Some(control_change.into());
+        }
+    }
+    None
+}
+
+fn parameter_message(parameter: Parameter, msb: u7, lsb: u7) -> ChannelVoice2<std::vec::Vec<u32>> {
+    let data = scale_up((u32::from(msb) << 7) | u32::from(lsb), 14, 32);
+    if parameter.kind == ParameterKind::Registered {
+        let mut message = cv2::RegisteredController::<std::vec::Vec<u32>>::new();
+        message.set_bank(parameter.bank);
+        message.set_index(parameter.index);
+        message.set_controller_data(data);
+        message.into()
+    } else {
+        let mut message = cv2::AssignableController::<std::vec::Vec<u32>>::new();
+        message.set_bank(parameter.bank);
+        message.set_index(parameter.index);
+        message.set_controller_data(data);
+        message.into()
+    }
+}
+
+/// Translates MIDI 2.0 channel voice messages to MIDI 1.0.
+///
+/// Controllers and banked program changes expand into
+/// several control change messages. Messages without a MIDI 1.0
+/// equivalent, such as per note controllers, are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct Downgrader {
+    parameters: [Parameter; 16],
+}
+
+impl Downgrader {
+    pub fn downgrade<B: crate::buffer::Ump>(
+        &mut self,
+        message: &ChannelVoice2<B>,
+        output: &mut std::vec::Vec<ChannelVoice1<[u8; 3]>>,
+    ) {
+        let channel = message.channel();
+        let first = output.len();
+
+        match message {
+            ChannelVoice2::NoteOn(m) => {
+                let mut note_on = cv1::NoteOn::<[u8; 3]>::new();
+                note_on.set_note_number(m.note_number());
+                // a zero velocity would be interpreted as a note off
+                let velocity = scale_down(u32::from(m.velocity()), 16, 7).max(1);
+                note_on.set_velocity(u7::new(velocity as u8));
+                output.push(note_on.into());
+            }
+            ChannelVoice2::NoteOff(m) => {
+                let mut note_off = cv1::NoteOff::<[u8; 3]>::new();
+                note_off.set_note_number(m.note_number());
+                note_off.set_velocity(u7::new(scale_down(u32::from(m.velocity()), 16, 7) as u8));
+                output.push(note_off.into());
+            }
+            ChannelVoice2::KeyPressure(m) => {
+                let mut key_pressure = cv1::KeyPressure::<[u8; 3]>::new();
+                key_pressure.set_note_number(m.note_number());
+                key_pressure.set_pressure(u7::new(scale_down(m.key_pressure_data(), 32, 7) as u8));
+                output.push(key_pressure.into());
+            }
+            ChannelVoice2::ChannelPressure(m) => {
+                let mut channel_pressure = cv1::ChannelPressure::<[u8; 3]>::new();
+                channel_pressure
+                    .set_pressure(u7::new(scale_down(m.channel_pressure_data(), 32, 7) as u8));
+                output.push(channel_pressure.into());
+            }
+            ChannelVoice2::ChannelPitchBend(m) => {
+                let mut pitch_bend = cv1::PitchBend::<[u8; 3]>::new();
+                pitch_bend.set_bend(u14::new(scale_down(m.pitch_bend_data(), 32, 14) as u16));
+                output.push(pitch_bend.into());
+            }
+            ChannelVoice2::ControlChange(m) => {
+                output.push(control_change(
+                    u8::from(m.control()),
+                    u7::new(scale_down(m.control_change_data(), 32, 7) as u8),
+                ));
+            }
+            ChannelVoice2::ProgramChange(m) => {
+                if let Some(bank) = m.bank() {
+                    let bank = u16::from(bank);
+                    output.push(control_change(BANK_SELECT_MSB, u7::new((bank >> 7) as u8)));
+                    output.push(control_change(
+                        BANK_SELECT_LSB,
+                        u7::new((bank & 0x7F) as u8),
+                    ));
+                }
+                let mut program_change = cv1::ProgramChange::<[u8; 3]>::new();
+                program_change.set_program(m.program());
+                output.push(program_change.into());
+            }
+            ChannelVoice2::RegisteredController(m) => self.downgrade_parameter(
+                channel,
+                Parameter {
+                    kind: ParameterKind::Registered,
+                    bank: m.bank(),
+                    index: m.index(),
+                },
+                m.controller_data(),
+                output,
+            ),
+            ChannelVoice2::AssignableController(m) => self.downgrade_parameter(
+                channel,
+                Parameter {
+                    kind: ParameterKind::Assignable,
+                    bank: m.bank(),
+                    index: m.index(),
+                },
+                m.controller_data(),
+                output,
+            ),
+            _ => {}
+        }
+
+        for message in output[first..].iter_mut() {
+            message.set_channel(channel);
+        }
+    }
+
+    fn downgrade_parameter(
+        &mut self,
+        channel: u4,
+        parameter: Parameter,
+        data: u32,
+        output: &mut std::vec::Vec<ChannelVoice1<[u8; 3]>>,
+    ) {
+        let selected = &mut self.parameters[usize::from(u8::from(channel))];
+        if *selected != parameter {
+            let (msb, lsb) = match parameter.kind {
+                ParameterKind::Registered => (RPN_MSB, RPN_LSB),
+                _ => (NRPN_MSB, NRPN_LSB),
+            };
+            output.push(control_change(msb, parameter.bank));
+            output.push(control_change(lsb, parameter.index));
+            *selected = parameter;
+        }
+        let data = scale_down(data, 32, 14);
+        output.push(control_change(DATA_ENTRY_MSB, u7::new((data >> 7) as u8)));
+        output.push(control_change(DATA_ENTRY_LSB, u7::new((data & 0x7F) as u8)));
+    }
+}
+
+fn control_change(control: u8, value: u7) -> ChannelVoice1<[u8; 3]> {
+    let mut message = cv1::ControlChange::<[u8; 3]>::new();
+    message.set_control(u7::new(control));
+    message.set_control_data(value);
+    message.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::{ArrayRebufferInto, Data};
+    use pretty_assertions::assert_eq;
+
+    fn upgrade_all(upgrader: &mut Upgrader, data: &[[u8; 3]]) -> std::vec::Vec<std::vec::Vec<u32>> {
+        data.iter()
+            .filter_map(|bytes| {
+                upgrader.upgrade(
+                    ChannelVoice1::try_from(&bytes[..])
+                        .unwrap()
+                        .array_rebuffer_into(),
+                )
+            })
+            .map(|m| m.data().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn scale_up_min_center_max() {
+        assert_eq!(scale_up(0x0, 7, 16), 0x0);
+        assert_eq!(scale_up(0x40, 7, 16), 0x8000);
+        assert_eq!(scale_up(0x7F, 7, 16), 0xFFFF);
+        assert_eq!(scale_up(0x7F, 7, 32), 0xFFFF_FFFF);
+        assert_eq!(scale_up(0x2000, 14, 32), 0x8000_0000);
+        assert_eq!(scale_up(0x3FFF, 14, 32), 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn upgrade_note_on() {
+        assert_eq!(
+            upgrade_all(&mut Upgrader::default(), &[[0x92, 0x3C, 0x7F]]),
+            std::vec![std::vec![0x4092_3C00, 0xFFFF_0000]],
+        );
+    }
+
+    #[test]
+    fn upgrade_note_on_zero_velocity() {
+        assert_eq!(
+            upgrade_all(&mut Upgrader::default(), &[[0x92, 0x3C, 0x00]]),
+            std::vec![std::vec![0x4082_3C00, 0x8000_0000]],
+        );
+    }
+
+    #[test]
+    fn upgrade_pitch_bend() {
+        assert_eq!(
+            upgrade_all(&mut Upgrader::default(), &[[0xE0, 0x00, 0x40]]),
+            std::vec![std::vec![0x40E0_0000, 0x8000_0000]],
+        );
+    }
+
+    #[test]
+    fn upgrade_banked_program_change() {
+        assert_eq!(
+            upgrade_all(
+                &mut Upgrader::default(),
+                &[[0xB1, 0x00, 0x01], [0xB1, 0x20, 0x02], [0xC1, 0x05, 0x00]]
+            ),
+            std::vec![std::vec![0x40C1_0001, 0x0500_0201]],
+        );
+    }
+
+    #[test]
+    fn upgrade_registered_parameter() {
+        assert_eq!(
+            upgrade_all(
+                &mut Upgrader::default(),
+                &[
+                    [0xB0, 0x65, 0x00],
+                    [0xB0, 0x64, 0x01],
+                    [0xB0, 0x06, 0x40],
+                    [0xB0, 0x26, 0x00],
+                ]
+            ),
+            std::vec![
+                std::vec![0x4020_0001, 0x8000_0000],
+                std::vec![0x4020_0001, 0x8000_0000],
+            ],
+        );
+    }
+
+    #[test]
+    fn upgrade_data_entry_without_parameter() {
+        assert_eq!(
+            upgrade_all(&mut Upgrader::default(), &[[0xB0, 0x06, 0x40]]),
+            std::vec![std::vec![0x40B0_0600, 0x8000_0000]],
+        );
+    }
+
+    #[test]
+    fn downgrade_note_on() {
+        let mut note_on = cv2::NoteOn::<[u32; 4]>::new();
+        note_on.set_channel(u4::new(0x3));
+        note_on.set_note_number(u7::new(0x3C));
+        note_on.set_velocity(0x0100);
+        let mut output = std::vec::Vec::new();
+        Downgrader::default().downgrade(&note_on.into(), &mut output);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].data(), &[0x93, 0x3C, 0x01]);
+    }
+
+    #[test]
+    fn downgrade_registered_controller() {
+        let mut controller = cv2::RegisteredController::<[u32; 4]>::new();
+        controller.set_bank(u7::new(0x0));
+        controller.set_index(u7::new(0x1));
+        controller.set_controller_data(0x8000_0000);
+        let controller: ChannelVoice2<[u32; 4]> = controller.into();
+
+        let mut downgrader = Downgrader::default();
+        let mut output = std::vec::Vec::new();
+        downgrader.downgrade(&controller, &mut output);
+        downgrader.downgrade(&controller, &mut output);
+        assert_eq!(
+            output
+                .iter()
+                .map(|m| m.data().to_vec())
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![
+                std::vec![0xB0, 0x65, 0x00],
+                std::vec![0xB0, 0x64, 0x01],
+                std::vec![0xB0, 0x06, 0x40],
+                std::vec![0xB0, 0x26, 0x00],
+                std::vec![0xB0, 0x06, 0x40],
+                std::vec![0xB0, 0x26, 0x00],
+            ],
+        );
+    }
+
+    #[test]
+    fn downgrade_banked_program_change() {
+        let mut program_change = cv2::ProgramChange::<[u32; 4]>::new();
+        program_change.set_program(u7::new(0x5));
+        program_change.set_bank(Some(u14::new(0x0102)));
+        let mut output = std::vec::Vec::new();
+        Downgrader::default().downgrade(&program_change.into(), &mut output);
+        assert_eq!(
+            output
+                .iter()
+                .map(|m| m.data().to_vec())
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![
+                std::vec![0xB0, 0x00, 0x02],
+                std::vec![0xB0, 0x20, 0x02],
+                std::vec![0xC0, 0x05],
+            ],
+        );
+    }
+}
@@ -0,0 +1,81 @@
+// Variable length quantities as used for delta times and data lengths
+// in standard midi files and for delta times in rtp midi command lists.
+
+use crate::error::InvalidData;
+
+const ERR_VLQ_TOO_LONG: &str = "Variable length quantity exceeds four bytes";
+const ERR_VLQ_TRUNCATED: &str = "Variable length quantity is truncated";
+
+pub const MAX: u32 = 0x0FFF_FFFF;
+
+// returns the value and the number of bytes read
+pub fn read(data: &[u8]) -> Result<(u32, usize), InvalidData> {
+    let mut value = 0_u32;
+    for (i, byte) in data.iter().enumerate() {
+        if i == 4 {
+            return Err(InvalidData(ERR_VLQ_TOO_LONG));
+        }
+        value = (value << 7) | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    if data.len() >= 4 {
+        Err(InvalidData(ERR_VLQ_TOO_LONG))
+    } else {
+        Err(InvalidData(ERR_VLQ_TRUNCATED))
+    }
+}
+
+// values greater than MAX are clamped
+pub fn write(data: &mut std::vec::Vec<u8>, value: u32) {
+    let value = value.min(MAX);
+    let mut shift = 21;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        data.push(0x80 | ((value >> shift) & 0x7F) as u8);
+        shift -= 7;
+    }
+    data.push((value & 0x7F) as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trip() {
+        for (value, bytes) in [
+            (0x0, &[0x00][..]),
+            (0x40, &[0x40][..]),
+            (0x7F, &[0x7F][..]),
+            (0x80, &[0x81, 0x00][..]),
+            (0x2000, &[0xC0, 0x00][..]),
+            (0x3FFF, &[0xFF, 0x7F][..]),
+            (0x4000, &[0x81, 0x80, 0x00][..]),
+            (0x10_0000, &[0xC0, 0x80, 0x00][..]),
+            (0x0FFF_FFFF, &[0xFF, 0xFF, 0xFF, 0x7F][..]),
+        ] {
+            assert_eq!(read(bytes), Ok((value, bytes.len())));
+            let mut written = std::vec::Vec::new();
+            write(&mut written, value);
+            assert_eq!(&written[..], bytes);
+        }
+    }
+
+    #[test]
+    fn too_long() {
+        assert_eq!(
+            read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F]),
+            Err(InvalidData(ERR_VLQ_TOO_LONG))
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(read(&[0xFF, 0xFF]), Err(InvalidData(ERR_VLQ_TRUNCATED)));
+    }
+}
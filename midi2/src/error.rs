@@ -1,4 +1,5 @@
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     BufferOverflow,
     InvalidData(InvalidData),
@@ -17,9 +18,11 @@ impl core::convert::From<InvalidData> for Error {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct BufferOverflow;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct InvalidData(pub &'static str);
 
 #[cfg(feature = "std")]
@@ -28,7 +31,20 @@ impl std::error::Error for BufferOverflow {}
 #[cfg(feature = "std")]
 impl std::fmt::Display for BufferOverflow {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        <Self as std::fmt::Debug>::fmt(self, f)
+        f.write_str("Buffer overflow")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::BufferOverflow => std::fmt::Display::fmt(&BufferOverflow, f),
+            Error::InvalidData(invalid_data) => std::fmt::Display::fmt(invalid_data, f),
+        }
     }
 }
 
@@ -38,7 +54,164 @@ impl std::error::Error for InvalidData {}
 #[cfg(feature = "std")]
 impl std::fmt::Display for InvalidData {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        <Self as std::fmt::Debug>::fmt(self, f)
+        f.write_str(self.0)
+    }
+}
+
+/// The category of fault found in message data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UnexpectedMessageType,
+    InvalidStatus,
+    InvalidPacketFormat,
+    ReservedBitsSet,
+    OutOfRange,
+    Truncated,
+}
+
+impl ErrorKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorKind::UnexpectedMessageType => "Unexpected message type",
+            ErrorKind::InvalidStatus => "Incorrect message status",
+            ErrorKind::InvalidPacketFormat => "Incorrect message format",
+            ErrorKind::ReservedBitsSet => "Reserved bits are set",
+            ErrorKind::OutOfRange => "Value out of range",
+            ErrorKind::Truncated => crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT,
+        }
+    }
+}
+
+/// The position of a fault in message data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Offset {
+    Word(usize),
+    Byte(usize),
+}
+
+/// A detailed account of why message data couldn't be interpreted.
+///
+/// The offset points at the word or byte at fault,
+/// or at the start of the packet when the fault can't be pinned down further.
+/// Where they are known, the expected and found values
+/// of the offending field are included.
+///
+/// Converts into [InvalidData] and [Error] for use with the rest of the api.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Diagnostic {
+    pub kind: ErrorKind,
+    pub offset: Offset,
+    pub expected: Option<u32>,
+    pub found: Option<u32>,
+    pub description: &'static str,
+}
+
+impl Diagnostic {
+    pub fn new(kind: ErrorKind, offset: Offset) -> Self {
+        Diagnostic {
+            kind,
+            offset,
+            expected: None,
+            found: None,
+            description: kind.description(),
+        }
+    }
+}
+
+impl core::convert::From<Diagnostic> for InvalidData {
+    fn from(value: Diagnostic) -> Self {
+        InvalidData(value.description)
+    }
+}
+
+impl core::convert::From<Diagnostic> for Error {
+    fn from(value: Diagnostic) -> Self {
+        Error::InvalidData(value.into())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Diagnostic {}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.kind.description())?;
+        match self.offset {
+            Offset::Word(offset) => write!(f, " at word {offset}")?,
+            Offset::Byte(offset) => write!(f, " at byte {offset}")?,
+        }
+        if self.description != self.kind.description() {
+            write!(f, ": {}", self.description)?;
+        }
+        // sizes read better in decimal, field values in hex
+        let value = |f: &mut core::fmt::Formatter<'_>, v: u32| match self.kind {
+            ErrorKind::Truncated => write!(f, "{v}"),
+            _ => write!(f, "{v:#X}"),
+        };
+        match (self.expected, self.found) {
+            (Some(expected), Some(found)) => {
+                f.write_str(" (expected ")?;
+                value(f, expected)?;
+                f.write_str(", found ")?;
+                value(f, found)?;
+                f.write_str(")")
+            }
+            (None, Some(found)) => {
+                f.write_str(" (found ")?;
+                value(f, found)?;
+                f.write_str(")")
+            }
+            (Some(expected), None) => {
+                f.write_str(" (expected ")?;
+                value(f, expected)?;
+                f.write_str(")")
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The reserved bit violations tolerated by a lenient parse.
+///
+/// Yields a [Diagnostic] of kind [ErrorKind::ReservedBitsSet]
+/// for each word of the parsed message which has reserved bits set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warnings<'a> {
+    data: &'a [u32],
+    offset: usize,
+}
+
+impl<'a> Warnings<'a> {
+    pub(crate) fn new(data: &'a [u32]) -> Self {
+        Warnings { data, offset: 0 }
+    }
+}
+
+impl core::iter::Iterator for Warnings<'_> {
+    type Item = Diagnostic;
+    fn next(&mut self) -> Option<Self::Item> {
+        use crate::detail::{diagnose, BitOps};
+
+        let packet_size = crate::packet::size(self.data.first()?.nibble(0).into());
+        while self.offset < self.data.len() {
+            let offset = self.offset;
+            self.offset += 1;
+
+            let start = offset - offset % packet_size;
+            let packet = &self.data[start..self.data.len().min(start + packet_size)];
+            let masks = diagnose::reserved_bits(packet, start).unwrap_or(&[]);
+            let warning = diagnose::reserved_bits_set(packet, start, masks)
+                .find(|warning| warning.offset == Offset::Word(offset));
+            if warning.is_some() {
+                return warning;
+            }
+        }
+        None
     }
 }
 
@@ -16,7 +16,10 @@ mod set_key_signature;
 mod set_metronome;
 mod set_tempo;
 mod set_time_signature;
+#[cfg(feature = "std")]
+mod tempo_map;
 mod tonic;
+mod unknown;
 mod unknown_metadata_text;
 mod project_name {
     use crate::{detail::common_properties, flex_data};
@@ -791,8 +794,11 @@ pub use set_key_signature::{SetKeySignature, SharpsFlats as SetKeySignatureSharp
 pub use set_metronome::*;
 pub use set_tempo::*;
 pub use set_time_signature::*;
+#[cfg(feature = "std")]
+pub use tempo_map::{Position, TempoChange, TempoMap, TimeSignatureChange};
 pub use text::TextBytesIterator;
 pub use tonic::Tonic;
+pub use unknown::*;
 pub use unknown_metadata_text::*;
 pub use unknown_performance_text::*;
 
@@ -812,6 +818,9 @@ pub(crate) const PERFORMANCE_TEXT_BANK: u8 = 0x2;
     midi2_proc::Grouped,
     midi2_proc::RebufferFrom,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -843,6 +852,7 @@ pub enum FlexData<B: crate::buffer::Ump> {
     LyricsLanguage(lyrics_language::LyricsLanguage<B>),
     Ruby(ruby::Ruby<B>),
     RubyLanguage(ruby_language::RubyLanguage<B>),
+    Unknown(unknown::UnknownFlexData<B>),
 }
 
 impl<'a> TryFrom<&'a [u32]> for FlexData<&'a [u32]> {
@@ -893,6 +903,23 @@ impl<'a> TryFrom<&'a [u32]> for FlexData<&'a [u32]> {
     }
 }
 
+impl<'a> FlexData<&'a [u32]> {
+    /// Parses the message at the front of the buffer without rejecting
+    /// messages which don't strictly follow the specification.
+    ///
+    /// Reserved bits which have been set are reported through the returned warnings.
+    /// Packets with unassigned statuses or invalid fields come back unmodified
+    /// as [FlexData::Unknown].
+    pub fn try_from_lenient(
+        buffer: &'a [u32],
+    ) -> Result<(Self, crate::error::Warnings<'a>), crate::error::Diagnostic> {
+        crate::detail::diagnose::lenient(
+            buffer,
+            Some(|packet| Ok(unknown::UnknownFlexData::try_from(packet)?.into())),
+        )
+    }
+}
+
 impl<B: Ump> FlexDataMessage<B> for FlexData<B> {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -900,6 +927,7 @@ pub enum Bank {
     SetupAndPerformance,
     MetadataText,
     PerformanceText,
+    Unknown(u8),
 }
 
 pub trait FlexDataMessage<B: crate::buffer::Ump>: crate::traits::Data<B> {
@@ -909,7 +937,7 @@ pub trait FlexDataMessage<B: crate::buffer::Ump>: crate::traits::Data<B> {
             0x0 => SetupAndPerformance,
             0x1 => MetadataText,
             0x2 => PerformanceText,
-            _ => panic!(),
+            bank => Unknown(bank as u8),
         }
     }
     fn status(&self) -> u8 {
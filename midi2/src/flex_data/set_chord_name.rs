@@ -52,6 +52,8 @@ struct SetChordName {
 impl<B: crate::buffer::Ump> flex_data::FlexDataMessage<B> for SetChordName<B> {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SharpsFlats {
     DoubleSharp,
     Sharp,
@@ -154,6 +156,8 @@ impl core::default::Default for SharpsFlats {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ChordType {
     ClearChord,
     Major,
@@ -321,11 +325,12 @@ impl core::default::Default for ChordType {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Alteration {
-    Add(u4),
-    Subtract(u4),
-    Raise(u4),
-    Lower(u4),
+    Add(#[cfg_attr(feature = "serde", serde(with = "crate::detail::serde::field"))] u4),
+    Subtract(#[cfg_attr(feature = "serde", serde(with = "crate::detail::serde::field"))] u4),
+    Raise(#[cfg_attr(feature = "serde", serde(with = "crate::detail::serde::field"))] u4),
+    Lower(#[cfg_attr(feature = "serde", serde(with = "crate::detail::serde::field"))] u4),
 }
 
 struct AlterationProperty<S: schema::UmpSchema>(S);
@@ -32,9 +32,10 @@ struct SetKeySignature {
 impl<B: crate::buffer::Ump> flex_data::FlexDataMessage<B> for SetKeySignature<B> {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharpsFlats {
-    Flats(u3),
-    Sharps(u3),
+    Flats(#[cfg_attr(feature = "serde", serde(with = "crate::detail::serde::field"))] u3),
+    Sharps(#[cfg_attr(feature = "serde", serde(with = "crate::detail::serde::field"))] u3),
     NonStandard,
 }
 
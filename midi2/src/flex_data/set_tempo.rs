@@ -31,6 +31,36 @@ struct SetTempo {
 
 impl<B: crate::buffer::Ump> flex_data::FlexDataMessage<B> for SetTempo<B> {}
 
+impl<B: crate::buffer::Ump> SetTempo<B> {
+    /// The tempo in quarter notes per minute.
+    pub fn bpm(&self) -> f64 {
+        bpm_from_10_nanosecond_units(self.number_of_10_nanosecond_units_per_quarter_note())
+    }
+}
+
+impl<B: crate::buffer::Ump + crate::buffer::BufferMut> SetTempo<B> {
+    /// Set the tempo in quarter notes per minute.
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.set_number_of_10_nanosecond_units_per_quarter_note(bpm_to_10_nanosecond_units(bpm));
+    }
+}
+
+const TEN_NANOSECOND_UNITS_PER_MINUTE: f64 = 6_000_000_000.0;
+
+/// Convert a tempo in quarter notes per minute
+/// into the number of 10 nanosecond units per quarter note.
+///
+/// The result saturates for tempos too slow to represent.
+pub fn bpm_to_10_nanosecond_units(bpm: f64) -> u32 {
+    (TEN_NANOSECOND_UNITS_PER_MINUTE / bpm + 0.5) as u32
+}
+
+/// Convert a number of 10 nanosecond units per quarter note
+/// into a tempo in quarter notes per minute.
+pub fn bpm_from_10_nanosecond_units(units: u32) -> f64 {
+    TEN_NANOSECOND_UNITS_PER_MINUTE / f64::from(units)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,4 +84,20 @@ mod tests {
             0xF751FE05,
         );
     }
+
+    #[test]
+    fn bpm() {
+        let mut message = SetTempo::<[u32; 4]>::new();
+        message.set_bpm(120.0);
+        assert_eq!(
+            message.number_of_10_nanosecond_units_per_quarter_note(),
+            50_000_000
+        );
+        assert_eq!(message.bpm(), 120.0);
+    }
+
+    #[test]
+    fn bpm_rounds_to_nearest_unit() {
+        assert_eq!(bpm_to_10_nanosecond_units(140.0), 42_857_143);
+    }
 }
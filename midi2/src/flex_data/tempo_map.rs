@@ -0,0 +1,433 @@
+use crate::flex_data::{FlexData, SetTempo, SetTimeSignature};
+
+// 120 quarter notes per minute
+const DEFAULT_TEMPO: u32 = 50_000_000;
+const TEN_NANOSECOND_UNITS_PER_SECOND: f64 = 100_000_000.0;
+
+/// Converts between ticks, seconds and musical positions
+/// according to a sequence of tempo and time signature changes.
+///
+/// The map starts at 120 quarter notes per minute in 4/4 time,
+/// matching the defaults of Standard MIDI Files.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::flex_data::{Position, TempoMap};
+///
+/// let mut tempo = flex_data::SetTempo::<[u32; 4]>::new();
+/// tempo.set_bpm(60.0);
+/// let mut time_signature = flex_data::SetTimeSignature::<[u32; 4]>::new();
+/// time_signature.set_numerator(3);
+/// time_signature.set_denominator(2);
+///
+/// let messages: [(u64, flex_data::FlexData<[u32; 4]>); 2] = [
+///     (0, tempo.into()),
+///     (0, time_signature.into()),
+/// ];
+/// let map = TempoMap::from_messages(96, messages.iter().map(|(tick, m)| (*tick, m)));
+///
+/// assert_eq!(map.ticks_to_seconds(192), 2.0);
+/// assert_eq!(map.seconds_to_ticks(2.0), 192);
+/// assert_eq!(
+///     map.ticks_to_position(300),
+///     Position { bar: 1, beat: 0, tick: 12 },
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TempoMap {
+    ticks_per_quarter_note: u16,
+    tempos: std::vec::Vec<TempoChange>,
+    time_signatures: std::vec::Vec<TimeSignatureChange>,
+}
+
+/// A change of tempo at an absolute tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TempoChange {
+    pub tick: u64,
+    pub number_of_10_nanosecond_units_per_quarter_note: u32,
+}
+
+/// A change of time signature at an absolute tick.
+///
+/// The `denominator` is expressed as a power of two, e.g. `2` represents a quarter note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSignatureChange {
+    pub tick: u64,
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+/// A musical position in bars, beats and ticks.
+///
+/// All fields count from zero. Beats are in units of the time signature denominator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub bar: u64,
+    pub beat: u32,
+    pub tick: u64,
+}
+
+impl TempoMap {
+    pub fn new(ticks_per_quarter_note: u16) -> Self {
+        TempoMap {
+            ticks_per_quarter_note: ticks_per_quarter_note.max(1),
+            tempos: std::vec![TempoChange {
+                tick: 0,
+                number_of_10_nanosecond_units_per_quarter_note: DEFAULT_TEMPO,
+            }],
+            time_signatures: std::vec![TimeSignatureChange {
+                tick: 0,
+                numerator: 4,
+                denominator: 2,
+            }],
+        }
+    }
+
+    /// Build a map from [SetTempo] and [SetTimeSignature] messages
+    /// at absolute ticks. Other messages are ignored.
+    pub fn from_messages<'a, B, I>(ticks_per_quarter_note: u16, messages: I) -> Self
+    where
+        B: crate::buffer::Ump + 'a,
+        I: core::iter::IntoIterator<Item = (u64, &'a FlexData<B>)>,
+    {
+        let mut map = TempoMap::new(ticks_per_quarter_note);
+        for (tick, message) in messages {
+            match message {
+                FlexData::SetTempo(m) => map.set_tempo(tick, m),
+                FlexData::SetTimeSignature(m) => map.set_time_signature(tick, m),
+                _ => {}
+            }
+        }
+        map
+    }
+
+    pub fn ticks_per_quarter_note(&self) -> u16 {
+        self.ticks_per_quarter_note
+    }
+
+    /// The tempo changes in ascending tick order.
+    pub fn tempos(&self) -> &[TempoChange] {
+        &self.tempos
+    }
+
+    /// The time signature changes in ascending tick order.
+    pub fn time_signatures(&self) -> &[TimeSignatureChange] {
+        &self.time_signatures
+    }
+
+    pub fn set_tempo<B: crate::buffer::Ump>(&mut self, tick: u64, message: &SetTempo<B>) {
+        self.insert_tempo(TempoChange {
+            tick,
+            number_of_10_nanosecond_units_per_quarter_note: message
+                .number_of_10_nanosecond_units_per_quarter_note(),
+        });
+    }
+
+    pub fn set_time_signature<B: crate::buffer::Ump>(
+        &mut self,
+        tick: u64,
+        message: &SetTimeSignature<B>,
+    ) {
+        self.insert_time_signature(TimeSignatureChange {
+            tick,
+            numerator: message.numerator(),
+            denominator: message.denominator(),
+        });
+    }
+
+    /// Add a tempo change, replacing any existing change at the same tick.
+    pub fn insert_tempo(&mut self, change: TempoChange) {
+        let change = TempoChange {
+            number_of_10_nanosecond_units_per_quarter_note: change
+                .number_of_10_nanosecond_units_per_quarter_note
+                .max(1),
+            ..change
+        };
+        match self.tempos.binary_search_by_key(&change.tick, |t| t.tick) {
+            Ok(index) => self.tempos[index] = change,
+            Err(index) => self.tempos.insert(index, change),
+        }
+    }
+
+    /// Add a time signature change, replacing any existing change at the same tick.
+    ///
+    /// A change which does not fall on a bar line starts a new bar.
+    pub fn insert_time_signature(&mut self, change: TimeSignatureChange) {
+        let change = TimeSignatureChange {
+            numerator: change.numerator.max(1),
+            ..change
+        };
+        match self
+            .time_signatures
+            .binary_search_by_key(&change.tick, |t| t.tick)
+        {
+            Ok(index) => self.time_signatures[index] = change,
+            Err(index) => self.time_signatures.insert(index, change),
+        }
+    }
+
+    /// The tempo in effect at the given tick,
+    /// in 10 nanosecond units per quarter note.
+    pub fn tempo_at(&self, tick: u64) -> u32 {
+        let index = self.tempos.partition_point(|t| t.tick <= tick) - 1;
+        self.tempos[index].number_of_10_nanosecond_units_per_quarter_note
+    }
+
+    pub fn ticks_to_seconds(&self, tick: u64) -> f64 {
+        // accumulate in units of 10ns per tick-per-quarter-note
+        // to keep the calculation exact
+        let mut total = 0_u128;
+        for (index, tempo) in self.tempos.iter().enumerate() {
+            if tempo.tick >= tick {
+                break;
+            }
+            let end = self
+                .tempos
+                .get(index + 1)
+                .map_or(tick, |next| next.tick.min(tick));
+            total += u128::from(end - tempo.tick)
+                * u128::from(tempo.number_of_10_nanosecond_units_per_quarter_note);
+        }
+        total as f64 / f64::from(self.ticks_per_quarter_note) / TEN_NANOSECOND_UNITS_PER_SECOND
+    }
+
+    /// The last tick at or before the given time.
+    pub fn seconds_to_ticks(&self, seconds: f64) -> u64 {
+        if seconds <= 0.0 {
+            return 0;
+        }
+        let target =
+            seconds * TEN_NANOSECOND_UNITS_PER_SECOND * f64::from(self.ticks_per_quarter_note);
+        let mut total = 0.0;
+        for (index, tempo) in self.tempos.iter().enumerate() {
+            let units = f64::from(tempo.number_of_10_nanosecond_units_per_quarter_note);
+            let remaining = (target - total) / units;
+            match self.tempos.get(index + 1) {
+                Some(next) if ((next.tick - tempo.tick) as f64) <= remaining => {
+                    total += (next.tick - tempo.tick) as f64 * units;
+                }
+                // allow for rounding errors which would place us just before a whole tick
+                _ => return tempo.tick + (remaining + 1e-9) as u64,
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn ticks_to_position(&self, tick: u64) -> Position {
+        let mut bar = 0;
+        for (index, signature) in self.time_signatures.iter().enumerate() {
+            let ticks_per_beat = self.ticks_per_beat(signature);
+            let ticks_per_bar = ticks_per_beat * u64::from(signature.numerator);
+            match self.time_signatures.get(index + 1) {
+                Some(next) if next.tick <= tick => {
+                    bar += (next.tick - signature.tick).div_ceil(ticks_per_bar);
+                }
+                _ => {
+                    let offset = tick.saturating_sub(signature.tick);
+                    let in_bar = offset % ticks_per_bar;
+                    return Position {
+                        bar: bar + offset / ticks_per_bar,
+                        beat: (in_bar / ticks_per_beat) as u32,
+                        tick: in_bar % ticks_per_beat,
+                    };
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn position_to_ticks(&self, position: Position) -> u64 {
+        let mut bar = 0;
+        for (index, signature) in self.time_signatures.iter().enumerate() {
+            let ticks_per_beat = self.ticks_per_beat(signature);
+            let ticks_per_bar = ticks_per_beat * u64::from(signature.numerator);
+            if let Some(next) = self.time_signatures.get(index + 1) {
+                let next_bar = bar + (next.tick - signature.tick).div_ceil(ticks_per_bar);
+                if next_bar <= position.bar {
+                    bar = next_bar;
+                    continue;
+                }
+            }
+            return signature.tick
+                + (position.bar - bar) * ticks_per_bar
+                + u64::from(position.beat) * ticks_per_beat
+                + position.tick;
+        }
+        unreachable!()
+    }
+
+    pub fn position_to_seconds(&self, position: Position) -> f64 {
+        self.ticks_to_seconds(self.position_to_ticks(position))
+    }
+
+    pub fn seconds_to_position(&self, seconds: f64) -> Position {
+        self.ticks_to_position(self.seconds_to_ticks(seconds))
+    }
+
+    fn ticks_per_beat(&self, signature: &TimeSignatureChange) -> u64 {
+        let whole_note = u64::from(self.ticks_per_quarter_note) * 4;
+        (whole_note >> signature.denominator.min(63)).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn tempo(tick: u64, bpm: f64) -> TempoChange {
+        TempoChange {
+            tick,
+            number_of_10_nanosecond_units_per_quarter_note:
+                crate::flex_data::bpm_to_10_nanosecond_units(bpm),
+        }
+    }
+
+    fn time_signature(tick: u64, numerator: u8, denominator: u8) -> TimeSignatureChange {
+        TimeSignatureChange {
+            tick,
+            numerator,
+            denominator,
+        }
+    }
+
+    #[test]
+    fn default_tempo() {
+        let map = TempoMap::new(96);
+        assert_eq!(map.ticks_to_seconds(96), 0.5);
+        assert_eq!(map.seconds_to_ticks(0.5), 96);
+    }
+
+    #[test]
+    fn tempo_change_between_beats() {
+        let mut map = TempoMap::new(96);
+        map.insert_tempo(tempo(48, 60.0));
+        assert_eq!(map.ticks_to_seconds(48), 0.25);
+        assert_eq!(map.ticks_to_seconds(96), 0.75);
+        assert_eq!(map.ticks_to_seconds(192), 1.75);
+        assert_eq!(map.seconds_to_ticks(0.25), 48);
+        assert_eq!(map.seconds_to_ticks(0.75), 96);
+        assert_eq!(map.seconds_to_ticks(1.75), 192);
+        assert_eq!(map.seconds_to_ticks(1.7), 187);
+    }
+
+    #[test]
+    fn replace_tempo() {
+        let mut map = TempoMap::new(96);
+        map.insert_tempo(tempo(0, 60.0));
+        assert_eq!(map.tempos().len(), 1);
+        assert_eq!(map.ticks_to_seconds(96), 1.0);
+    }
+
+    #[test]
+    fn tempo_at() {
+        let mut map = TempoMap::new(96);
+        map.insert_tempo(tempo(48, 60.0));
+        assert_eq!(map.tempo_at(47), 50_000_000);
+        assert_eq!(map.tempo_at(48), 100_000_000);
+    }
+
+    #[test]
+    fn position() {
+        let map = TempoMap::new(96);
+        assert_eq!(
+            map.ticks_to_position(4 * 96 + 96 + 10),
+            Position {
+                bar: 1,
+                beat: 1,
+                tick: 10,
+            }
+        );
+        assert_eq!(
+            map.position_to_ticks(Position {
+                bar: 1,
+                beat: 1,
+                tick: 10,
+            }),
+            4 * 96 + 96 + 10,
+        );
+    }
+
+    #[test]
+    fn time_signature_change() {
+        let mut map = TempoMap::new(96);
+        // two bars of 4/4 then 6/8
+        map.insert_time_signature(time_signature(8 * 96, 6, 3));
+        let position = Position {
+            bar: 3,
+            beat: 4,
+            tick: 5,
+        };
+        let tick = 8 * 96 + 6 * 48 + 4 * 48 + 5;
+        assert_eq!(map.ticks_to_position(tick), position);
+        assert_eq!(map.position_to_ticks(position), tick);
+    }
+
+    #[test]
+    fn time_signature_change_within_a_bar() {
+        let mut map = TempoMap::new(96);
+        map.insert_time_signature(time_signature(96, 3, 2));
+        assert_eq!(
+            map.ticks_to_position(96),
+            Position {
+                bar: 1,
+                beat: 0,
+                tick: 0,
+            }
+        );
+        assert_eq!(
+            map.position_to_ticks(Position {
+                bar: 2,
+                beat: 0,
+                tick: 0,
+            }),
+            96 + 3 * 96,
+        );
+    }
+
+    #[test]
+    fn position_to_seconds() {
+        let mut map = TempoMap::new(96);
+        map.insert_tempo(tempo(0, 60.0));
+        assert_eq!(
+            map.position_to_seconds(Position {
+                bar: 1,
+                beat: 0,
+                tick: 0,
+            }),
+            4.0,
+        );
+        assert_eq!(
+            map.seconds_to_position(4.5),
+            Position {
+                bar: 1,
+                beat: 0,
+                tick: 48,
+            }
+        );
+    }
+
+    #[test]
+    fn from_messages() {
+        let mut set_tempo = SetTempo::<[u32; 4]>::new();
+        set_tempo.set_number_of_10_nanosecond_units_per_quarter_note(100_000_000);
+        let mut set_time_signature = SetTimeSignature::<[u32; 4]>::new();
+        set_time_signature.set_numerator(6);
+        set_time_signature.set_denominator(3);
+        let messages: [(u64, FlexData<[u32; 4]>); 2] =
+            [(96, set_tempo.into()), (0, set_time_signature.into())];
+
+        let map = TempoMap::from_messages(96, messages.iter().map(|(t, m)| (*t, m)));
+        assert_eq!(
+            map.tempos(),
+            &[
+                tempo(0, 120.0),
+                TempoChange {
+                    tick: 96,
+                    number_of_10_nanosecond_units_per_quarter_note: 100_000_000,
+                }
+            ]
+        );
+        assert_eq!(map.time_signatures(), &[time_signature(0, 6, 3)]);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{detail::common_properties, flex_data};
+
+/// MIDI 2.0 Flex Data Message with an unassigned bank or status
+///
+/// Only produced by the lenient parsing path, which uses it
+/// to carry packets it can't interpret without modifying them.
+/// Multi-packet messages are carried one packet at a time.
+/// The format, bank, status and data bits are kept as they were found in `raw_data`.
+///
+/// See the [module docs](crate::flex_data) for more info.
+#[midi2_proc::generate_message(Via(crate::flex_data::FlexData), FixedSize, MinSizeUmp(4))]
+struct UnknownFlexData {
+    #[property(common_properties::UmpMessageTypeProperty<{flex_data::UMP_MESSAGE_TYPE}>)]
+    ump_type: (),
+    #[property(flex_data::GroupProperty)]
+    group: crate::ux::u4,
+    #[property(flex_data::OptionalChannelProperty)]
+    optional_channel: Option<crate::ux::u4>,
+    #[property(common_properties::UnknownDataProperty<4, 0x00C0_FFFF>)]
+    raw_data: [u32; 4],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn from_data() {
+        let message = UnknownFlexData::try_from(&[0xD34B_7F01, 0x1234_5678, 0x0, 0x0][..]).unwrap();
+        assert_eq!(message.optional_channel(), Some(crate::ux::u4::new(0xB)));
+        assert_eq!(message.raw_data(), [0x0040_7F01, 0x1234_5678, 0x0, 0x0]);
+    }
+}
@@ -0,0 +1,106 @@
+//! Read and write streams of messages over byte oriented transports.
+//!
+//! The [UmpDecoder] and [BytesDecoder] frame messages from a stream of bytes
+//! without allocating, and can be fed from any source.
+//! Adapters are provided on top of them for
+//! - `std::io` with the **std** feature (in this module),
+//! - `embedded-io` with the **embedded-io** feature (in `io::embedded`),
+//! - `embedded-io-async` with the **embedded-io-async** feature (in `io::embedded_async`),
+//! - `tokio_util::codec` with the **tokio-util** feature (in this module).
+//!
+//! ```rust
+//! use midi2::{io::UmpReader, prelude::*};
+//!
+//! let bytes: &[u8] = &[0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00];
+//! let mut reader = UmpReader::<_>::new(bytes);
+//!
+//! let message = reader.read().unwrap().unwrap();
+//! assert_eq!(message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+//! assert!(reader.read().unwrap().is_none());
+//! ```
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+mod bytes;
+#[cfg(feature = "tokio-util")]
+mod codec;
+#[cfg(feature = "embedded-io")]
+pub mod embedded;
+#[cfg(feature = "embedded-io-async")]
+pub mod embedded_async;
+#[cfg(feature = "std")]
+mod std_io;
+mod ump;
+
+#[cfg(feature = "rtp-midi")]
+pub(crate) use bytes::message_size;
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+pub use bytes::BytesDecoder;
+#[cfg(feature = "tokio-util")]
+pub use codec::*;
+#[cfg(feature = "std")]
+pub use std_io::*;
+pub use ump::UmpDecoder;
+
+/// The byte order of the words of a ump stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+impl Endian {
+    fn word(&self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endian::Big => u32::from_be_bytes(bytes),
+            Endian::Little => u32::from_le_bytes(bytes),
+        }
+    }
+
+    // used by the writers
+    #[cfg(any(
+        feature = "std",
+        feature = "embedded-io",
+        feature = "embedded-io-async"
+    ))]
+    fn bytes(&self, word: u32) -> [u8; 4] {
+        match self {
+            Endian::Big => word.to_be_bytes(),
+            Endian::Little => word.to_le_bytes(),
+        }
+    }
+}
+
+/// Errors from the `embedded-io` adapters.
+#[cfg(feature = "embedded-io")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// The underlying transport failed.
+    Io(E),
+    /// The stream contained data which couldn't be framed into a message.
+    Decode(crate::error::Error),
+}
+
+#[cfg(feature = "embedded-io")]
+impl<E> core::convert::From<crate::error::Error> for Error<E> {
+    fn from(value: crate::error::Error) -> Self {
+        Error::Decode(value)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<E> core::convert::From<crate::error::InvalidData> for Error<E> {
+    fn from(value: crate::error::InvalidData) -> Self {
+        Error::Decode(value.into())
+    }
+}
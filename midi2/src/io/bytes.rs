@@ -0,0 +1,318 @@
+use crate::{
+    error::{BufferOverflow, Error, InvalidData},
+    message::BytesMessage,
+};
+
+const ERR_NO_STATUS: &str = "Data byte found without a running status";
+const ERR_INTERRUPTED_MESSAGE: &str = "Message was interrupted by another status byte";
+const ERR_UNEXPECTED_END_OF_EXCLUSIVE: &str = "End of exclusive found outside of a sysex message";
+
+/// Frames MIDI 1.0 messages from a stream of bytes without allocating.
+///
+/// Bytes are fed in with [decode](BytesDecoder::decode) in chunks of any size.
+/// Running status is expanded, so every message handed out starts with its status byte,
+/// and system real time messages interleaved with other messages are handed out
+/// as soon as they arrive.
+/// System exclusive messages are collected into an internal buffer of `SIZE` bytes.
+///
+/// ```rust
+/// use midi2::{io::BytesDecoder, prelude::*};
+///
+/// let mut decoder = BytesDecoder::<16>::new();
+///
+/// let mut input: &[u8] = &[0x90, 0x3C, 0x40, 0x3E];
+/// assert_eq!(decoder.decode(&mut input).unwrap().unwrap().data(), &[0x90, 0x3C, 0x40]);
+/// assert_eq!(decoder.decode(&mut input), Ok(None));
+///
+/// let mut input: &[u8] = &[0x40];
+/// assert_eq!(decoder.decode(&mut input).unwrap().unwrap().data(), &[0x90, 0x3E, 0x40]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BytesDecoder<const SIZE: usize = 64> {
+    buffer: [u8; SIZE],
+    len: usize,
+    running_status: Option<u8>,
+    // data bytes are dropped until the next status byte after an error
+    skip: bool,
+    // the rest of a sysex message which overflowed the buffer is dropped
+    // up to and including its end of exclusive
+    dropping_sysex: bool,
+    real_time: u8,
+    ready: Ready,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Ready {
+    None,
+    Message,
+    RealTime,
+}
+
+impl<const SIZE: usize> Default for BytesDecoder<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> BytesDecoder<SIZE> {
+    pub fn new() -> Self {
+        BytesDecoder {
+            buffer: [0x0; SIZE],
+            len: 0,
+            running_status: None,
+            skip: false,
+            dropping_sysex: false,
+            real_time: 0x0,
+            ready: Ready::None,
+        }
+    }
+
+    /// Consume bytes from the front of the input until a message is complete.
+    ///
+    /// Returns `Ok(None)` once the input is used up without completing a message.
+    /// The partially received message is kept for the next call.
+    ///
+    /// Errors are reported as soon as they're found and the offending data is dropped,
+    /// so decoding can carry on with the next call.
+    /// System exclusive messages longer than the internal buffer
+    /// fail with [Error::BufferOverflow] and the rest of the message is dropped.
+    pub fn decode<'a>(
+        &'a mut self,
+        input: &mut &[u8],
+    ) -> Result<Option<BytesMessage<&'a [u8]>>, Error> {
+        if !self.frame(input)? {
+            return Ok(None);
+        }
+        Ok(Some(self.framed()?))
+    }
+
+    /// Drop any partially received data and the running status.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.running_status = None;
+        self.skip = false;
+        self.dropping_sysex = false;
+        self.ready = Ready::None;
+    }
+
+    /// Advance through the input until a message has been framed.
+    pub(crate) fn frame(&mut self, input: &mut &[u8]) -> Result<bool, Error> {
+        if self.ready == Ready::Message {
+            self.len = 0;
+        }
+        self.ready = Ready::None;
+
+        while let Some((&byte, rest)) = input.split_first() {
+            *input = rest;
+            match byte {
+                0xF8..=0xFF => {
+                    self.real_time = byte;
+                    self.ready = Ready::RealTime;
+                    return Ok(true);
+                }
+                0xF7 => {
+                    self.skip = false;
+                    self.running_status = None;
+                    if core::mem::take(&mut self.dropping_sysex) {
+                        continue;
+                    }
+                    if self.len == 0 || self.buffer[0] != 0xF0 {
+                        self.len = 0;
+                        return Err(InvalidData(ERR_UNEXPECTED_END_OF_EXCLUSIVE).into());
+                    }
+                    self.push(byte)?;
+                    self.ready = Ready::Message;
+                    return Ok(true);
+                }
+                0x80..=0xF6 => {
+                    let interrupted = self.len != 0;
+                    self.skip = false;
+                    self.dropping_sysex = false;
+                    self.running_status = (byte < 0xF0).then_some(byte);
+                    self.len = 0;
+                    self.push(byte)?;
+                    if interrupted {
+                        return Err(InvalidData(ERR_INTERRUPTED_MESSAGE).into());
+                    }
+                }
+                _ => {
+                    if self.skip {
+                        continue;
+                    }
+                    if self.len == 0 {
+                        let Some(status) = self.running_status else {
+                            self.skip = true;
+                            return Err(InvalidData(ERR_NO_STATUS).into());
+                        };
+                        self.push(status)?;
+                    }
+                    self.push(byte)?;
+                }
+            }
+            if message_size(self.buffer[0]) == Some(self.len) {
+                self.ready = Ready::Message;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The message most recently framed by [frame](Self::frame).
+    pub(crate) fn framed(&self) -> Result<BytesMessage<&[u8]>, InvalidData> {
+        match self.ready {
+            Ready::Message => BytesMessage::try_from(&self.buffer[..self.len]),
+            Ready::RealTime => BytesMessage::try_from(core::slice::from_ref(&self.real_time)),
+            Ready::None => Err(InvalidData(
+                crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT,
+            )),
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), BufferOverflow> {
+        if self.len == SIZE {
+            self.dropping_sysex = self.buffer[0] == 0xF0;
+            self.len = 0;
+            self.skip = true;
+            return Err(BufferOverflow);
+        }
+        self.buffer[self.len] = byte;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+/// The number of bytes in a message with the given status,
+/// or `None` for system exclusive messages which run until the end of exclusive.
+pub(crate) fn message_size(status: u8) -> Option<usize> {
+    match status {
+        0xC0..=0xDF | 0xF1 | 0xF3 => Some(2),
+        0x80..=0xEF | 0xF2 => Some(3),
+        0xF0 => None,
+        _ => Some(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+
+    fn decode_all<const SIZE: usize>(
+        decoder: &mut BytesDecoder<SIZE>,
+        mut input: &[u8],
+    ) -> std::vec::Vec<Result<std::vec::Vec<u8>, Error>> {
+        let mut messages = std::vec::Vec::new();
+        loop {
+            match decoder.decode(&mut input) {
+                Ok(Some(message)) => messages.push(Ok(message.data().to_vec())),
+                Ok(None) => return messages,
+                Err(e) => messages.push(Err(e)),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn running_status() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[0x90, 0x3C, 0x40, 0x3E, 0x40, 0xC0, 0x05, 0x06]
+            ),
+            [
+                Ok(std::vec![0x90, 0x3C, 0x40]),
+                Ok(std::vec![0x90, 0x3E, 0x40]),
+                Ok(std::vec![0xC0, 0x05]),
+                Ok(std::vec![0xC0, 0x06]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "channel-voice1", feature = "system-common"))]
+    fn interleaved_real_time() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &[0x90, 0x3C, 0xF8, 0x40]),
+            [Ok(std::vec![0xF8]), Ok(std::vec![0x90, 0x3C, 0x40])]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "channel-voice1", feature = "system-common"))]
+    fn system_common_cancels_running_status() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &[0x90, 0x3C, 0x40, 0xF6, 0x3E, 0x40]),
+            [
+                Ok(std::vec![0x90, 0x3C, 0x40]),
+                Ok(std::vec![0xF6]),
+                Err(InvalidData(ERR_NO_STATUS).into()),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "system-common")]
+    fn system_common() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &[0xF2, 0x7D, 0x6C, 0xF1, 0x5F, 0xF6, 0xFA]),
+            [
+                Ok(std::vec![0xF2, 0x7D, 0x6C]),
+                Ok(std::vec![0xF1, 0x5F]),
+                Ok(std::vec![0xF6]),
+                Ok(std::vec![0xFA]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &[0xF0, 0x01, 0x02, 0x03, 0xF7]),
+            [Ok(std::vec![0xF0, 0x01, 0x02, 0x03, 0xF7])]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn sysex_too_long() {
+        let mut decoder = BytesDecoder::<4>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0xF7, 0xF0, 0x06, 0xF7]
+            ),
+            [Err(BufferOverflow.into()), Ok(std::vec![0xF0, 0x06, 0xF7])]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn interrupted_message() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &[0x90, 0x3C, 0x80, 0x3C, 0x40]),
+            [
+                Err(InvalidData(ERR_INTERRUPTED_MESSAGE).into()),
+                Ok(std::vec![0x80, 0x3C, 0x40]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn split_across_inputs() {
+        let mut decoder = BytesDecoder::<8>::new();
+        assert_eq!(decode_all(&mut decoder, &[0x90, 0x3C]), []);
+        assert_eq!(
+            decode_all(&mut decoder, &[0x40]),
+            [Ok(std::vec![0x90, 0x3C, 0x40])]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    buffer::Ump,
+    io::{Endian, UmpDecoder},
+    message::UmpMessage,
+    traits::{Data, RebufferInto},
+};
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A [tokio_util::codec] for ump messages framed as bytes.
+///
+/// Each word is four bytes in the configured [Endian] order.
+/// Messages of up to `SIZE` words are supported.
+/// See [UmpDecoder] for how the stream is framed into messages.
+///
+/// Data which can't be framed into a message is dropped and decoding
+/// picks up again with the messages after it, so a stream which is joined
+/// part way through a message doesn't end at its first bytes.
+///
+/// ```rust
+/// use futures::{SinkExt, StreamExt};
+/// use midi2::{io::UmpCodec, prelude::*};
+/// use tokio_util::codec::{FramedRead, FramedWrite};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let (client, server) = tokio::io::duplex(64);
+/// let mut sink = FramedWrite::new(client, UmpCodec::<64>::new());
+/// let mut stream = FramedRead::new(server, UmpCodec::<64>::new());
+///
+/// let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+/// sink.send(message).await.unwrap();
+///
+/// let received = stream.next().await.unwrap().unwrap();
+/// assert_eq!(received.data(), &[0x4090_3C00, 0xFFFF_0000]);
+/// # });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UmpCodec<const SIZE: usize = 64> {
+    endian: Endian,
+    decoder: UmpDecoder<SIZE>,
+}
+
+impl<const SIZE: usize> UmpCodec<SIZE> {
+    /// Create a codec of big endian words.
+    pub fn new() -> Self {
+        Self::with_endian(Endian::Big)
+    }
+
+    pub fn with_endian(endian: Endian) -> Self {
+        UmpCodec {
+            endian,
+            decoder: UmpDecoder::new(endian),
+        }
+    }
+}
+
+impl<const SIZE: usize> Decoder for UmpCodec<SIZE> {
+    type Item = UmpMessage<std::vec::Vec<u32>>;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let mut input = &src[..];
+        // a framed stream ends at the first error,
+        // so errors the decoder recovers from are skipped
+        let message = loop {
+            if let Ok(message) = self.decoder.decode(&mut input) {
+                break message.map(|m| m.rebuffer_into());
+            }
+        };
+        let consumed = src.len() - input.len();
+        src.advance(consumed);
+        Ok(message)
+    }
+}
+
+impl<B: Ump, const SIZE: usize> Encoder<UmpMessage<B>> for UmpCodec<SIZE> {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: UmpMessage<B>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let data = item.data();
+        dst.reserve(data.len() * 4);
+        for word in data {
+            dst.put_slice(&self.endian.bytes(*word));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+mod bytes_codec {
+    use super::*;
+    use crate::{buffer::Bytes, io::BytesDecoder, message::BytesMessage};
+
+    /// A [tokio_util::codec] for MIDI 1.0 byte streams.
+    ///
+    /// System exclusive messages of up to `SIZE` bytes are supported.
+    /// See [BytesDecoder] for how the stream is framed into messages.
+    ///
+    /// Data which can't be framed into a message is dropped and decoding
+    /// picks up again with the messages after it.
+    #[derive(Clone, Debug, Default)]
+    pub struct BytesCodec<const SIZE: usize = 64> {
+        decoder: BytesDecoder<SIZE>,
+    }
+
+    impl<const SIZE: usize> BytesCodec<SIZE> {
+        pub fn new() -> Self {
+            BytesCodec {
+                decoder: BytesDecoder::new(),
+            }
+        }
+    }
+
+    impl<const SIZE: usize> Decoder for BytesCodec<SIZE> {
+        type Item = BytesMessage<std::vec::Vec<u8>>;
+        type Error = std::io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            let mut input = &src[..];
+            // a framed stream ends at the first error,
+            // so errors the decoder recovers from are skipped
+            let message = loop {
+                if let Ok(message) = self.decoder.decode(&mut input) {
+                    break message.map(|m| m.rebuffer_into());
+                }
+            };
+            let consumed = src.len() - input.len();
+            src.advance(consumed);
+            Ok(message)
+        }
+    }
+
+    impl<B: Bytes, const SIZE: usize> Encoder<BytesMessage<B>> for BytesCodec<SIZE> {
+        type Error = std::io::Error;
+
+        fn encode(&mut self, item: BytesMessage<B>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            dst.extend_from_slice(item.data());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+pub use bytes_codec::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use pretty_assertions::assert_eq;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    #[test]
+    fn decode_partial_frames() {
+        let mut codec = UmpCodec::<4>::new();
+        let mut src = BytesMut::from(&[0x40, 0x90, 0x3C][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert!(src.is_empty());
+
+        src.extend_from_slice(&[0x00, 0xFF, 0xFF, 0x00, 0x00, 0x40]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+        assert_eq!(&src[..], &[0x40]);
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn decode_skips_invalid_data() {
+        let mut codec = UmpCodec::<4>::new();
+        let mut src = BytesMut::from(&[0xF3, 0xFF, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0][..]);
+        src.extend_from_slice(&[0x0; 8]);
+        src.extend_from_slice(&[0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn encode_little_endian() {
+        let mut codec = UmpCodec::<4>::with_endian(Endian::Little);
+        let mut dst = BytesMut::new();
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        codec.encode(message, &mut dst).unwrap();
+        assert_eq!(&dst[..], &[0x00, 0x3C, 0x90, 0x40, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    #[tokio::test]
+    async fn framed_duplex() {
+        let (client, server) = tokio::io::duplex(6);
+        let mut sink = FramedWrite::new(client, UmpCodec::<4>::new());
+        let mut stream = FramedRead::new(server, UmpCodec::<4>::new());
+
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        let send = async {
+            for _ in 0..3 {
+                sink.send(message).await.unwrap();
+            }
+            SinkExt::<UmpMessage<&[u32]>>::close(&mut sink)
+                .await
+                .unwrap();
+        };
+        let receive = async {
+            let mut received = std::vec::Vec::new();
+            while let Some(m) = stream.next().await {
+                received.push(m.unwrap());
+            }
+            received
+        };
+        let ((), received) = tokio::join!(send, receive);
+
+        assert_eq!(received.len(), 3);
+        assert!(received.iter().all(|m| m.data() == message.data()));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "channel-voice1")]
+    async fn bytes_framed_duplex() {
+        let (client, server) = tokio::io::duplex(2);
+        let mut sink = FramedWrite::new(client, BytesCodec::<8>::new());
+        let mut stream = FramedRead::new(server, BytesCodec::<8>::new());
+
+        let message = crate::BytesMessage::try_from(&[0x90, 0x3C, 0x40][..]).unwrap();
+        let send = async {
+            sink.send(message).await.unwrap();
+            SinkExt::<crate::BytesMessage<&[u8]>>::close(&mut sink)
+                .await
+                .unwrap();
+        };
+        let receive = async { stream.next().await.unwrap().unwrap() };
+        let ((), received) = tokio::join!(send, receive);
+
+        assert_eq!(received.data(), message.data());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "sysex7")]
+    async fn framed_stream_joined_mid_message() {
+        // the end of a sysex message whose start was missed
+        let data: &[u8] = &[
+            0x30, 0x32, 0x06, 0x07, 0x00, 0x00, 0x00, 0x00, 0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF,
+            0x00, 0x00, 0x40, 0x80, 0x3C, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let received = FramedRead::new(data, UmpCodec::<4>::new())
+            .map(|m| m.unwrap().data().to_vec())
+            .collect::<std::vec::Vec<_>>()
+            .await;
+        assert_eq!(
+            received,
+            [[0x4090_3C00, 0xFFFF_0000], [0x4080_3C00, 0x0000_0000]]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "channel-voice1")]
+    async fn bytes_framed_stream_joined_mid_message() {
+        // data bytes of a message whose status was missed
+        let data: &[u8] = &[0x3C, 0x40, 0x90, 0x3C, 0x40, 0x80, 0x3C, 0x00];
+        let received = FramedRead::new(data, BytesCodec::<8>::new())
+            .map(|m| m.unwrap().data().to_vec())
+            .collect::<std::vec::Vec<_>>()
+            .await;
+        assert_eq!(received, [[0x90, 0x3C, 0x40], [0x80, 0x3C, 0x00]]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Adapters for async `embedded-io-async` transports.
+//!
+//! Messages are read into the decoder's internal buffer
+//! and handed out borrowed, so no allocation is needed.
+//!
+//! ```rust
+//! use midi2::{io::embedded_async::UmpReader, prelude::*};
+//!
+//! let bytes: &[u8] = &[0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00];
+//! let mut reader = UmpReader::<_>::new(bytes);
+//!
+//! # let _ = async {
+//! let message = reader.read().await.unwrap().unwrap();
+//! assert_eq!(message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+//! assert!(reader.read().await.unwrap().is_none());
+//! # };
+//! ```
+
+use crate::{
+    buffer::Ump,
+    io::{Endian, Error, UmpDecoder},
+    message::UmpMessage,
+    traits::Data,
+};
+use embedded_io_async::{Read, Write};
+
+const READ_BUFFER_SIZE: usize = 16;
+
+/// Reads ump messages from an [embedded_io_async::Read] byte stream.
+///
+/// Each word is read as four bytes in the configured [Endian] order.
+/// Messages of up to `SIZE` words are supported.
+/// See [UmpDecoder] for how the stream is framed into messages.
+///
+/// Returns `Ok(None)` at the end of the stream.
+/// Reading can carry on after a [Error::Decode] failure.
+#[derive(Debug)]
+pub struct UmpReader<R, const SIZE: usize = 64> {
+    reader: R,
+    buffer: [u8; READ_BUFFER_SIZE],
+    start: usize,
+    end: usize,
+    decoder: UmpDecoder<SIZE>,
+}
+
+impl<R: Read, const SIZE: usize> UmpReader<R, SIZE> {
+    /// Create a reader of big endian words.
+    pub fn new(reader: R) -> Self {
+        Self::with_endian(reader, Endian::Big)
+    }
+
+    pub fn with_endian(reader: R, endian: Endian) -> Self {
+        UmpReader {
+            reader,
+            buffer: [0x0; READ_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            decoder: UmpDecoder::new(endian),
+        }
+    }
+
+    pub async fn read(&mut self) -> Result<Option<UmpMessage<&[u32]>>, Error<R::Error>> {
+        loop {
+            let mut bytes = &self.buffer[self.start..self.end];
+            let framed = self.decoder.frame(&mut bytes);
+            self.start = self.end - bytes.len();
+            if framed? {
+                break;
+            }
+            match self
+                .reader
+                .read(&mut self.buffer)
+                .await
+                .map_err(Error::Io)?
+            {
+                0 => return Ok(None),
+                n => {
+                    self.start = 0;
+                    self.end = n;
+                }
+            }
+        }
+        Ok(Some(self.decoder.framed()?))
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Writes ump messages to an [embedded_io_async::Write] byte stream.
+///
+/// Each word is written as four bytes in the configured [Endian] order.
+#[derive(Debug)]
+pub struct UmpWriter<W> {
+    writer: W,
+    endian: Endian,
+}
+
+impl<W: Write> UmpWriter<W> {
+    /// Create a writer of big endian words.
+    pub fn new(writer: W) -> Self {
+        Self::with_endian(writer, Endian::Big)
+    }
+
+    pub fn with_endian(writer: W, endian: Endian) -> Self {
+        UmpWriter { writer, endian }
+    }
+
+    pub async fn write<B: Ump, M: Data<B>>(&mut self, message: &M) -> Result<(), W::Error> {
+        for word in message.data() {
+            self.writer.write_all(&self.endian.bytes(*word)).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), W::Error> {
+        self.writer.flush().await
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+mod bytes {
+    use super::*;
+    use crate::{buffer::Bytes, io::BytesDecoder, message::BytesMessage};
+
+    /// Reads MIDI 1.0 messages from an [embedded_io_async::Read] byte stream,
+    /// such as a UART.
+    ///
+    /// System exclusive messages of up to `SIZE` bytes are supported.
+    /// See [BytesDecoder] for how the stream is framed into messages.
+    ///
+    /// Returns `Ok(None)` at the end of the stream.
+    /// Reading can carry on after a [Error::Decode] failure.
+    #[derive(Debug)]
+    pub struct BytesReader<R, const SIZE: usize = 64> {
+        reader: R,
+        buffer: [u8; READ_BUFFER_SIZE],
+        start: usize,
+        end: usize,
+        decoder: BytesDecoder<SIZE>,
+    }
+
+    impl<R: Read, const SIZE: usize> BytesReader<R, SIZE> {
+        pub fn new(reader: R) -> Self {
+            BytesReader {
+                reader,
+                buffer: [0x0; READ_BUFFER_SIZE],
+                start: 0,
+                end: 0,
+                decoder: BytesDecoder::new(),
+            }
+        }
+
+        pub async fn read(&mut self) -> Result<Option<BytesMessage<&[u8]>>, Error<R::Error>> {
+            loop {
+                let mut bytes = &self.buffer[self.start..self.end];
+                let framed = self.decoder.frame(&mut bytes);
+                self.start = self.end - bytes.len();
+                if framed? {
+                    break;
+                }
+                match self
+                    .reader
+                    .read(&mut self.buffer)
+                    .await
+                    .map_err(Error::Io)?
+                {
+                    0 => return Ok(None),
+                    n => {
+                        self.start = 0;
+                        self.end = n;
+                    }
+                }
+            }
+            Ok(Some(self.decoder.framed()?))
+        }
+
+        pub fn get_ref(&self) -> &R {
+            &self.reader
+        }
+
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.reader
+        }
+
+        pub fn into_inner(self) -> R {
+            self.reader
+        }
+    }
+
+    /// Writes MIDI 1.0 messages to an [embedded_io_async::Write] byte stream.
+    #[derive(Debug)]
+    pub struct BytesWriter<W> {
+        writer: W,
+    }
+
+    impl<W: Write> BytesWriter<W> {
+        pub fn new(writer: W) -> Self {
+            BytesWriter { writer }
+        }
+
+        pub async fn write<B: Bytes, M: Data<B>>(&mut self, message: &M) -> Result<(), W::Error> {
+            self.writer.write_all(message.data()).await
+        }
+
+        pub async fn flush(&mut self) -> Result<(), W::Error> {
+            self.writer.flush().await
+        }
+
+        pub fn get_ref(&self) -> &W {
+            &self.writer
+        }
+
+        pub fn get_mut(&mut self) -> &mut W {
+            &mut self.writer
+        }
+
+        pub fn into_inner(self) -> W {
+            self.writer
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+pub use bytes::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut context = core::task::Context::from_waker(core::task::Waker::noop());
+        loop {
+            if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+
+        let mut buffer = [0x0; 8];
+        let mut writer = UmpWriter::new(&mut buffer[..]);
+        block_on(writer.write(&message)).unwrap();
+
+        let mut reader = UmpReader::<_>::new(&buffer[..]);
+        block_on(async {
+            assert_eq!(reader.read().await.unwrap(), Some(message));
+            assert_eq!(reader.read().await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn bytes_running_status() {
+        let data = [0x90, 0x3C, 0x40, 0x3E, 0x40];
+        let mut reader = BytesReader::<_>::new(&data[..]);
+        block_on(async {
+            assert_eq!(
+                reader.read().await.unwrap().unwrap().data(),
+                &[0x90, 0x3C, 0x40]
+            );
+            assert_eq!(
+                reader.read().await.unwrap().unwrap().data(),
+                &[0x90, 0x3E, 0x40]
+            );
+            assert!(reader.read().await.unwrap().is_none());
+        });
+    }
+}
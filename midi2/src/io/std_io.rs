@@ -0,0 +1,424 @@
+use crate::{
+    buffer::Ump,
+    io::{Endian, UmpDecoder},
+    message::UmpMessage,
+    traits::{Data, RebufferInto},
+};
+use std::io::{ErrorKind, Read, Write};
+
+const READ_BUFFER_SIZE: usize = 64;
+
+fn invalid_data(error: crate::error::Error) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// Buffers the bytes read from the underlying reader
+/// so that partial reads can be picked up by the decoder.
+#[derive(Debug)]
+struct Input<R> {
+    reader: R,
+    buffer: [u8; READ_BUFFER_SIZE],
+    start: usize,
+    end: usize,
+}
+
+impl<R: Read> Input<R> {
+    fn new(reader: R) -> Self {
+        Input {
+            reader,
+            buffer: [0x0; READ_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Pass the buffered bytes to `f`, reading more whenever they're used up.
+    ///
+    /// Returns `Ok(None)` at the end of the reader.
+    fn read<T>(
+        &mut self,
+        mut f: impl FnMut(&mut &[u8]) -> Result<Option<T>, crate::error::Error>,
+    ) -> std::io::Result<Option<T>> {
+        loop {
+            let mut bytes = &self.buffer[self.start..self.end];
+            let result = f(&mut bytes);
+            self.start = self.end - bytes.len();
+            if let Some(value) = result.map_err(invalid_data)? {
+                return Ok(Some(value));
+            }
+
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => return Ok(None),
+                Ok(n) => {
+                    self.start = 0;
+                    self.end = n;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Reads ump messages from a [std::io::Read] byte stream.
+///
+/// Each word is read as four bytes in the configured [Endian] order.
+/// See [UmpDecoder] for how the stream is framed into messages.
+///
+/// Messages of up to `SIZE` words are supported.
+///
+/// Returns `Ok(None)` at the end of the stream and fails with
+/// [ErrorKind::InvalidData] when the stream contains data
+/// which can't be framed into a message.
+/// Reading can carry on after a failure.
+#[derive(Debug)]
+pub struct UmpReader<R, const SIZE: usize = 64> {
+    input: Input<R>,
+    decoder: UmpDecoder<SIZE>,
+}
+
+impl<R: Read, const SIZE: usize> UmpReader<R, SIZE> {
+    /// Create a reader of big endian words.
+    pub fn new(reader: R) -> Self {
+        Self::with_endian(reader, Endian::Big)
+    }
+
+    pub fn with_endian(reader: R, endian: Endian) -> Self {
+        UmpReader {
+            input: Input::new(reader),
+            decoder: UmpDecoder::new(endian),
+        }
+    }
+
+    pub fn read(&mut self) -> std::io::Result<Option<UmpMessage<std::vec::Vec<u32>>>> {
+        let decoder = &mut self.decoder;
+        self.input.read(|bytes| {
+            Ok(decoder
+                .decode(bytes)?
+                .map(|message| message.rebuffer_into()))
+        })
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.input.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input.reader
+    }
+}
+
+impl<R: Read, const SIZE: usize> Iterator for UmpReader<R, SIZE> {
+    type Item = std::io::Result<UmpMessage<std::vec::Vec<u32>>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Writes ump messages to a [std::io::Write] byte stream.
+///
+/// Each word is written as four bytes in the configured [Endian] order.
+///
+/// ```rust
+/// use midi2::{io::UmpWriter, prelude::*};
+///
+/// let mut writer = UmpWriter::new(std::vec::Vec::new());
+///
+/// let mut note_on = channel_voice2::NoteOn::<[u32; 4]>::new();
+/// note_on.set_note_number(u7::new(0x3C));
+/// note_on.set_velocity(0xFFFF);
+/// writer.write(&note_on).unwrap();
+///
+/// assert_eq!(writer.into_inner(), [0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00]);
+/// ```
+#[derive(Debug)]
+pub struct UmpWriter<W> {
+    writer: W,
+    endian: Endian,
+}
+
+impl<W: Write> UmpWriter<W> {
+    /// Create a writer of big endian words.
+    pub fn new(writer: W) -> Self {
+        Self::with_endian(writer, Endian::Big)
+    }
+
+    pub fn with_endian(writer: W, endian: Endian) -> Self {
+        UmpWriter { writer, endian }
+    }
+
+    pub fn write<B: Ump, M: Data<B>>(&mut self, message: &M) -> std::io::Result<()> {
+        for word in message.data() {
+            self.writer.write_all(&self.endian.bytes(*word))?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+mod bytes {
+    use super::*;
+    use crate::{buffer::Bytes, io::BytesDecoder, message::BytesMessage};
+
+    /// Reads MIDI 1.0 messages from a [std::io::Read] byte stream.
+    ///
+    /// See [BytesDecoder] for how the stream is framed into messages.
+    /// System exclusive messages of up to `SIZE` bytes are supported.
+    ///
+    /// Returns `Ok(None)` at the end of the stream and fails with
+    /// [ErrorKind::InvalidData] when the stream contains data
+    /// which can't be framed into a message.
+    /// Reading can carry on after a failure.
+    ///
+    /// ```rust
+    /// use midi2::{io::BytesReader, prelude::*};
+    ///
+    /// let bytes: &[u8] = &[0x90, 0x3C, 0x40, 0x3E, 0x40];
+    /// let messages = BytesReader::<_>::new(bytes)
+    ///     .map(|message| message.map(|m| m.data().to_vec()))
+    ///     .collect::<std::io::Result<Vec<_>>>()
+    ///     .unwrap();
+    /// assert_eq!(messages, [[0x90, 0x3C, 0x40], [0x90, 0x3E, 0x40]]);
+    /// ```
+    #[derive(Debug)]
+    pub struct BytesReader<R, const SIZE: usize = 64> {
+        input: Input<R>,
+        decoder: BytesDecoder<SIZE>,
+    }
+
+    impl<R: Read, const SIZE: usize> BytesReader<R, SIZE> {
+        pub fn new(reader: R) -> Self {
+            BytesReader {
+                input: Input::new(reader),
+                decoder: BytesDecoder::new(),
+            }
+        }
+
+        pub fn read(&mut self) -> std::io::Result<Option<BytesMessage<std::vec::Vec<u8>>>> {
+            let decoder = &mut self.decoder;
+            self.input.read(|bytes| {
+                Ok(decoder
+                    .decode(bytes)?
+                    .map(|message| message.rebuffer_into()))
+            })
+        }
+
+        pub fn get_ref(&self) -> &R {
+            &self.input.reader
+        }
+
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.input.reader
+        }
+
+        pub fn into_inner(self) -> R {
+            self.input.reader
+        }
+    }
+
+    impl<R: Read, const SIZE: usize> Iterator for BytesReader<R, SIZE> {
+        type Item = std::io::Result<BytesMessage<std::vec::Vec<u8>>>;
+        fn next(&mut self) -> Option<Self::Item> {
+            self.read().transpose()
+        }
+    }
+
+    /// Writes MIDI 1.0 messages to a [std::io::Write] byte stream.
+    #[derive(Debug)]
+    pub struct BytesWriter<W> {
+        writer: W,
+    }
+
+    impl<W: Write> BytesWriter<W> {
+        pub fn new(writer: W) -> Self {
+            BytesWriter { writer }
+        }
+
+        pub fn write<B: Bytes, M: Data<B>>(&mut self, message: &M) -> std::io::Result<()> {
+            self.writer.write_all(message.data())
+        }
+
+        pub fn flush(&mut self) -> std::io::Result<()> {
+            self.writer.flush()
+        }
+
+        pub fn get_ref(&self) -> &W {
+            &self.writer
+        }
+
+        pub fn get_mut(&mut self) -> &mut W {
+            &mut self.writer
+        }
+
+        pub fn into_inner(self) -> W {
+            self.writer
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+pub use bytes::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Hands out at most one byte per read and is interrupted in between.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            let Some((&byte, rest)) = self.data.split_first() else {
+                return Ok(0);
+            };
+            self.data = rest;
+            buf[0] = byte;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn read_partial_reads() {
+        let mut reader = UmpReader::<_>::new(Trickle {
+            data: &[0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00],
+            interrupt: false,
+        });
+        assert_eq!(
+            reader.read().unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+        assert!(reader.read().unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn read_invalid_data() {
+        let data: &[u8] = &[
+            0xF3, 0xFF, 0x00, 0x00, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+            0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00,
+        ];
+        let mut reader = UmpReader::<_>::new(data);
+        assert_eq!(
+            reader.read().map_err(|e| e.kind()),
+            Err(ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            reader.read().unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+
+        let mut writer = UmpWriter::with_endian(std::vec::Vec::new(), Endian::Little);
+        writer.write(&message).unwrap();
+        writer.write(&message).unwrap();
+        let bytes = writer.into_inner();
+
+        let messages = UmpReader::<_>::with_endian(&bytes[..], Endian::Little)
+            .collect::<std::io::Result<std::vec::Vec<_>>>()
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].data(), message.data());
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn bytes_write_read_round_trip() {
+        let message = crate::BytesMessage::try_from(&[0x90, 0x3C, 0x40][..]).unwrap();
+
+        let mut writer = BytesWriter::new(std::vec::Vec::new());
+        writer.write(&message).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = BytesReader::<_>::new(Trickle {
+            data: &bytes[..],
+            interrupt: false,
+        });
+        assert_eq!(reader.read().unwrap().unwrap().data(), message.data());
+        assert!(reader.read().unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "sysex7", feature = "channel-voice1"))]
+    fn bytes_sysex_longer_than_default_size() {
+        let mut data = std::vec![0xF0];
+        data.extend((0..100).map(|i| i as u8));
+        data.push(0xF7);
+        data.extend([0x90, 0x3C, 0x40]);
+
+        let mut reader = BytesReader::<_>::new(&data[..]);
+        assert_eq!(
+            reader.read().map_err(|e| e.kind()),
+            Err(ErrorKind::InvalidData)
+        );
+        assert_eq!(reader.read().unwrap().unwrap().data(), &[0x90, 0x3C, 0x40]);
+        assert!(reader.read().unwrap().is_none());
+
+        let mut reader = BytesReader::<_, 128>::new(&data[..]);
+        assert_eq!(reader.read().unwrap().unwrap().data(), &data[..102]);
+        assert_eq!(reader.read().unwrap().unwrap().data(), &[0x90, 0x3C, 0x40]);
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn ump_sysex_longer_than_default_size() {
+        use crate::traits::Sysex;
+
+        let mut message = crate::sysex7::Sysex7::<std::vec::Vec<u32>>::new();
+        message.set_payload((0..200).map(|i| crate::ux::u7::new(i % 0x80)));
+        assert!(message.data().len() > 64);
+
+        let mut writer = UmpWriter::new(std::vec::Vec::new());
+        writer.write(&message).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = UmpReader::<_>::new(&bytes[..]);
+        assert_eq!(
+            reader.read().map_err(|e| e.kind()),
+            Err(ErrorKind::InvalidData)
+        );
+        assert!(reader.read().unwrap().is_none());
+
+        let mut reader = UmpReader::<_, 128>::new(&bytes[..]);
+        assert_eq!(reader.read().unwrap().unwrap().data(), message.data());
+    }
+}
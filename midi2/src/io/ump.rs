@@ -0,0 +1,457 @@
+use crate::{
+    detail::BitOps,
+    error::{BufferOverflow, Error, InvalidData},
+    io::Endian,
+    message::UmpMessage,
+    packet::{self, Position},
+};
+
+const ERR_ORPHANED_PACKET: &str = "Packet continues a message which was never started";
+const ERR_INTERRUPTED_MESSAGE: &str = "Multi-packet message was interrupted by another message";
+
+/// Frames ump messages from a stream of bytes without allocating.
+///
+/// Bytes are fed in with [decode](UmpDecoder::decode) in chunks of any size.
+/// The words of a message are collected into an internal buffer of `SIZE` words
+/// and the message is handed out borrowed from it once it's complete.
+///
+/// Single packet messages which arrive between the packets of a multi-packet
+/// message are handed out as they come, without disturbing the longer message.
+///
+/// ```rust
+/// use midi2::{io::{Endian, UmpDecoder}, prelude::*};
+///
+/// let mut decoder = UmpDecoder::<16>::new(Endian::Big);
+///
+/// let mut input: &[u8] = &[0x40, 0x90, 0x3C];
+/// assert_eq!(decoder.decode(&mut input), Ok(None));
+///
+/// let mut input: &[u8] = &[0x00, 0xFF, 0xFF, 0x00, 0x00];
+/// let message = decoder.decode(&mut input).unwrap().unwrap();
+/// assert_eq!(message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct UmpDecoder<const SIZE: usize = 64> {
+    endian: Endian,
+    word: [u8; 4],
+    word_len: usize,
+    buffer: [u32; SIZE],
+    len: usize,
+    // the offset of the packet currently being received
+    packet_start: usize,
+    // the framed message waiting to be handed out
+    ready: Option<core::ops::Range<usize>>,
+    // the message was framed while an error was reported
+    // and is handed out by the next call
+    deferred: bool,
+    // the message type whose remaining packets are dropped
+    // after the message overflowed the buffer
+    dropping: Option<u8>,
+    // the words left of a packet which is larger than the buffer
+    skip: usize,
+}
+
+impl<const SIZE: usize> Default for UmpDecoder<SIZE> {
+    fn default() -> Self {
+        Self::new(Endian::default())
+    }
+}
+
+impl<const SIZE: usize> UmpDecoder<SIZE> {
+    pub fn new(endian: Endian) -> Self {
+        UmpDecoder {
+            endian,
+            word: [0x0; 4],
+            word_len: 0,
+            buffer: [0x0; SIZE],
+            len: 0,
+            packet_start: 0,
+            ready: None,
+            deferred: false,
+            dropping: None,
+            skip: 0,
+        }
+    }
+
+    /// Consume bytes from the front of the input until a message is complete.
+    ///
+    /// Returns `Ok(None)` once the input is used up without completing a message.
+    /// The partially received message is kept for the next call.
+    ///
+    /// Errors are reported as soon as they're found and the offending data is dropped,
+    /// so decoding can carry on with the next call.
+    /// Messages longer than the internal buffer fail with [Error::BufferOverflow]
+    /// and the rest of their packets are dropped.
+    pub fn decode<'a>(
+        &'a mut self,
+        input: &mut &[u8],
+    ) -> Result<Option<UmpMessage<&'a [u32]>>, Error> {
+        if !self.frame(input)? {
+            return Ok(None);
+        }
+        Ok(Some(self.framed()?))
+    }
+
+    /// Drop any partially received data.
+    pub fn reset(&mut self) {
+        self.word_len = 0;
+        self.len = 0;
+        self.packet_start = 0;
+        self.ready = None;
+        self.deferred = false;
+        self.dropping = None;
+        self.skip = 0;
+    }
+
+    /// Advance through the input until a message has been framed.
+    pub(crate) fn frame(&mut self, input: &mut &[u8]) -> Result<bool, Error> {
+        if core::mem::take(&mut self.deferred) {
+            return Ok(true);
+        }
+        self.release();
+        while let Some((&byte, rest)) = input.split_first() {
+            *input = rest;
+            self.word[self.word_len] = byte;
+            self.word_len += 1;
+            if self.word_len < 4 {
+                continue;
+            }
+            self.word_len = 0;
+            if self.push_word(self.endian.word(self.word))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Advance through already assembled words until a message has been framed.
+    #[cfg(any(feature = "net", feature = "serial"))]
+    pub(crate) fn frame_words(&mut self, input: &mut &[u32]) -> Result<bool, Error> {
+        if core::mem::take(&mut self.deferred) {
+            return Ok(true);
+        }
+        self.release();
+        while let Some((&word, rest)) = input.split_first() {
+            *input = rest;
+            if self.push_word(word)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The message most recently framed by [frame](Self::frame).
+    pub(crate) fn framed(&self) -> Result<UmpMessage<&[u32]>, InvalidData> {
+        let Some(ready) = self.ready.clone() else {
+            return Err(InvalidData(
+                crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT,
+            ));
+        };
+        UmpMessage::try_from(&self.buffer[ready])
+    }
+
+    // drop the message which was handed out by the last call
+    fn release(&mut self) {
+        if let Some(ready) = self.ready.take() {
+            self.len = ready.start;
+            self.packet_start = ready.start;
+        }
+    }
+
+    fn push_word(&mut self, word: u32) -> Result<bool, Error> {
+        if self.skip != 0 {
+            self.skip -= 1;
+            return Ok(false);
+        }
+        if self.packet_start == self.len && self.len + packet::size(word.nibble(0).into()) > SIZE {
+            return self.overflow(word);
+        }
+        self.buffer[self.len] = word;
+        self.len += 1;
+
+        let first = self.buffer[self.packet_start];
+        if self.len - self.packet_start < packet::size(first.nibble(0).into()) {
+            return Ok(false);
+        }
+
+        let packet_start = self.packet_start;
+        self.packet_start = self.len;
+        if self.is_dropped(first) {
+            self.len = packet_start;
+            self.packet_start = packet_start;
+            return Ok(false);
+        }
+        let continues_message = packet_start != 0 && self.buffer[0].nibble(0) == first.nibble(0);
+        match packet::position(first) {
+            Position::Complete => {
+                self.ready = Some(packet_start..self.len);
+                Ok(true)
+            }
+            Position::Start if packet_start == 0 => Ok(false),
+            Position::Start => {
+                self.buffer.copy_within(packet_start..self.len, 0);
+                self.len -= packet_start;
+                self.packet_start = self.len;
+                Err(InvalidData(ERR_INTERRUPTED_MESSAGE).into())
+            }
+            Position::Continue if continues_message => Ok(false),
+            Position::End if continues_message => {
+                self.ready = Some(0..self.len);
+                Ok(true)
+            }
+            Position::Continue | Position::End => {
+                self.len = packet_start;
+                self.packet_start = packet_start;
+                Err(InvalidData(ERR_ORPHANED_PACKET).into())
+            }
+        }
+    }
+
+    // the packet which starts with the word doesn't fit in the buffer
+    fn overflow(&mut self, word: u32) -> Result<bool, Error> {
+        let message_type = u8::from(word.nibble(0));
+        if self.len == 0 {
+            // the packet alone is larger than the buffer
+            self.skip = packet::size(message_type) - 1;
+            if self.is_dropped(word) {
+                return Ok(false);
+            }
+            if packet::position(word) == Position::Start {
+                self.dropping = Some(message_type);
+            }
+            return Err(BufferOverflow.into());
+        }
+
+        // drop the message being received along with its remaining packets
+        self.dropping = Some(self.buffer[0].nibble(0).into());
+        self.len = 0;
+        self.packet_start = 0;
+        if let Ok(true) = self.push_word(word) {
+            self.deferred = true;
+        }
+        Err(BufferOverflow.into())
+    }
+
+    // whether the packet belongs to a message which overflowed the buffer
+    fn is_dropped(&mut self, first: u32) -> bool {
+        if self.dropping != Some(first.nibble(0).into()) {
+            return false;
+        }
+        match packet::position(first) {
+            Position::Continue => true,
+            Position::End => {
+                self.dropping = None;
+                true
+            }
+            Position::Complete | Position::Start => {
+                self.dropping = None;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+
+    fn bytes(words: &[u32]) -> std::vec::Vec<u8> {
+        words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn byte_at_a_time() {
+        let mut decoder = UmpDecoder::<4>::default();
+        let data = bytes(&[0x4090_3C00, 0xFFFF_0000]);
+        let mut messages = std::vec::Vec::new();
+        for byte in data.iter() {
+            let mut input = core::slice::from_ref(byte);
+            if let Some(message) = decoder.decode(&mut input).unwrap() {
+                messages.push(message.data().to_vec());
+            }
+        }
+        assert_eq!(messages, [[0x4090_3C00, 0xFFFF_0000]]);
+    }
+
+    #[test]
+    fn little_endian() {
+        let mut decoder = UmpDecoder::<4>::new(Endian::Little);
+        let mut input: &[u8] = &[0x00, 0x3C, 0x90, 0x40, 0x00, 0x00, 0xFF, 0xFF];
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+    }
+
+    #[test]
+    fn several_messages_in_one_input() {
+        let mut decoder = UmpDecoder::<4>::default();
+        let data = bytes(&[0x4090_3C00, 0xFFFF_0000, 0x4080_3C00, 0x0]);
+        let mut input = &data[..];
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+        assert_eq!(input.len(), 8);
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4080_3C00, 0x0]
+        );
+        assert_eq!(decoder.decode(&mut input), Ok(None));
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn multi_packet_message() {
+        let mut decoder = UmpDecoder::<8>::default();
+        let data = bytes(&[
+            0x3016_0001,
+            0x0203_0405,
+            0x4090_3C00,
+            0xFFFF_0000,
+            0x3031_0600,
+            0x0,
+        ]);
+        let mut input = &data[..];
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x3016_0001, 0x0203_0405, 0x3031_0600, 0x0]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn orphaned_packet() {
+        let mut decoder = UmpDecoder::<8>::default();
+        let data = bytes(&[0x3031_0600, 0x0, 0x4090_3C00, 0xFFFF_0000]);
+        let mut input = &data[..];
+        assert_eq!(
+            decoder.decode(&mut input),
+            Err(InvalidData(ERR_ORPHANED_PACKET).into())
+        );
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn interrupted_message() {
+        let mut decoder = UmpDecoder::<8>::default();
+        let data = bytes(&[0x3016_0001, 0x0203_0405, 0x3011_0600, 0x0, 0x3031_0700, 0x0]);
+        let mut input = &data[..];
+        assert_eq!(
+            decoder.decode(&mut input),
+            Err(InvalidData(ERR_INTERRUPTED_MESSAGE).into())
+        );
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x3011_0600, 0x0, 0x3031_0700, 0x0]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn message_too_long() {
+        let mut decoder = UmpDecoder::<4>::default();
+        let data = bytes(&[
+            0x3016_0001,
+            0x0203_0405,
+            0x3026_0001,
+            0x0203_0405,
+            0x3031_0600,
+        ]);
+        let mut input = &data[..];
+        assert_eq!(decoder.decode(&mut input), Err(BufferOverflow.into()));
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn rest_of_message_too_long_is_dropped() {
+        let mut decoder = UmpDecoder::<4>::default();
+        let data = bytes(&[
+            0x3016_0001,
+            0x0203_0405,
+            0x3026_0001,
+            0x0203_0405,
+            0x3026_0001,
+            0x0203_0405,
+            0x3031_0600,
+            0x0,
+            0x4090_3C00,
+            0xFFFF_0000,
+        ]);
+        let mut input = &data[..];
+        assert_eq!(decoder.decode(&mut input), Err(BufferOverflow.into()));
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+        assert_eq!(decoder.decode(&mut input), Ok(None));
+    }
+
+    #[test]
+    #[cfg(all(feature = "sysex7", feature = "system-common"))]
+    fn message_framed_on_overflow() {
+        let mut decoder = UmpDecoder::<4>::default();
+        let data = bytes(&[
+            0x3016_0001,
+            0x0203_0405,
+            0x3026_0001,
+            0x0203_0405,
+            0x10F8_0000,
+            0x3031_0600,
+            0x0,
+        ]);
+        let mut input = &data[..];
+        assert_eq!(decoder.decode(&mut input), Err(BufferOverflow.into()));
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x10F8_0000]
+        );
+        assert_eq!(decoder.decode(&mut input), Ok(None));
+    }
+
+    #[test]
+    #[cfg(all(feature = "sysex8", feature = "system-common"))]
+    fn packet_larger_than_buffer() {
+        let mut decoder = UmpDecoder::<2>::default();
+        let data = bytes(&[
+            0x5011_0000,
+            0x0,
+            0x0,
+            0x0,
+            0x5031_0000,
+            0x0,
+            0x0,
+            0x0,
+            0x10F8_0000,
+        ]);
+        let mut input = &data[..];
+        assert_eq!(decoder.decode(&mut input), Err(BufferOverflow.into()));
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x10F8_0000]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn invalid_message() {
+        let mut decoder = UmpDecoder::<4>::default();
+        let data = bytes(&[0xF3FF_0000, 0x0, 0x0, 0x0, 0x4090_3C00, 0xFFFF_0000]);
+        let mut input = &data[..];
+        assert!(decoder.decode(&mut input).is_err());
+        assert_eq!(
+            decoder.decode(&mut input).unwrap().unwrap().data(),
+            &[0x4090_3C00, 0xFFFF_0000]
+        );
+    }
+}
@@ -5,39 +5,66 @@
 #[cfg(any(feature = "std", test))]
 extern crate std;
 
+#[cfg(feature = "ble-midi")]
+pub mod ble_midi;
 #[cfg(feature = "channel-voice1")]
 pub mod channel_voice1;
 #[cfg(feature = "channel-voice2")]
 pub mod channel_voice2;
 #[cfg(feature = "ci")]
 pub mod ci;
+#[cfg(feature = "clip")]
+pub mod clip;
 #[cfg(feature = "flex-data")]
 pub mod flex_data;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "rtp-midi")]
+pub mod rtp_midi;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+#[cfg(feature = "serial")]
+pub mod serial;
+#[cfg(feature = "smf")]
+pub mod smf;
 #[cfg(feature = "sysex7")]
 pub mod sysex7;
 #[cfg(feature = "sysex8")]
 pub mod sysex8;
 #[cfg(feature = "system-common")]
 pub mod system_common;
+#[cfg(feature = "tracker")]
+pub mod tracker;
 #[cfg(feature = "ump-stream")]
 pub mod ump_stream;
+#[cfg(feature = "usb-midi")]
+pub mod usb_midi;
 #[cfg(feature = "utility")]
 pub mod utility;
 
 pub mod buffer;
 pub mod error;
+pub mod io;
 
 mod detail;
 mod message;
 mod packet;
 mod packets;
+#[cfg(target_has_atomic = "ptr")]
+mod queue;
 mod traits;
+mod unknown;
+mod writer;
 
 pub use ux;
 
 pub use message::*;
 pub use packets::*;
+#[cfg(target_has_atomic = "ptr")]
+pub use queue::*;
 pub use traits::*;
+pub use unknown::UnknownMessage;
+pub use writer::*;
 
 pub mod num {
     pub use ux::*;
@@ -4,6 +4,9 @@
     midi2_proc::Packets,
     midi2_proc::RebufferFrom,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -28,6 +31,7 @@ pub enum UmpMessage<B: crate::buffer::Ump> {
     UmpStream(crate::ump_stream::UmpStream<B>),
     #[cfg(feature = "utility")]
     Utility(crate::utility::Utility<B>),
+    Unknown(crate::unknown::UnknownMessage<B>),
 }
 
 impl<'a> core::convert::TryFrom<&'a [u32]> for UmpMessage<&'a [u32]> {
@@ -67,11 +71,167 @@ impl<'a> core::convert::TryFrom<&'a [u32]> for UmpMessage<&'a [u32]> {
             }
             #[cfg(feature = "utility")]
             crate::utility::UMP_MESSAGE_TYPE => Utility(crate::utility::Utility::try_from(buffer)?),
-            _ => Err(crate::error::InvalidData(
-                "Couldn't interpret ump message type",
-            ))?,
+            _ => Unknown(crate::unknown::UnknownMessage::try_from(buffer)?),
+        })
+    }
+}
+
+impl<'a> UmpMessage<&'a [u32]> {
+    /// Interprets the (possibly multi-packet) message at the front of the data.
+    ///
+    /// Accepts the same messages as `try_from`, but failures are reported with a
+    /// [Diagnostic](crate::error::Diagnostic) which locates the fault.
+    /// Use [diagnose_strict](Self::diagnose_strict) to also reject reserved bits which are set.
+    ///
+    /// ```rust
+    /// use midi2::{prelude::*, error::{ErrorKind, Offset}};
+    ///
+    /// let diagnostic = UmpMessage::diagnose(&[0x4898_5E03][..]).unwrap_err();
+    /// assert_eq!(diagnostic.kind, ErrorKind::Truncated);
+    /// assert_eq!(diagnostic.offset, Offset::Word(1));
+    /// assert_eq!(diagnostic.expected, Some(2));
+    /// assert_eq!(diagnostic.found, Some(1));
+    /// ```
+    pub fn diagnose(buffer: &'a [u32]) -> Result<Self, crate::error::Diagnostic> {
+        let size = crate::detail::diagnose::ump(buffer)?;
+        UmpMessage::try_from(&buffer[..size]).map_err(|e| {
+            crate::detail::diagnose::from_invalid_data(e, crate::error::Offset::Word(0))
         })
     }
+
+    /// As [diagnose](Self::diagnose), but messages with reserved bits set are rejected
+    /// with a [Diagnostic](crate::error::Diagnostic) of kind
+    /// [ReservedBitsSet](crate::error::ErrorKind::ReservedBitsSet).
+    ///
+    /// ```rust
+    /// use midi2::{prelude::*, error::{ErrorKind, Offset}};
+    ///
+    /// // a note on with reserved bits set in the first word
+    /// let buffer = [0x4090_BC00, 0xFFFF_0000];
+    /// assert!(UmpMessage::diagnose(&buffer[..]).is_ok());
+    ///
+    /// let diagnostic = UmpMessage::diagnose_strict(&buffer[..]).unwrap_err();
+    /// assert_eq!(diagnostic.kind, ErrorKind::ReservedBitsSet);
+    /// assert_eq!(diagnostic.offset, Offset::Word(0));
+    /// ```
+    pub fn diagnose_strict(buffer: &'a [u32]) -> Result<Self, crate::error::Diagnostic> {
+        let size = crate::detail::diagnose::ump_strict(buffer)?;
+        UmpMessage::try_from(&buffer[..size]).map_err(|e| {
+            crate::detail::diagnose::from_invalid_data(e, crate::error::Offset::Word(0))
+        })
+    }
+
+    /// Interprets the (possibly multi-packet) message at the front of the data
+    /// without rejecting messages which don't strictly follow the specification.
+    ///
+    /// Reserved bits which have been set are reported through the returned
+    /// [Warnings](crate::error::Warnings).
+    /// Packets with unassigned statuses or invalid fields within the channel voice 2,
+    /// flex data, ump stream and utility message types come back unmodified
+    /// as the `Unknown` variant of the corresponding enum,
+    /// so that they can be forwarded as they are.
+    /// The other message types have no unassigned statuses to fall back on
+    /// and are interpreted strictly apart from their reserved bits.
+    ///
+    /// ```rust
+    /// use midi2::{prelude::*, error::{ErrorKind, Offset}};
+    ///
+    /// // a note on with reserved bits set in the first word
+    /// let (message, mut warnings) = UmpMessage::try_from_lenient(&[0x4090_BC00, 0xFFFF_0000]).unwrap();
+    /// assert!(matches!(
+    ///     message,
+    ///     UmpMessage::ChannelVoice2(channel_voice2::ChannelVoice2::NoteOn(_))
+    /// ));
+    ///
+    /// let warning = warnings.next().unwrap();
+    /// assert_eq!(warning.kind, ErrorKind::ReservedBitsSet);
+    /// assert_eq!(warning.offset, Offset::Word(0));
+    /// assert_eq!(warning.found, Some(0x8000));
+    /// assert_eq!(warnings.next(), None);
+    /// ```
+    pub fn try_from_lenient(
+        buffer: &'a [u32],
+    ) -> Result<(Self, crate::error::Warnings<'a>), crate::error::Diagnostic> {
+        use crate::detail::BitOps;
+
+        match buffer.first().map(|w| u8::from(w.nibble(0))) {
+            #[cfg(feature = "channel-voice2")]
+            Some(crate::channel_voice2::UMP_MESSAGE_TYPE) => {
+                crate::channel_voice2::ChannelVoice2::try_from_lenient(buffer)
+                    .map(|(message, warnings)| (message.into(), warnings))
+            }
+            #[cfg(feature = "flex-data")]
+            Some(crate::flex_data::UMP_MESSAGE_TYPE) => {
+                crate::flex_data::FlexData::try_from_lenient(buffer)
+                    .map(|(message, warnings)| (message.into(), warnings))
+            }
+            #[cfg(feature = "ump-stream")]
+            Some(crate::ump_stream::UMP_MESSAGE_TYPE) => {
+                crate::ump_stream::UmpStream::try_from_lenient(buffer)
+                    .map(|(message, warnings)| (message.into(), warnings))
+            }
+            #[cfg(feature = "utility")]
+            Some(crate::utility::UMP_MESSAGE_TYPE) => {
+                crate::utility::Utility::try_from_lenient(buffer)
+                    .map(|(message, warnings)| (message.into(), warnings))
+            }
+            _ => crate::detail::diagnose::lenient(buffer, None),
+        }
+    }
+}
+
+/// Iterates over the messages in a buffer of contiguous ump data.
+///
+/// Each item borrows the data of a single, possibly multi-packet, message.
+/// The packets of a multi-packet message must be contiguous in the buffer.
+/// Data which cannot be interpreted is reported with an error
+/// and the offending packet is skipped.
+///
+/// ```rust
+/// use midi2::prelude::*;
+///
+/// let buffer = [
+///     0x4090_3C00, 0xFFFF_0000, // note on
+///     0x4080_3C00, 0x0000_0000, // note off
+/// ];
+/// let mut messages = UmpMessageIterator::new(&buffer[..]);
+/// assert!(matches!(
+///     messages.next(),
+///     Some(Ok(UmpMessage::ChannelVoice2(channel_voice2::ChannelVoice2::NoteOn(_))))
+/// ));
+/// assert!(matches!(
+///     messages.next(),
+///     Some(Ok(UmpMessage::ChannelVoice2(channel_voice2::ChannelVoice2::NoteOff(_))))
+/// ));
+/// assert!(messages.next().is_none());
+/// ```
+#[derive(Clone, Debug)]
+pub struct UmpMessageIterator<'a>(&'a [u32]);
+
+impl<'a> UmpMessageIterator<'a> {
+    pub fn new(data: &'a [u32]) -> Self {
+        UmpMessageIterator(data)
+    }
+}
+
+impl<'a> core::iter::Iterator for UmpMessageIterator<'a> {
+    type Item = Result<UmpMessage<&'a [u32]>, crate::error::InvalidData>;
+    fn next(&mut self) -> Option<Self::Item> {
+        use crate::detail::BitOps;
+
+        let first = self.0.first()?;
+        let size = match crate::packet::message_size(self.0) {
+            Ok(size) => size,
+            Err(e) => {
+                let skip = crate::packet::size(first.nibble(0).into()).min(self.0.len());
+                self.0 = &self.0[skip..];
+                return Some(Err(e));
+            }
+        };
+        let (message, rest) = self.0.split_at(size);
+        self.0 = rest;
+        Some(UmpMessage::try_from(message))
+    }
 }
 
 #[derive(
@@ -79,6 +239,9 @@ impl<'a> core::convert::TryFrom<&'a [u32]> for UmpMessage<&'a [u32]> {
     midi2_proc::Data,
     midi2_proc::RebufferFrom,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -129,10 +292,29 @@ impl<'a> core::convert::TryFrom<&'a [u8]> for BytesMessage<&'a [u8]> {
     }
 }
 
+#[cfg(any(
+    feature = "channel-voice1",
+    feature = "sysex7",
+    feature = "system-common"
+))]
+impl<'a> BytesMessage<&'a [u8]> {
+    /// Interprets the message at the front of the data.
+    ///
+    /// Unlike `try_from`, failures are reported with a
+    /// [Diagnostic](crate::error::Diagnostic) which locates the fault.
+    pub fn diagnose(buffer: &'a [u8]) -> Result<Self, crate::error::Diagnostic> {
+        let size = crate::detail::diagnose::bytes(buffer)?;
+        BytesMessage::try_from(&buffer[..size]).map_err(|e| {
+            crate::detail::diagnose::from_invalid_data(e, crate::error::Offset::Byte(0))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    use crate::ux::*;
     #[allow(unused_imports)]
     use pretty_assertions::assert_eq;
 
@@ -151,6 +333,185 @@ mod tests {
     static_assertions::assert_impl_all!(UmpMessage<&[u32]>: Copy);
     static_assertions::assert_impl_all!(UmpMessage<[u32; 4]>: Copy);
 
+    #[test]
+    fn diagnose_built_messages() {
+        use crate::{channel_voice2, num::Fixed7_9, traits::Data};
+
+        let mut program_change = channel_voice2::ProgramChange::<[u32; 4]>::new();
+        program_change.set_program(u7::new(0x7F));
+        program_change.set_bank(Some(u14::new(0x3FFF)));
+        assert!(UmpMessage::diagnose(program_change.data()).is_ok());
+
+        let per_note_management = [0x40F0_7F03, 0x0];
+        assert!(UmpMessage::diagnose(&per_note_management[..]).is_ok());
+
+        let mut note_on = channel_voice2::NoteOn::<[u32; 4]>::new();
+        note_on.set_note_number(u7::new(0x7F));
+        note_on.set_attribute(Some(channel_voice2::NoteAttribute::Pitch7_9(
+            Fixed7_9::from_bits(0xFFFF),
+        )));
+        assert!(UmpMessage::diagnose(note_on.data()).is_ok());
+    }
+
+    #[test]
+    fn diagnose_falls_back_to_message_validation() {
+        use crate::error::{ErrorKind, Offset};
+
+        let diagnostic = UmpMessage::diagnose(&[0x4000_0004, 0x0][..]).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::OutOfRange);
+        assert_eq!(diagnostic.offset, Offset::Word(0));
+        assert_eq!(
+            crate::error::InvalidData::from(diagnostic),
+            crate::error::InvalidData("Couldn't interpret controller index"),
+        );
+    }
+
+    #[test]
+    fn reserved_message_types_pass_through() {
+        use crate::traits::Data;
+
+        let buffer = [
+            0x6000_0001, // 1 word
+            0xB000_0000,
+            0x1,
+            0x2, // 3 words
+            0x4090_3C00,
+            0xFFFF_0000, // note on
+        ];
+        let messages: std::vec::Vec<_> = UmpMessageIterator::new(&buffer[..])
+            .map(|m| m.unwrap())
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages[0], UmpMessage::Unknown(_)));
+        assert_eq!(messages[1].data(), &[0xB000_0000, 0x1, 0x2]);
+        assert!(matches!(messages[2], UmpMessage::ChannelVoice2(_)));
+    }
+
+    #[test]
+    #[cfg(not(feature = "sysex7"))]
+    fn disabled_message_types_pass_through() {
+        use crate::traits::Data;
+
+        let buffer = [0x3016_0001, 0x0203_0405, 0x3032_0607, 0x0];
+        let messages: std::vec::Vec<_> = UmpMessageIterator::new(&buffer[..])
+            .map(|m| m.unwrap())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0], UmpMessage::Unknown(_)));
+        assert_eq!(messages[0].data(), &[0x3016_0001, 0x0203_0405]);
+        assert_eq!(messages[1].data(), &[0x3032_0607, 0x0]);
+        assert!(UmpMessage::diagnose_strict(&buffer[..]).is_ok());
+    }
+
+    #[test]
+    fn lenient_reports_reserved_bits() {
+        use crate::error::{ErrorKind, Offset};
+
+        let buffer = [0x4090_BC00, 0xFFFF_0000];
+        assert!(UmpMessage::diagnose(&buffer[..]).is_ok());
+        assert!(UmpMessage::diagnose_strict(&buffer[..]).is_err());
+
+        let (message, warnings) = UmpMessage::try_from_lenient(&buffer[..]).unwrap();
+        assert_eq!(message, UmpMessage::try_from(&buffer[..]).unwrap());
+        let warnings: std::vec::Vec<_> = warnings.collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, ErrorKind::ReservedBitsSet);
+        assert_eq!(warnings[0].offset, Offset::Word(0));
+    }
+
+    #[test]
+    fn lenient_unknown_channel_voice2_status() {
+        use crate::{channel_voice2::ChannelVoice2, traits::Data};
+
+        let buffer = [0x4378_1234, 0x5678_9ABC];
+        assert!(UmpMessage::try_from(&buffer[..]).is_err());
+
+        let (message, mut warnings) = UmpMessage::try_from_lenient(&buffer[..]).unwrap();
+        let UmpMessage::ChannelVoice2(ChannelVoice2::Unknown(unknown)) = message else {
+            panic!();
+        };
+        assert_eq!(unknown.data(), &buffer[..]);
+        assert_eq!(unknown.raw_data(), [0x0070_1234, 0x5678_9ABC]);
+        assert_eq!(warnings.next(), None);
+    }
+
+    #[test]
+    fn lenient_invalid_fields_come_back_unknown() {
+        use crate::channel_voice2::ChannelVoice2;
+
+        let (message, _) = UmpMessage::try_from_lenient(&[0x4000_0004, 0x0][..]).unwrap();
+        assert!(matches!(
+            message,
+            UmpMessage::ChannelVoice2(ChannelVoice2::Unknown(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn lenient_unknown_flex_data_bank() {
+        use crate::{
+            flex_data::{Bank, FlexData, FlexDataMessage},
+            traits::Data,
+        };
+
+        let buffer = [
+            0xD110_0701,
+            0x1234_5678,
+            0x0,
+            0x0,
+            0xD1D0_0701,
+            0x0,
+            0x0,
+            0x0,
+        ];
+        let (message, _) = UmpMessage::try_from_lenient(&buffer[..]).unwrap();
+        let UmpMessage::FlexData(message) = message else {
+            panic!();
+        };
+        assert!(matches!(message, FlexData::Unknown(_)));
+        assert_eq!(message.bank(), Bank::Unknown(0x7));
+        assert_eq!(message.data(), &buffer[..4]);
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn lenient_unknown_ump_stream_status() {
+        use crate::ump_stream::UmpStream;
+
+        let buffer = [0xF3FF_0000, 0x0, 0x0, 0x0];
+        let (message, _) = UmpMessage::try_from_lenient(&buffer[..]).unwrap();
+        assert!(matches!(
+            message,
+            UmpMessage::UmpStream(UmpStream::Unknown(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "utility")]
+    fn lenient_unknown_utility_status() {
+        use crate::utility::Utility;
+
+        let (message, _) = UmpMessage::try_from_lenient(&[0x0070_0000][..]).unwrap();
+        assert!(matches!(message, UmpMessage::Utility(Utility::Unknown(_))));
+    }
+
+    #[test]
+    fn lenient_truncated_is_an_error() {
+        use crate::error::ErrorKind;
+
+        let diagnostic = UmpMessage::try_from_lenient(&[0x4378_1234][..]).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::Truncated);
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn lenient_without_unknown_variant() {
+        use crate::error::ErrorKind;
+
+        let diagnostic = UmpMessage::try_from_lenient(&[0x3040_0000, 0x0][..]).unwrap_err();
+        assert_eq!(diagnostic.kind, ErrorKind::InvalidStatus);
+    }
+
     #[test]
     #[cfg(feature = "channel-voice1")]
     fn from_byte_data() {
@@ -395,4 +756,44 @@ mod tests {
         let level2_message = Stop::<[u32; 4]>::new();
         let _: UmpMessage<[u32; 4]> = level2_message.into();
     }
+
+    #[cfg(all(feature = "channel-voice2", feature = "sysex7"))]
+    #[test]
+    fn iterate_messages() {
+        use crate::channel_voice2::ChannelVoice2;
+        use crate::Data;
+
+        let buffer = [
+            0x4090_3C00,
+            0xFFFF_0000,
+            0x3016_0001,
+            0x0203_0405,
+            0x3032_0607,
+            0x0000_0000,
+            0x4080_3C00,
+            0x0000_0000,
+        ];
+        let mut messages = UmpMessageIterator::new(&buffer[..]);
+        let Some(Ok(UmpMessage::ChannelVoice2(ChannelVoice2::NoteOn(_)))) = messages.next() else {
+            panic!();
+        };
+        let Some(Ok(UmpMessage::Sysex7(sysex))) = messages.next() else {
+            panic!();
+        };
+        assert_eq!(sysex.data(), &buffer[2..6]);
+        let Some(Ok(UmpMessage::ChannelVoice2(ChannelVoice2::NoteOff(_)))) = messages.next() else {
+            panic!();
+        };
+        assert_eq!(messages.next(), None);
+    }
+
+    #[cfg(all(feature = "channel-voice2", feature = "sysex7"))]
+    #[test]
+    fn iterate_messages_skips_invalid_packets() {
+        let buffer = [0x3026_0001, 0x0203_0405, 0x4090_3C00, 0xFFFF_0000];
+        let mut messages = UmpMessageIterator::new(&buffer[..]);
+        assert!(messages.next().unwrap().is_err());
+        assert!(messages.next().unwrap().is_ok());
+        assert_eq!(messages.next(), None);
+    }
 }
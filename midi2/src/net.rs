@@ -0,0 +1,48 @@
+//! Network MIDI 2.0, carrying ump messages over UDP.
+//!
+//! The module is sans-IO: a [Session] is fed the datagrams received from its peer
+//! and hands back the datagrams to transmit, the events to act on
+//! and the next time it needs waking.
+//! Sockets, clocks and discovery are left to the application,
+//! which keeps the protocol usable with any runtime and testable in memory.
+//!
+//! A session covers one connection between a client and a host, including
+//! invitation, optional shared secret or user authentication,
+//! sequenced UMP Data commands with forward error correction,
+//! retransmission, ping and bye.
+//! The [command] packets can also be encoded and decoded directly.
+//!
+//! ```rust
+//! use midi2::{net::{Config, Event, Session}, prelude::*};
+//! use std::time::Instant;
+//!
+//! let now = Instant::now();
+//! let mut client = Session::<64>::client(Config::default());
+//! let mut host = Session::<64>::host(Config::default(), || [0x5A; 16]);
+//!
+//! client.invite(now);
+//! while let Some(datagram) = client.poll_transmit() {
+//!     host.handle_datagram(now, &datagram).unwrap();
+//! }
+//! while let Some(datagram) = host.poll_transmit() {
+//!     client.handle_datagram(now, &datagram).unwrap();
+//! }
+//! assert!(matches!(client.poll_event(), Some(Event::Established { .. })));
+//!
+//! let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+//! client.send(&message).unwrap();
+//! while let Some(datagram) = client.poll_transmit() {
+//!     host.handle_datagram(now, &datagram).unwrap();
+//! }
+//! assert!(matches!(host.poll_event(), Some(Event::Established { .. })));
+//! let Some(Event::Message(received)) = host.poll_event() else {
+//!     panic!();
+//! };
+//! assert_eq!(received.data(), message.data());
+//! ```
+
+pub mod command;
+mod session;
+
+pub use command::{ByeReason, Command, NakReason};
+pub use session::{Authentication, Config, Error, Event, Session};
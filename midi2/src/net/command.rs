@@ -0,0 +1,737 @@
+use crate::error::InvalidData;
+use std::{string::String, vec::Vec};
+
+/// Every Network MIDI 2.0 datagram starts with these bytes.
+pub const SIGNATURE: [u8; 4] = *b"MIDI";
+
+const ERR_NO_SIGNATURE: &str = "Network MIDI datagrams should begin with the MIDI signature";
+const ERR_TRUNCATED_COMMAND: &str = "Command packet is truncated";
+const ERR_PAYLOAD_TOO_SHORT: &str = "Command payload is too short";
+const ERR_INVALID_STRING: &str = "Command payload string is not valid UTF-8";
+const ERR_PAYLOAD_TOO_LONG: &str = "Command payload is longer than 255 words";
+
+/// The largest payload of a command, in words.
+pub const MAX_PAYLOAD_WORDS: usize = 0xFF;
+
+mod code {
+    pub const INVITATION: u8 = 0x01;
+    pub const INVITATION_WITH_AUTHENTICATION: u8 = 0x02;
+    pub const INVITATION_WITH_USER_AUTHENTICATION: u8 = 0x03;
+    pub const INVITATION_ACCEPTED: u8 = 0x10;
+    pub const INVITATION_PENDING: u8 = 0x11;
+    pub const AUTHENTICATION_REQUIRED: u8 = 0x12;
+    pub const USER_AUTHENTICATION_REQUIRED: u8 = 0x13;
+    pub const PING: u8 = 0x20;
+    pub const PING_REPLY: u8 = 0x21;
+    pub const RETRANSMIT_REQUEST: u8 = 0x80;
+    pub const RETRANSMIT_ERROR: u8 = 0x81;
+    pub const SESSION_RESET: u8 = 0x82;
+    pub const SESSION_RESET_REPLY: u8 = 0x83;
+    pub const NAK: u8 = 0x8F;
+    pub const BYE: u8 = 0xF0;
+    pub const BYE_REPLY: u8 = 0xF1;
+    pub const UMP_DATA: u8 = 0xFF;
+}
+
+/// The authentication methods which a client supports,
+/// advertised in its invitation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub authentication: bool,
+    pub user_authentication: bool,
+}
+
+impl Capabilities {
+    fn from_byte(byte: u8) -> Self {
+        Capabilities {
+            authentication: byte & 0b01 != 0,
+            user_authentication: byte & 0b10 != 0,
+        }
+    }
+
+    fn byte(&self) -> u8 {
+        u8::from(self.authentication) | u8::from(self.user_authentication) << 1
+    }
+}
+
+/// Generates an enum for a one byte reason code
+/// which keeps unrecognised values.
+macro_rules! reason {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+            Reserved(u8),
+        }
+
+        impl core::convert::From<u8> for $name {
+            fn from(value: u8) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    v => $name::Reserved(v),
+                }
+            }
+        }
+
+        impl core::convert::From<$name> for u8 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Reserved(v) => v,
+                }
+            }
+        }
+    };
+}
+
+reason!(
+    /// Whether an authentication request is the first
+    /// or follows an incorrect digest.
+    AuthenticationState {
+        FirstRequest = 0x00,
+        IncorrectDigest = 0x01,
+    }
+);
+
+reason!(
+    /// Why the requested UMP Data commands can't be retransmitted.
+    RetransmitErrorReason {
+        Unknown = 0x00,
+        BufferDoesNotContainSequence = 0x01,
+    }
+);
+
+reason!(
+    /// Why a command was rejected.
+    NakReason {
+        Other = 0x00,
+        CommandNotSupported = 0x01,
+        CommandNotExpected = 0x02,
+        CommandMalformed = 0x03,
+        BadPingReply = 0x20,
+    }
+);
+
+reason!(
+    /// Why a session was ended.
+    ByeReason {
+        Unknown = 0x00,
+        UserTerminated = 0x01,
+        PowerDown = 0x02,
+        TooManyMissingPackets = 0x03,
+        Timeout = 0x04,
+        SessionNotEstablished = 0x05,
+        NoPendingSession = 0x06,
+        ProtocolError = 0x07,
+        TooManySessions = 0x40,
+        AuthenticationRejected = 0x41,
+        InvitationRejected = 0x42,
+        AuthenticationFailed = 0x43,
+        UsernameNotFound = 0x44,
+        NoMatchingAuthenticationMethod = 0x45,
+        InvitationCanceled = 0x80,
+    }
+);
+
+/// A command packet of the Network MIDI 2.0 protocol.
+///
+/// One or more commands are carried in each datagram,
+/// see [encode_datagram] and [decode_datagram].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Invitation {
+        endpoint_name: String,
+        product_instance_id: String,
+        capabilities: Capabilities,
+    },
+    InvitationWithAuthentication {
+        digest: [u8; 32],
+    },
+    InvitationWithUserAuthentication {
+        digest: [u8; 32],
+        username: String,
+    },
+    InvitationAccepted {
+        endpoint_name: String,
+        product_instance_id: String,
+    },
+    InvitationPending {
+        endpoint_name: String,
+        product_instance_id: String,
+    },
+    AuthenticationRequired {
+        nonce: [u8; 16],
+        state: AuthenticationState,
+        endpoint_name: String,
+        product_instance_id: String,
+    },
+    UserAuthenticationRequired {
+        nonce: [u8; 16],
+        state: AuthenticationState,
+        endpoint_name: String,
+        product_instance_id: String,
+    },
+    Ping {
+        id: u32,
+    },
+    PingReply {
+        id: u32,
+    },
+    RetransmitRequest {
+        sequence: u16,
+        count: u16,
+    },
+    RetransmitError {
+        sequence: u16,
+        reason: RetransmitErrorReason,
+    },
+    SessionReset,
+    SessionResetReply,
+    Nak {
+        reason: NakReason,
+        /// The first word of the rejected command.
+        command_header: u32,
+        message: String,
+    },
+    Bye {
+        reason: ByeReason,
+        message: String,
+    },
+    ByeReply,
+    /// Zero or more whole ump packets.
+    UmpData {
+        sequence: u16,
+        words: Vec<u32>,
+    },
+    /// A command with a code which isn't recognised.
+    Unknown {
+        code: u8,
+        specific_data: u16,
+        payload: Vec<u32>,
+    },
+}
+
+impl Command {
+    /// Append the encoded command to the buffer.
+    ///
+    /// Fails, leaving the buffer untouched, when the payload is longer
+    /// than [MAX_PAYLOAD_WORDS].
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> Result<(), InvalidData> {
+        use Command::*;
+
+        let mut payload = Vec::new();
+        let (code, specific_data) = match self {
+            Invitation {
+                endpoint_name,
+                product_instance_id,
+                capabilities,
+            } => {
+                let name_len = put_str(&mut payload, endpoint_name);
+                put_str(&mut payload, product_instance_id);
+                (code::INVITATION, specific(name_len, capabilities.byte()))
+            }
+            InvitationWithAuthentication { digest } => {
+                payload.extend_from_slice(digest);
+                (code::INVITATION_WITH_AUTHENTICATION, 0x0)
+            }
+            InvitationWithUserAuthentication { digest, username } => {
+                payload.extend_from_slice(digest);
+                put_str(&mut payload, username);
+                (code::INVITATION_WITH_USER_AUTHENTICATION, 0x0)
+            }
+            InvitationAccepted {
+                endpoint_name,
+                product_instance_id,
+            } => {
+                let name_len = put_str(&mut payload, endpoint_name);
+                put_str(&mut payload, product_instance_id);
+                (code::INVITATION_ACCEPTED, specific(name_len, 0x0))
+            }
+            InvitationPending {
+                endpoint_name,
+                product_instance_id,
+            } => {
+                let name_len = put_str(&mut payload, endpoint_name);
+                put_str(&mut payload, product_instance_id);
+                (code::INVITATION_PENDING, specific(name_len, 0x0))
+            }
+            AuthenticationRequired {
+                nonce,
+                state,
+                endpoint_name,
+                product_instance_id,
+            }
+            | UserAuthenticationRequired {
+                nonce,
+                state,
+                endpoint_name,
+                product_instance_id,
+            } => {
+                payload.extend_from_slice(nonce);
+                let name_len = put_str(&mut payload, endpoint_name);
+                put_str(&mut payload, product_instance_id);
+                let code = if matches!(self, AuthenticationRequired { .. }) {
+                    code::AUTHENTICATION_REQUIRED
+                } else {
+                    code::USER_AUTHENTICATION_REQUIRED
+                };
+                (code, specific(name_len, (*state).into()))
+            }
+            Ping { id } => {
+                payload.extend_from_slice(&id.to_be_bytes());
+                (code::PING, 0x0)
+            }
+            PingReply { id } => {
+                payload.extend_from_slice(&id.to_be_bytes());
+                (code::PING_REPLY, 0x0)
+            }
+            RetransmitRequest { sequence, count } => {
+                payload.extend_from_slice(&(u32::from(*count) << 16).to_be_bytes());
+                (code::RETRANSMIT_REQUEST, *sequence)
+            }
+            RetransmitError { sequence, reason } => {
+                payload.extend_from_slice(&(u32::from(*sequence) << 16).to_be_bytes());
+                (code::RETRANSMIT_ERROR, specific(u8::from(*reason), 0x0))
+            }
+            SessionReset => (code::SESSION_RESET, 0x0),
+            SessionResetReply => (code::SESSION_RESET_REPLY, 0x0),
+            Nak {
+                reason,
+                command_header,
+                message,
+            } => {
+                payload.extend_from_slice(&command_header.to_be_bytes());
+                put_str(&mut payload, message);
+                (code::NAK, specific(u8::from(*reason), 0x0))
+            }
+            Bye { reason, message } => {
+                put_str(&mut payload, message);
+                (code::BYE, specific(u8::from(*reason), 0x0))
+            }
+            ByeReply => (code::BYE_REPLY, 0x0),
+            UmpData { sequence, words } => {
+                for word in words {
+                    payload.extend_from_slice(&word.to_be_bytes());
+                }
+                (code::UMP_DATA, *sequence)
+            }
+            Unknown {
+                code,
+                specific_data,
+                payload: words,
+            } => {
+                for word in words {
+                    payload.extend_from_slice(&word.to_be_bytes());
+                }
+                (*code, *specific_data)
+            }
+        };
+
+        let Ok(payload_words) = u8::try_from(payload.len() / 4) else {
+            return Err(InvalidData(ERR_PAYLOAD_TOO_LONG));
+        };
+        buffer.push(code);
+        buffer.push(payload_words);
+        buffer.extend_from_slice(&specific_data.to_be_bytes());
+        buffer.extend_from_slice(&payload);
+        Ok(())
+    }
+
+    /// Decode the command at the front of the data.
+    ///
+    /// Returns the command and the number of bytes it takes up.
+    pub fn decode(data: &[u8]) -> Result<(Command, usize), InvalidData> {
+        use Command::*;
+
+        if data.len() < 4 {
+            return Err(InvalidData(ERR_TRUNCATED_COMMAND));
+        }
+        let code = data[0];
+        let size = 4 + 4 * usize::from(data[1]);
+        let specific_data = u16::from_be_bytes([data[2], data[3]]);
+        let [specific_1, specific_2] = [data[2], data[3]];
+        let Some(payload) = data.get(4..size) else {
+            return Err(InvalidData(ERR_TRUNCATED_COMMAND));
+        };
+        let mut payload = Payload(payload);
+
+        let command = match code {
+            code::INVITATION => {
+                let endpoint_name = payload.str(specific_1)?;
+                Invitation {
+                    endpoint_name,
+                    product_instance_id: payload.rest_str()?,
+                    capabilities: Capabilities::from_byte(specific_2),
+                }
+            }
+            code::INVITATION_WITH_AUTHENTICATION => InvitationWithAuthentication {
+                digest: payload.array()?,
+            },
+            code::INVITATION_WITH_USER_AUTHENTICATION => InvitationWithUserAuthentication {
+                digest: payload.array()?,
+                username: payload.rest_str()?,
+            },
+            code::INVITATION_ACCEPTED | code::INVITATION_PENDING => {
+                let endpoint_name = payload.str(specific_1)?;
+                let product_instance_id = payload.rest_str()?;
+                if code == code::INVITATION_ACCEPTED {
+                    InvitationAccepted {
+                        endpoint_name,
+                        product_instance_id,
+                    }
+                } else {
+                    InvitationPending {
+                        endpoint_name,
+                        product_instance_id,
+                    }
+                }
+            }
+            code::AUTHENTICATION_REQUIRED | code::USER_AUTHENTICATION_REQUIRED => {
+                let nonce = payload.array()?;
+                let state = specific_2.into();
+                let endpoint_name = payload.str(specific_1)?;
+                let product_instance_id = payload.rest_str()?;
+                if code == code::AUTHENTICATION_REQUIRED {
+                    AuthenticationRequired {
+                        nonce,
+                        state,
+                        endpoint_name,
+                        product_instance_id,
+                    }
+                } else {
+                    UserAuthenticationRequired {
+                        nonce,
+                        state,
+                        endpoint_name,
+                        product_instance_id,
+                    }
+                }
+            }
+            code::PING => Ping {
+                id: payload.word()?,
+            },
+            code::PING_REPLY => PingReply {
+                id: payload.word()?,
+            },
+            code::RETRANSMIT_REQUEST => RetransmitRequest {
+                sequence: specific_data,
+                count: (payload.word()? >> 16) as u16,
+            },
+            code::RETRANSMIT_ERROR => RetransmitError {
+                sequence: (payload.word()? >> 16) as u16,
+                reason: specific_1.into(),
+            },
+            code::SESSION_RESET => SessionReset,
+            code::SESSION_RESET_REPLY => SessionResetReply,
+            code::NAK => Nak {
+                reason: specific_1.into(),
+                command_header: payload.word()?,
+                message: payload.rest_str()?,
+            },
+            code::BYE => Bye {
+                reason: specific_1.into(),
+                message: payload.rest_str()?,
+            },
+            code::BYE_REPLY => ByeReply,
+            code::UMP_DATA => UmpData {
+                sequence: specific_data,
+                words: payload.words(),
+            },
+            code => Unknown {
+                code,
+                specific_data,
+                payload: payload.words(),
+            },
+        };
+        Ok((command, size))
+    }
+
+    /// The first word of the encoded command.
+    ///
+    /// Fails when the command can't be encoded, see [encode](Self::encode).
+    pub fn header(&self) -> Result<u32, InvalidData> {
+        let mut buffer = Vec::new();
+        self.encode(&mut buffer)?;
+        Ok(u32::from_be_bytes([
+            buffer[0], buffer[1], buffer[2], buffer[3],
+        ]))
+    }
+}
+
+/// Encode the commands into a single datagram, after the [SIGNATURE].
+///
+/// Fails when any of the commands can't be encoded, see [Command::encode].
+pub fn encode_datagram(commands: &[Command]) -> Result<Vec<u8>, InvalidData> {
+    let mut buffer = SIGNATURE.to_vec();
+    for command in commands {
+        command.encode(&mut buffer)?;
+    }
+    Ok(buffer)
+}
+
+/// Decode all the commands of a datagram.
+pub fn decode_datagram(data: &[u8]) -> Result<Vec<Command>, InvalidData> {
+    let Some(mut data) = data.strip_prefix(&SIGNATURE) else {
+        return Err(InvalidData(ERR_NO_SIGNATURE));
+    };
+    let mut commands = Vec::new();
+    while !data.is_empty() {
+        let (command, size) = Command::decode(data)?;
+        commands.push(command);
+        data = &data[size..];
+    }
+    Ok(commands)
+}
+
+fn specific(first: u8, second: u8) -> u16 {
+    u16::from_be_bytes([first, second])
+}
+
+/// Append the string padded with nulls to a whole number of words.
+///
+/// Returns the padded length in words. A string too long for
+/// the one byte length saturates it, the whole payload is then
+/// too long and is rejected by [Command::encode].
+fn put_str(payload: &mut Vec<u8>, s: &str) -> u8 {
+    let start = payload.len();
+    payload.extend_from_slice(s.as_bytes());
+    while payload.len() % 4 != 0 {
+        payload.push(0x0);
+    }
+    u8::try_from((payload.len() - start) / 4).unwrap_or(u8::MAX)
+}
+
+struct Payload<'a>(&'a [u8]);
+
+impl Payload<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], InvalidData> {
+        if self.0.len() < len {
+            return Err(InvalidData(ERR_PAYLOAD_TOO_SHORT));
+        }
+        let (front, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(front)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], InvalidData> {
+        let mut array = [0x0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn word(&mut self) -> Result<u32, InvalidData> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn words(&mut self) -> Vec<u32> {
+        let words = self
+            .0
+            .chunks_exact(4)
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        self.0 = &[];
+        words
+    }
+
+    fn str(&mut self, words: u8) -> Result<String, InvalidData> {
+        let bytes = self.take(4 * usize::from(words))?;
+        to_string(bytes)
+    }
+
+    fn rest_str(&mut self) -> Result<String, InvalidData> {
+        let bytes = self.0;
+        self.0 = &[];
+        to_string(bytes)
+    }
+}
+
+fn to_string(bytes: &[u8]) -> Result<String, InvalidData> {
+    let end = bytes.iter().position(|&b| b == 0x0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..end])
+        .map(String::from)
+        .map_err(|_| InvalidData(ERR_INVALID_STRING))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn round_trip(command: Command) {
+        let mut buffer = Vec::new();
+        command.encode(&mut buffer).unwrap();
+        assert_eq!(buffer.len() % 4, 0);
+        assert_eq!(Command::decode(&buffer), Ok((command, buffer.len())));
+    }
+
+    #[test]
+    fn encode_invitation() {
+        let mut buffer = Vec::new();
+        Command::Invitation {
+            endpoint_name: "Synth".into(),
+            product_instance_id: "ABC".into(),
+            capabilities: Capabilities {
+                authentication: true,
+                user_authentication: false,
+            },
+        }
+        .encode(&mut buffer)
+        .unwrap();
+        assert_eq!(
+            buffer,
+            [
+                0x01, 0x03, 0x02, 0x01, b'S', b'y', b'n', b't', b'h', 0x0, 0x0, 0x0, b'A', b'B',
+                b'C', 0x0,
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_ump_data() {
+        let mut buffer = Vec::new();
+        Command::UmpData {
+            sequence: 0x1234,
+            words: std::vec![0x4090_3C00, 0xFFFF_0000],
+        }
+        .encode(&mut buffer)
+        .unwrap();
+        assert_eq!(
+            buffer,
+            [0xFF, 0x02, 0x12, 0x34, 0x40, 0x90, 0x3C, 0x00, 0xFF, 0xFF, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(Command::Invitation {
+            endpoint_name: "Endpoint".into(),
+            product_instance_id: "12345".into(),
+            capabilities: Capabilities {
+                authentication: true,
+                user_authentication: true,
+            },
+        });
+        round_trip(Command::InvitationWithAuthentication { digest: [0xAB; 32] });
+        round_trip(Command::InvitationWithUserAuthentication {
+            digest: [0xAB; 32],
+            username: "user".into(),
+        });
+        round_trip(Command::InvitationAccepted {
+            endpoint_name: "Host".into(),
+            product_instance_id: "".into(),
+        });
+        round_trip(Command::InvitationPending {
+            endpoint_name: "".into(),
+            product_instance_id: "X".into(),
+        });
+        round_trip(Command::AuthenticationRequired {
+            nonce: [0x1; 16],
+            state: AuthenticationState::IncorrectDigest,
+            endpoint_name: "Host".into(),
+            product_instance_id: "1".into(),
+        });
+        round_trip(Command::UserAuthenticationRequired {
+            nonce: [0x2; 16],
+            state: AuthenticationState::FirstRequest,
+            endpoint_name: "Host".into(),
+            product_instance_id: "1".into(),
+        });
+        round_trip(Command::Ping { id: 0xDEAD_BEEF });
+        round_trip(Command::PingReply { id: 0xDEAD_BEEF });
+        round_trip(Command::RetransmitRequest {
+            sequence: 0xFFFE,
+            count: 3,
+        });
+        round_trip(Command::RetransmitError {
+            sequence: 0xFFFE,
+            reason: RetransmitErrorReason::BufferDoesNotContainSequence,
+        });
+        round_trip(Command::SessionReset);
+        round_trip(Command::SessionResetReply);
+        round_trip(Command::Nak {
+            reason: NakReason::CommandNotSupported,
+            command_header: 0x4200_0000,
+            message: "what".into(),
+        });
+        round_trip(Command::Bye {
+            reason: ByeReason::Reserved(0x99),
+            message: "".into(),
+        });
+        round_trip(Command::ByeReply);
+        round_trip(Command::UmpData {
+            sequence: 7,
+            words: std::vec![0x2090_3C40],
+        });
+        round_trip(Command::Unknown {
+            code: 0x42,
+            specific_data: 0x1234,
+            payload: std::vec![0x1, 0x2],
+        });
+    }
+
+    #[test]
+    fn datagram_round_trip() {
+        let commands = std::vec![
+            Command::Ping { id: 1 },
+            Command::UmpData {
+                sequence: 0,
+                words: std::vec![0x2090_3C40],
+            },
+        ];
+        let datagram = encode_datagram(&commands).unwrap();
+        assert_eq!(&datagram[..4], b"MIDI");
+        assert_eq!(decode_datagram(&datagram), Ok(commands));
+    }
+
+    #[test]
+    fn longest_payload() {
+        round_trip(Command::UmpData {
+            sequence: 0,
+            words: std::vec![0x0; MAX_PAYLOAD_WORDS],
+        });
+    }
+
+    #[test]
+    fn payload_too_long() {
+        let mut buffer = Vec::new();
+        assert_eq!(
+            Command::UmpData {
+                sequence: 0,
+                words: std::vec![0x0; MAX_PAYLOAD_WORDS + 1],
+            }
+            .encode(&mut buffer),
+            Err(InvalidData(ERR_PAYLOAD_TOO_LONG))
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn name_too_long() {
+        let endpoint_name = String::from_utf8(std::vec![b'a'; 4 * MAX_PAYLOAD_WORDS + 1]).unwrap();
+        assert_eq!(
+            Command::InvitationAccepted {
+                endpoint_name,
+                product_instance_id: String::new(),
+            }
+            .header(),
+            Err(InvalidData(ERR_PAYLOAD_TOO_LONG))
+        );
+    }
+
+    #[test]
+    fn datagram_without_signature() {
+        assert_eq!(
+            decode_datagram(&[0x20, 0x01, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1]),
+            Err(InvalidData(ERR_NO_SIGNATURE))
+        );
+    }
+
+    #[test]
+    fn truncated_command() {
+        assert_eq!(
+            Command::decode(&[0x20, 0x01, 0x0, 0x0, 0x0, 0x0]),
+            Err(InvalidData(ERR_TRUNCATED_COMMAND))
+        );
+    }
+}
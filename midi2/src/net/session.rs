@@ -0,0 +1,1403 @@
+use crate::{
+    buffer::Ump,
+    error::InvalidData,
+    io::UmpDecoder,
+    message::UmpMessage,
+    net::command::{
+        self, AuthenticationState, ByeReason, Capabilities, Command, NakReason,
+        RetransmitErrorReason,
+    },
+    traits::{Data, RebufferInto},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+// the largest number of words in a UMP Data command
+// which is a whole number of packets of any size
+const MAX_COMMAND_WORDS: usize = 252;
+const MAX_OUTSTANDING_PINGS: usize = 16;
+// the limits of the UMP Endpoint Name and Product Instance Id notifications
+const MAX_ENDPOINT_NAME_BYTES: usize = 98;
+const MAX_PRODUCT_INSTANCE_ID_BYTES: usize = 42;
+// the payload of an invitation with user authentication after the digest
+const MAX_USERNAME_BYTES: usize = 4 * (command::MAX_PAYLOAD_WORDS - 8);
+// keeps a datagram within the MTU of an ethernet link
+const MAX_DATAGRAM_SIZE: usize = 1400;
+
+/// How a host requires its clients to authenticate,
+/// or which credentials a client offers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Authentication {
+    #[default]
+    None,
+    SharedSecret(String),
+    User {
+        username: String,
+        password: String,
+    },
+}
+
+/// The settings of a [Session].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The name advertised to the peer, cut to 98 bytes.
+    pub endpoint_name: String,
+    /// The product instance id advertised to the peer, cut to 42 bytes.
+    pub product_instance_id: String,
+    pub authentication: Authentication,
+    /// How many previous UMP Data commands are repeated in each datagram.
+    pub fec_count: usize,
+    /// How many sent UMP Data commands are kept for retransmission.
+    pub retransmit_buffer: usize,
+    /// How long to wait for a reply to an invitation or a bye.
+    pub reply_timeout: Duration,
+    /// How many times an invitation is sent before giving up.
+    pub invitation_attempts: u32,
+    /// How many incorrect digests a host accepts before ending the session.
+    pub authentication_attempts: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            endpoint_name: String::new(),
+            product_instance_id: String::new(),
+            authentication: Authentication::None,
+            fec_count: 2,
+            retransmit_buffer: 64,
+            reply_timeout: Duration::from_secs(1),
+            invitation_attempts: 3,
+            authentication_attempts: 3,
+        }
+    }
+}
+
+/// Something the application should know about, see [Session::poll_event].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The session is ready for messages.
+    Established {
+        endpoint_name: String,
+        product_instance_id: String,
+    },
+    /// The host is waiting for the invitation to be confirmed.
+    Pending,
+    Message(UmpMessage<Vec<u32>>),
+    PingReply {
+        id: u32,
+        round_trip: Duration,
+    },
+    /// UMP Data commands were missed and couldn't be retransmitted.
+    Lost {
+        count: u16,
+    },
+    /// The peer rejected a command.
+    Nak {
+        reason: NakReason,
+        message: String,
+    },
+    Closed {
+        reason: ByeReason,
+    },
+}
+
+/// Errors from [Session] operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Messages can only be sent once the session is established.
+    NotEstablished,
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        <Self as std::fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Client,
+    Host,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Inviting {
+        attempts: u32,
+        deadline: Instant,
+    },
+    Authenticating {
+        failures: u32,
+    },
+    Established,
+    Closing {
+        reason: ByeReason,
+        deadline: Instant,
+    },
+    Closed,
+}
+
+/// One end of a Network MIDI 2.0 session.
+///
+/// The session doesn't do any IO itself. The application
+/// - passes every datagram received from the peer to [handle_datagram](Self::handle_datagram),
+/// - sends every datagram from [poll_transmit](Self::poll_transmit) to the peer,
+/// - acts on every [Event] from [poll_event](Self::poll_event),
+/// - calls [handle_timeout](Self::handle_timeout) once the instant
+///   from [poll_timeout](Self::poll_timeout) has passed.
+///
+/// Received messages of up to `SIZE` words are supported.
+///
+/// A client starts the session with [invite](Self::invite).
+/// A host accepts the first invitation it receives, challenging the client
+/// first when [Config::authentication] is set.
+/// Each challenge carries a fresh nonce from the host's nonce source,
+/// and the session ends once [Config::authentication_attempts] incorrect digests
+/// have been received.
+///
+/// Each datagram of sent messages also repeats the previous [Config::fec_count]
+/// UMP Data commands, so the receiver can recover from occasional lost datagrams.
+/// Longer gaps are filled by requesting retransmission.
+#[derive(Debug)]
+pub struct Session<const SIZE: usize = 64> {
+    role: Role,
+    config: Config,
+    nonce_source: NonceSource,
+    nonce: [u8; 16],
+    state: State,
+    invitation: Option<Command>,
+    peer: (String, String),
+    send_sequence: u16,
+    history: VecDeque<(u16, Vec<u32>)>,
+    receive_sequence: Option<u16>,
+    out_of_order: BTreeMap<u16, Vec<u32>>,
+    retransmit_requested: bool,
+    decoder: UmpDecoder<SIZE>,
+    ping_id: u32,
+    pings: VecDeque<(u32, Instant)>,
+    transmit: VecDeque<Vec<u8>>,
+    events: VecDeque<Event>,
+}
+
+struct NonceSource(Box<dyn FnMut() -> [u8; 16] + Send>);
+
+impl core::fmt::Debug for NonceSource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("NonceSource")
+    }
+}
+
+impl<const SIZE: usize> Session<SIZE> {
+    pub fn client(config: Config) -> Self {
+        Self::new(Role::Client, config, NonceSource(Box::new(|| [0x0; 16])))
+    }
+
+    /// `nonce` is called for every authentication challenge the host sends
+    /// and should return bytes freshly generated from a random source.
+    pub fn host(config: Config, nonce: impl FnMut() -> [u8; 16] + Send + 'static) -> Self {
+        Self::new(Role::Host, config, NonceSource(Box::new(nonce)))
+    }
+
+    fn new(role: Role, mut config: Config, nonce_source: NonceSource) -> Self {
+        truncate(&mut config.endpoint_name, MAX_ENDPOINT_NAME_BYTES);
+        truncate(
+            &mut config.product_instance_id,
+            MAX_PRODUCT_INSTANCE_ID_BYTES,
+        );
+        if let Authentication::User { username, .. } = &mut config.authentication {
+            truncate(username, MAX_USERNAME_BYTES);
+        }
+        Session {
+            role,
+            config,
+            nonce_source,
+            nonce: [0x0; 16],
+            state: State::Idle,
+            invitation: None,
+            peer: (String::new(), String::new()),
+            send_sequence: 0,
+            history: VecDeque::new(),
+            receive_sequence: None,
+            out_of_order: BTreeMap::new(),
+            retransmit_requested: false,
+            decoder: UmpDecoder::default(),
+            ping_id: 0,
+            pings: VecDeque::new(),
+            transmit: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn is_established(&self) -> bool {
+        self.state == State::Established
+    }
+
+    /// Invite the host to a session.
+    ///
+    /// The invitation is repeated every [Config::reply_timeout]
+    /// until it's answered or [Config::invitation_attempts] run out.
+    /// Does nothing for a host session.
+    pub fn invite(&mut self, now: Instant) {
+        if self.role != Role::Client {
+            return;
+        }
+        let invitation = Command::Invitation {
+            endpoint_name: self.config.endpoint_name.clone(),
+            product_instance_id: self.config.product_instance_id.clone(),
+            capabilities: Capabilities {
+                authentication: matches!(
+                    self.config.authentication,
+                    Authentication::SharedSecret(_)
+                ),
+                user_authentication: matches!(
+                    self.config.authentication,
+                    Authentication::User { .. }
+                ),
+            },
+        };
+        self.queue(core::slice::from_ref(&invitation));
+        self.invitation = Some(invitation);
+        self.state = State::Inviting {
+            attempts: 1,
+            deadline: now + self.config.reply_timeout,
+        };
+    }
+
+    /// Send a message to the peer.
+    ///
+    /// Messages longer than a single UMP Data command are split over several.
+    pub fn send<B: Ump, M: Data<B>>(&mut self, message: &M) -> Result<(), Error> {
+        if self.state != State::Established {
+            return Err(Error::NotEstablished);
+        }
+        for words in message.data().chunks(MAX_COMMAND_WORDS) {
+            let sequence = self.send_sequence;
+            self.send_sequence = sequence.wrapping_add(1);
+            self.history.push_back((sequence, words.to_vec()));
+            let history_size = self.config.retransmit_buffer.max(self.config.fec_count + 1);
+            while self.history.len() > history_size {
+                self.history.pop_front();
+            }
+
+            let start = self.history.len().saturating_sub(self.config.fec_count + 1);
+            let commands: Vec<Command> = self
+                .history
+                .range(start..)
+                .map(|(sequence, words)| Command::UmpData {
+                    sequence: *sequence,
+                    words: words.clone(),
+                })
+                .collect();
+            self.queue(&commands);
+        }
+        Ok(())
+    }
+
+    /// Send a ping and return its id.
+    ///
+    /// The reply is reported with [Event::PingReply].
+    pub fn ping(&mut self, now: Instant) -> u32 {
+        let id = self.ping_id;
+        self.ping_id = id.wrapping_add(1);
+        if self.pings.len() == MAX_OUTSTANDING_PINGS {
+            self.pings.pop_front();
+        }
+        self.pings.push_back((id, now));
+        self.queue(&[Command::Ping { id }]);
+        id
+    }
+
+    /// Restart the sequence numbers of both ends of the session.
+    pub fn reset(&mut self) -> Result<(), Error> {
+        if self.state != State::Established {
+            return Err(Error::NotEstablished);
+        }
+        self.reset_sequences();
+        self.queue(&[Command::SessionReset]);
+        Ok(())
+    }
+
+    /// End the session.
+    ///
+    /// [Event::Closed] follows once the peer replies,
+    /// or after [Config::reply_timeout].
+    pub fn close(&mut self, now: Instant, reason: ByeReason) {
+        if matches!(self.state, State::Idle | State::Closed) {
+            return;
+        }
+        self.queue(&[Command::Bye {
+            reason,
+            message: String::new(),
+        }]);
+        self.state = State::Closing {
+            reason,
+            deadline: now + self.config.reply_timeout,
+        };
+    }
+
+    /// Handle a datagram received from the peer.
+    ///
+    /// Fails when the datagram can't be decoded, in which case it's ignored.
+    pub fn handle_datagram(&mut self, now: Instant, datagram: &[u8]) -> Result<(), InvalidData> {
+        let commands = command::decode_datagram(datagram)?;
+        let mut replies = Vec::new();
+        for command in commands {
+            self.handle_command(now, command, &mut replies);
+        }
+        self.request_retransmit(&mut replies);
+        self.queue(&replies);
+        Ok(())
+    }
+
+    /// The instant at which [handle_timeout](Self::handle_timeout) should next be called.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        match self.state {
+            State::Inviting { deadline, .. } | State::Closing { deadline, .. } => Some(deadline),
+            _ => None,
+        }
+    }
+
+    pub fn handle_timeout(&mut self, now: Instant) {
+        match self.state {
+            State::Inviting { attempts, deadline } if deadline <= now => {
+                if attempts < self.config.invitation_attempts {
+                    if let Some(invitation) = self.invitation.clone() {
+                        self.queue(&[invitation]);
+                    }
+                    self.state = State::Inviting {
+                        attempts: attempts + 1,
+                        deadline: now + self.config.reply_timeout,
+                    };
+                } else {
+                    self.queue(&[Command::Bye {
+                        reason: ByeReason::InvitationCanceled,
+                        message: String::new(),
+                    }]);
+                    self.closed(ByeReason::Timeout);
+                }
+            }
+            State::Closing { reason, deadline } if deadline <= now => self.closed(reason),
+            _ => {}
+        }
+    }
+
+    /// The next datagram to send to the peer.
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.transmit.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn handle_command(&mut self, now: Instant, command: Command, replies: &mut Vec<Command>) {
+        use Command::*;
+
+        let established = self.state == State::Established;
+        let inviting = matches!(self.state, State::Inviting { .. });
+        let authenticating = matches!(self.state, State::Authenticating { .. });
+        let host = self.role == Role::Host;
+
+        match command {
+            Invitation {
+                endpoint_name,
+                product_instance_id,
+                capabilities,
+            } if host => {
+                self.peer = (endpoint_name, product_instance_id);
+                self.invited(capabilities, replies);
+            }
+            InvitationWithAuthentication { digest } if host && authenticating => {
+                let correct = match &self.config.authentication {
+                    Authentication::SharedSecret(secret) => {
+                        digests_match(&hash(&self.nonce, &[secret]), &digest)
+                    }
+                    _ => false,
+                };
+                self.check_digest(correct, false, replies);
+            }
+            InvitationWithUserAuthentication { digest, username } if host && authenticating => {
+                let correct = match &self.config.authentication {
+                    Authentication::User {
+                        username: expected_username,
+                        password,
+                    } => {
+                        // the digest is checked even for an unknown user
+                        digests_match(&hash(&self.nonce, &[&username, password]), &digest)
+                            & (*expected_username == username)
+                    }
+                    _ => false,
+                };
+                self.check_digest(correct, true, replies);
+            }
+            InvitationAccepted {
+                endpoint_name,
+                product_instance_id,
+            } if !host && inviting => {
+                self.peer = (endpoint_name, product_instance_id);
+                self.establish();
+            }
+            InvitationPending { .. } if !host && inviting => {
+                self.events.push_back(Event::Pending);
+            }
+            AuthenticationRequired { nonce, state, .. } if !host && inviting => {
+                self.authenticate(now, nonce, state, false, replies);
+            }
+            UserAuthenticationRequired { nonce, state, .. } if !host && inviting => {
+                self.authenticate(now, nonce, state, true, replies);
+            }
+            Ping { id } => replies.push(PingReply { id }),
+            PingReply { id } => match self.pings.iter().position(|(ping_id, _)| *ping_id == id) {
+                Some(index) => {
+                    let (_, sent) = self.pings.remove(index).unwrap();
+                    self.events.push_back(Event::PingReply {
+                        id,
+                        round_trip: now.saturating_duration_since(sent),
+                    });
+                }
+                None => replies.push(Nak {
+                    reason: NakReason::BadPingReply,
+                    command_header: PingReply { id }
+                        .header()
+                        .expect("Ping replies fit in a command"),
+                    message: String::new(),
+                }),
+            },
+            UmpData { sequence, words } if established => self.receive(sequence, words),
+            RetransmitRequest { sequence, count } if established => {
+                self.retransmit(sequence, count, replies)
+            }
+            RetransmitError { sequence, .. } if established => self.skip_to(sequence),
+            SessionReset if established => {
+                self.reset_sequences();
+                replies.push(SessionResetReply);
+            }
+            SessionResetReply if established => {}
+            UmpData { .. } | RetransmitRequest { .. } | RetransmitError { .. } | SessionReset => {
+                replies.push(Bye {
+                    reason: ByeReason::SessionNotEstablished,
+                    message: String::new(),
+                });
+            }
+            Nak {
+                reason, message, ..
+            } => self.events.push_back(Event::Nak { reason, message }),
+            Bye { reason, .. } => {
+                replies.push(ByeReply);
+                if !matches!(self.state, State::Idle | State::Closed) {
+                    self.closed(reason);
+                }
+            }
+            ByeReply => {
+                if let State::Closing { reason, .. } = self.state {
+                    self.closed(reason);
+                }
+            }
+            unknown @ Unknown { .. } => replies.push(Nak {
+                reason: NakReason::CommandNotSupported,
+                command_header: unknown.header().expect("Decoded commands fit in a command"),
+                message: String::new(),
+            }),
+            unexpected => replies.push(Nak {
+                reason: NakReason::CommandNotExpected,
+                command_header: unexpected
+                    .header()
+                    .expect("Decoded commands fit in a command"),
+                message: String::new(),
+            }),
+        }
+    }
+
+    fn invited(&mut self, capabilities: Capabilities, replies: &mut Vec<Command>) {
+        let endpoint_name = self.config.endpoint_name.clone();
+        let product_instance_id = self.config.product_instance_id.clone();
+        // inviting again doesn't clear the count of incorrect digests
+        let failures = match self.state {
+            State::Authenticating { failures } => failures,
+            _ => 0,
+        };
+        match self.config.authentication {
+            Authentication::None => self.accept(replies),
+            Authentication::SharedSecret(_) if capabilities.authentication => {
+                self.state = State::Authenticating { failures };
+                replies.push(Command::AuthenticationRequired {
+                    nonce: self.challenge(),
+                    state: AuthenticationState::FirstRequest,
+                    endpoint_name,
+                    product_instance_id,
+                });
+            }
+            Authentication::User { .. } if capabilities.user_authentication => {
+                self.state = State::Authenticating { failures };
+                replies.push(Command::UserAuthenticationRequired {
+                    nonce: self.challenge(),
+                    state: AuthenticationState::FirstRequest,
+                    endpoint_name,
+                    product_instance_id,
+                });
+            }
+            _ => replies.push(Command::Bye {
+                reason: ByeReason::NoMatchingAuthenticationMethod,
+                message: String::new(),
+            }),
+        }
+    }
+
+    fn check_digest(&mut self, correct: bool, user: bool, replies: &mut Vec<Command>) {
+        if correct {
+            self.accept(replies);
+            return;
+        }
+        let State::Authenticating { failures } = self.state else {
+            return;
+        };
+        let failures = failures + 1;
+        if failures >= self.config.authentication_attempts {
+            return self.fail(ByeReason::AuthenticationFailed, replies);
+        }
+        self.state = State::Authenticating { failures };
+        let nonce = self.challenge();
+        let state = AuthenticationState::IncorrectDigest;
+        let endpoint_name = self.config.endpoint_name.clone();
+        let product_instance_id = self.config.product_instance_id.clone();
+        replies.push(if user {
+            Command::UserAuthenticationRequired {
+                nonce,
+                state,
+                endpoint_name,
+                product_instance_id,
+            }
+        } else {
+            Command::AuthenticationRequired {
+                nonce,
+                state,
+                endpoint_name,
+                product_instance_id,
+            }
+        });
+    }
+
+    fn challenge(&mut self) -> [u8; 16] {
+        self.nonce = (self.nonce_source.0)();
+        self.nonce
+    }
+
+    fn accept(&mut self, replies: &mut Vec<Command>) {
+        replies.push(Command::InvitationAccepted {
+            endpoint_name: self.config.endpoint_name.clone(),
+            product_instance_id: self.config.product_instance_id.clone(),
+        });
+        self.establish();
+    }
+
+    fn authenticate(
+        &mut self,
+        now: Instant,
+        nonce: [u8; 16],
+        state: AuthenticationState,
+        user: bool,
+        replies: &mut Vec<Command>,
+    ) {
+        let reply = match (&self.config.authentication, user) {
+            _ if state == AuthenticationState::IncorrectDigest => {
+                return self.fail(ByeReason::AuthenticationFailed, replies);
+            }
+            (Authentication::SharedSecret(secret), false) => {
+                Command::InvitationWithAuthentication {
+                    digest: hash(&nonce, &[secret]),
+                }
+            }
+            (Authentication::User { username, password }, true) => {
+                Command::InvitationWithUserAuthentication {
+                    digest: hash(&nonce, &[username, password]),
+                    username: username.clone(),
+                }
+            }
+            _ => return self.fail(ByeReason::NoMatchingAuthenticationMethod, replies),
+        };
+        replies.push(reply.clone());
+        self.invitation = Some(reply);
+        if let State::Inviting { attempts, .. } = self.state {
+            self.state = State::Inviting {
+                attempts,
+                deadline: now + self.config.reply_timeout,
+            };
+        }
+    }
+
+    fn fail(&mut self, reason: ByeReason, replies: &mut Vec<Command>) {
+        replies.push(Command::Bye {
+            reason,
+            message: String::new(),
+        });
+        self.closed(reason);
+    }
+
+    fn establish(&mut self) {
+        self.state = State::Established;
+        self.reset_sequences();
+        self.events.push_back(Event::Established {
+            endpoint_name: self.peer.0.clone(),
+            product_instance_id: self.peer.1.clone(),
+        });
+    }
+
+    fn closed(&mut self, reason: ByeReason) {
+        self.state = State::Closed;
+        self.invitation = None;
+        self.events.push_back(Event::Closed { reason });
+    }
+
+    fn reset_sequences(&mut self) {
+        self.send_sequence = 0;
+        self.history.clear();
+        self.receive_sequence = None;
+        self.out_of_order.clear();
+        self.retransmit_requested = false;
+        self.decoder.reset();
+    }
+
+    fn receive(&mut self, sequence: u16, words: Vec<u32>) {
+        let expected = *self.receive_sequence.get_or_insert(sequence);
+        match sequence.wrapping_sub(expected) as i16 {
+            // already received, most likely repeated for error correction
+            i16::MIN..=-1 => {}
+            0 => {
+                self.deliver(&words);
+                self.receive_sequence = Some(sequence.wrapping_add(1));
+                self.retransmit_requested = false;
+                self.drain();
+            }
+            _ => {
+                self.out_of_order.entry(sequence).or_insert(words);
+            }
+        }
+    }
+
+    // deliver the buffered commands which now follow on in sequence
+    fn drain(&mut self) {
+        let Some(mut next) = self.receive_sequence else {
+            return;
+        };
+        while let Some(words) = self.out_of_order.remove(&next) {
+            self.deliver(&words);
+            next = next.wrapping_add(1);
+        }
+        self.receive_sequence = Some(next);
+    }
+
+    // give up on the commands before the sequence
+    fn skip_to(&mut self, sequence: u16) {
+        let Some(mut next) = self.receive_sequence else {
+            return;
+        };
+        if sequence.wrapping_sub(next) as i16 <= 0 {
+            return;
+        }
+        let mut lost = 0;
+        while next != sequence {
+            match self.out_of_order.remove(&next) {
+                Some(words) => self.deliver(&words),
+                None => lost += 1,
+            }
+            next = next.wrapping_add(1);
+        }
+        if lost > 0 {
+            self.events.push_back(Event::Lost { count: lost });
+        }
+        self.receive_sequence = Some(next);
+        self.retransmit_requested = false;
+        self.drain();
+    }
+
+    fn deliver(&mut self, mut words: &[u32]) {
+        loop {
+            match self.decoder.frame_words(&mut words) {
+                Ok(true) => {
+                    if let Ok(message) = self.decoder.framed() {
+                        self.events
+                            .push_back(Event::Message(message.rebuffer_into()));
+                    }
+                }
+                Ok(false) => break,
+                // malformed data is dropped and framing carries on
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn request_retransmit(&mut self, replies: &mut Vec<Command>) {
+        if self.retransmit_requested {
+            return;
+        }
+        let Some(expected) = self.receive_sequence else {
+            return;
+        };
+        let Some(count) = self
+            .out_of_order
+            .keys()
+            .map(|sequence| sequence.wrapping_sub(expected))
+            .min()
+        else {
+            return;
+        };
+        replies.push(Command::RetransmitRequest {
+            sequence: expected,
+            count,
+        });
+        self.retransmit_requested = true;
+    }
+
+    fn retransmit(&mut self, sequence: u16, count: u16, replies: &mut Vec<Command>) {
+        let count = match count {
+            0 => self.send_sequence.wrapping_sub(sequence),
+            count => count,
+        };
+        let commands: Vec<Command> = self
+            .history
+            .iter()
+            .filter(|(s, _)| s.wrapping_sub(sequence) < count)
+            .map(|(sequence, words)| Command::UmpData {
+                sequence: *sequence,
+                words: words.clone(),
+            })
+            .collect();
+        if commands.len() == usize::from(count) {
+            replies.extend(commands);
+        } else {
+            let oldest = self
+                .history
+                .front()
+                .map_or(self.send_sequence, |(sequence, _)| *sequence);
+            replies.push(Command::RetransmitError {
+                sequence: oldest,
+                reason: RetransmitErrorReason::BufferDoesNotContainSequence,
+            });
+        }
+    }
+
+    // commands are split over as many datagrams as are needed
+    // to keep each under the maximum datagram size
+    fn queue(&mut self, commands: &[Command]) {
+        let mut datagram = command::SIGNATURE.to_vec();
+        for command in commands {
+            let mut encoded = Vec::new();
+            command
+                .encode(&mut encoded)
+                .expect("Session commands fit in a command");
+            if datagram.len() > command::SIGNATURE.len()
+                && datagram.len() + encoded.len() > MAX_DATAGRAM_SIZE
+            {
+                let full = core::mem::replace(&mut datagram, command::SIGNATURE.to_vec());
+                self.transmit.push_back(full);
+            }
+            datagram.extend_from_slice(&encoded);
+        }
+        if datagram.len() > command::SIGNATURE.len() {
+            self.transmit.push_back(datagram);
+        }
+    }
+}
+
+fn truncate(s: &mut String, len: usize) {
+    if s.len() > len {
+        let end = (0..=len)
+            .rev()
+            .find(|&i| s.is_char_boundary(i))
+            .unwrap_or(0);
+        s.truncate(end);
+    }
+}
+
+fn hash(nonce: &[u8; 16], parts: &[&str]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce);
+    for part in parts {
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+// compares every byte, so the time taken doesn't tell how much of a guess was right
+fn digests_match(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let difference = a
+        .iter()
+        .zip(b.iter())
+        .fold(0x0, |difference, (a, b)| difference | (a ^ b));
+    core::hint::black_box(difference) == 0x0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const NOTE_ON: [u32; 2] = [0x4090_3C00, 0xFFFF_0000];
+    const NOTE_OFF: [u32; 2] = [0x4080_3C00, 0x0];
+
+    fn message(words: &[u32]) -> UmpMessage<&[u32]> {
+        UmpMessage::try_from(words).unwrap()
+    }
+
+    fn config(name: &str, authentication: Authentication) -> Config {
+        Config {
+            endpoint_name: name.into(),
+            product_instance_id: "1234".into(),
+            authentication,
+            ..Default::default()
+        }
+    }
+
+    // exchange datagrams until both sides go quiet
+    fn pump(now: Instant, a: &mut Session, b: &mut Session) {
+        loop {
+            let mut quiet = true;
+            while let Some(datagram) = a.poll_transmit() {
+                b.handle_datagram(now, &datagram).unwrap();
+                quiet = false;
+            }
+            while let Some(datagram) = b.poll_transmit() {
+                a.handle_datagram(now, &datagram).unwrap();
+                quiet = false;
+            }
+            if quiet {
+                break;
+            }
+        }
+    }
+
+    fn events(session: &mut Session) -> Vec<Event> {
+        core::iter::from_fn(|| session.poll_event()).collect()
+    }
+
+    fn messages(session: &mut Session) -> Vec<Vec<u32>> {
+        events(session)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Message(message) => Some(message.data().to_vec()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn connect(client_config: Config, host_config: Config) -> (Session, Session, Instant) {
+        let now = Instant::now();
+        let mut client = Session::client(client_config);
+        let mut host = Session::host(host_config, || [0x42; 16]);
+        client.invite(now);
+        pump(now, &mut client, &mut host);
+        (client, host, now)
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut client, mut host, _) = connect(
+            config("Client", Authentication::None),
+            config("Host", Authentication::None),
+        );
+        assert_eq!(
+            events(&mut client),
+            [Event::Established {
+                endpoint_name: "Host".into(),
+                product_instance_id: "1234".into(),
+            }]
+        );
+        assert_eq!(
+            events(&mut host),
+            [Event::Established {
+                endpoint_name: "Client".into(),
+                product_instance_id: "1234".into(),
+            }]
+        );
+        assert_eq!(client.poll_timeout(), None);
+    }
+
+    #[test]
+    fn messages_both_ways() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut client);
+        events(&mut host);
+
+        client.send(&message(&NOTE_ON)).unwrap();
+        host.send(&message(&NOTE_OFF)).unwrap();
+        pump(now, &mut client, &mut host);
+
+        assert_eq!(messages(&mut host), [NOTE_ON]);
+        assert_eq!(messages(&mut client), [NOTE_OFF]);
+    }
+
+    #[test]
+    fn send_before_established() {
+        let mut client = Session::<64>::client(Config::default());
+        assert_eq!(client.send(&message(&NOTE_ON)), Err(Error::NotEstablished));
+    }
+
+    #[test]
+    fn shared_secret_authentication() {
+        let (client, host, _) = connect(
+            config("Client", Authentication::SharedSecret("secret".into())),
+            config("Host", Authentication::SharedSecret("secret".into())),
+        );
+        assert!(client.is_established());
+        assert!(host.is_established());
+    }
+
+    #[test]
+    fn incorrect_shared_secret() {
+        let (mut client, mut host, _) = connect(
+            config("Client", Authentication::SharedSecret("guess".into())),
+            config("Host", Authentication::SharedSecret("secret".into())),
+        );
+        assert_eq!(
+            events(&mut client),
+            [Event::Closed {
+                reason: ByeReason::AuthenticationFailed
+            }]
+        );
+        assert_eq!(
+            events(&mut host),
+            [Event::Closed {
+                reason: ByeReason::AuthenticationFailed
+            }]
+        );
+    }
+
+    fn challenged_host(authentication_attempts: u32) -> (Session, Instant) {
+        let now = Instant::now();
+        let mut nonce = 0x0;
+        let mut host = Session::host(
+            Config {
+                authentication_attempts,
+                ..config("Host", Authentication::SharedSecret("secret".into()))
+            },
+            move || {
+                nonce += 1;
+                [nonce; 16]
+            },
+        );
+        let datagram = command::encode_datagram(&[Command::Invitation {
+            endpoint_name: "Client".into(),
+            product_instance_id: "1234".into(),
+            capabilities: Capabilities {
+                authentication: true,
+                user_authentication: false,
+            },
+        }])
+        .unwrap();
+        host.handle_datagram(now, &datagram).unwrap();
+        (host, now)
+    }
+
+    fn send_digest(host: &mut Session, now: Instant, digest: [u8; 32]) -> Vec<Command> {
+        let datagram =
+            command::encode_datagram(&[Command::InvitationWithAuthentication { digest }]).unwrap();
+        host.handle_datagram(now, &datagram).unwrap();
+        core::iter::from_fn(|| host.poll_transmit())
+            .flat_map(|datagram| command::decode_datagram(&datagram).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fresh_nonce_for_each_challenge() {
+        let (mut host, now) = challenged_host(3);
+        let Some(Command::AuthenticationRequired { nonce, .. }) = host
+            .poll_transmit()
+            .and_then(|datagram| command::decode_datagram(&datagram).unwrap().pop())
+        else {
+            panic!();
+        };
+        assert_eq!(nonce, [0x1; 16]);
+
+        let replies = send_digest(&mut host, now, hash(&[0x1; 16], &["guess"]));
+        let [Command::AuthenticationRequired { nonce, state, .. }] = &replies[..] else {
+            panic!();
+        };
+        assert_eq!(*nonce, [0x2; 16]);
+        assert_eq!(*state, AuthenticationState::IncorrectDigest);
+
+        // a digest of the previous challenge is no longer accepted
+        let replies = send_digest(&mut host, now, hash(&[0x1; 16], &["secret"]));
+        assert!(matches!(
+            replies[..],
+            [Command::AuthenticationRequired {
+                nonce: [0x3, ..],
+                ..
+            }]
+        ));
+
+        let replies = send_digest(&mut host, now, hash(&[0x3; 16], &["secret"]));
+        assert!(matches!(replies[..], [Command::InvitationAccepted { .. }]));
+        assert!(host.is_established());
+    }
+
+    #[test]
+    fn failed_authentication_attempts_are_capped() {
+        let (mut host, now) = challenged_host(2);
+        host.poll_transmit();
+        send_digest(&mut host, now, [0x0; 32]);
+        assert_eq!(
+            send_digest(&mut host, now, [0x0; 32]),
+            [Command::Bye {
+                reason: ByeReason::AuthenticationFailed,
+                message: String::new(),
+            }]
+        );
+        assert_eq!(
+            events(&mut host),
+            [Event::Closed {
+                reason: ByeReason::AuthenticationFailed
+            }]
+        );
+    }
+
+    #[test]
+    fn digests_match_compares_every_byte() {
+        let digest = [0x5A; 32];
+        let mut other = digest;
+        assert!(digests_match(&digest, &other));
+        other[31] = 0x0;
+        assert!(!digests_match(&digest, &other));
+    }
+
+    #[test]
+    fn user_authentication() {
+        let user = Authentication::User {
+            username: "user".into(),
+            password: "password".into(),
+        };
+        let (client, host, _) = connect(config("Client", user.clone()), config("Host", user));
+        assert!(client.is_established());
+        assert!(host.is_established());
+    }
+
+    #[test]
+    fn no_matching_authentication_method() {
+        let (mut client, host, _) = connect(
+            config("Client", Authentication::None),
+            config("Host", Authentication::SharedSecret("secret".into())),
+        );
+        assert_eq!(
+            events(&mut client),
+            [Event::Closed {
+                reason: ByeReason::NoMatchingAuthenticationMethod
+            }]
+        );
+        assert!(!host.is_established());
+    }
+
+    #[test]
+    fn invitation_times_out() {
+        let mut now = Instant::now();
+        let mut client = Session::<64>::client(Config::default());
+        client.invite(now);
+        let mut invitations = 0;
+        while let Some(timeout) = client.poll_timeout() {
+            while client.poll_transmit().is_some() {
+                invitations += 1;
+            }
+            now = timeout;
+            client.handle_timeout(now);
+        }
+        assert_eq!(invitations, 3);
+        assert_eq!(
+            events(&mut client),
+            [Event::Closed {
+                reason: ByeReason::Timeout
+            }]
+        );
+    }
+
+    #[test]
+    fn error_correction_recovers_lost_datagram() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut host);
+
+        for words in [NOTE_ON, NOTE_OFF, NOTE_ON] {
+            client.send(&message(&words)).unwrap();
+        }
+        let first = client.poll_transmit().unwrap();
+        client.poll_transmit().unwrap();
+        let third = client.poll_transmit().unwrap();
+        host.handle_datagram(now, &first).unwrap();
+        host.handle_datagram(now, &third).unwrap();
+
+        assert_eq!(messages(&mut host), [NOTE_ON, NOTE_OFF, NOTE_ON]);
+        assert!(host.poll_transmit().is_none());
+    }
+
+    #[test]
+    fn retransmit_lost_datagram() {
+        let no_fec = Config {
+            fec_count: 0,
+            ..Default::default()
+        };
+        let (mut client, mut host, now) = connect(no_fec.clone(), no_fec);
+        events(&mut host);
+
+        for words in [NOTE_ON, NOTE_OFF, NOTE_ON] {
+            client.send(&message(&words)).unwrap();
+        }
+        let first = client.poll_transmit().unwrap();
+        client.poll_transmit().unwrap();
+        let third = client.poll_transmit().unwrap();
+        host.handle_datagram(now, &first).unwrap();
+        host.handle_datagram(now, &third).unwrap();
+        assert_eq!(messages(&mut host), [NOTE_ON]);
+
+        pump(now, &mut client, &mut host);
+        assert_eq!(messages(&mut host), [NOTE_OFF, NOTE_ON]);
+    }
+
+    #[test]
+    fn retransmit_error_reports_lost() {
+        let small_buffer = Config {
+            fec_count: 0,
+            retransmit_buffer: 1,
+            ..Default::default()
+        };
+        let (mut client, mut host, now) = connect(small_buffer.clone(), small_buffer);
+        events(&mut host);
+
+        for words in [NOTE_ON, NOTE_OFF, NOTE_ON] {
+            client.send(&message(&words)).unwrap();
+        }
+        let first = client.poll_transmit().unwrap();
+        client.poll_transmit().unwrap();
+        let third = client.poll_transmit().unwrap();
+        host.handle_datagram(now, &first).unwrap();
+        host.handle_datagram(now, &third).unwrap();
+        pump(now, &mut client, &mut host);
+
+        assert_eq!(
+            events(&mut host),
+            [
+                Event::Message(message(&NOTE_ON).rebuffer_into()),
+                Event::Lost { count: 1 },
+                Event::Message(message(&NOTE_ON).rebuffer_into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn retransmit_reply_is_split_over_datagrams() {
+        let large_buffer = Config {
+            fec_count: 0,
+            retransmit_buffer: 256,
+            ..Default::default()
+        };
+        let (mut client, mut host, now) = connect(large_buffer.clone(), large_buffer);
+        events(&mut host);
+
+        for _ in 0..200 {
+            client.send(&message(&NOTE_ON)).unwrap();
+        }
+        let sent: Vec<Vec<u8>> = core::iter::from_fn(|| client.poll_transmit()).collect();
+        host.handle_datagram(now, &sent[0]).unwrap();
+        host.handle_datagram(now, &sent[199]).unwrap();
+        while let Some(datagram) = host.poll_transmit() {
+            client.handle_datagram(now, &datagram).unwrap();
+        }
+
+        let replies: Vec<Vec<u8>> = core::iter::from_fn(|| client.poll_transmit()).collect();
+        assert!(replies.len() > 1);
+        assert!(replies.iter().all(|d| d.len() <= MAX_DATAGRAM_SIZE));
+        for datagram in replies {
+            host.handle_datagram(now, &datagram).unwrap();
+        }
+        assert_eq!(messages(&mut host).len(), 200);
+    }
+
+    #[test]
+    fn long_names_are_truncated() {
+        let (mut client, _, _) = connect(
+            Config {
+                endpoint_name: "é".repeat(60),
+                product_instance_id: "1".repeat(60),
+                ..Default::default()
+            },
+            Config::default(),
+        );
+        let invitation = client.invitation.clone().unwrap();
+        let Command::Invitation {
+            endpoint_name,
+            product_instance_id,
+            ..
+        } = invitation
+        else {
+            panic!();
+        };
+        assert_eq!(endpoint_name, "é".repeat(49));
+        assert_eq!(product_instance_id, "1".repeat(42));
+        events(&mut client);
+    }
+
+    #[test]
+    fn duplicates_are_dropped() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut host);
+
+        client.send(&message(&NOTE_ON)).unwrap();
+        let datagram = client.poll_transmit().unwrap();
+        host.handle_datagram(now, &datagram).unwrap();
+        host.handle_datagram(now, &datagram).unwrap();
+        assert_eq!(messages(&mut host), [NOTE_ON]);
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn multi_packet_message() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut host);
+
+        let sysex = [0x3016_0001, 0x0203_0405, 0x3031_0600, 0x0];
+        client.send(&message(&sysex)).unwrap();
+        pump(now, &mut client, &mut host);
+        assert_eq!(messages(&mut host), [sysex]);
+    }
+
+    #[test]
+    fn session_reset() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut host);
+
+        client.send(&message(&NOTE_ON)).unwrap();
+        pump(now, &mut client, &mut host);
+        client.reset().unwrap();
+        client.send(&message(&NOTE_OFF)).unwrap();
+        pump(now, &mut client, &mut host);
+
+        assert_eq!(messages(&mut host), [NOTE_ON, NOTE_OFF]);
+    }
+
+    #[test]
+    fn ump_data_before_established() {
+        let now = Instant::now();
+        let mut host = Session::<64>::host(Config::default(), || [0x0; 16]);
+        let datagram = command::encode_datagram(&[Command::UmpData {
+            sequence: 0,
+            words: NOTE_ON.to_vec(),
+        }])
+        .unwrap();
+        host.handle_datagram(now, &datagram).unwrap();
+        assert_eq!(
+            command::decode_datagram(&host.poll_transmit().unwrap()),
+            Ok(std::vec![Command::Bye {
+                reason: ByeReason::SessionNotEstablished,
+                message: String::new(),
+            }])
+        );
+        assert_eq!(events(&mut host), []);
+    }
+
+    #[test]
+    fn unknown_command_is_rejected() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut client);
+        let unknown = Command::Unknown {
+            code: 0x42,
+            specific_data: 0x0,
+            payload: Vec::new(),
+        };
+        host.handle_datagram(
+            now,
+            &command::encode_datagram(core::slice::from_ref(&unknown)).unwrap(),
+        )
+        .unwrap();
+        pump(now, &mut client, &mut host);
+        assert_eq!(
+            events(&mut client),
+            [Event::Nak {
+                reason: NakReason::CommandNotSupported,
+                message: String::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn ping() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut client);
+
+        let id = client.ping(now);
+        let later = now + Duration::from_millis(5);
+        pump(later, &mut client, &mut host);
+        assert_eq!(
+            events(&mut client),
+            [Event::PingReply {
+                id,
+                round_trip: Duration::from_millis(5),
+            }]
+        );
+    }
+
+    #[test]
+    fn bye() {
+        let (mut client, mut host, now) = connect(Config::default(), Config::default());
+        events(&mut client);
+        events(&mut host);
+
+        client.close(now, ByeReason::UserTerminated);
+        pump(now, &mut client, &mut host);
+
+        let closed = [Event::Closed {
+            reason: ByeReason::UserTerminated,
+        }];
+        assert_eq!(events(&mut client), closed);
+        assert_eq!(events(&mut host), closed);
+        assert!(client.send(&message(&NOTE_ON)).is_err());
+    }
+
+    #[test]
+    fn localhost_udp() {
+        use std::net::UdpSocket;
+
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let host_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client_socket
+            .connect(host_socket.local_addr().unwrap())
+            .unwrap();
+        host_socket
+            .connect(client_socket.local_addr().unwrap())
+            .unwrap();
+        for socket in [&client_socket, &host_socket] {
+            socket
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+        }
+
+        let mut client = Session::<64>::client(Config::default());
+        let mut host = Session::<64>::host(Config::default(), || [0x7; 16]);
+        let mut buffer = [0x0; 1500];
+
+        let mut exchange = |from: &mut Session, from_socket: &UdpSocket, to: &mut Session| {
+            let to_socket = if core::ptr::eq(from_socket, &client_socket) {
+                &host_socket
+            } else {
+                &client_socket
+            };
+            while let Some(datagram) = from.poll_transmit() {
+                from_socket.send(&datagram).unwrap();
+                let len = to_socket.recv(&mut buffer).unwrap();
+                to.handle_datagram(Instant::now(), &buffer[..len]).unwrap();
+            }
+        };
+
+        client.invite(Instant::now());
+        exchange(&mut client, &client_socket, &mut host);
+        exchange(&mut host, &host_socket, &mut client);
+        assert!(client.is_established());
+
+        client.send(&message(&NOTE_ON)).unwrap();
+        exchange(&mut client, &client_socket, &mut host);
+        assert_eq!(messages(&mut host), [NOTE_ON]);
+    }
+}
@@ -20,6 +20,7 @@ pub enum Packet {
     UmpStream(crate::ump_stream::Packet),
     #[cfg(feature = "utility")]
     Utility(crate::utility::Packet),
+    Unknown(crate::unknown::Packet),
 }
 
 impl core::ops::Deref for Packet {
@@ -42,6 +43,7 @@ impl core::ops::Deref for Packet {
             Self::UmpStream(p) => p.deref(),
             #[cfg(feature = "utility")]
             Self::Utility(p) => p.deref(),
+            Self::Unknown(p) => p.deref(),
         }
     }
 }
@@ -88,9 +90,124 @@ impl<'a> core::convert::TryFrom<&'a [u32]> for Packet {
             ump_stream::UMP_MESSAGE_TYPE => Ok(ump_stream::Packet::try_from(data)?.into()),
             #[cfg(feature = "utility")]
             utility::UMP_MESSAGE_TYPE => Ok(utility::Packet::try_from(data)?.into()),
-            _ => Err(crate::error::InvalidData(
-                common_err_strings::ERR_INCORRECT_UMP_MESSAGE_TYPE,
-            )),
+            _ => Ok(crate::unknown::Packet::try_from(data)?.into()),
+        }
+    }
+}
+
+const ERR_INCOMPLETE_MESSAGE: &str = "Multi-packet message is incomplete";
+
+/// The number of words in a packet with the given ump message type.
+pub(crate) fn size(message_type: u8) -> usize {
+    match message_type {
+        0x0..=0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8..=0xA => 2,
+        0xB | 0xC => 3,
+        _ => 4,
+    }
+}
+
+/// The position of a packet within a multi-packet message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Position {
+    Complete,
+    Start,
+    Continue,
+    End,
+}
+
+/// Whether an enabled feature interprets the ump message type.
+///
+/// Packets of the other message types, whether reserved by the specification
+/// or belonging to a disabled feature, are carried as
+/// [UnknownMessage](crate::unknown::UnknownMessage)s.
+pub(crate) fn is_interpreted(message_type: u8) -> bool {
+    match message_type {
+        #[cfg(feature = "utility")]
+        crate::utility::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "system-common")]
+        crate::system_common::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "channel-voice1")]
+        crate::channel_voice1::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "sysex7")]
+        crate::sysex7::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "channel-voice2")]
+        crate::channel_voice2::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "sysex8")]
+        crate::sysex8::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "flex-data")]
+        crate::flex_data::UMP_MESSAGE_TYPE => true,
+        #[cfg(feature = "ump-stream")]
+        crate::ump_stream::UMP_MESSAGE_TYPE => true,
+        _ => false,
+    }
+}
+
+/// The raw format field of a packet of a multi-packet message type.
+///
+/// Packets of message types which aren't interpreted are each carried
+/// as a message of their own, so they always read as complete.
+pub(crate) fn format(first_word: u32) -> u8 {
+    use crate::detail::BitOps;
+
+    if !is_interpreted(first_word.nibble(0).into()) {
+        return 0x0;
+    }
+    match u8::from(first_word.nibble(0)) {
+        0x3 | 0x5 => u8::from(first_word.nibble(2)),
+        0xD => u8::from(first_word.nibble(2)) >> 2,
+        0xF => u8::from(first_word.crumb(2)),
+        _ => 0x0,
+    }
+}
+
+pub(crate) fn position(first_word: u32) -> Position {
+    match format(first_word) {
+        0x1 => Position::Start,
+        0x2 => Position::Continue,
+        0x3 => Position::End,
+        _ => Position::Complete,
+    }
+}
+
+/// The number of words in the (possibly multi-packet) message
+/// at the front of the data.
+///
+/// The packets of a multi-packet message are expected to be contiguous.
+pub(crate) fn message_size(data: &[u32]) -> Result<usize, InvalidData> {
+    use crate::detail::BitOps;
+
+    let Some(first) = data.first() else {
+        return Err(InvalidData(common_err_strings::ERR_SLICE_TOO_SHORT));
+    };
+    let message_type = first.nibble(0);
+    let packet_size = size(message_type.into());
+    if data.len() < packet_size {
+        return Err(InvalidData(common_err_strings::ERR_SLICE_TOO_SHORT));
+    }
+
+    match position(*first) {
+        Position::Complete => Ok(packet_size),
+        Position::Continue | Position::End => Err(InvalidData(ERR_INCOMPLETE_MESSAGE)),
+        Position::Start => {
+            let mut offset = packet_size;
+            loop {
+                let Some(word) = data.get(offset) else {
+                    return Err(InvalidData(ERR_INCOMPLETE_MESSAGE));
+                };
+                if word.nibble(0) != message_type {
+                    return Err(InvalidData(ERR_INCOMPLETE_MESSAGE));
+                }
+                if data.len() < offset + packet_size {
+                    return Err(InvalidData(common_err_strings::ERR_SLICE_TOO_SHORT));
+                }
+                offset += packet_size;
+                match position(*word) {
+                    Position::Continue => continue,
+                    Position::End => return Ok(offset),
+                    _ => return Err(InvalidData(ERR_INCOMPLETE_MESSAGE)),
+                }
+            }
         }
     }
 }
@@ -98,6 +215,7 @@ impl<'a> core::convert::TryFrom<&'a [u32]> for Packet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[allow(unused_imports)]
     use core::ops::Deref;
 
     #[test]
@@ -128,12 +246,78 @@ mod tests {
 
     #[test]
     fn construction_from_reserved_ump_type_field() {
-        let data = [0xE000_0000, 0x0, 0x0, 0x0];
+        let data = [0xB000_0000, 0x1, 0x2, 0x3];
         assert_eq!(
-            Packet::try_from(&data[..]),
-            Err(InvalidData(
-                common_err_strings::ERR_INCORRECT_UMP_MESSAGE_TYPE
-            ))
+            Packet::try_from(&data[..]).unwrap().deref(),
+            &[0xB000_0000, 0x1, 0x2]
+        );
+    }
+
+    #[test]
+    fn packet_sizes() {
+        assert_eq!(size(0x0), 1);
+        assert_eq!(size(0x2), 1);
+        assert_eq!(size(0x3), 2);
+        assert_eq!(size(0x4), 2);
+        assert_eq!(size(0x5), 4);
+        assert_eq!(size(0xC), 3);
+        assert_eq!(size(0xF), 4);
+    }
+
+    #[test]
+    fn message_size_single_packet() {
+        assert_eq!(message_size(&[0x4090_3C00, 0xFFFF_0000, 0x0]), Ok(2));
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn message_size_multi_packet() {
+        assert_eq!(
+            message_size(&[
+                0x3016_0001,
+                0x0203_0405,
+                0x3026_0607,
+                0x0809_0A0B,
+                0x3032_0C0D,
+                0x0000_0000,
+                0x2090_3C40,
+            ]),
+            Ok(6)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn message_size_flex_data() {
+        assert_eq!(
+            message_size(&[0xD050_0100, 0x0, 0x0, 0x0, 0xD0D0_0100, 0x0, 0x0, 0x0,]),
+            Ok(8)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn message_size_incomplete() {
+        assert_eq!(
+            message_size(&[0x3016_0001, 0x0203_0405, 0x2090_3C40]),
+            Err(InvalidData(ERR_INCOMPLETE_MESSAGE))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn message_size_orphaned_continuation() {
+        assert_eq!(
+            message_size(&[0x3026_0607, 0x0809_0A0B]),
+            Err(InvalidData(ERR_INCOMPLETE_MESSAGE))
+        );
+    }
+
+    #[test]
+    fn message_size_truncated_packet() {
+        assert_eq!(
+            message_size(&[0x4090_3C00]),
+            Err(InvalidData(common_err_strings::ERR_SLICE_TOO_SHORT))
         );
     }
 }
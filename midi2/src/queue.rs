@@ -0,0 +1,344 @@
+use crate::{
+    buffer::Ump,
+    detail::BitOps,
+    error::{BufferOverflow, Error},
+    message::UmpMessage,
+    packet,
+    traits::Data,
+};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// A wait-free single-producer single-consumer queue of ump messages.
+///
+/// Messages are stored as whole packets in a fixed size backing buffer of words,
+/// so the queue can live in a `static` and be used without allocating.
+/// The packets of a message are always stored contiguously,
+/// which means a multi-packet message is never torn
+/// and the consumer can borrow each message straight out of the queue.
+///
+/// The queue is used through the [UmpProducer] and [UmpConsumer] handles
+/// returned from [split](UmpQueue::split).
+///
+/// ```rust
+/// use midi2::prelude::*;
+///
+/// static QUEUE: UmpQueue<64> = UmpQueue::new();
+///
+/// let (mut producer, mut consumer) = QUEUE.split().unwrap();
+///
+/// let mut note_on = channel_voice2::NoteOn::<[u32; 4]>::new();
+/// note_on.set_note_number(u7::new(0x3C));
+/// producer.push(&note_on).unwrap();
+///
+/// let Some(UmpMessage::ChannelVoice2(channel_voice2::ChannelVoice2::NoteOn(message))) =
+///     consumer.pop()
+/// else {
+///     panic!();
+/// };
+/// assert_eq!(message.note_number(), u7::new(0x3C));
+/// assert!(consumer.pop().is_none());
+/// ```
+pub struct UmpQueue<const SIZE: usize> {
+    buffer: UnsafeCell<[u32; SIZE]>,
+    // the next word to read, only written by the consumer
+    head: AtomicUsize,
+    // the next word to write, only written by the producer
+    tail: AtomicUsize,
+    // the end of the valid data when the producer has wrapped
+    // around to the start of the buffer
+    watermark: AtomicUsize,
+    split: AtomicBool,
+}
+
+// SAFETY: the producer and consumer only ever access disjoint regions of the buffer
+// and the regions are handed over through the head and tail indices.
+unsafe impl<const SIZE: usize> Sync for UmpQueue<SIZE> {}
+
+impl<const SIZE: usize> Default for UmpQueue<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> UmpQueue<SIZE> {
+    /// Create an empty queue which holds up to `SIZE` words.
+    pub const fn new() -> Self {
+        UmpQueue {
+            buffer: UnsafeCell::new([0x0; SIZE]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            watermark: AtomicUsize::new(SIZE),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// Take the producer and consumer handles of the queue.
+    ///
+    /// Returns `None` when the queue has already been split.
+    pub fn split(&self) -> Option<(UmpProducer<'_, SIZE>, UmpConsumer<'_, SIZE>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((
+            UmpProducer { queue: self },
+            UmpConsumer {
+                queue: self,
+                pending: 0,
+            },
+        ))
+    }
+
+    fn words(&self) -> *mut u32 {
+        self.buffer.get() as *mut u32
+    }
+}
+
+/// Pushes messages onto an [UmpQueue].
+pub struct UmpProducer<'a, const SIZE: usize> {
+    queue: &'a UmpQueue<SIZE>,
+}
+
+impl<const SIZE: usize> UmpProducer<'_, SIZE> {
+    /// Push the whole message onto the queue, or nothing at all.
+    ///
+    /// Messages which don't fill their last packet are padded
+    /// to the packet size of their message type.
+    /// Fails with [Error::BufferOverflow] when there isn't enough contiguous room
+    /// for the message and with [Error::InvalidData] when the data
+    /// isn't a message which the consumer could interpret.
+    ///
+    /// When the queue is empty but the message only fits from the start
+    /// of the buffer, the queue is rewound and the push succeeds
+    /// once the consumer has next called [pop](UmpConsumer::pop).
+    pub fn push<B: Ump, M: Data<B>>(&mut self, message: &M) -> Result<(), Error> {
+        let data = message.data();
+        UmpMessage::try_from(data)?;
+
+        let packet_size = packet::size(data[0].nibble(0).into());
+        let size = data.len().div_ceil(packet_size) * packet_size;
+
+        let queue = self.queue;
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let head = queue.head.load(Ordering::Acquire);
+        let offset = if tail >= head {
+            if SIZE - tail >= size {
+                tail
+            } else if size < head {
+                queue.watermark.store(tail, Ordering::Relaxed);
+                0
+            } else {
+                if tail == head && tail != 0 {
+                    // the queue is empty, so wrap around with nothing written
+                    // and the consumer follows back to the start on its next pop
+                    queue.watermark.store(tail, Ordering::Relaxed);
+                    queue.tail.store(0, Ordering::Release);
+                }
+                return Err(BufferOverflow.into());
+            }
+        } else if head - tail > size {
+            tail
+        } else {
+            return Err(BufferOverflow.into());
+        };
+
+        // SAFETY: the consumer doesn't read this region
+        // until it has been published by the tail store below
+        let words = unsafe { core::slice::from_raw_parts_mut(queue.words().add(offset), size) };
+        words[..data.len()].copy_from_slice(data);
+        words[data.len()..].fill(0x0);
+
+        queue.tail.store(offset + size, Ordering::Release);
+        Ok(())
+    }
+
+    /// The number of words the queue can hold.
+    pub fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+/// Pops messages from an [UmpQueue].
+pub struct UmpConsumer<'a, const SIZE: usize> {
+    queue: &'a UmpQueue<SIZE>,
+    // the size of the last popped message,
+    // released back to the producer on the next pop
+    pending: usize,
+}
+
+impl<const SIZE: usize> UmpConsumer<'_, SIZE> {
+    /// Pop the next message, borrowed straight from the queue.
+    ///
+    /// The space taken by the message is released back to the producer
+    /// on the next call to `pop`.
+    pub fn pop(&mut self) -> Option<UmpMessage<&[u32]>> {
+        let queue = self.queue;
+        let mut head = queue.head.load(Ordering::Relaxed) + self.pending;
+        self.pending = 0;
+
+        let tail = queue.tail.load(Ordering::Acquire);
+        let watermark = queue.watermark.load(Ordering::Relaxed);
+        if head != tail && tail < head && head >= watermark {
+            head = 0;
+        }
+        queue.head.store(head, Ordering::Release);
+        if head == tail {
+            return None;
+        }
+
+        let end = if tail > head { tail } else { watermark };
+        // SAFETY: the producer doesn't write to this region
+        // until it has been released by a later head store
+        let data = unsafe { core::slice::from_raw_parts(queue.words().add(head), end - head) };
+        // the producer only pushes whole, valid messages
+        let size = packet::message_size(data).ok()?;
+        self.pending = size;
+        UmpMessage::try_from(&data[..size]).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn split_once() {
+        let queue = UmpQueue::<4>::new();
+        assert!(queue.split().is_some());
+        assert!(queue.split().is_none());
+    }
+
+    #[test]
+    fn push_until_full() {
+        let queue = UmpQueue::<5>::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        producer.push(&message).unwrap();
+        producer.push(&message).unwrap();
+        assert_eq!(producer.push(&message), Err(Error::BufferOverflow));
+
+        assert_eq!(consumer.pop(), Some(message));
+        assert_eq!(consumer.pop(), Some(message));
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn wraps_around_without_tearing() {
+        let queue = UmpQueue::<5>::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        producer.push(&message).unwrap();
+        producer.push(&message).unwrap();
+        assert_eq!(consumer.pop(), Some(message));
+        assert_eq!(consumer.pop(), Some(message));
+        assert_eq!(consumer.pop(), None);
+
+        // only one word left at the back, so the message goes to the front
+        producer.push(&message).unwrap();
+        assert_eq!(
+            consumer.pop().map(|m| m.data().as_ptr()),
+            Some(queue.words() as *const u32)
+        );
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn rewinds_when_empty() {
+        let queue = UmpQueue::<5>::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+
+        let message = UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        producer.push(&message).unwrap();
+        assert_eq!(consumer.pop(), Some(message));
+        assert_eq!(consumer.pop(), None);
+
+        // drained at an offset of two, with only three words left at the back
+        let sysex =
+            UmpMessage::try_from(&[0x3016_0001, 0x0203_0405, 0x3032_0607, 0x0][..]).unwrap();
+        assert_eq!(producer.push(&sysex), Err(Error::BufferOverflow));
+        assert_eq!(consumer.pop(), None);
+        producer.push(&sysex).unwrap();
+
+        let popped = consumer.pop().unwrap();
+        assert_eq!(popped, sysex);
+        assert_eq!(popped.data().as_ptr(), queue.words() as *const u32);
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn pads_short_packets() {
+        let queue = UmpQueue::<4>::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+
+        let message = crate::channel_voice2::PerNoteManagement::<[u32; 4]>::new();
+        assert_eq!(message.data().len(), 1);
+        producer.push(&message).unwrap();
+        producer.push(&message).unwrap();
+
+        assert!(consumer.pop().is_some());
+        assert!(consumer.pop().is_some());
+        assert!(consumer.pop().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "ump-stream")]
+    fn rejects_invalid_data() {
+        struct UmpMessageData<'a>(&'a [u32]);
+
+        impl<'a> Data<&'a [u32]> for UmpMessageData<'a> {
+            fn data(&self) -> &[u32] {
+                self.0
+            }
+        }
+
+        let queue = UmpQueue::<4>::new();
+        let (mut producer, _) = queue.split().unwrap();
+        assert!(matches!(
+            producer.push(&UmpMessageData(&[0xF3FF_0000, 0x0, 0x0, 0x0])),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn threads() {
+        use crate::traits::Sysex;
+
+        const MESSAGES: usize = 1000;
+        let queue = UmpQueue::<16>::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for i in 0..MESSAGES {
+                    let mut message = crate::sysex7::Sysex7::<[u32; 8]>::new();
+                    message
+                        .try_set_payload((0..(i % 20) as u8).map(crate::ux::u7::new))
+                        .unwrap();
+                    while producer.push(&message).is_err() {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            scope.spawn(move || {
+                let mut received = 0;
+                while received < MESSAGES {
+                    match consumer.pop() {
+                        Some(UmpMessage::Sysex7(message)) => {
+                            assert_eq!(message.payload().count(), received % 20);
+                            received += 1;
+                        }
+                        Some(_) => panic!(),
+                        None => std::thread::yield_now(),
+                    }
+                }
+            });
+        });
+    }
+}
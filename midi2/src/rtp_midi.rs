@@ -0,0 +1,55 @@
+//! RTP-MIDI, carrying MIDI 1.0 messages over UDP as used by AppleMIDI.
+//!
+//! Like the **net** feature, the module is sans-IO: a [Session] is fed the datagrams
+//! received on its control and data ports and hands back the datagrams to transmit,
+//! the events to act on and the next time it needs waking.
+//!
+//! A session covers the AppleMIDI session protocol of invitations,
+//! clock synchronisation, receiver feedback and ending the session.
+//! Messages travel in the rtp midi payload format with delta times,
+//! running status, segmented system exclusive messages
+//! and a recovery journal of channel state.
+//! The [apple_midi] control packets and the [RtpMidiPacket] payload
+//! can also be encoded and decoded directly.
+//!
+//! ```rust
+//! use midi2::{prelude::*, rtp_midi::{Config, Event, Session}};
+//! use std::time::Instant;
+//!
+//! let now = Instant::now();
+//! let mut initiator = Session::initiator(Config::default());
+//! let mut responder = Session::responder(Config::default());
+//!
+//! initiator.invite(now, 0x1234);
+//! while !initiator.is_established() {
+//!     while let Some((port, datagram)) = initiator.poll_transmit() {
+//!         responder.handle_datagram(now, port, &datagram).unwrap();
+//!     }
+//!     while let Some((port, datagram)) = responder.poll_transmit() {
+//!         initiator.handle_datagram(now, port, &datagram).unwrap();
+//!     }
+//! }
+//!
+//! let message = BytesMessage::try_from(&[0x90, 0x3C, 0x40][..]).unwrap();
+//! initiator.send(now, &message).unwrap();
+//! while let Some((port, datagram)) = initiator.poll_transmit() {
+//!     responder.handle_datagram(now, port, &datagram).unwrap();
+//! }
+//! let received = core::iter::from_fn(|| responder.poll_event())
+//!     .find_map(|event| match event {
+//!         Event::Message { message, .. } => Some(message),
+//!         _ => None,
+//!     })
+//!     .unwrap();
+//! assert_eq!(received.data(), &[0x90, 0x3C, 0x40]);
+//! ```
+
+pub mod apple_midi;
+mod journal;
+mod payload;
+mod session;
+
+pub use apple_midi::ControlPacket;
+pub use journal::{ChannelJournal, RecoveryJournal};
+pub use payload::{MidiCommand, RtpMidiPacket, PAYLOAD_TYPE};
+pub use session::{CloseReason, Config, Error, Event, Port, Session};
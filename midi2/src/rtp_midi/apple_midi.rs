@@ -0,0 +1,236 @@
+use crate::error::InvalidData;
+use std::{string::String, vec::Vec};
+
+/// Every AppleMIDI control packet starts with these bytes.
+pub const SIGNATURE: [u8; 2] = [0xFF, 0xFF];
+
+const PROTOCOL_VERSION: u32 = 2;
+
+const ERR_NO_SIGNATURE: &str = "AppleMIDI packets should begin 0xFFFF";
+const ERR_UNKNOWN_COMMAND: &str = "Unknown AppleMIDI command";
+const ERR_UNSUPPORTED_VERSION: &str = "Unsupported AppleMIDI protocol version";
+const ERR_TRUNCATED_PACKET: &str = "AppleMIDI packet is truncated";
+const ERR_INVALID_NAME: &str = "AppleMIDI session name is not valid UTF-8";
+
+/// The fields shared by the invitation commands.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exchange {
+    pub initiator_token: u32,
+    pub ssrc: u32,
+    /// The session name, which is absent from [ControlPacket::EndSession].
+    pub name: String,
+}
+
+/// A packet of the AppleMIDI session protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlPacket {
+    /// `IN`
+    Invitation(Exchange),
+    /// `OK`
+    InvitationAccepted(Exchange),
+    /// `NO`
+    InvitationRejected(Exchange),
+    /// `BY`
+    EndSession(Exchange),
+    /// `CK`, exchanged three times to synchronise clocks.
+    Synchronization {
+        ssrc: u32,
+        count: u8,
+        timestamps: [u64; 3],
+    },
+    /// `RS`, acknowledging the packets received so far.
+    ReceiverFeedback { ssrc: u32, sequence: u16 },
+}
+
+impl ControlPacket {
+    /// Whether the datagram is a control packet rather than an rtp packet.
+    pub fn is_control(datagram: &[u8]) -> bool {
+        datagram.starts_with(&SIGNATURE)
+    }
+
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        use ControlPacket::*;
+
+        buffer.extend_from_slice(&SIGNATURE);
+        match self {
+            Invitation(exchange)
+            | InvitationAccepted(exchange)
+            | InvitationRejected(exchange)
+            | EndSession(exchange) => {
+                buffer.extend_from_slice(match self {
+                    Invitation(_) => b"IN",
+                    InvitationAccepted(_) => b"OK",
+                    InvitationRejected(_) => b"NO",
+                    _ => b"BY",
+                });
+                buffer.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+                buffer.extend_from_slice(&exchange.initiator_token.to_be_bytes());
+                buffer.extend_from_slice(&exchange.ssrc.to_be_bytes());
+                if !matches!(self, EndSession(_)) {
+                    buffer.extend_from_slice(exchange.name.as_bytes());
+                    buffer.push(0x0);
+                }
+            }
+            Synchronization {
+                ssrc,
+                count,
+                timestamps,
+            } => {
+                buffer.extend_from_slice(b"CK");
+                buffer.extend_from_slice(&ssrc.to_be_bytes());
+                buffer.extend_from_slice(&[*count, 0x0, 0x0, 0x0]);
+                for timestamp in timestamps {
+                    buffer.extend_from_slice(&timestamp.to_be_bytes());
+                }
+            }
+            ReceiverFeedback { ssrc, sequence } => {
+                buffer.extend_from_slice(b"RS");
+                buffer.extend_from_slice(&ssrc.to_be_bytes());
+                buffer.extend_from_slice(&(u32::from(*sequence) << 16).to_be_bytes());
+            }
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, InvalidData> {
+        use ControlPacket::*;
+
+        let Some(data) = data.strip_prefix(&SIGNATURE) else {
+            return Err(InvalidData(ERR_NO_SIGNATURE));
+        };
+        let Some((command, body)) = data.split_first_chunk::<2>() else {
+            return Err(InvalidData(ERR_TRUNCATED_PACKET));
+        };
+        match command {
+            b"IN" | b"OK" | b"NO" | b"BY" => {
+                if word(body, 0)? != PROTOCOL_VERSION {
+                    return Err(InvalidData(ERR_UNSUPPORTED_VERSION));
+                }
+                let initiator_token = word(body, 4)?;
+                let ssrc = word(body, 8)?;
+                let name = body[12..].split(|b| *b == 0x0).next().unwrap_or(&[]);
+                let exchange = Exchange {
+                    initiator_token,
+                    ssrc,
+                    name: core::str::from_utf8(name)
+                        .map_err(|_| InvalidData(ERR_INVALID_NAME))?
+                        .into(),
+                };
+                Ok(match command {
+                    b"IN" => Invitation(exchange),
+                    b"OK" => InvitationAccepted(exchange),
+                    b"NO" => InvitationRejected(exchange),
+                    _ => EndSession(exchange),
+                })
+            }
+            b"CK" => {
+                if body.len() < 32 {
+                    return Err(InvalidData(ERR_TRUNCATED_PACKET));
+                }
+                let mut timestamps = [0x0; 3];
+                for (i, timestamp) in timestamps.iter_mut().enumerate() {
+                    let start = 8 + 8 * i;
+                    *timestamp = u64::from_be_bytes(body[start..start + 8].try_into().unwrap());
+                }
+                Ok(Synchronization {
+                    ssrc: word(body, 0)?,
+                    count: body[4],
+                    timestamps,
+                })
+            }
+            b"RS" => Ok(ReceiverFeedback {
+                ssrc: word(body, 0)?,
+                sequence: (word(body, 4)? >> 16) as u16,
+            }),
+            _ => Err(InvalidData(ERR_UNKNOWN_COMMAND)),
+        }
+    }
+}
+
+fn word(data: &[u8], offset: usize) -> Result<u32, InvalidData> {
+    data.get(offset..offset + 4)
+        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+        .ok_or(InvalidData(ERR_TRUNCATED_PACKET))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn round_trip(packet: ControlPacket) {
+        let mut buffer = Vec::new();
+        packet.encode(&mut buffer);
+        assert!(ControlPacket::is_control(&buffer));
+        assert_eq!(ControlPacket::decode(&buffer), Ok(packet));
+    }
+
+    #[test]
+    fn encode_invitation() {
+        let mut buffer = Vec::new();
+        ControlPacket::Invitation(Exchange {
+            initiator_token: 0x1234_5678,
+            ssrc: 0xAABB_CCDD,
+            name: "Mac".into(),
+        })
+        .encode(&mut buffer);
+        assert_eq!(
+            buffer,
+            [
+                0xFF, 0xFF, b'I', b'N', 0x0, 0x0, 0x0, 0x2, 0x12, 0x34, 0x56, 0x78, 0xAA, 0xBB,
+                0xCC, 0xDD, b'M', b'a', b'c', 0x0,
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_receiver_feedback() {
+        let mut buffer = Vec::new();
+        ControlPacket::ReceiverFeedback {
+            ssrc: 0x1,
+            sequence: 0xABCD,
+        }
+        .encode(&mut buffer);
+        assert_eq!(
+            buffer,
+            [0xFF, 0xFF, b'R', b'S', 0x0, 0x0, 0x0, 0x1, 0xAB, 0xCD, 0x0, 0x0]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let exchange = Exchange {
+            initiator_token: 0x1,
+            ssrc: 0x2,
+            name: "Session".into(),
+        };
+        round_trip(ControlPacket::Invitation(exchange.clone()));
+        round_trip(ControlPacket::InvitationAccepted(exchange.clone()));
+        round_trip(ControlPacket::InvitationRejected(exchange));
+        round_trip(ControlPacket::EndSession(Exchange {
+            initiator_token: 0x1,
+            ssrc: 0x2,
+            name: String::new(),
+        }));
+        round_trip(ControlPacket::Synchronization {
+            ssrc: 0x2,
+            count: 1,
+            timestamps: [0x1, 0x1_0000_0000, 0x0],
+        });
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let data = [
+            0xFF, 0xFF, b'I', b'N', 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+        ];
+        assert_eq!(
+            ControlPacket::decode(&data),
+            Err(InvalidData(ERR_UNSUPPORTED_VERSION))
+        );
+    }
+
+    #[test]
+    fn rtp_packet_is_not_control() {
+        assert!(!ControlPacket::is_control(&[0x80, 0x61, 0x0, 0x0]));
+    }
+}
@@ -0,0 +1,580 @@
+use crate::{error::InvalidData, ux::u4};
+use std::{collections::BTreeMap, vec::Vec};
+
+const ERR_TRUNCATED_JOURNAL: &str = "Recovery journal is truncated";
+
+const CHAPTER_P: u8 = 0x80;
+const CHAPTER_C: u8 = 0x40;
+const CHAPTER_M: u8 = 0x20;
+const CHAPTER_W: u8 = 0x10;
+const CHAPTER_N: u8 = 0x08;
+const CHAPTER_E: u8 = 0x04;
+const CHAPTER_T: u8 = 0x02;
+const CHAPTER_A: u8 = 0x01;
+
+// the largest number of logs in a chapter
+const MAX_LOGS: usize = 128;
+const MAX_NOTE_LOGS: usize = 127;
+
+/// The recovery journal carried at the end of an rtp midi packet.
+///
+/// It describes the channel state built up since the checkpoint packet,
+/// so a receiver which misses packets can repair its state
+/// from the next packet which arrives.
+/// Only the channel journal is supported. System journals are skipped when decoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryJournal {
+    pub checkpoint: u16,
+    pub channels: Vec<ChannelJournal>,
+}
+
+/// The journalled state of one channel.
+///
+/// Chapters M (parameter numbers) and E (note extras) aren't supported
+/// and are skipped when decoding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelJournal {
+    pub channel: u4,
+    /// Chapter P, the latest program change.
+    pub program: Option<u8>,
+    /// Chapter C, the latest value of each controller.
+    pub controllers: Vec<(u8, u8)>,
+    /// Chapter W, the latest 14 bit pitch bend.
+    pub pitch_bend: Option<u16>,
+    /// Chapter N, the notes which are sounding and their velocities.
+    pub notes_on: Vec<(u8, u8)>,
+    /// Chapter N, the notes which have been released.
+    pub notes_off: Vec<u8>,
+    /// Chapter T, the latest channel pressure.
+    pub channel_pressure: Option<u8>,
+    /// Chapter A, the latest pressure of each key.
+    pub key_pressure: Vec<(u8, u8)>,
+}
+
+impl ChannelJournal {
+    pub fn new(channel: u4) -> Self {
+        ChannelJournal {
+            channel,
+            program: None,
+            controllers: Vec::new(),
+            pitch_bend: None,
+            notes_on: Vec::new(),
+            notes_off: Vec::new(),
+            channel_pressure: None,
+            key_pressure: Vec::new(),
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+        buffer.extend_from_slice(&[0x0; 3]);
+        let mut chapters = 0x0;
+
+        if let Some(program) = self.program {
+            chapters |= CHAPTER_P;
+            buffer.extend_from_slice(&[program & 0x7F, 0x0, 0x0]);
+        }
+        if !self.controllers.is_empty() {
+            chapters |= CHAPTER_C;
+            put_logs(buffer, &self.controllers);
+        }
+        if let Some(pitch_bend) = self.pitch_bend {
+            chapters |= CHAPTER_W;
+            buffer.extend_from_slice(&[(pitch_bend & 0x7F) as u8, (pitch_bend >> 7 & 0x7F) as u8]);
+        }
+        if !self.notes_on.is_empty() || !self.notes_off.is_empty() {
+            chapters |= CHAPTER_N;
+            self.encode_notes(buffer);
+        }
+        if let Some(pressure) = self.channel_pressure {
+            chapters |= CHAPTER_T;
+            buffer.push(pressure & 0x7F);
+        }
+        if !self.key_pressure.is_empty() {
+            chapters |= CHAPTER_A;
+            put_logs(buffer, &self.key_pressure);
+        }
+
+        let length = buffer.len() - start;
+        buffer[start] = u8::from(self.channel) << 3 | (length >> 8 & 0x3) as u8;
+        buffer[start + 1] = length as u8;
+        buffer[start + 2] = chapters;
+    }
+
+    fn encode_notes(&self, buffer: &mut Vec<u8>) {
+        let logs = &self.notes_on[..self.notes_on.len().min(MAX_NOTE_LOGS)];
+        let (low, high) = match (self.notes_off.iter().min(), self.notes_off.iter().max()) {
+            (Some(min), Some(max)) => (min >> 3, max >> 3),
+            // no offbits octets follow
+            _ => (1, 0),
+        };
+        buffer.extend_from_slice(&[logs.len() as u8, low << 4 | high]);
+        for (note, velocity) in logs {
+            // the Y bit recommends the note is played
+            buffer.extend_from_slice(&[note & 0x7F, 0x80 | (velocity & 0x7F)]);
+        }
+        for octet in low..=high {
+            let mut bits = 0x0;
+            for note in self.notes_off.iter().filter(|n| *n >> 3 == octet) {
+                bits |= 0x80 >> (note & 0x7);
+            }
+            buffer.push(bits);
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<(Self, usize), InvalidData> {
+        let header = take(data, 0, 3)?;
+        let length = usize::from(header[0] & 0x3) << 8 | usize::from(header[1]);
+        let chapters = header[2];
+        let data = take(data, 0, length)?;
+        let mut journal = ChannelJournal::new(u4::new(header[0] >> 3 & 0xF));
+        let mut offset = 3;
+
+        if chapters & CHAPTER_P != 0 {
+            journal.program = Some(take(data, offset, 3)?[0] & 0x7F);
+            offset += 3;
+        }
+        if chapters & CHAPTER_C != 0 {
+            offset += get_logs(data, offset, &mut journal.controllers)?;
+        }
+        if chapters & CHAPTER_M != 0 {
+            let header = take(data, offset, 2)?;
+            offset += usize::from(header[0] & 0x3) << 8 | usize::from(header[1]);
+        }
+        if chapters & CHAPTER_W != 0 {
+            let bytes = take(data, offset, 2)?;
+            journal.pitch_bend = Some(u16::from(bytes[0] & 0x7F) | u16::from(bytes[1] & 0x7F) << 7);
+            offset += 2;
+        }
+        if chapters & CHAPTER_N != 0 {
+            offset += journal.decode_notes(data, offset)?;
+        }
+        if chapters & CHAPTER_E != 0 {
+            let len = usize::from(take(data, offset, 1)?[0] & 0x7F) + 1;
+            offset += 1 + 2 * len;
+        }
+        if chapters & CHAPTER_T != 0 {
+            journal.channel_pressure = Some(take(data, offset, 1)?[0] & 0x7F);
+            offset += 1;
+        }
+        if chapters & CHAPTER_A != 0 {
+            get_logs(data, offset, &mut journal.key_pressure)?;
+        }
+        Ok((journal, length))
+    }
+
+    fn decode_notes(&mut self, data: &[u8], offset: usize) -> Result<usize, InvalidData> {
+        let header = take(data, offset, 2)?;
+        let (low, high) = (header[1] >> 4, header[1] & 0xF);
+        let mut logs = usize::from(header[0] & 0x7F);
+        if logs == MAX_NOTE_LOGS && low == 0xF && high == 0x0 {
+            logs = MAX_LOGS;
+        }
+        let mut size = 2;
+        for log in take(data, offset + size, 2 * logs)?.chunks_exact(2) {
+            self.notes_on.push((log[0] & 0x7F, log[1] & 0x7F));
+        }
+        size += 2 * logs;
+        if low <= high {
+            let octets = usize::from(high - low) + 1;
+            for (i, bits) in take(data, offset + size, octets)?.iter().enumerate() {
+                for bit in 0..8 {
+                    if bits & (0x80 >> bit) != 0 {
+                        self.notes_off.push(8 * (low + i as u8) + bit);
+                    }
+                }
+            }
+            size += octets;
+        }
+        Ok(size)
+    }
+}
+
+impl RecoveryJournal {
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let channels = self.channels.len().min(16);
+        let mut header = 0x0;
+        if channels > 0 {
+            // the A flag and TOTCHAN
+            header = 0x20 | (channels - 1) as u8;
+        }
+        buffer.push(header);
+        buffer.extend_from_slice(&self.checkpoint.to_be_bytes());
+        for channel in &self.channels[..channels] {
+            channel.encode(buffer);
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, InvalidData> {
+        let header = take(data, 0, 3)?;
+        let mut journal = RecoveryJournal {
+            checkpoint: u16::from_be_bytes([header[1], header[2]]),
+            channels: Vec::new(),
+        };
+        let mut offset = 3;
+        // the Y flag, a system journal which isn't supported
+        if header[0] & 0x40 != 0 {
+            let system = take(data, offset, 2)?;
+            offset += usize::from(system[0] & 0x3) << 8 | usize::from(system[1]);
+        }
+        // the A flag
+        if header[0] & 0x20 != 0 {
+            for _ in 0..=(header[0] & 0xF) {
+                let (channel, size) = ChannelJournal::decode(data.get(offset..).unwrap_or(&[]))?;
+                journal.channels.push(channel);
+                offset += size;
+            }
+        }
+        Ok(journal)
+    }
+}
+
+fn take(data: &[u8], offset: usize, len: usize) -> Result<&[u8], InvalidData> {
+    data.get(offset..offset + len)
+        .ok_or(InvalidData(ERR_TRUNCATED_JOURNAL))
+}
+
+fn put_logs(buffer: &mut Vec<u8>, logs: &[(u8, u8)]) {
+    let logs = &logs[..logs.len().min(MAX_LOGS)];
+    buffer.push((logs.len() - 1) as u8);
+    for (number, value) in logs {
+        buffer.extend_from_slice(&[number & 0x7F, value & 0x7F]);
+    }
+}
+
+fn get_logs(data: &[u8], offset: usize, logs: &mut Vec<(u8, u8)>) -> Result<usize, InvalidData> {
+    let len = usize::from(take(data, offset, 1)?[0] & 0x7F) + 1;
+    for log in take(data, offset + 1, 2 * len)?.chunks_exact(2) {
+        logs.push((log[0] & 0x7F, log[1] & 0x7F));
+    }
+    Ok(1 + 2 * len)
+}
+
+/// The channel state built up by a stream of messages.
+///
+/// Each entry remembers the sequence number of the packet which last changed it,
+/// so that a sender can drop what the receiver has acknowledged.
+#[derive(Clone, Debug, Default)]
+struct ChannelHistory {
+    program: Option<(u16, u8)>,
+    controllers: BTreeMap<u8, (u16, u8)>,
+    pitch_bend: Option<(u16, u16)>,
+    // None once the note has been released
+    notes: BTreeMap<u8, (u16, Option<u8>)>,
+    channel_pressure: Option<(u16, u8)>,
+    key_pressure: BTreeMap<u8, (u16, u8)>,
+}
+
+impl ChannelHistory {
+    fn is_empty(&self) -> bool {
+        self.program.is_none()
+            && self.controllers.is_empty()
+            && self.pitch_bend.is_none()
+            && self.notes.is_empty()
+            && self.channel_pressure.is_none()
+            && self.key_pressure.is_empty()
+    }
+
+    fn is_on(&self, note: u8) -> bool {
+        matches!(self.notes.get(&note), Some((_, Some(_))))
+    }
+}
+
+/// Tracks the channel state for the recovery journal.
+///
+/// Senders record each message and attach [journal](History::journal) to their packets.
+/// Receivers record each message they deliver and [recover](History::recover)
+/// from the journal of the packet following a loss.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct History {
+    checkpoint: u16,
+    channels: [ChannelHistory; 16],
+}
+
+impl History {
+    /// Record a channel voice message sent or received in the packet.
+    ///
+    /// Other messages aren't journalled and are ignored.
+    pub fn record(&mut self, sequence: u16, message: &[u8]) {
+        let Some(&status) = message.first() else {
+            return;
+        };
+        if !(0x80..=0xEF).contains(&status) {
+            return;
+        }
+        let first = message.get(1).copied().unwrap_or_default();
+        let second = message.get(2).copied().unwrap_or_default();
+        let channel = &mut self.channels[usize::from(status & 0xF)];
+        match status & 0xF0 {
+            0x80 => {
+                channel.notes.insert(first, (sequence, None));
+            }
+            0x90 => {
+                let velocity = (second != 0).then_some(second);
+                channel.notes.insert(first, (sequence, velocity));
+            }
+            0xA0 => {
+                channel.key_pressure.insert(first, (sequence, second));
+            }
+            0xB0 => {
+                channel.controllers.insert(first, (sequence, second));
+                // all sound off and all notes off
+                if first == 120 || first == 123 {
+                    for entry in channel.notes.values_mut() {
+                        if entry.1.is_some() {
+                            *entry = (sequence, None);
+                        }
+                    }
+                }
+            }
+            0xC0 => channel.program = Some((sequence, first)),
+            0xD0 => channel.channel_pressure = Some((sequence, first)),
+            _ => {
+                channel.pitch_bend = Some((sequence, u16::from(first) | u16::from(second) << 7));
+            }
+        }
+    }
+
+    /// Forget the state which the receiver has acknowledged up to the sequence number.
+    pub fn acknowledge(&mut self, sequence: u16) {
+        let keep = |entry: u16| entry.wrapping_sub(sequence) as i16 > 0;
+        for channel in self.channels.iter_mut() {
+            if channel.program.is_some_and(|(s, _)| !keep(s)) {
+                channel.program = None;
+            }
+            channel.controllers.retain(|_, (s, _)| keep(*s));
+            if channel.pitch_bend.is_some_and(|(s, _)| !keep(s)) {
+                channel.pitch_bend = None;
+            }
+            channel.notes.retain(|_, (s, _)| keep(*s));
+            if channel.channel_pressure.is_some_and(|(s, _)| !keep(s)) {
+                channel.channel_pressure = None;
+            }
+            channel.key_pressure.retain(|_, (s, _)| keep(*s));
+        }
+        self.checkpoint = sequence.wrapping_add(1);
+    }
+
+    /// The journal of everything recorded since the last acknowledgement.
+    pub fn journal(&self) -> Option<RecoveryJournal> {
+        let channels: Vec<ChannelJournal> = self
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, history)| !history.is_empty())
+            .map(|(i, history)| ChannelJournal {
+                channel: u4::new(i as u8),
+                program: history.program.map(|(_, program)| program),
+                controllers: values(&history.controllers),
+                pitch_bend: history.pitch_bend.map(|(_, value)| value),
+                notes_on: history
+                    .notes
+                    .iter()
+                    .filter_map(|(note, (_, velocity))| velocity.map(|v| (*note, v)))
+                    .collect(),
+                notes_off: history
+                    .notes
+                    .iter()
+                    .filter(|(_, (_, velocity))| velocity.is_none())
+                    .map(|(note, _)| *note)
+                    .collect(),
+                channel_pressure: history.channel_pressure.map(|(_, value)| value),
+                key_pressure: values(&history.key_pressure),
+            })
+            .collect();
+        (!channels.is_empty()).then_some(RecoveryJournal {
+            checkpoint: self.checkpoint,
+            channels,
+        })
+    }
+
+    /// The messages which bring this state in line with the journal.
+    pub fn recover(&self, journal: &RecoveryJournal) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        for journal in &journal.channels {
+            let channel = u8::from(journal.channel);
+            let state = &self.channels[usize::from(channel)];
+
+            if let Some(program) = journal.program {
+                if state.program.map(|(_, p)| p) != Some(program) {
+                    messages.push(std::vec![0xC0 | channel, program]);
+                }
+            }
+            for &(number, value) in &journal.controllers {
+                if state.controllers.get(&number).map(|(_, v)| *v) != Some(value) {
+                    messages.push(std::vec![0xB0 | channel, number, value]);
+                }
+            }
+            if let Some(pitch_bend) = journal.pitch_bend {
+                if state.pitch_bend.map(|(_, v)| v) != Some(pitch_bend) {
+                    messages.push(std::vec![
+                        0xE0 | channel,
+                        (pitch_bend & 0x7F) as u8,
+                        (pitch_bend >> 7) as u8,
+                    ]);
+                }
+            }
+            for &note in &journal.notes_off {
+                if state.is_on(note) {
+                    messages.push(std::vec![0x80 | channel, note, 0x40]);
+                }
+            }
+            for &(note, velocity) in &journal.notes_on {
+                if !state.is_on(note) {
+                    messages.push(std::vec![0x90 | channel, note, velocity]);
+                }
+            }
+            if let Some(pressure) = journal.channel_pressure {
+                if state.channel_pressure.map(|(_, v)| v) != Some(pressure) {
+                    messages.push(std::vec![0xD0 | channel, pressure]);
+                }
+            }
+            for &(note, pressure) in &journal.key_pressure {
+                if state.key_pressure.get(&note).map(|(_, v)| *v) != Some(pressure) {
+                    messages.push(std::vec![0xA0 | channel, note, pressure]);
+                }
+            }
+        }
+        messages
+    }
+}
+
+fn values(entries: &BTreeMap<u8, (u16, u8)>) -> Vec<(u8, u8)> {
+    entries
+        .iter()
+        .map(|(number, (_, value))| (*number, *value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn round_trip(journal: RecoveryJournal) {
+        let mut buffer = Vec::new();
+        journal.encode(&mut buffer);
+        assert_eq!(RecoveryJournal::decode(&buffer), Ok(journal));
+    }
+
+    #[test]
+    fn encode_chapter_n() {
+        let journal = RecoveryJournal {
+            checkpoint: 0x1234,
+            channels: std::vec![ChannelJournal {
+                notes_on: std::vec![(0x3C, 0x40)],
+                notes_off: std::vec![0x3E],
+                ..ChannelJournal::new(u4::new(0x2))
+            }],
+        };
+        let mut buffer = Vec::new();
+        journal.encode(&mut buffer);
+        assert_eq!(
+            buffer,
+            [
+                0x20,
+                0x12,
+                0x34,
+                0x10,
+                0x08,
+                0x08,
+                0x01,
+                0x77,
+                0x3C,
+                0xC0,
+                0b0000_0010
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(RecoveryJournal::default());
+        round_trip(RecoveryJournal {
+            checkpoint: 0xFFFF,
+            channels: std::vec![
+                ChannelJournal {
+                    program: Some(0x5),
+                    controllers: std::vec![(0x7, 0x64), (0x40, 0x7F)],
+                    pitch_bend: Some(0x2000),
+                    notes_on: std::vec![(0x3C, 0x40), (0x40, 0x50)],
+                    notes_off: std::vec![0x0, 0x3E, 0x7F],
+                    channel_pressure: Some(0x20),
+                    key_pressure: std::vec![(0x3C, 0x10)],
+                    ..ChannelJournal::new(u4::new(0x0))
+                },
+                ChannelJournal {
+                    program: Some(0x1),
+                    ..ChannelJournal::new(u4::new(0xF))
+                },
+            ],
+        });
+    }
+
+    #[test]
+    fn skips_unsupported_chapters() {
+        // chapter M with no logs, chapter W, then chapter E with a single log
+        let data = [0x00, 0x0A, 0x34, 0x00, 0x02, 0x00, 0x40, 0x00, 0x3C, 0x00];
+        let (journal, size) = ChannelJournal::decode(&data).unwrap();
+        assert_eq!(size, 10);
+        assert_eq!(
+            journal,
+            ChannelJournal {
+                pitch_bend: Some(0x2000),
+                ..ChannelJournal::new(u4::new(0x0))
+            }
+        );
+    }
+
+    #[test]
+    fn history_journal_and_acknowledge() {
+        let mut history = History::default();
+        history.record(1, &[0x90, 0x3C, 0x40]);
+        history.record(2, &[0xB1, 0x07, 0x64]);
+        history.record(3, &[0x80, 0x3C, 0x40]);
+
+        let journal = history.journal().unwrap();
+        assert_eq!(journal.channels.len(), 2);
+        assert_eq!(journal.channels[0].notes_off, [0x3C]);
+        assert_eq!(journal.channels[1].controllers, [(0x07, 0x64)]);
+
+        history.acknowledge(2);
+        let journal = history.journal().unwrap();
+        assert_eq!(journal.checkpoint, 3);
+        assert_eq!(journal.channels.len(), 1);
+
+        history.acknowledge(3);
+        assert_eq!(history.journal(), None);
+    }
+
+    #[test]
+    fn recover_from_journal() {
+        let mut sender = History::default();
+        sender.record(1, &[0x90, 0x3C, 0x40]);
+        sender.record(2, &[0x90, 0x3E, 0x40]);
+        sender.record(3, &[0x80, 0x3C, 0x0]);
+        sender.record(4, &[0xC0, 0x05]);
+
+        let mut receiver = History::default();
+        receiver.record(0, &[0x90, 0x3C, 0x40]);
+
+        assert_eq!(
+            receiver.recover(&sender.journal().unwrap()),
+            [
+                std::vec![0xC0, 0x05],
+                std::vec![0x80, 0x3C, 0x40],
+                std::vec![0x90, 0x3E, 0x40],
+            ]
+        );
+    }
+
+    #[test]
+    fn all_notes_off_releases_notes() {
+        let mut history = History::default();
+        history.record(1, &[0x90, 0x3C, 0x40]);
+        history.record(2, &[0xB0, 123, 0x0]);
+        let journal = history.journal().unwrap();
+        assert_eq!(journal.channels[0].notes_on, []);
+        assert_eq!(journal.channels[0].notes_off, [0x3C]);
+    }
+}
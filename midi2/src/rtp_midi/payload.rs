@@ -0,0 +1,338 @@
+use crate::{detail::vlq, error::InvalidData, io::message_size, rtp_midi::RecoveryJournal};
+use std::vec::Vec;
+
+/// The dynamic rtp payload type conventionally used for midi.
+pub const PAYLOAD_TYPE: u8 = 0x61;
+
+const RTP_VERSION: u8 = 2;
+const MAX_SHORT_LENGTH: usize = 0xF;
+/// The longest command list of a packet, in bytes.
+pub(crate) const MAX_LENGTH: usize = 0xFFF;
+
+const ERR_TRUNCATED_PACKET: &str = "Rtp packet is truncated";
+const ERR_UNSUPPORTED_VERSION: &str = "Unsupported rtp version";
+const ERR_NO_STATUS: &str = "Command has no status byte and there is no running status";
+
+/// One command of an rtp midi command list.
+///
+/// The data is the complete message, with any running status restored,
+/// or a segment of a system exclusive message.
+/// Segments begin `0xF0` or `0xF7` and end `0xF0` when more segments follow,
+/// `0xF7` for the last segment and `0xF4` when the message is cancelled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MidiCommand {
+    /// Ticks after the previous command, or after the packet timestamp for the first command.
+    pub delta: u32,
+    pub data: Vec<u8>,
+}
+
+/// An rtp packet carrying the rtp midi payload format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RtpMidiPacket {
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub commands: Vec<MidiCommand>,
+    pub journal: Option<RecoveryJournal>,
+}
+
+impl RtpMidiPacket {
+    /// Append the encoded packet to the buffer.
+    ///
+    /// Running status is used to shorten the command list.
+    /// The command list holds at most 4095 bytes, and stops after the last
+    /// command which fits whole.
+    /// Returns the number of commands written, the rest should be sent
+    /// in a following packet.
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> usize {
+        buffer.extend_from_slice(&[RTP_VERSION << 6, PAYLOAD_TYPE]);
+        buffer.extend_from_slice(&self.sequence.to_be_bytes());
+        buffer.extend_from_slice(&self.timestamp.to_be_bytes());
+        buffer.extend_from_slice(&self.ssrc.to_be_bytes());
+
+        let mut list = Vec::new();
+        let mut running_status = None;
+        let mut written = 0;
+        for (i, command) in self.commands.iter().enumerate() {
+            let end = list.len();
+            if i > 0 || command.delta != 0 {
+                vlq::write(&mut list, command.delta);
+            }
+            if let Some(&status) = command.data.first() {
+                if status < 0xF0 && running_status == Some(status) {
+                    list.extend_from_slice(&command.data[1..]);
+                } else {
+                    list.extend_from_slice(&command.data);
+                }
+                if list.len() <= MAX_LENGTH {
+                    running_status = update_running_status(running_status, status);
+                }
+            }
+            if list.len() > MAX_LENGTH {
+                list.truncate(end);
+                break;
+            }
+            written += 1;
+        }
+
+        let mut flags = 0x0;
+        if self.journal.is_some() {
+            flags |= 0x40;
+        }
+        if written > 0 && self.commands[0].delta != 0 {
+            flags |= 0x20;
+        }
+        if list.len() > MAX_SHORT_LENGTH {
+            buffer.extend_from_slice(&[0x80 | flags | (list.len() >> 8) as u8, list.len() as u8]);
+        } else {
+            buffer.push(flags | list.len() as u8);
+        }
+        buffer.extend_from_slice(&list);
+
+        if let Some(journal) = &self.journal {
+            journal.encode(buffer);
+        }
+        written
+    }
+
+    /// Decode a packet.
+    ///
+    /// The running status left by the previous packet is needed
+    /// when the first command of the packet omits its status byte.
+    pub fn decode(data: &[u8], running_status: Option<u8>) -> Result<Self, InvalidData> {
+        let header = data.get(..12).ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+        if header[0] >> 6 != RTP_VERSION {
+            return Err(InvalidData(ERR_UNSUPPORTED_VERSION));
+        }
+        let mut data = &data[12..];
+        // padding
+        if header[0] & 0x20 != 0 {
+            let padding = usize::from(*data.last().ok_or(InvalidData(ERR_TRUNCATED_PACKET))?);
+            data = &data[..data.len().saturating_sub(padding)];
+        }
+        // contributing sources
+        data = skip(data, 4 * usize::from(header[0] & 0xF))?;
+        // header extension
+        if header[0] & 0x10 != 0 {
+            let extension = data.get(..4).ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+            let words = usize::from(u16::from_be_bytes([extension[2], extension[3]]));
+            data = skip(data, 4 + 4 * words)?;
+        }
+
+        let flags = *data.first().ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+        let (length, start) = if flags & 0x80 != 0 {
+            let low = *data.get(1).ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+            (usize::from(flags & 0xF) << 8 | usize::from(low), 2)
+        } else {
+            (usize::from(flags & 0xF), 1)
+        };
+        let list = data
+            .get(start..start + length)
+            .ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+        let phantom = flags & 0x10 != 0;
+        let commands = decode_list(
+            list,
+            flags & 0x20 != 0,
+            if phantom { running_status } else { None },
+        )?;
+
+        let journal = if flags & 0x40 != 0 {
+            Some(RecoveryJournal::decode(&data[start + length..])?)
+        } else {
+            None
+        };
+
+        Ok(RtpMidiPacket {
+            sequence: u16::from_be_bytes([header[2], header[3]]),
+            timestamp: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+            ssrc: u32::from_be_bytes([header[8], header[9], header[10], header[11]]),
+            commands,
+            journal,
+        })
+    }
+}
+
+/// The running status after a command with the given status.
+pub(crate) fn update_running_status(running_status: Option<u8>, status: u8) -> Option<u8> {
+    match status {
+        0x80..=0xEF => Some(status),
+        0xF0..=0xF7 => None,
+        // real time messages leave running status alone
+        _ => running_status,
+    }
+}
+
+fn skip(data: &[u8], len: usize) -> Result<&[u8], InvalidData> {
+    data.get(len..).ok_or(InvalidData(ERR_TRUNCATED_PACKET))
+}
+
+fn decode_list(
+    mut list: &[u8],
+    first_has_delta: bool,
+    mut running_status: Option<u8>,
+) -> Result<Vec<MidiCommand>, InvalidData> {
+    let mut commands = Vec::new();
+    while !list.is_empty() {
+        let mut delta = 0;
+        if first_has_delta || !commands.is_empty() {
+            let (value, size) = vlq::read(list)?;
+            delta = value;
+            list = &list[size..];
+        }
+
+        let first = *list.first().ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+        let mut data = Vec::new();
+        let status = if first < 0x80 {
+            let status = running_status.ok_or(InvalidData(ERR_NO_STATUS))?;
+            data.push(status);
+            status
+        } else {
+            first
+        };
+        let size = match status {
+            0xF0 | 0xF7 => list[1..]
+                .iter()
+                .position(|b| matches!(b, 0xF0 | 0xF7 | 0xF4))
+                .map(|end| end + 2)
+                .ok_or(InvalidData(ERR_TRUNCATED_PACKET))?,
+            status => message_size(status).unwrap_or(1) - data.len(),
+        };
+        let bytes = list.get(..size).ok_or(InvalidData(ERR_TRUNCATED_PACKET))?;
+        data.extend_from_slice(bytes);
+        list = &list[size..];
+
+        running_status = update_running_status(running_status, status);
+        commands.push(MidiCommand { delta, data });
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rtp_midi::ChannelJournal, ux::u4};
+    use pretty_assertions::assert_eq;
+
+    fn command(delta: u32, data: &[u8]) -> MidiCommand {
+        MidiCommand {
+            delta,
+            data: data.to_vec(),
+        }
+    }
+
+    fn packet(commands: Vec<MidiCommand>) -> RtpMidiPacket {
+        RtpMidiPacket {
+            sequence: 0x1234,
+            timestamp: 0x100,
+            ssrc: 0xAABB_CCDD,
+            commands,
+            journal: None,
+        }
+    }
+
+    fn round_trip(packet: RtpMidiPacket) {
+        let mut buffer = Vec::new();
+        packet.encode(&mut buffer);
+        assert_eq!(RtpMidiPacket::decode(&buffer, None), Ok(packet));
+    }
+
+    #[test]
+    fn encode_running_status() {
+        let mut buffer = Vec::new();
+        packet(std::vec![
+            command(0, &[0x90, 0x3C, 0x40]),
+            command(0x81, &[0x90, 0x3E, 0x40]),
+        ])
+        .encode(&mut buffer);
+        assert_eq!(
+            buffer,
+            [
+                0x80, 0x61, 0x12, 0x34, 0x0, 0x0, 0x1, 0x0, 0xAA, 0xBB, 0xCC, 0xDD, 0x07, 0x90,
+                0x3C, 0x40, 0x81, 0x01, 0x3E, 0x40,
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(packet(std::vec![]));
+        round_trip(packet(std::vec![
+            command(5, &[0xC0, 0x01]),
+            command(0, &[0xF8]),
+            command(0, &[0xC0, 0x02]),
+            command(0x3FFF, &[0xF0, 0x01, 0x02, 0xF7]),
+            command(0, &[0xB0, 0x07, 0x64]),
+        ]));
+        round_trip(RtpMidiPacket {
+            journal: Some(RecoveryJournal {
+                checkpoint: 0x1,
+                channels: std::vec![ChannelJournal {
+                    program: Some(0x1),
+                    ..ChannelJournal::new(u4::new(0x3))
+                }],
+            }),
+            ..packet(std::vec![command(0, &[0x90, 0x3C, 0x40])])
+        });
+    }
+
+    #[test]
+    fn sysex_segments() {
+        round_trip(packet(std::vec![command(0, &[0xF0, 0x01, 0x02, 0xF0])]));
+        round_trip(packet(std::vec![command(0, &[0xF7, 0x03, 0xF0])]));
+        round_trip(packet(std::vec![command(0, &[0xF7, 0x04, 0xF7])]));
+        round_trip(packet(std::vec![command(0, &[0xF7, 0xF4])]));
+    }
+
+    #[test]
+    fn long_command_list() {
+        let commands = (0..8).map(|i| command(0, &[0x90, i, 0x40])).collect();
+        round_trip(packet(commands));
+    }
+
+    #[test]
+    fn command_list_stops_at_a_whole_command() {
+        // alternating channels, so that running status doesn't apply
+        let commands: Vec<MidiCommand> = (0..2048)
+            .map(|i| command(0, &[0x90 | (i % 2) as u8, 0x3C, 0x40]))
+            .collect();
+        let mut buffer = Vec::new();
+        let written = packet(commands.clone()).encode(&mut buffer);
+        // three bytes for the first command, and a delta byte more for each other
+        assert_eq!(written, 1024);
+        let decoded = RtpMidiPacket::decode(&buffer, None).unwrap();
+        assert_eq!(decoded.commands, commands[..written]);
+    }
+
+    #[test]
+    fn command_too_long_for_a_packet() {
+        let mut data = std::vec![0x0; MAX_LENGTH + 1];
+        data[0] = 0xF0;
+        data[MAX_LENGTH] = 0xF7;
+        let mut buffer = Vec::new();
+        assert_eq!(packet(std::vec![command(0, &data)]).encode(&mut buffer), 0);
+        assert_eq!(RtpMidiPacket::decode(&buffer, None).unwrap().commands, []);
+    }
+
+    #[test]
+    fn phantom_status() {
+        let data = [
+            0x80, 0x61, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x12, 0x3C, 0x40,
+        ];
+        assert_eq!(
+            RtpMidiPacket::decode(&data, Some(0x91)).unwrap().commands,
+            [command(0, &[0x91, 0x3C, 0x40])]
+        );
+        assert_eq!(
+            RtpMidiPacket::decode(&data, None),
+            Err(InvalidData(ERR_NO_STATUS))
+        );
+    }
+
+    #[test]
+    fn truncated_packet() {
+        assert_eq!(
+            RtpMidiPacket::decode(&[0x80, 0x61, 0x0, 0x0], None),
+            Err(InvalidData(ERR_TRUNCATED_PACKET))
+        );
+    }
+}
@@ -0,0 +1,952 @@
+use crate::{
+    buffer::Bytes,
+    error::InvalidData,
+    message::BytesMessage,
+    rtp_midi::{
+        apple_midi::{ControlPacket, Exchange},
+        journal::History,
+        payload::{update_running_status, MidiCommand, RtpMidiPacket, MAX_LENGTH},
+    },
+    traits::{Data, RebufferInto},
+};
+use std::{
+    collections::VecDeque,
+    string::String,
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+// clock ticks are a tenth of a millisecond
+const TICKS_PER_SECOND: u64 = 10_000;
+
+/// The two ports of an AppleMIDI session.
+///
+/// The data port is conventionally the control port plus one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Port {
+    Control,
+    Data,
+}
+
+/// The settings of a [Session].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The session name advertised to the peer.
+    pub name: String,
+    /// Identifies this end of the session and should be chosen at random.
+    pub ssrc: u32,
+    /// How long to wait for a reply to an invitation.
+    pub reply_timeout: Duration,
+    /// How many times an invitation is sent before giving up.
+    pub invitation_attempts: u32,
+    /// How often the initiator synchronises clocks.
+    pub sync_interval: Duration,
+    /// How long a receiver waits before acknowledging received packets.
+    pub feedback_interval: Duration,
+    /// The most system exclusive data bytes sent in a single packet,
+    /// at most 4093 to fit the command list.
+    /// Longer messages are split into segments.
+    pub max_sysex_segment: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            name: String::new(),
+            ssrc: 0x0,
+            reply_timeout: Duration::from_secs(1),
+            invitation_attempts: 3,
+            sync_interval: Duration::from_secs(10),
+            feedback_interval: Duration::from_secs(1),
+            max_sysex_segment: 1024,
+        }
+    }
+}
+
+/// Why a session closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The responder turned down the invitation.
+    Rejected,
+    /// The peer ended the session.
+    Ended,
+    /// The invitation went unanswered.
+    Timeout,
+}
+
+/// Something the application should know about, see [Session::poll_event].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The session is ready for messages.
+    Established {
+        name: String,
+        ssrc: u32,
+    },
+    /// A message with its timestamp in the sender's clock ticks.
+    Message {
+        timestamp: u32,
+        message: BytesMessage<Vec<u8>>,
+    },
+    /// A round of clock synchronisation completed.
+    Synchronized {
+        /// How far the peer's clock is ahead of ours, in clock ticks.
+        offset: i64,
+        latency: Duration,
+    },
+    /// Packets were missed. Channel state has been repaired from the recovery journal.
+    Lost {
+        count: u16,
+    },
+    Closed {
+        reason: CloseReason,
+    },
+}
+
+/// Errors from [Session] operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Messages can only be sent once the session is established.
+    NotEstablished,
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        <Self as std::fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Initiator,
+    Responder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Inviting {
+        port: Port,
+        attempts: u32,
+        deadline: Instant,
+    },
+    Established,
+    Closed,
+}
+
+/// One end of an AppleMIDI session.
+///
+/// Like [net::Session](crate::net::Session) when the **net** feature is enabled,
+/// the session doesn't do any IO itself. The application
+/// - passes every datagram received on either port to [handle_datagram](Self::handle_datagram),
+/// - sends every datagram from [poll_transmit](Self::poll_transmit) from the given port,
+/// - acts on every [Event] from [poll_event](Self::poll_event),
+/// - calls [handle_timeout](Self::handle_timeout) once the instant
+///   from [poll_timeout](Self::poll_timeout) has passed.
+///
+/// An initiator starts the session with [invite](Self::invite),
+/// first on the control port and then on the data port,
+/// and synchronises clocks every [Config::sync_interval].
+/// A responder accepts the first invitation it receives.
+///
+/// Timestamps are in ticks of 100 microseconds from the session's first activity.
+/// Every packet carries a recovery journal of the channel state
+/// which the receiver hasn't yet acknowledged.
+#[derive(Clone, Debug)]
+pub struct Session {
+    role: Role,
+    config: Config,
+    state: State,
+    epoch: Option<Instant>,
+    initiator_token: u32,
+    peer_ssrc: u32,
+    peer_name: String,
+    send_sequence: u16,
+    sent: History,
+    receive_sequence: Option<u16>,
+    received: History,
+    running_status: Option<u8>,
+    sysex: Option<Vec<u8>>,
+    feedback_deadline: Option<Instant>,
+    next_sync: Option<Instant>,
+    transmit: VecDeque<(Port, Vec<u8>)>,
+    events: VecDeque<Event>,
+}
+
+impl Session {
+    pub fn initiator(config: Config) -> Self {
+        Self::new(Role::Initiator, config)
+    }
+
+    pub fn responder(config: Config) -> Self {
+        Self::new(Role::Responder, config)
+    }
+
+    fn new(role: Role, config: Config) -> Self {
+        Session {
+            role,
+            config,
+            state: State::Idle,
+            epoch: None,
+            initiator_token: 0x0,
+            peer_ssrc: 0x0,
+            peer_name: String::new(),
+            send_sequence: 0,
+            sent: History::default(),
+            receive_sequence: None,
+            received: History::default(),
+            running_status: None,
+            sysex: None,
+            feedback_deadline: None,
+            next_sync: None,
+            transmit: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn is_established(&self) -> bool {
+        self.state == State::Established
+    }
+
+    /// Invite the responder to a session.
+    ///
+    /// The token identifies the invitation and should be chosen at random.
+    /// Does nothing for a responder session.
+    pub fn invite(&mut self, now: Instant, initiator_token: u32) {
+        if self.role != Role::Initiator {
+            return;
+        }
+        self.initiator_token = initiator_token;
+        self.invite_on(now, Port::Control, 1);
+    }
+
+    /// Send a message to the peer.
+    pub fn send<B: Bytes, M: Data<B>>(&mut self, now: Instant, message: &M) -> Result<(), Error> {
+        if self.state != State::Established {
+            return Err(Error::NotEstablished);
+        }
+        let data = message.data();
+        // a segment and its two framing bytes fill a command list at most
+        let segment = self.config.max_sysex_segment.clamp(1, MAX_LENGTH - 2);
+        if data.first() != Some(&0xF0) || data.len() <= segment + 2 {
+            self.send_command(now, data.to_vec());
+            return Ok(());
+        }
+
+        let segments = data[1..data.len() - 1].chunks(segment);
+        let count = segments.len();
+        for (i, segment) in segments.enumerate() {
+            let mut command = Vec::with_capacity(segment.len() + 2);
+            command.push(if i == 0 { 0xF0 } else { 0xF7 });
+            command.extend_from_slice(segment);
+            command.push(if i + 1 == count { 0xF7 } else { 0xF0 });
+            self.send_command(now, command);
+        }
+        Ok(())
+    }
+
+    /// Start a round of clock synchronisation.
+    ///
+    /// The result is reported with [Event::Synchronized].
+    pub fn synchronize(&mut self, now: Instant) {
+        let timestamp = self.clock(now);
+        self.queue(
+            Port::Data,
+            &ControlPacket::Synchronization {
+                ssrc: self.config.ssrc,
+                count: 0,
+                timestamps: [timestamp, 0x0, 0x0],
+            },
+        );
+    }
+
+    /// End the session.
+    pub fn close(&mut self) {
+        if matches!(self.state, State::Idle | State::Closed) {
+            return;
+        }
+        self.queue(
+            Port::Control,
+            &ControlPacket::EndSession(Exchange {
+                initiator_token: self.initiator_token,
+                ssrc: self.config.ssrc,
+                name: String::new(),
+            }),
+        );
+        self.state = State::Closed;
+        self.next_sync = None;
+        self.feedback_deadline = None;
+    }
+
+    /// Handle a datagram received on one of the session's ports.
+    ///
+    /// Fails when the datagram can't be decoded, in which case it's ignored.
+    pub fn handle_datagram(
+        &mut self,
+        now: Instant,
+        port: Port,
+        datagram: &[u8],
+    ) -> Result<(), InvalidData> {
+        if ControlPacket::is_control(datagram) {
+            let packet = ControlPacket::decode(datagram)?;
+            self.handle_control(now, port, packet);
+        } else if port == Port::Data && self.state == State::Established {
+            let packet = RtpMidiPacket::decode(datagram, self.running_status)?;
+            self.handle_rtp(now, packet);
+        }
+        Ok(())
+    }
+
+    /// The instant at which [handle_timeout](Self::handle_timeout) should next be called.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let inviting = match self.state {
+            State::Inviting { deadline, .. } => Some(deadline),
+            _ => None,
+        };
+        [inviting, self.next_sync, self.feedback_deadline]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    pub fn handle_timeout(&mut self, now: Instant) {
+        if let State::Inviting {
+            port,
+            attempts,
+            deadline,
+        } = self.state
+        {
+            if deadline <= now {
+                if attempts < self.config.invitation_attempts {
+                    self.invite_on(now, port, attempts + 1);
+                } else {
+                    self.closed(CloseReason::Timeout);
+                }
+            }
+        }
+        if self.next_sync.is_some_and(|deadline| deadline <= now) {
+            self.synchronize(now);
+            self.next_sync = Some(now + self.config.sync_interval);
+        }
+        if self
+            .feedback_deadline
+            .is_some_and(|deadline| deadline <= now)
+        {
+            self.feedback_deadline = None;
+            if let Some(next) = self.receive_sequence {
+                self.queue(
+                    Port::Control,
+                    &ControlPacket::ReceiverFeedback {
+                        ssrc: self.config.ssrc,
+                        sequence: next.wrapping_sub(1),
+                    },
+                );
+            }
+        }
+    }
+
+    /// The next datagram to send and the port to send it from.
+    pub fn poll_transmit(&mut self) -> Option<(Port, Vec<u8>)> {
+        self.transmit.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn handle_control(&mut self, now: Instant, port: Port, packet: ControlPacket) {
+        use ControlPacket::*;
+
+        let responder = self.role == Role::Responder;
+        let established = self.state == State::Established;
+        let invited_on = match self.state {
+            State::Inviting { port, .. } => Some(port),
+            _ => None,
+        };
+
+        match packet {
+            Invitation(exchange) if responder => {
+                if established && exchange.ssrc != self.peer_ssrc {
+                    let rejection = InvitationRejected(self.exchange(exchange.initiator_token));
+                    self.queue(port, &rejection);
+                    return;
+                }
+                self.initiator_token = exchange.initiator_token;
+                self.peer_ssrc = exchange.ssrc;
+                self.peer_name = exchange.name;
+                let acceptance = InvitationAccepted(self.exchange(self.initiator_token));
+                self.queue(port, &acceptance);
+                if port == Port::Data && !established {
+                    self.establish(now);
+                }
+            }
+            InvitationAccepted(exchange)
+                if invited_on == Some(port) && exchange.initiator_token == self.initiator_token =>
+            {
+                match port {
+                    Port::Control => {
+                        self.peer_ssrc = exchange.ssrc;
+                        self.peer_name = exchange.name;
+                        self.invite_on(now, Port::Data, 1);
+                    }
+                    Port::Data => self.establish(now),
+                }
+            }
+            InvitationRejected(exchange)
+                if invited_on.is_some() && exchange.initiator_token == self.initiator_token =>
+            {
+                self.closed(CloseReason::Rejected);
+            }
+            EndSession(exchange)
+                if exchange.ssrc == self.peer_ssrc
+                    && !matches!(self.state, State::Idle | State::Closed) =>
+            {
+                self.closed(CloseReason::Ended);
+            }
+            Synchronization {
+                count, timestamps, ..
+            } if established => {
+                let now = self.clock(now);
+                match count {
+                    0 => self.queue(
+                        port,
+                        &Synchronization {
+                            ssrc: self.config.ssrc,
+                            count: 1,
+                            timestamps: [timestamps[0], now, 0x0],
+                        },
+                    ),
+                    1 => {
+                        self.queue(
+                            port,
+                            &Synchronization {
+                                ssrc: self.config.ssrc,
+                                count: 2,
+                                timestamps: [timestamps[0], timestamps[1], now],
+                            },
+                        );
+                        let [sent, peer, _] = timestamps;
+                        self.synchronized(
+                            peer as i64 - midpoint(sent, now) as i64,
+                            now.wrapping_sub(sent) / 2,
+                        );
+                    }
+                    2 => {
+                        let [sent, ours, received] = timestamps;
+                        self.synchronized(
+                            midpoint(sent, received) as i64 - ours as i64,
+                            received.wrapping_sub(sent) / 2,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            ReceiverFeedback { sequence, .. } if established => self.sent.acknowledge(sequence),
+            _ => {}
+        }
+    }
+
+    fn handle_rtp(&mut self, now: Instant, packet: RtpMidiPacket) {
+        let expected = *self.receive_sequence.get_or_insert(packet.sequence);
+        match packet.sequence.wrapping_sub(expected) as i16 {
+            // already received or arrived too late
+            i16::MIN..=-1 => return,
+            0 => {}
+            lost => {
+                self.events.push_back(Event::Lost { count: lost as u16 });
+                self.sysex = None;
+                if let Some(journal) = &packet.journal {
+                    for message in self.received.recover(journal) {
+                        self.deliver(packet.timestamp, message);
+                    }
+                }
+            }
+        }
+        self.receive_sequence = Some(packet.sequence.wrapping_add(1));
+        self.feedback_deadline
+            .get_or_insert(now + self.config.feedback_interval);
+
+        let mut timestamp = packet.timestamp;
+        for MidiCommand { delta, data } in packet.commands {
+            timestamp = timestamp.wrapping_add(delta);
+            self.receive_command(timestamp, data);
+        }
+    }
+
+    fn receive_command(&mut self, timestamp: u32, mut data: Vec<u8>) {
+        let segment = data.len() >= 2;
+        match (data.first(), data.last()) {
+            (Some(0xF0), Some(0xF0)) if segment => {
+                data.pop();
+                self.sysex = Some(data);
+            }
+            (Some(0xF7), Some(0xF0)) if segment => {
+                if let Some(sysex) = &mut self.sysex {
+                    sysex.extend_from_slice(&data[1..data.len() - 1]);
+                }
+            }
+            (Some(0xF7), Some(0xF7)) if segment => {
+                if let Some(mut sysex) = self.sysex.take() {
+                    sysex.extend_from_slice(&data[1..]);
+                    self.deliver(timestamp, sysex);
+                }
+            }
+            // a cancelled system exclusive message
+            (Some(0xF7), Some(0xF4)) => self.sysex = None,
+            _ => self.deliver(timestamp, data),
+        }
+    }
+
+    fn deliver(&mut self, timestamp: u32, data: Vec<u8>) {
+        let Some(&status) = data.first() else {
+            return;
+        };
+        self.running_status = update_running_status(self.running_status, status);
+        self.received.record(0, &data);
+        if let Ok(message) = BytesMessage::try_from(&data[..]) {
+            self.events.push_back(Event::Message {
+                timestamp,
+                message: message.rebuffer_into(),
+            });
+        }
+    }
+
+    fn send_command(&mut self, now: Instant, data: Vec<u8>) {
+        let sequence = self.send_sequence;
+        self.send_sequence = sequence.wrapping_add(1);
+        let journal = self.sent.journal();
+        self.sent.record(sequence, &data);
+        let packet = RtpMidiPacket {
+            sequence,
+            timestamp: self.clock(now) as u32,
+            ssrc: self.config.ssrc,
+            commands: std::vec![MidiCommand { delta: 0, data }],
+            journal,
+        };
+        let mut datagram = Vec::new();
+        packet.encode(&mut datagram);
+        self.transmit.push_back((Port::Data, datagram));
+    }
+
+    fn invite_on(&mut self, now: Instant, port: Port, attempts: u32) {
+        let invitation = ControlPacket::Invitation(self.exchange(self.initiator_token));
+        self.queue(port, &invitation);
+        self.state = State::Inviting {
+            port,
+            attempts,
+            deadline: now + self.config.reply_timeout,
+        };
+    }
+
+    fn establish(&mut self, now: Instant) {
+        self.state = State::Established;
+        self.send_sequence = 0;
+        self.sent = History::default();
+        self.receive_sequence = None;
+        self.received = History::default();
+        self.running_status = None;
+        self.sysex = None;
+        self.events.push_back(Event::Established {
+            name: self.peer_name.clone(),
+            ssrc: self.peer_ssrc,
+        });
+        if self.role == Role::Initiator {
+            self.synchronize(now);
+            self.next_sync = Some(now + self.config.sync_interval);
+        }
+    }
+
+    fn closed(&mut self, reason: CloseReason) {
+        self.state = State::Closed;
+        self.next_sync = None;
+        self.feedback_deadline = None;
+        self.events.push_back(Event::Closed { reason });
+    }
+
+    fn synchronized(&mut self, offset: i64, latency: u64) {
+        self.events.push_back(Event::Synchronized {
+            offset,
+            latency: Duration::from_micros(latency * (1_000_000 / TICKS_PER_SECOND)),
+        });
+    }
+
+    fn exchange(&self, initiator_token: u32) -> Exchange {
+        Exchange {
+            initiator_token,
+            ssrc: self.config.ssrc,
+            name: self.config.name.clone(),
+        }
+    }
+
+    fn clock(&mut self, now: Instant) -> u64 {
+        let epoch = *self.epoch.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(epoch);
+        (elapsed.as_micros() * u128::from(TICKS_PER_SECOND) / 1_000_000) as u64
+    }
+
+    fn queue(&mut self, port: Port, packet: &ControlPacket) {
+        let mut datagram = Vec::new();
+        packet.encode(&mut datagram);
+        self.transmit.push_back((port, datagram));
+    }
+}
+
+fn midpoint(a: u64, b: u64) -> u64 {
+    a / 2 + b / 2 + (a % 2 + b % 2) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const NOTE_ON: [u8; 3] = [0x90, 0x3C, 0x40];
+    const NOTE_OFF: [u8; 3] = [0x80, 0x3C, 0x40];
+
+    fn message(data: &[u8]) -> BytesMessage<&[u8]> {
+        BytesMessage::try_from(data).unwrap()
+    }
+
+    fn config(name: &str, ssrc: u32) -> Config {
+        Config {
+            name: name.into(),
+            ssrc,
+            ..Default::default()
+        }
+    }
+
+    // exchange datagrams until both sides go quiet
+    fn pump(now: Instant, a: &mut Session, b: &mut Session) {
+        loop {
+            let mut quiet = true;
+            while let Some((port, datagram)) = a.poll_transmit() {
+                b.handle_datagram(now, port, &datagram).unwrap();
+                quiet = false;
+            }
+            while let Some((port, datagram)) = b.poll_transmit() {
+                a.handle_datagram(now, port, &datagram).unwrap();
+                quiet = false;
+            }
+            if quiet {
+                break;
+            }
+        }
+    }
+
+    fn events(session: &mut Session) -> Vec<Event> {
+        core::iter::from_fn(|| session.poll_event()).collect()
+    }
+
+    fn messages(session: &mut Session) -> Vec<Vec<u8>> {
+        events(session)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Message { message, .. } => Some(message.data().to_vec()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn connect() -> (Session, Session, Instant) {
+        let now = Instant::now();
+        let mut initiator = Session::initiator(config("Initiator", 0x1111));
+        let mut responder = Session::responder(config("Responder", 0x2222));
+        initiator.invite(now, 0xABCD);
+        pump(now, &mut initiator, &mut responder);
+        (initiator, responder, now)
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut initiator, mut responder, _) = connect();
+        let initiator_events = events(&mut initiator);
+        assert_eq!(
+            initiator_events[0],
+            Event::Established {
+                name: "Responder".into(),
+                ssrc: 0x2222,
+            }
+        );
+        assert!(matches!(
+            initiator_events[1],
+            Event::Synchronized { offset: 0, .. }
+        ));
+        let responder_events = events(&mut responder);
+        assert_eq!(
+            responder_events[0],
+            Event::Established {
+                name: "Initiator".into(),
+                ssrc: 0x1111,
+            }
+        );
+        assert!(matches!(
+            responder_events[1],
+            Event::Synchronized { offset: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn clock_offset() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut initiator);
+        events(&mut responder);
+
+        // the responder's clock started a second earlier
+        responder.epoch = Some(now - Duration::from_secs(1));
+        initiator.synchronize(now);
+        pump(now, &mut initiator, &mut responder);
+        assert_eq!(
+            events(&mut initiator),
+            [Event::Synchronized {
+                offset: 10_000,
+                latency: Duration::ZERO,
+            }]
+        );
+        assert_eq!(
+            events(&mut responder),
+            [Event::Synchronized {
+                offset: -10_000,
+                latency: Duration::ZERO,
+            }]
+        );
+    }
+
+    #[test]
+    fn messages_both_ways() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut initiator);
+        events(&mut responder);
+
+        initiator.send(now, &message(&NOTE_ON)).unwrap();
+        responder.send(now, &message(&NOTE_OFF)).unwrap();
+        pump(now, &mut initiator, &mut responder);
+
+        assert_eq!(messages(&mut responder), [NOTE_ON]);
+        assert_eq!(messages(&mut initiator), [NOTE_OFF]);
+    }
+
+    #[test]
+    fn send_before_established() {
+        let mut initiator = Session::initiator(Config::default());
+        assert_eq!(
+            initiator.send(Instant::now(), &message(&NOTE_ON)),
+            Err(Error::NotEstablished)
+        );
+    }
+
+    #[test]
+    fn segmented_sysex() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut responder);
+        initiator.config.max_sysex_segment = 2;
+
+        let sysex = [0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0xF7];
+        initiator.send(now, &message(&sysex)).unwrap();
+        assert_eq!(initiator.transmit.len(), 3);
+        pump(now, &mut initiator, &mut responder);
+        assert_eq!(messages(&mut responder), [sysex]);
+    }
+
+    #[test]
+    fn sysex_segment_fits_a_packet() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut responder);
+        initiator.config.max_sysex_segment = 10_000;
+
+        let mut sysex = std::vec![0x0; 5000];
+        sysex[0] = 0xF0;
+        sysex[4999] = 0xF7;
+        initiator.send(now, &message(&sysex)).unwrap();
+        assert_eq!(initiator.transmit.len(), 2);
+        pump(now, &mut initiator, &mut responder);
+        assert_eq!(messages(&mut responder), [sysex]);
+    }
+
+    #[test]
+    fn recover_from_lost_packet() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut responder);
+
+        initiator.send(now, &message(&NOTE_ON)).unwrap();
+        initiator.send(now, &message(&[0xC0, 0x05])).unwrap();
+        initiator.send(now, &message(&NOTE_OFF)).unwrap();
+        let (_, first) = initiator.poll_transmit().unwrap();
+        initiator.poll_transmit().unwrap();
+        let (_, third) = initiator.poll_transmit().unwrap();
+        responder.handle_datagram(now, Port::Data, &first).unwrap();
+        responder.handle_datagram(now, Port::Data, &third).unwrap();
+
+        let events: Vec<Event> = events(&mut responder)
+            .into_iter()
+            .map(|event| match event {
+                Event::Message { message, .. } => Event::Message {
+                    timestamp: 0,
+                    message,
+                },
+                event => event,
+            })
+            .collect();
+        let delivered = |data: &[u8]| Event::Message {
+            timestamp: 0,
+            message: message(data).rebuffer_into(),
+        };
+        assert_eq!(
+            events,
+            [
+                delivered(&NOTE_ON),
+                Event::Lost { count: 1 },
+                delivered(&[0xC0, 0x05]),
+                delivered(&NOTE_OFF),
+            ]
+        );
+    }
+
+    #[test]
+    fn receiver_feedback_trims_journal() {
+        let (mut initiator, mut responder, now) = connect();
+        initiator.send(now, &message(&NOTE_ON)).unwrap();
+        pump(now, &mut initiator, &mut responder);
+        assert!(initiator.sent.journal().is_some());
+
+        let later = responder.poll_timeout().unwrap();
+        responder.handle_timeout(later);
+        pump(later, &mut initiator, &mut responder);
+        assert_eq!(initiator.sent.journal(), None);
+    }
+
+    #[test]
+    fn periodic_synchronization() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut initiator);
+
+        let later = now + initiator.config.sync_interval;
+        assert_eq!(initiator.poll_timeout(), Some(later));
+        initiator.handle_timeout(later);
+        pump(later, &mut initiator, &mut responder);
+        assert!(matches!(
+            events(&mut initiator)[..],
+            [Event::Synchronized { .. }]
+        ));
+    }
+
+    #[test]
+    fn invitation_times_out() {
+        let mut now = Instant::now();
+        let mut initiator = Session::initiator(Config::default());
+        initiator.invite(now, 0x1);
+        let mut invitations = 0;
+        while let Some(timeout) = initiator.poll_timeout() {
+            while initiator.poll_transmit().is_some() {
+                invitations += 1;
+            }
+            now = timeout;
+            initiator.handle_timeout(now);
+        }
+        assert_eq!(invitations, 3);
+        assert_eq!(
+            events(&mut initiator),
+            [Event::Closed {
+                reason: CloseReason::Timeout
+            }]
+        );
+    }
+
+    #[test]
+    fn second_initiator_is_rejected() {
+        let (_, mut responder, now) = connect();
+        let mut intruder = Session::initiator(config("Intruder", 0x3333));
+        intruder.invite(now, 0x1);
+        pump(now, &mut intruder, &mut responder);
+        assert_eq!(
+            events(&mut intruder),
+            [Event::Closed {
+                reason: CloseReason::Rejected
+            }]
+        );
+    }
+
+    #[test]
+    fn end_session() {
+        let (mut initiator, mut responder, now) = connect();
+        events(&mut responder);
+
+        initiator.close();
+        pump(now, &mut initiator, &mut responder);
+        assert_eq!(
+            events(&mut responder),
+            [Event::Closed {
+                reason: CloseReason::Ended
+            }]
+        );
+        assert!(initiator.send(now, &message(&NOTE_ON)).is_err());
+    }
+
+    #[test]
+    fn loopback_udp() {
+        use std::net::UdpSocket;
+
+        fn bind() -> [UdpSocket; 2] {
+            [
+                UdpSocket::bind("127.0.0.1:0").unwrap(),
+                UdpSocket::bind("127.0.0.1:0").unwrap(),
+            ]
+        }
+        let initiator_sockets = bind();
+        let responder_sockets = bind();
+        for (a, b) in initiator_sockets.iter().zip(responder_sockets.iter()) {
+            a.connect(b.local_addr().unwrap()).unwrap();
+            b.connect(a.local_addr().unwrap()).unwrap();
+            for socket in [a, b] {
+                socket
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .unwrap();
+            }
+        }
+        let index = |port| match port {
+            Port::Control => 0,
+            Port::Data => 1,
+        };
+
+        let mut buffer = [0x0; 1500];
+        let mut exchange = |from: &mut Session,
+                            from_sockets: &[UdpSocket; 2],
+                            to: &mut Session,
+                            to_sockets: &[UdpSocket; 2]| {
+            while let Some((port, datagram)) = from.poll_transmit() {
+                from_sockets[index(port)].send(&datagram).unwrap();
+                let len = to_sockets[index(port)].recv(&mut buffer).unwrap();
+                to.handle_datagram(Instant::now(), port, &buffer[..len])
+                    .unwrap();
+            }
+        };
+
+        let mut initiator = Session::initiator(config("Initiator", 0x1));
+        let mut responder = Session::responder(config("Responder", 0x2));
+        initiator.invite(Instant::now(), 0x1234);
+        while !initiator.is_established() {
+            exchange(
+                &mut initiator,
+                &initiator_sockets,
+                &mut responder,
+                &responder_sockets,
+            );
+            exchange(
+                &mut responder,
+                &responder_sockets,
+                &mut initiator,
+                &initiator_sockets,
+            );
+        }
+        assert!(responder.is_established());
+
+        initiator.send(Instant::now(), &message(&NOTE_ON)).unwrap();
+        exchange(
+            &mut initiator,
+            &initiator_sockets,
+            &mut responder,
+            &responder_sockets,
+        );
+        assert_eq!(messages(&mut responder), [NOTE_ON]);
+    }
+}
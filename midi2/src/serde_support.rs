@@ -0,0 +1,358 @@
+//! Serde support for messages.
+//!
+//! With the `serde` feature enabled every strongly typed message,
+//! the aggregate message enums and the supporting enums implement
+//! [Serialize] and [Deserialize].
+//!
+//! By default messages take a structured, human readable form
+//! with a named field for each property of the message.
+//! The aggregate enums are externally tagged with the name of the variant.
+//!
+//! ```rust
+//! use midi2::prelude::*;
+//!
+//! let mut message = channel_voice2::NoteOn::<[u32; 4]>::new();
+//! message.set_group(u4::new(0x2));
+//! message.set_channel(u4::new(0x4));
+//! message.set_note_number(u7::new(0x3C));
+//! message.set_velocity(0x8000);
+//!
+//! let json = serde_json::to_string(&message).unwrap();
+//! assert_eq!(
+//!     json,
+//!     r#"{"channel":4,"group":2,"note_number":60,"velocity":32768,"attribute":null}"#,
+//! );
+//! assert_eq!(
+//!     serde_json::from_str::<channel_voice2::NoteOn<[u32; 4]>>(&json).unwrap(),
+//!     message,
+//! );
+//! ```
+//!
+//! Deserialization writes each field through the same properties as the setters
+//! and validates the result in the same way as `try_from` does for a slice,
+//! so an out of range or inconsistent value is reported as an error.
+//!
+//! Fields which are missing take their default value
+//! and unrecognised fields are ignored.
+//!
+//! Messages backed by a fixed size buffer can be deserialized
+//! without an allocator, but the text properties of the flex data
+//! and ump stream messages are only available with the `std` feature.
+//!
+//! # Raw Form
+//!
+//! The compact alternative is the sequence of raw ump words or bytes
+//! of the message data. Use the [Raw] wrapper, or the [raw] module with
+//! serde's `with` attribute.
+//!
+//! ```rust
+//! use midi2::prelude::*;
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Event {
+//!     time: u64,
+//!     #[serde(with = "midi2::serde_support::raw")]
+//!     message: UmpMessage<[u32; 4]>,
+//! }
+//!
+//! let event: Event = serde_json::from_str(
+//!     r#"{"time":100,"message":[1083194368,4294901760]}"#,
+//! ).unwrap();
+//!
+//! assert_eq!(event.message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+//! ```
+//!
+//! Raw data is validated with `try_from` before the message is created.
+
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Messages which can be serialized as their raw data.
+pub trait SerializeRaw {
+    fn serialize_raw<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+/// Messages which can be deserialized from their raw data.
+pub trait DeserializeRaw<'de>: Sized {
+    fn deserialize_raw<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+/// Wraps a message to serialize and deserialize it in the raw form.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::serde_support::Raw;
+///
+/// let message = Raw(sysex7::Sysex7::try_from(&[0xF0_u8, 0x01, 0x02, 0xF7][..]).unwrap());
+///
+/// assert_eq!(serde_json::to_string(&message).unwrap(), "[240,1,2,247]");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Raw<M>(pub M);
+
+impl<M: SerializeRaw> Serialize for Raw<M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_raw(serializer)
+    }
+}
+
+impl<'de, M: DeserializeRaw<'de>> Deserialize<'de> for Raw<M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        M::deserialize_raw(deserializer).map(Raw)
+    }
+}
+
+/// The raw form for use with serde's `with` field attribute.
+///
+/// See the [module docs](crate::serde_support) for an example.
+pub mod raw {
+    use super::*;
+
+    pub fn serialize<M: SerializeRaw, S: Serializer>(
+        message: &M,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        message.serialize_raw(serializer)
+    }
+
+    pub fn deserialize<'de, M: DeserializeRaw<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<M, D::Error> {
+        M::deserialize_raw(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, traits::Sysex};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn structured() {
+        let message = channel_voice2::NoteOn::try_from(&[0x4898_5E03, 0x6A14_E98A][..]).unwrap();
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"channel":8,"group":8,"note_number":94,"velocity":27156,"attribute":{"Pitch7_9":{"bits":59786}}}"#,
+        );
+    }
+
+    #[test]
+    fn structured_round_trip() {
+        let message: channel_voice2::NoteOn<[u32; 4]> =
+            channel_voice2::NoteOn::try_from(&[0x4898_5E03, 0x6A14_E98A][..])
+                .unwrap()
+                .array_rebuffer_into();
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            serde_json::from_str::<channel_voice2::NoteOn<[u32; 4]>>(&json).unwrap(),
+            message,
+        );
+    }
+
+    #[test]
+    fn structured_missing_fields_take_defaults() {
+        let message: channel_voice2::NoteOn<std::vec::Vec<u32>> =
+            serde_json::from_str(r#"{"note_number":60}"#).unwrap();
+        assert_eq!(message.data(), &[0x4090_3C00, 0x0]);
+    }
+
+    #[test]
+    fn structured_unknown_fields_are_ignored() {
+        let message: channel_voice2::NoteOn<[u32; 4]> =
+            serde_json::from_str(r#"{"note_number":60,"colour":"blue"}"#).unwrap();
+        assert_eq!(message.note_number(), u7::new(60));
+    }
+
+    #[test]
+    fn structured_out_of_range() {
+        assert!(
+            serde_json::from_str::<channel_voice2::NoteOn<[u32; 4]>>(r#"{"note_number":128}"#)
+                .is_err()
+        );
+        assert!(
+            serde_json::from_str::<channel_voice2::NoteOn<[u32; 4]>>(r#"{"group":16}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn structured_invalid_controller() {
+        assert!(
+            serde_json::from_str::<channel_voice2::RegisteredPerNoteController<[u32; 4]>>(
+                r#"{"controller":{"SoundController":{"index":11,"data":0}}}"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn structured_buffer_overflow() {
+        assert!(serde_json::from_str::<channel_voice2::NoteOn<[u32; 1]>>("{}").is_err());
+    }
+
+    #[test]
+    fn structured_aggregate() {
+        let message = UmpMessage::try_from(&[0x4898_5E00, 0x6A14_0000][..]).unwrap();
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ChannelVoice2":{"NoteOn":{"channel":8,"group":8,"note_number":94,"velocity":27156,"attribute":null}}}"#,
+        );
+        assert_eq!(
+            serde_json::from_str::<UmpMessage<std::vec::Vec<u32>>>(&json)
+                .unwrap()
+                .data(),
+            message.data(),
+        );
+    }
+
+    #[test]
+    fn structured_aggregate_unknown_variant() {
+        assert!(serde_json::from_str::<UmpMessage<[u32; 4]>>(r#"{"Sysex9":{}}"#).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "channel-voice1")]
+    fn structured_bytes_message_has_no_group() {
+        let message = channel_voice1::NoteOn::try_from(&[0x93_u8, 0x3C, 0x40][..]).unwrap();
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, r#"{"channel":3,"note_number":60,"velocity":64}"#);
+        assert_eq!(
+            serde_json::from_str::<channel_voice1::NoteOn<[u8; 3]>>(&json)
+                .unwrap()
+                .data(),
+            &[0x93, 0x3C, 0x40],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn structured_sysex7() {
+        let mut message = sysex7::Sysex7::<std::vec::Vec<u32>>::new();
+        message.set_group(u4::new(0x2));
+        message.set_payload((0..8).map(u7::new));
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, r#"{"group":2,"payload":[0,1,2,3,4,5,6,7]}"#);
+
+        let deserialized: sysex7::Sysex7<[u32; 8]> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.data(), message.data());
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn structured_sysex7_invalid_payload() {
+        assert!(serde_json::from_str::<sysex7::Sysex7<[u8; 8]>>(r#"{"payload":[0,128]}"#).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn structured_sysex7_payload_overflow() {
+        assert!(serde_json::from_str::<sysex7::Sysex7<[u8; 4]>>(r#"{"payload":[0,1,2]}"#).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn structured_flex_data_text() {
+        let mut message = flex_data::ProjectName::<std::vec::Vec<u32>>::new();
+        message.set_text("Shadows of the Moon");
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            r#"{"group":0,"optional_channel":null,"text":"Shadows of the Moon"}"#,
+        );
+        assert_eq!(
+            serde_json::from_str::<flex_data::ProjectName<std::vec::Vec<u32>>>(&json).unwrap(),
+            message,
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn structured_supporting_enums() {
+        let message = flex_data::SetChordName::try_from(
+            &[0xD70B_0006, 0xF703_3519, 0x4B00_0000, 0x110A_0020][..],
+        )
+        .unwrap();
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            serde_json::from_str::<flex_data::SetChordName<[u32; 4]>>(&json)
+                .unwrap()
+                .data(),
+            message.data(),
+        );
+        assert!(serde_json::from_str::<flex_data::SetChordName<[u32; 4]>>(
+            r#"{"chord_alteration1":{"Raise":16}}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn raw() {
+        let message = UmpMessage::try_from(&[0x4898_5E00, 0x6A14_0000][..]).unwrap();
+        assert_eq!(
+            serde_json::to_string(&Raw(message)).unwrap(),
+            "[1217945088,1779695616]",
+        );
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let message = UmpMessage::try_from(&[0x4898_5E00, 0x6A14_0000][..]).unwrap();
+        let json = serde_json::to_string(&Raw(message)).unwrap();
+        let Raw(deserialized) = serde_json::from_str::<Raw<UmpMessage<[u32; 4]>>>(&json).unwrap();
+        assert_eq!(deserialized.data(), message.data());
+        let Raw(deserialized) =
+            serde_json::from_str::<Raw<channel_voice2::NoteOn<std::vec::Vec<u32>>>>(&json).unwrap();
+        assert_eq!(deserialized.data(), message.data());
+    }
+
+    #[test]
+    fn raw_invalid_data() {
+        assert!(serde_json::from_str::<Raw<UmpMessage<[u32; 4]>>>("[]").is_err());
+        assert!(
+            serde_json::from_str::<Raw<channel_voice2::NoteOn<[u32; 4]>>>("[1082130432]").is_err()
+        );
+        assert!(
+            serde_json::from_str::<Raw<channel_voice2::NoteOn<[u32; 4]>>>("[1082130432,0,0,0,0]")
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn raw_bytes() {
+        let json = "[240,1,2,247]";
+        let Raw(message) = serde_json::from_str::<Raw<BytesMessage<[u8; 8]>>>(json).unwrap();
+        assert_eq!(message.data(), &[0xF0, 0x01, 0x02, 0xF7]);
+        assert_eq!(serde_json::to_string(&Raw(message)).unwrap(), json);
+    }
+
+    #[test]
+    fn raw_with_attribute() {
+        #[derive(::serde::Serialize, ::serde::Deserialize)]
+        struct Event {
+            time: u64,
+            #[serde(with = "crate::serde_support::raw")]
+            message: channel_voice2::ChannelVoice2<std::vec::Vec<u32>>,
+        }
+
+        let event: Event =
+            serde_json::from_str(r#"{"time":10,"message":[1217945088,1779695616]}"#).unwrap();
+        assert_eq!(event.time, 10);
+        assert_eq!(event.message.data(), &[0x4898_5E00, 0x6A14_0000]);
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"time":10,"message":[1217945088,1779695616]}"#,
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn payload_bytes_are_u7() {
+        let message: sysex7::Sysex7<std::vec::Vec<u8>> =
+            serde_json::from_str(r#"{"payload":[127]}"#).unwrap();
+        assert_eq!(
+            message.payload().collect::<std::vec::Vec<_>>(),
+            [u7::new(127)]
+        );
+    }
+}
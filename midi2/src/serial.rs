@@ -0,0 +1,602 @@
+//! Framing for sending ump over links with no packet boundaries, such as a UART.
+//!
+//! Every ump packet is sent in a frame of its own, so frames always carry whole packets.
+//! The words of the packet are sent big endian, optionally followed by
+//! a CRC-16/CCITT-FALSE checksum, and the frame is delimited with either
+//! [COBS](Framing::Cobs) or [SLIP](Framing::Slip).
+//! A corrupted frame is dropped and the decoder picks up again from the next delimiter.
+//!
+//! ```rust
+//! use midi2::{prelude::*, serial::{Config, SerialDecoder, SerialEncoder}};
+//!
+//! let mut message = sysex7::Sysex7::<Vec<u32>>::new();
+//! message.set_payload((0..10).map(u7::new));
+//!
+//! let encoder = SerialEncoder::new(Config::default());
+//! let mut link = Vec::new();
+//! for frame in encoder.frames(&message) {
+//!     link.extend_from_slice(&frame);
+//! }
+//!
+//! let mut decoder = SerialDecoder::<8>::new(Config::default());
+//! let mut input = &link[..];
+//! let received = decoder.decode(&mut input).unwrap().unwrap();
+//! assert_eq!(received.data(), message.data());
+//! ```
+
+use crate::{
+    detail::BitOps,
+    error::{Error, InvalidData},
+    io::UmpDecoder,
+    message::UmpMessage,
+    packet,
+    packets::{Packets, PacketsIterator},
+};
+
+const ERR_CORRUPT_FRAME: &str = "Serial frame is corrupt";
+const ERR_FRAME_TOO_LONG: &str = "Serial frame is longer than a ump packet";
+const ERR_CHECKSUM_MISMATCH: &str = "Serial frame checksum does not match";
+const ERR_INCOMPLETE_PACKET: &str = "Serial frame does not carry a whole ump packet";
+
+const CRC_SIZE: usize = 2;
+// the largest packet and its checksum
+const MAX_PAYLOAD_SIZE: usize = 16 + CRC_SIZE;
+// every byte escaped between two delimiters
+const MAX_FRAME_SIZE: usize = 2 * MAX_PAYLOAD_SIZE + 2;
+
+const COBS_DELIMITER: u8 = 0x00;
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// How frames are delimited on the link.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Framing {
+    /// Consistent Overhead Byte Stuffing, with frames ending in a zero byte.
+    #[default]
+    Cobs,
+    /// Serial Line Internet Protocol (RFC 1055), with frames between `0xC0` bytes.
+    Slip,
+}
+
+/// Settings which both ends of the link must agree on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub framing: Framing,
+    /// Append a CRC-16/CCITT-FALSE checksum to every frame.
+    pub crc: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            framing: Framing::default(),
+            crc: true,
+        }
+    }
+}
+
+/// An encoded frame, ready to be written to the link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    data: [u8; MAX_FRAME_SIZE],
+    len: usize,
+}
+
+impl core::ops::Deref for Frame {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        &self.data[..self.len]
+    }
+}
+
+impl Frame {
+    fn push(&mut self, byte: u8) {
+        self.data[self.len] = byte;
+        self.len += 1;
+    }
+}
+
+/// Wraps ump packets in frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SerialEncoder {
+    config: Config,
+}
+
+impl SerialEncoder {
+    pub fn new(config: Config) -> Self {
+        SerialEncoder { config }
+    }
+
+    /// Encode a single ump packet into a frame.
+    ///
+    /// Fails when the data is not exactly one whole packet.
+    pub fn encode(&self, data: &[u32]) -> Result<Frame, InvalidData> {
+        let Some(&first) = data.first() else {
+            return Err(InvalidData(ERR_INCOMPLETE_PACKET));
+        };
+        if data.len() != packet::size(first.nibble(0).into()) {
+            return Err(InvalidData(ERR_INCOMPLETE_PACKET));
+        }
+
+        let mut payload = [0x0; MAX_PAYLOAD_SIZE];
+        let mut len = 0;
+        for word in data {
+            payload[len..len + 4].copy_from_slice(&word.to_be_bytes());
+            len += 4;
+        }
+        if self.config.crc {
+            let crc = crc16(&payload[..len]);
+            payload[len..len + CRC_SIZE].copy_from_slice(&crc.to_be_bytes());
+            len += CRC_SIZE;
+        }
+
+        let mut frame = Frame {
+            data: [0x0; MAX_FRAME_SIZE],
+            len: 0,
+        };
+        match self.config.framing {
+            Framing::Cobs => cobs_encode(&payload[..len], &mut frame),
+            Framing::Slip => slip_encode(&payload[..len], &mut frame),
+        }
+        Ok(frame)
+    }
+
+    /// The frames carrying each packet of the message.
+    pub fn frames<'a, M: Packets>(&self, message: &'a M) -> Frames<'a> {
+        Frames {
+            encoder: *self,
+            packets: message.packets(),
+        }
+    }
+}
+
+/// Iterator over the frames of a message.
+///
+/// Returned from [SerialEncoder::frames].
+#[derive(Debug, Clone)]
+pub struct Frames<'a> {
+    encoder: SerialEncoder,
+    packets: PacketsIterator<'a>,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let packet = self.packets.next()?;
+        // packets of a message are always whole
+        Some(self.encoder.encode(&packet).unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.packets.size_hint()
+    }
+}
+
+impl core::iter::FusedIterator for Frames<'_> {}
+
+impl core::iter::ExactSizeIterator for Frames<'_> {
+    fn len(&self) -> usize {
+        self.packets.len()
+    }
+}
+
+/// Recovers ump messages from a stream of framed packets without allocating.
+///
+/// Bytes are fed in with [decode](SerialDecoder::decode) in chunks of any size.
+/// Multi-packet messages are reassembled into an internal buffer of `SIZE` words,
+/// with the same behaviour as the [UmpDecoder].
+///
+/// Corrupt frames are reported as [Error::InvalidData] and dropped,
+/// and decoding resumes with the frame after the next delimiter.
+#[derive(Clone, Debug)]
+pub struct SerialDecoder<const SIZE: usize = 64> {
+    config: Config,
+    payload: [u8; MAX_PAYLOAD_SIZE],
+    len: usize,
+    // the bytes still to come in the current cobs block
+    remaining: u8,
+    // the code of the current cobs block
+    code: u8,
+    escaped: bool,
+    // the rest of the frame is dropped after an error
+    skip: bool,
+    ump: UmpDecoder<SIZE>,
+}
+
+impl<const SIZE: usize> Default for SerialDecoder<SIZE> {
+    fn default() -> Self {
+        Self::new(Config::default())
+    }
+}
+
+impl<const SIZE: usize> SerialDecoder<SIZE> {
+    pub fn new(config: Config) -> Self {
+        SerialDecoder {
+            config,
+            payload: [0x0; MAX_PAYLOAD_SIZE],
+            len: 0,
+            remaining: 0,
+            code: 0xFF,
+            escaped: false,
+            skip: false,
+            ump: UmpDecoder::default(),
+        }
+    }
+
+    /// Consume bytes from the front of the input until a message is complete.
+    ///
+    /// Returns `Ok(None)` once the input is used up without completing a message.
+    /// The partially received frame and message are kept for the next call.
+    ///
+    /// Errors are reported as soon as they're found and the offending data is dropped,
+    /// so decoding can carry on with the next call.
+    pub fn decode<'a>(
+        &'a mut self,
+        input: &mut &[u8],
+    ) -> Result<Option<UmpMessage<&'a [u32]>>, Error> {
+        while let Some((&byte, rest)) = input.split_first() {
+            *input = rest;
+            let end = match self.config.framing {
+                Framing::Cobs => self.cobs_byte(byte),
+                Framing::Slip => self.slip_byte(byte),
+            };
+            match end {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => return Err(e.into()),
+            }
+            let skipped = core::mem::replace(&mut self.skip, false);
+            let len = core::mem::replace(&mut self.len, 0);
+            if skipped || len == 0 {
+                continue;
+            }
+            if self.end_frame(len)? {
+                return Ok(Some(self.ump.framed()?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Drop any partially received frame and message.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.remaining = 0;
+        self.code = 0xFF;
+        self.escaped = false;
+        self.skip = false;
+        self.ump.reset();
+    }
+
+    // returns whether the byte ended a frame
+    fn cobs_byte(&mut self, byte: u8) -> Result<bool, InvalidData> {
+        if byte == COBS_DELIMITER {
+            let truncated = self.remaining != 0;
+            self.remaining = 0;
+            self.code = 0xFF;
+            if truncated && !self.skip {
+                self.len = 0;
+                return Err(InvalidData(ERR_CORRUPT_FRAME));
+            }
+            return Ok(true);
+        }
+        if self.skip {
+            return Ok(false);
+        }
+        if self.remaining == 0 {
+            if self.code != 0xFF {
+                self.push(0x0)?;
+            }
+            self.code = byte;
+            self.remaining = byte - 1;
+            return Ok(false);
+        }
+        self.remaining -= 1;
+        self.push(byte)?;
+        Ok(false)
+    }
+
+    // returns whether the byte ended a frame
+    fn slip_byte(&mut self, byte: u8) -> Result<bool, InvalidData> {
+        if byte == SLIP_END {
+            self.escaped = false;
+            return Ok(true);
+        }
+        if self.skip {
+            return Ok(false);
+        }
+        if core::mem::replace(&mut self.escaped, false) {
+            return match byte {
+                SLIP_ESC_END => self.push(SLIP_END).map(|_| false),
+                SLIP_ESC_ESC => self.push(SLIP_ESC).map(|_| false),
+                _ => {
+                    self.skip = true;
+                    Err(InvalidData(ERR_CORRUPT_FRAME))
+                }
+            };
+        }
+        if byte == SLIP_ESC {
+            self.escaped = true;
+            return Ok(false);
+        }
+        self.push(byte)?;
+        Ok(false)
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), InvalidData> {
+        if self.len == MAX_PAYLOAD_SIZE {
+            self.skip = true;
+            return Err(InvalidData(ERR_FRAME_TOO_LONG));
+        }
+        self.payload[self.len] = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    // check the frame and pass its packet on to be reassembled
+    fn end_frame(&mut self, len: usize) -> Result<bool, Error> {
+        let mut payload = &self.payload[..len];
+        if self.config.crc {
+            let Some((data, crc)) = payload.split_last_chunk::<CRC_SIZE>() else {
+                return Err(InvalidData(ERR_CORRUPT_FRAME).into());
+            };
+            if crc16(data) != u16::from_be_bytes(*crc) {
+                return Err(InvalidData(ERR_CHECKSUM_MISMATCH).into());
+            }
+            payload = data;
+        }
+        if payload.len() % 4 != 0 {
+            return Err(InvalidData(ERR_INCOMPLETE_PACKET).into());
+        }
+
+        let mut words = [0x0; 4];
+        let mut len = 0;
+        for (word, bytes) in words.iter_mut().zip(payload.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            len += 1;
+        }
+        if len == 0 || len != packet::size(words[0].nibble(0).into()) {
+            return Err(InvalidData(ERR_INCOMPLETE_PACKET).into());
+        }
+        self.ump.frame_words(&mut &words[..len])
+    }
+}
+
+fn cobs_encode(payload: &[u8], frame: &mut Frame) {
+    let mut code_index = frame.len;
+    frame.push(0x1);
+    for &byte in payload {
+        if byte == 0x0 {
+            code_index = frame.len;
+            frame.push(0x1);
+            continue;
+        }
+        frame.push(byte);
+        frame.data[code_index] += 1;
+        if frame.data[code_index] == 0xFF {
+            code_index = frame.len;
+            frame.push(0x1);
+        }
+    }
+    frame.push(COBS_DELIMITER);
+}
+
+fn slip_encode(payload: &[u8], frame: &mut Frame) {
+    // the leading delimiter flushes any line noise received beforehand
+    frame.push(SLIP_END);
+    for &byte in payload {
+        match byte {
+            SLIP_END => {
+                frame.push(SLIP_ESC);
+                frame.push(SLIP_ESC_END);
+            }
+            SLIP_ESC => {
+                frame.push(SLIP_ESC);
+                frame.push(SLIP_ESC_ESC);
+            }
+            _ => frame.push(byte),
+        }
+    }
+    frame.push(SLIP_END);
+}
+
+/// CRC-16/CCITT-FALSE.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+    use std::vec::Vec;
+
+    const NO_CRC: Config = Config {
+        framing: Framing::Cobs,
+        crc: false,
+    };
+
+    fn decode_all<const SIZE: usize>(
+        decoder: &mut SerialDecoder<SIZE>,
+        mut input: &[u8],
+    ) -> Vec<Result<Vec<u32>, Error>> {
+        let mut messages = Vec::new();
+        loop {
+            match decoder.decode(&mut input) {
+                Ok(Some(message)) => messages.push(Ok(message.data().to_vec())),
+                Ok(None) => return messages,
+                Err(e) => messages.push(Err(e)),
+            }
+        }
+    }
+
+    fn link(config: Config, packets: &[&[u32]]) -> Vec<u8> {
+        let encoder = SerialEncoder::new(config);
+        packets
+            .iter()
+            .flat_map(|p| encoder.encode(p).unwrap().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn encode_cobs() {
+        let encoder = SerialEncoder::new(NO_CRC);
+        assert_eq!(
+            &*encoder.encode(&[0x2090_3C00]).unwrap(),
+            &[0x4, 0x20, 0x90, 0x3C, 0x1, 0x0]
+        );
+        assert_eq!(
+            &*encoder.encode(&[0x0000_0000]).unwrap(),
+            &[0x1, 0x1, 0x1, 0x1, 0x1, 0x0]
+        );
+    }
+
+    #[test]
+    fn encode_slip() {
+        let encoder = SerialEncoder::new(Config {
+            framing: Framing::Slip,
+            crc: false,
+        });
+        assert_eq!(
+            &*encoder.encode(&[0x20C0_DB00]).unwrap(),
+            &[0xC0, 0x20, 0xDB, 0xDC, 0xDB, 0xDD, 0x00, 0xC0]
+        );
+    }
+
+    #[test]
+    fn encode_requires_whole_packet() {
+        let encoder = SerialEncoder::default();
+        assert_eq!(
+            encoder.encode(&[0x4090_3C00]),
+            Err(InvalidData(ERR_INCOMPLETE_PACKET))
+        );
+        assert_eq!(encoder.encode(&[]), Err(InvalidData(ERR_INCOMPLETE_PACKET)));
+    }
+
+    #[test]
+    fn round_trips() {
+        for framing in [Framing::Cobs, Framing::Slip] {
+            for crc in [false, true] {
+                let config = Config { framing, crc };
+                let packets: [&[u32]; 3] = [
+                    &[0x4090_3C00, 0xFFFF_0000],
+                    &[0x4080_3C00, 0xC0DB_0000],
+                    &[0x40B0_0700, 0xDBC0_C0DB],
+                ];
+                let mut decoder = SerialDecoder::<4>::new(config);
+                assert_eq!(
+                    decode_all(&mut decoder, &link(config, &packets)),
+                    packets.iter().map(|p| Ok(p.to_vec())).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn multi_packet_message() {
+        use crate::traits::Sysex;
+
+        let mut message = crate::sysex7::Sysex7::<Vec<u32>>::new();
+        message.set_payload((0..20).map(crate::ux::u7::new));
+        let encoder = SerialEncoder::default();
+        assert_eq!(encoder.frames(&message).len(), 4);
+        let data: Vec<u8> = encoder.frames(&message).flat_map(|f| f.to_vec()).collect();
+        let mut decoder = SerialDecoder::<8>::default();
+        assert_eq!(
+            decode_all(&mut decoder, &data),
+            [Ok(message.data().to_vec())]
+        );
+    }
+
+    #[test]
+    fn resynchronise_after_corruption() {
+        let packets: [&[u32]; 2] = [&[0x4090_3C00, 0xFFFF_0000], &[0x4080_3C00, 0x0]];
+        let mut data = link(Config::default(), &packets);
+        data[2] ^= 0x10;
+        let mut decoder = SerialDecoder::<4>::default();
+        assert_eq!(
+            decode_all(&mut decoder, &data),
+            [
+                Err(InvalidData(ERR_CHECKSUM_MISMATCH).into()),
+                Ok(std::vec![0x4080_3C00, 0x0]),
+            ]
+        );
+
+        // line noise swallows the start of the first frame
+        let mut data = link(Config::default(), &packets);
+        data.splice(0..0, [0x12, 0x34]);
+        let mut decoder = SerialDecoder::<4>::default();
+        assert_eq!(
+            decode_all(&mut decoder, &data),
+            [
+                Err(InvalidData(ERR_CORRUPT_FRAME).into()),
+                Ok(std::vec![0x4080_3C00, 0x0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_cobs_frame() {
+        let mut decoder = SerialDecoder::<4>::new(NO_CRC);
+        let mut data = std::vec![0x5, 0x20, 0x0];
+        data.extend(link(NO_CRC, &[&[0x4090_3C00, 0xFFFF_0000]]));
+        assert_eq!(
+            decode_all(&mut decoder, &data),
+            [
+                Err(InvalidData(ERR_CORRUPT_FRAME).into()),
+                Ok(std::vec![0x4090_3C00, 0xFFFF_0000]),
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_too_long() {
+        let config = Config {
+            framing: Framing::Slip,
+            crc: false,
+        };
+        let mut data = std::vec![SLIP_END];
+        data.extend([0x1; 20]);
+        data.push(SLIP_END);
+        data.extend(link(config, &[&[0x4090_3C00, 0xFFFF_0000]]));
+        let mut decoder = SerialDecoder::<4>::new(config);
+        assert_eq!(
+            decode_all(&mut decoder, &data),
+            [
+                Err(InvalidData(ERR_FRAME_TOO_LONG).into()),
+                Ok(std::vec![0x4090_3C00, 0xFFFF_0000]),
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_with_partial_packet() {
+        let mut decoder = SerialDecoder::<4>::new(NO_CRC);
+        assert_eq!(
+            decode_all(&mut decoder, &[0x5, 0x40, 0x90, 0x3C, 0x1, 0x0]),
+            [Err(InvalidData(ERR_INCOMPLETE_PACKET).into())]
+        );
+    }
+}
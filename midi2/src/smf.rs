@@ -0,0 +1,378 @@
+#![doc = include_str!("smf/README.md")]
+
+use crate::{error::InvalidData, ux::u15};
+
+mod meta;
+mod track;
+
+pub use meta::{KeySignature, Meta, SmpteOffset, Tempo, TextKind, TimeSignature};
+pub use track::{Continuation, Event, SysexEvent, Track, TrackEvent};
+
+const HEADER_CHUNK_ID: &[u8; 4] = b"MThd";
+const TRACK_CHUNK_ID: &[u8; 4] = b"MTrk";
+const HEADER_SIZE: usize = 6;
+
+const ERR_NO_HEADER: &str = "Standard midi files should begin with an MThd chunk";
+const ERR_TRUNCATED_CHUNK: &str = "Chunk is truncated";
+const ERR_INVALID_FORMAT: &str = "Unknown standard midi file format";
+const ERR_INVALID_TIMING: &str = "Unknown timecode frame rate";
+
+/// A Standard MIDI File.
+///
+/// See the [module docs](crate::smf) for more info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Smf {
+    pub format: Format,
+    pub timing: Timing,
+    pub tracks: std::vec::Vec<Track>,
+    /// Chunks of unknown type, which are kept so that they're written back.
+    pub alien_chunks: std::vec::Vec<AlienChunk>,
+}
+
+/// A chunk of unknown type found in the file.
+///
+/// See the [module docs](crate::smf) for more info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlienChunk {
+    /// The number of track chunks which came before this chunk in the file.
+    pub position: usize,
+    pub id: [u8; 4],
+    pub data: std::vec::Vec<u8>,
+}
+
+/// The format field of the file header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Format 0: a single multi-channel track.
+    SingleTrack,
+    /// Format 1: one or more simultaneous tracks.
+    Simultaneous,
+    /// Format 2: one or more sequentially independent tracks.
+    Sequential,
+}
+
+/// The division field of the file header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+    /// Delta times are in ticks per quarter note.
+    Metrical(u15),
+    /// Delta times are subdivisions of SMPTE frames.
+    ///
+    /// `frames_per_second` is one of 24, 25, 29 (30 drop frame) or 30.
+    Timecode {
+        frames_per_second: u8,
+        ticks_per_frame: u8,
+    },
+}
+
+impl Smf {
+    pub fn new(format: Format, timing: Timing) -> Self {
+        Smf {
+            format,
+            timing,
+            tracks: std::vec::Vec::new(),
+            alien_chunks: std::vec::Vec::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> std::vec::Vec<u8> {
+        let mut data = std::vec::Vec::new();
+
+        data.extend_from_slice(HEADER_CHUNK_ID);
+        data.extend_from_slice(&(HEADER_SIZE as u32).to_be_bytes());
+        data.extend_from_slice(&self.format.to_u16().to_be_bytes());
+        data.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        data.extend_from_slice(&self.timing.to_u16().to_be_bytes());
+
+        let mut alien_chunks = self.alien_chunks.iter().peekable();
+        for position in 0..=self.tracks.len() {
+            while let Some(chunk) = alien_chunks.next_if(|chunk| chunk.position <= position) {
+                data.extend_from_slice(&chunk.id);
+                data.extend_from_slice(&(chunk.data.len() as u32).to_be_bytes());
+                data.extend_from_slice(&chunk.data);
+            }
+            let Some(track) = self.tracks.get(position) else {
+                break;
+            };
+            data.extend_from_slice(TRACK_CHUNK_ID);
+            let size_position = data.len();
+            data.extend_from_slice(&[0x0; 4]);
+            track.write(&mut data);
+            let size = (data.len() - size_position - 4) as u32;
+            data[size_position..size_position + 4].copy_from_slice(&size.to_be_bytes());
+        }
+        // chunks positioned past the last track
+        for chunk in alien_chunks {
+            data.extend_from_slice(&chunk.id);
+            data.extend_from_slice(&(chunk.data.len() as u32).to_be_bytes());
+            data.extend_from_slice(&chunk.data);
+        }
+
+        data
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for Smf {
+    type Error = InvalidData;
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut chunks = Chunks(data);
+
+        let Some((HEADER_CHUNK_ID, header)) = chunks.next().transpose()? else {
+            return Err(InvalidData(ERR_NO_HEADER));
+        };
+        if header.len() < HEADER_SIZE {
+            return Err(InvalidData(ERR_TRUNCATED_CHUNK));
+        }
+        let format = Format::from_u16(u16::from_be_bytes([header[0], header[1]]))?;
+        let timing = Timing::from_u16(u16::from_be_bytes([header[4], header[5]]))?;
+
+        let mut tracks = std::vec::Vec::new();
+        let mut alien_chunks = std::vec::Vec::new();
+        for chunk in chunks {
+            match chunk? {
+                (TRACK_CHUNK_ID, track_data) => tracks.push(Track::read(track_data)?),
+                (id, data) => alien_chunks.push(AlienChunk {
+                    position: tracks.len(),
+                    id: *id,
+                    data: data.to_vec(),
+                }),
+            }
+        }
+
+        Ok(Smf {
+            format,
+            timing,
+            tracks,
+            alien_chunks,
+        })
+    }
+}
+
+impl Format {
+    fn from_u16(v: u16) -> Result<Self, InvalidData> {
+        match v {
+            0 => Ok(Format::SingleTrack),
+            1 => Ok(Format::Simultaneous),
+            2 => Ok(Format::Sequential),
+            _ => Err(InvalidData(ERR_INVALID_FORMAT)),
+        }
+    }
+    fn to_u16(self) -> u16 {
+        match self {
+            Format::SingleTrack => 0,
+            Format::Simultaneous => 1,
+            Format::Sequential => 2,
+        }
+    }
+}
+
+impl Timing {
+    fn from_u16(v: u16) -> Result<Self, InvalidData> {
+        if v & 0x8000 == 0 {
+            return Ok(Timing::Metrical(u15::new(v)));
+        }
+        let frames_per_second = (-((v >> 8) as u8 as i8)) as u8;
+        if !matches!(frames_per_second, 24 | 25 | 29 | 30) {
+            return Err(InvalidData(ERR_INVALID_TIMING));
+        }
+        Ok(Timing::Timecode {
+            frames_per_second,
+            ticks_per_frame: (v & 0xFF) as u8,
+        })
+    }
+    fn to_u16(self) -> u16 {
+        match self {
+            Timing::Metrical(ticks) => u16::from(ticks),
+            Timing::Timecode {
+                frames_per_second,
+                ticks_per_frame,
+            } => (u16::from((-(frames_per_second as i8)) as u8) << 8) | u16::from(ticks_per_frame),
+        }
+    }
+}
+
+struct Chunks<'a>(&'a [u8]);
+
+impl<'a> core::iter::Iterator for Chunks<'a> {
+    type Item = Result<(&'a [u8; 4], &'a [u8]), InvalidData>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        if self.0.len() < 8 {
+            self.0 = &[];
+            return Some(Err(InvalidData(ERR_TRUNCATED_CHUNK)));
+        }
+        let id: &[u8; 4] = self.0[..4].try_into().unwrap();
+        let size = u32::from_be_bytes(self.0[4..8].try_into().unwrap()) as usize;
+        let Some(data) = 8usize.checked_add(size).and_then(|end| self.0.get(8..end)) else {
+            self.0 = &[];
+            return Some(Err(InvalidData(ERR_TRUNCATED_CHUNK)));
+        };
+        self.0 = &self.0[8 + data.len()..];
+        Some(Ok((id, data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        channel_voice1::{ChannelVoice1, NoteOn},
+        ux::{u24, u7},
+    };
+    use pretty_assertions::assert_eq;
+
+    const FORMAT_1: [u8; 60] = [
+        0x4D, 0x54, 0x68, 0x64, 0x00, 0x00, 0x00, 0x06, //
+        0x00, 0x01, 0x00, 0x02, 0x00, 0x60, //
+        0x4D, 0x54, 0x72, 0x6B, 0x00, 0x00, 0x00, 0x13, //
+        0x00, 0xFF, 0x58, 0x04, 0x04, 0x02, 0x18, 0x08, //
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, //
+        0x00, 0xFF, 0x2F, 0x00, //
+        0x4D, 0x54, 0x72, 0x6B, 0x00, 0x00, 0x00, 0x0B, //
+        0x00, 0xC1, 0x05, //
+        0x60, 0x91, 0x4C, 0x20, //
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+
+    #[test]
+    fn read_format_1() {
+        let smf = Smf::try_from(&FORMAT_1[..]).unwrap();
+        assert_eq!(smf.format, Format::Simultaneous);
+        assert_eq!(smf.timing, Timing::Metrical(u15::new(96)));
+        assert_eq!(smf.tracks.len(), 2);
+        assert_eq!(
+            smf.tracks[0].events[0].event,
+            Event::Meta(Meta::TimeSignature(TimeSignature {
+                numerator: 4,
+                denominator: 2,
+                clocks_per_click: 24,
+                number_of_32nd_notes: 8,
+            })),
+        );
+        assert_eq!(
+            smf.tracks[0].events[1].event,
+            Event::Meta(Meta::Tempo(Tempo {
+                microseconds_per_quarter_note: u24::new(500_000)
+            })),
+        );
+        assert_eq!(smf.tracks[1].events[1].delta, 0x60);
+        let Event::Midi(ChannelVoice1::NoteOn(note_on)) = &smf.tracks[1].events[1].event else {
+            panic!();
+        };
+        assert_eq!(note_on.note_number(), u7::new(0x4C));
+    }
+
+    #[test]
+    fn write_format_1() {
+        let smf = Smf::try_from(&FORMAT_1[..]).unwrap();
+        assert_eq!(smf.to_bytes(), &FORMAT_1[..]);
+    }
+
+    #[test]
+    fn write_new() {
+        let mut smf = Smf::new(Format::SingleTrack, Timing::Metrical(u15::new(480)));
+        let mut note_on = NoteOn::<[u8; 3]>::new();
+        note_on.set_note_number(u7::new(0x3C));
+        note_on.set_velocity(u7::new(0x7F));
+        smf.tracks.push(Track {
+            events: std::vec![TrackEvent::new(0, ChannelVoice1::from(note_on).into())],
+        });
+        assert_eq!(
+            smf.to_bytes(),
+            std::vec![
+                0x4D, 0x54, 0x68, 0x64, 0x00, 0x00, 0x00, 0x06, //
+                0x00, 0x00, 0x00, 0x01, 0x01, 0xE0, //
+                0x4D, 0x54, 0x72, 0x6B, 0x00, 0x00, 0x00, 0x08, //
+                0x00, 0x90, 0x3C, 0x7F, //
+                0x00, 0xFF, 0x2F, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn timecode_timing() {
+        let timing = Timing::from_u16(0xE728).unwrap();
+        assert_eq!(
+            timing,
+            Timing::Timecode {
+                frames_per_second: 25,
+                ticks_per_frame: 40,
+            }
+        );
+        assert_eq!(timing.to_u16(), 0xE728);
+    }
+
+    #[test]
+    fn invalid_timecode_timing() {
+        assert_eq!(
+            Timing::from_u16(0xE028),
+            Err(InvalidData(ERR_INVALID_TIMING))
+        );
+    }
+
+    #[test]
+    fn read_alien_chunks() {
+        let mut data = std::vec::Vec::from(&FORMAT_1[..]);
+        data.extend_from_slice(&[0x58, 0x59, 0x5A, 0x5A, 0x00, 0x00, 0x00, 0x02, 0xAB, 0xCD]);
+        let smf = Smf::try_from(&data[..]).unwrap();
+        assert_eq!(smf.tracks.len(), 2);
+        assert_eq!(
+            smf.alien_chunks,
+            [AlienChunk {
+                position: 2,
+                id: *b"XYZZ",
+                data: std::vec![0xAB, 0xCD],
+            }]
+        );
+    }
+
+    #[test]
+    fn write_alien_chunks() {
+        let alien_chunk = [0x58, 0x59, 0x5A, 0x5A, 0x00, 0x00, 0x00, 0x01, 0xAB];
+        let mut data = std::vec::Vec::from(&FORMAT_1[..41]);
+        data.extend_from_slice(&alien_chunk);
+        data.extend_from_slice(&FORMAT_1[41..]);
+        data.extend_from_slice(&alien_chunk);
+        let smf = Smf::try_from(&data[..]).unwrap();
+        assert_eq!(smf.alien_chunks.len(), 2);
+        assert_eq!(smf.to_bytes(), data);
+    }
+
+    #[test]
+    fn chunk_size_past_the_end() {
+        let mut data = FORMAT_1;
+        data[18..22].copy_from_slice(&[0xFF; 4]);
+        assert_eq!(
+            Smf::try_from(&data[..]),
+            Err(InvalidData(ERR_TRUNCATED_CHUNK))
+        );
+    }
+
+    #[test]
+    fn no_header() {
+        assert_eq!(
+            Smf::try_from(&FORMAT_1[14..]),
+            Err(InvalidData(ERR_NO_HEADER))
+        );
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        assert_eq!(
+            Smf::try_from(&FORMAT_1[..59]),
+            Err(InvalidData(ERR_TRUNCATED_CHUNK))
+        );
+    }
+
+    #[test]
+    fn invalid_format() {
+        let mut data = FORMAT_1;
+        data[9] = 0x3;
+        assert_eq!(
+            Smf::try_from(&data[..]),
+            Err(InvalidData(ERR_INVALID_FORMAT))
+        );
+    }
+}
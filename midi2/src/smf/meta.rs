@@ -0,0 +1,320 @@
+use crate::ux::{u24, u4};
+
+pub(crate) const STATUS: u8 = 0xFF;
+
+const SEQUENCE_NUMBER: u8 = 0x00;
+const CHANNEL_PREFIX: u8 = 0x20;
+const PORT: u8 = 0x21;
+const END_OF_TRACK: u8 = 0x2F;
+const TEMPO: u8 = 0x51;
+const SMPTE_OFFSET: u8 = 0x54;
+const TIME_SIGNATURE: u8 = 0x58;
+const KEY_SIGNATURE: u8 = 0x59;
+const SEQUENCER_SPECIFIC: u8 = 0x7F;
+
+/// A Standard MIDI File meta event.
+///
+/// Meta events with a known type but a malformed length
+/// are read as [Meta::Unknown] so that they survive a round trip unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Meta {
+    /// The sequence number. The number may be omitted in the file,
+    /// in which case the position of the track is used.
+    SequenceNumber(Option<u16>),
+    Text(TextKind, std::vec::Vec<u8>),
+    ChannelPrefix(u4),
+    Port(u8),
+    EndOfTrack,
+    Tempo(Tempo),
+    SmpteOffset(SmpteOffset),
+    TimeSignature(TimeSignature),
+    KeySignature(KeySignature),
+    SequencerSpecific(std::vec::Vec<u8>),
+    Unknown {
+        kind: u8,
+        data: std::vec::Vec<u8>,
+    },
+}
+
+/// The different flavours of text meta event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextKind {
+    Text,
+    Copyright,
+    TrackName,
+    InstrumentName,
+    Lyric,
+    Marker,
+    CuePoint,
+    ProgramName,
+    DeviceName,
+}
+
+/// Tempo, in microseconds per quarter note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tempo {
+    pub microseconds_per_quarter_note: u24,
+}
+
+/// The SMPTE time at which the track starts.
+///
+/// The `hours` byte holds the frame rate in bits 5 and 6
+/// as described in the MIDI file specification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SmpteOffset {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub fractional_frames: u8,
+}
+
+/// Time signature.
+///
+/// The `denominator` is expressed as a power of two, e.g. `2` represents a quarter note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+    pub clocks_per_click: u8,
+    pub number_of_32nd_notes: u8,
+}
+
+/// Key signature.
+///
+/// `sharps_flats` is the number of sharps when positive, and flats when negative.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeySignature {
+    pub sharps_flats: i8,
+    pub minor: bool,
+}
+
+impl Tempo {
+    pub fn from_bpm(bpm: f64) -> Self {
+        let us = (60_000_000.0 / bpm + 0.5) as u32;
+        Tempo {
+            microseconds_per_quarter_note: u24::new(us.min(u32::from(u24::MAX))),
+        }
+    }
+    pub fn bpm(&self) -> f64 {
+        60_000_000.0 / f64::from(u32::from(self.microseconds_per_quarter_note))
+    }
+}
+
+impl TextKind {
+    fn from_u8(v: u8) -> Option<Self> {
+        use TextKind::*;
+        Some(match v {
+            0x01 => Text,
+            0x02 => Copyright,
+            0x03 => TrackName,
+            0x04 => InstrumentName,
+            0x05 => Lyric,
+            0x06 => Marker,
+            0x07 => CuePoint,
+            0x08 => ProgramName,
+            0x09 => DeviceName,
+            _ => return None,
+        })
+    }
+    fn to_u8(self) -> u8 {
+        use TextKind::*;
+        match self {
+            Text => 0x01,
+            Copyright => 0x02,
+            TrackName => 0x03,
+            InstrumentName => 0x04,
+            Lyric => 0x05,
+            Marker => 0x06,
+            CuePoint => 0x07,
+            ProgramName => 0x08,
+            DeviceName => 0x09,
+        }
+    }
+}
+
+impl Meta {
+    /// The text of a text event, if it is valid utf8.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Meta::Text(_, data) => core::str::from_utf8(data).ok(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_data(kind: u8, data: &[u8]) -> Self {
+        match (kind, data.len()) {
+            (SEQUENCE_NUMBER, 0) => Meta::SequenceNumber(None),
+            (SEQUENCE_NUMBER, 2) => {
+                Meta::SequenceNumber(Some(u16::from_be_bytes([data[0], data[1]])))
+            }
+            (CHANNEL_PREFIX, 1) if data[0] < 0x10 => Meta::ChannelPrefix(u4::new(data[0])),
+            (PORT, 1) => Meta::Port(data[0]),
+            (END_OF_TRACK, 0) => Meta::EndOfTrack,
+            (TEMPO, 3) => Meta::Tempo(Tempo {
+                microseconds_per_quarter_note: u24::new(u32::from_be_bytes([
+                    0x0, data[0], data[1], data[2],
+                ])),
+            }),
+            (SMPTE_OFFSET, 5) => Meta::SmpteOffset(SmpteOffset {
+                hours: data[0],
+                minutes: data[1],
+                seconds: data[2],
+                frames: data[3],
+                fractional_frames: data[4],
+            }),
+            (TIME_SIGNATURE, 4) => Meta::TimeSignature(TimeSignature {
+                numerator: data[0],
+                denominator: data[1],
+                clocks_per_click: data[2],
+                number_of_32nd_notes: data[3],
+            }),
+            (KEY_SIGNATURE, 2) if data[1] < 2 => Meta::KeySignature(KeySignature {
+                sharps_flats: data[0] as i8,
+                minor: data[1] == 1,
+            }),
+            (SEQUENCER_SPECIFIC, _) => Meta::SequencerSpecific(data.to_vec()),
+            (kind, _) => match TextKind::from_u8(kind) {
+                Some(text_kind) => Meta::Text(text_kind, data.to_vec()),
+                None => Meta::Unknown {
+                    kind,
+                    data: data.to_vec(),
+                },
+            },
+        }
+    }
+
+    pub(crate) fn kind(&self) -> u8 {
+        match self {
+            Meta::SequenceNumber(_) => SEQUENCE_NUMBER,
+            Meta::Text(kind, _) => kind.to_u8(),
+            Meta::ChannelPrefix(_) => CHANNEL_PREFIX,
+            Meta::Port(_) => PORT,
+            Meta::EndOfTrack => END_OF_TRACK,
+            Meta::Tempo(_) => TEMPO,
+            Meta::SmpteOffset(_) => SMPTE_OFFSET,
+            Meta::TimeSignature(_) => TIME_SIGNATURE,
+            Meta::KeySignature(_) => KEY_SIGNATURE,
+            Meta::SequencerSpecific(_) => SEQUENCER_SPECIFIC,
+            Meta::Unknown { kind, .. } => *kind,
+        }
+    }
+
+    pub(crate) fn write_data(&self, data: &mut std::vec::Vec<u8>) {
+        match self {
+            Meta::SequenceNumber(None) | Meta::EndOfTrack => {}
+            Meta::SequenceNumber(Some(n)) => data.extend_from_slice(&n.to_be_bytes()),
+            Meta::Text(_, text) => data.extend_from_slice(text),
+            Meta::ChannelPrefix(channel) => data.push(u8::from(*channel)),
+            Meta::Port(port) => data.push(*port),
+            Meta::Tempo(tempo) => data.extend_from_slice(
+                &u32::from(tempo.microseconds_per_quarter_note).to_be_bytes()[1..],
+            ),
+            Meta::SmpteOffset(offset) => data.extend_from_slice(&[
+                offset.hours,
+                offset.minutes,
+                offset.seconds,
+                offset.frames,
+                offset.fractional_frames,
+            ]),
+            Meta::TimeSignature(signature) => data.extend_from_slice(&[
+                signature.numerator,
+                signature.denominator,
+                signature.clocks_per_click,
+                signature.number_of_32nd_notes,
+            ]),
+            Meta::KeySignature(signature) => {
+                data.extend_from_slice(&[signature.sharps_flats as u8, u8::from(signature.minor)])
+            }
+            Meta::SequencerSpecific(bytes) | Meta::Unknown { data: bytes, .. } => {
+                data.extend_from_slice(bytes)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn round_trip(kind: u8, data: &[u8]) -> Meta {
+        let meta = Meta::from_data(kind, data);
+        let mut written = std::vec::Vec::new();
+        meta.write_data(&mut written);
+        assert_eq!(meta.kind(), kind);
+        assert_eq!(&written[..], data);
+        meta
+    }
+
+    #[test]
+    fn tempo() {
+        assert_eq!(
+            round_trip(0x51, &[0x07, 0xA1, 0x20]),
+            Meta::Tempo(Tempo {
+                microseconds_per_quarter_note: u24::new(500_000)
+            }),
+        );
+    }
+
+    #[test]
+    fn tempo_bpm() {
+        assert_eq!(Tempo::from_bpm(120.0).bpm(), 120.0);
+        assert_eq!(
+            Tempo::from_bpm(140.0).microseconds_per_quarter_note,
+            u24::new(428_571)
+        );
+    }
+
+    #[test]
+    fn time_signature() {
+        assert_eq!(
+            round_trip(0x58, &[0x06, 0x03, 0x18, 0x08]),
+            Meta::TimeSignature(TimeSignature {
+                numerator: 6,
+                denominator: 3,
+                clocks_per_click: 24,
+                number_of_32nd_notes: 8,
+            }),
+        );
+    }
+
+    #[test]
+    fn key_signature() {
+        assert_eq!(
+            round_trip(0x59, &[0xFD, 0x01]),
+            Meta::KeySignature(KeySignature {
+                sharps_flats: -3,
+                minor: true,
+            }),
+        );
+    }
+
+    #[test]
+    fn text() {
+        let meta = round_trip(0x03, b"Piano");
+        assert_eq!(meta, Meta::Text(TextKind::TrackName, b"Piano".to_vec()));
+        assert_eq!(meta.text(), Some("Piano"));
+    }
+
+    #[test]
+    fn sequence_number_omitted() {
+        assert_eq!(round_trip(0x00, &[]), Meta::SequenceNumber(None));
+    }
+
+    #[test]
+    fn channel_prefix() {
+        assert_eq!(round_trip(0x20, &[0x0A]), Meta::ChannelPrefix(u4::new(0xA)));
+    }
+
+    #[test]
+    fn malformed_tempo_is_unknown() {
+        assert_eq!(
+            round_trip(0x51, &[0x07, 0xA1]),
+            Meta::Unknown {
+                kind: 0x51,
+                data: std::vec![0x07, 0xA1],
+            },
+        );
+    }
+}
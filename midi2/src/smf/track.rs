@@ -0,0 +1,529 @@
+use crate::{
+    channel_voice1::ChannelVoice1,
+    detail::vlq,
+    error::InvalidData,
+    smf::{meta, Meta},
+    sysex7::Sysex7,
+    traits::{ArrayRebufferInto, Data, Sysex},
+    ux::u7,
+};
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_ESCAPE: u8 = 0xF7;
+
+const ERR_NO_RUNNING_STATUS: &str = "Data byte found without a running status";
+const ERR_UNEXPECTED_STATUS: &str = "Unexpected status byte in track data";
+const ERR_INVALID_SYSEX_DATA: &str = "Sysex event contains a non 7bit byte";
+const ERR_TRUNCATED_EVENT: &str = "Track event is truncated";
+
+/// A track chunk: a sequence of timed events.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Track {
+    pub events: std::vec::Vec<TrackEvent>,
+}
+
+/// An event in a [Track] along with its delta time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackEvent {
+    /// Ticks since the previous event in the track.
+    pub delta: u32,
+    pub event: Event,
+    /// Whether the status byte of a channel event is omitted in the file.
+    ///
+    /// See the [module docs](crate::smf) for more info.
+    pub running_status: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Midi(ChannelVoice1<[u8; 3]>),
+    Sysex(SysexEvent),
+    /// An `F7` escape packet carrying arbitrary bytes.
+    Escape(std::vec::Vec<u8>),
+    Meta(Meta),
+}
+
+/// A system exclusive event.
+///
+/// See the [module docs](crate::smf) for more info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SysexEvent {
+    pub message: Sysex7<std::vec::Vec<u8>>,
+    /// The `F7` continuation packets which followed the initial `F0` packet.
+    pub continuations: std::vec::Vec<Continuation>,
+    /// Whether the terminating `F7` byte is present in the file.
+    pub terminated: bool,
+}
+
+/// A continuation packet of a divided sysex message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Continuation {
+    /// Ticks since the previous packet of the message.
+    pub delta: u32,
+    /// The number of payload bytes in the packet, not including the terminating `F7`.
+    pub size: usize,
+}
+
+impl TrackEvent {
+    pub fn new(delta: u32, event: Event) -> Self {
+        TrackEvent {
+            delta,
+            event,
+            running_status: false,
+        }
+    }
+}
+
+impl SysexEvent {
+    pub fn new(message: Sysex7<std::vec::Vec<u8>>) -> Self {
+        SysexEvent {
+            message,
+            continuations: std::vec::Vec::new(),
+            terminated: true,
+        }
+    }
+}
+
+impl From<Sysex7<std::vec::Vec<u8>>> for Event {
+    fn from(value: Sysex7<std::vec::Vec<u8>>) -> Self {
+        Event::Sysex(SysexEvent::new(value))
+    }
+}
+
+impl<B: crate::buffer::Bytes> From<ChannelVoice1<B>> for Event {
+    fn from(value: ChannelVoice1<B>) -> Self {
+        Event::Midi(value.array_rebuffer_into())
+    }
+}
+
+impl From<Meta> for Event {
+    fn from(value: Meta) -> Self {
+        Event::Meta(value)
+    }
+}
+
+impl Track {
+    pub(crate) fn read(data: &[u8]) -> Result<Self, InvalidData> {
+        let mut events: std::vec::Vec<TrackEvent> = std::vec::Vec::new();
+        let mut reader = Reader { data, position: 0 };
+        let mut running_status: Option<u8> = None;
+        let mut open_sysex: Option<(usize, std::vec::Vec<u8>)> = None;
+
+        while !reader.is_empty() {
+            let delta = reader.vlq()?;
+            let status = reader.peek()?;
+
+            if status == SYSEX_ESCAPE {
+                reader.advance(1);
+                let bytes = reader.sized_bytes()?;
+                running_status = None;
+                if let Some((index, mut payload)) = open_sysex.take() {
+                    let terminated = bytes.last() == Some(&SYSEX_ESCAPE);
+                    let continuation = &bytes[..bytes.len() - usize::from(terminated)];
+                    payload.extend_from_slice(continuation);
+                    let Event::Sysex(sysex) = &mut events[index].event else {
+                        unreachable!();
+                    };
+                    sysex.continuations.push(Continuation {
+                        delta,
+                        size: continuation.len(),
+                    });
+                    if terminated {
+                        sysex.terminated = true;
+                        sysex.message = sysex7_from_payload(&payload)?;
+                    } else {
+                        open_sysex = Some((index, payload));
+                    }
+                } else {
+                    events.push(TrackEvent::new(delta, Event::Escape(bytes.to_vec())));
+                }
+                continue;
+            }
+
+            if let Some((index, payload)) = open_sysex.take() {
+                // the divided message was interrupted
+                let Event::Sysex(sysex) = &mut events[index].event else {
+                    unreachable!();
+                };
+                sysex.message = sysex7_from_payload(&payload)?;
+            }
+
+            match status {
+                SYSEX_START => {
+                    reader.advance(1);
+                    let bytes = reader.sized_bytes()?;
+                    running_status = None;
+                    let terminated = bytes.last() == Some(&SYSEX_ESCAPE);
+                    let payload = &bytes[..bytes.len() - usize::from(terminated)];
+                    events.push(TrackEvent::new(
+                        delta,
+                        Event::Sysex(SysexEvent {
+                            message: sysex7_from_payload(payload)?,
+                            continuations: std::vec::Vec::new(),
+                            terminated,
+                        }),
+                    ));
+                    if !terminated {
+                        open_sysex = Some((events.len() - 1, payload.to_vec()));
+                    }
+                }
+                meta::STATUS => {
+                    reader.advance(1);
+                    let kind = reader.byte()?;
+                    let data = reader.sized_bytes()?;
+                    running_status = None;
+                    events.push(TrackEvent::new(
+                        delta,
+                        Event::Meta(Meta::from_data(kind, data)),
+                    ));
+                }
+                0x80..=0xEF => {
+                    reader.advance(1);
+                    running_status = Some(status);
+                    events.push(TrackEvent::new(
+                        delta,
+                        Event::Midi(reader.channel_event(status)?),
+                    ));
+                }
+                0x00..=0x7F => {
+                    let Some(status) = running_status else {
+                        return Err(InvalidData(ERR_NO_RUNNING_STATUS));
+                    };
+                    events.push(TrackEvent {
+                        delta,
+                        event: Event::Midi(reader.channel_event(status)?),
+                        running_status: true,
+                    });
+                }
+                _ => return Err(InvalidData(ERR_UNEXPECTED_STATUS)),
+            }
+        }
+
+        if let Some((index, payload)) = open_sysex.take() {
+            let Event::Sysex(sysex) = &mut events[index].event else {
+                unreachable!();
+            };
+            sysex.message = sysex7_from_payload(&payload)?;
+        }
+
+        Ok(Track { events })
+    }
+
+    pub(crate) fn write(&self, data: &mut std::vec::Vec<u8>) {
+        let mut running_status: Option<u8> = None;
+        for event in self.events.iter() {
+            vlq::write(data, event.delta);
+            match &event.event {
+                Event::Midi(message) => {
+                    let bytes = message.data();
+                    if event.running_status && running_status == Some(bytes[0]) {
+                        data.extend_from_slice(&bytes[1..]);
+                    } else {
+                        data.extend_from_slice(bytes);
+                    }
+                    running_status = Some(bytes[0]);
+                }
+                Event::Sysex(sysex) => {
+                    running_status = None;
+                    write_sysex(data, sysex);
+                }
+                Event::Escape(bytes) => {
+                    running_status = None;
+                    data.push(SYSEX_ESCAPE);
+                    vlq::write(data, bytes.len() as u32);
+                    data.extend_from_slice(bytes);
+                }
+                Event::Meta(meta) => {
+                    running_status = None;
+                    let mut meta_data = std::vec::Vec::new();
+                    meta.write_data(&mut meta_data);
+                    data.push(meta::STATUS);
+                    data.push(meta.kind());
+                    vlq::write(data, meta_data.len() as u32);
+                    data.extend_from_slice(&meta_data);
+                }
+            }
+        }
+        if !matches!(
+            self.events.last(),
+            Some(TrackEvent {
+                event: Event::Meta(Meta::EndOfTrack),
+                ..
+            })
+        ) {
+            data.extend_from_slice(&[0x00, meta::STATUS, 0x2F, 0x00]);
+        }
+    }
+}
+
+fn write_sysex(data: &mut std::vec::Vec<u8>, sysex: &SysexEvent) {
+    let payload: std::vec::Vec<u8> = sysex.message.payload().map(u8::from).collect();
+    let continued: usize = sysex.continuations.iter().map(|c| c.size).sum();
+    let first_size = payload.len().saturating_sub(continued);
+
+    let write_packet = |data: &mut std::vec::Vec<u8>, bytes: &[u8], last: bool| {
+        let terminator = last && sysex.terminated;
+        vlq::write(data, (bytes.len() + usize::from(terminator)) as u32);
+        data.extend_from_slice(bytes);
+        if terminator {
+            data.push(SYSEX_ESCAPE);
+        }
+    };
+
+    data.push(SYSEX_START);
+    write_packet(data, &payload[..first_size], sysex.continuations.is_empty());
+
+    let mut position = first_size;
+    for (i, continuation) in sysex.continuations.iter().enumerate() {
+        let end = if i + 1 == sysex.continuations.len() {
+            payload.len()
+        } else {
+            (position + continuation.size).min(payload.len())
+        };
+        vlq::write(data, continuation.delta);
+        data.push(SYSEX_ESCAPE);
+        write_packet(
+            data,
+            &payload[position..end],
+            i + 1 == sysex.continuations.len(),
+        );
+        position = end;
+    }
+}
+
+fn sysex7_from_payload(payload: &[u8]) -> Result<Sysex7<std::vec::Vec<u8>>, InvalidData> {
+    if payload.iter().any(|b| *b > 0x7F) {
+        return Err(InvalidData(ERR_INVALID_SYSEX_DATA));
+    }
+    let mut message = Sysex7::<std::vec::Vec<u8>>::new();
+    message.set_payload(payload.iter().cloned().map(u7::new));
+    Ok(message)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.position += n;
+    }
+
+    fn peek(&self) -> Result<u8, InvalidData> {
+        self.data
+            .get(self.position)
+            .cloned()
+            .ok_or(InvalidData(ERR_TRUNCATED_EVENT))
+    }
+
+    fn byte(&mut self) -> Result<u8, InvalidData> {
+        let byte = self.peek()?;
+        self.advance(1);
+        Ok(byte)
+    }
+
+    fn vlq(&mut self) -> Result<u32, InvalidData> {
+        let (value, size) = vlq::read(&self.data[self.position..])?;
+        self.advance(size);
+        Ok(value)
+    }
+
+    fn sized_bytes(&mut self) -> Result<&'a [u8], InvalidData> {
+        let size = self.vlq()? as usize;
+        let bytes = self
+            .position
+            .checked_add(size)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or(InvalidData(ERR_TRUNCATED_EVENT))?;
+        self.advance(size);
+        Ok(bytes)
+    }
+
+    fn channel_event(&mut self, status: u8) -> Result<ChannelVoice1<[u8; 3]>, InvalidData> {
+        let mut buffer = [status, 0x0, 0x0];
+        let size = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        for byte in buffer[1..=size].iter_mut() {
+            *byte = self.byte()?;
+            if *byte > 0x7F {
+                return Err(InvalidData(ERR_UNEXPECTED_STATUS));
+            }
+        }
+        let message: ChannelVoice1<&[u8]> = ChannelVoice1::try_from(&buffer[..=size])?;
+        Ok(message.array_rebuffer_into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{channel_voice1::NoteOn, traits::Channeled, ux::u4};
+    use pretty_assertions::assert_eq;
+
+    fn round_trip(data: &[u8]) -> Track {
+        let track = Track::read(data).unwrap();
+        let mut written = std::vec::Vec::new();
+        track.write(&mut written);
+        assert_eq!(&written[..], data);
+        track
+    }
+
+    #[test]
+    fn running_status() {
+        let track = round_trip(&[
+            0x00, 0x92, 0x3C, 0x40, 0x10, 0x3E, 0x40, 0x10, 0x3C, 0x00, 0x00, 0xFF, 0x2F, 0x00,
+        ]);
+        assert_eq!(track.events.len(), 4);
+        assert!(!track.events[0].running_status);
+        assert!(track.events[1].running_status);
+        assert!(track.events[2].running_status);
+        let Event::Midi(ChannelVoice1::NoteOn(note_on)) = &track.events[2].event else {
+            panic!();
+        };
+        assert_eq!(note_on.velocity(), u7::new(0));
+        assert_eq!(note_on.channel(), u4::new(0x2));
+    }
+
+    #[test]
+    fn running_status_cancelled_by_meta() {
+        assert_eq!(
+            Track::read(&[0x00, 0x92, 0x3C, 0x40, 0x00, 0xFF, 0x01, 0x00, 0x00, 0x3C, 0x00]),
+            Err(InvalidData(ERR_NO_RUNNING_STATUS)),
+        );
+    }
+
+    #[test]
+    fn program_change() {
+        let track = round_trip(&[0x00, 0xC5, 0x07, 0x00, 0x08, 0x00, 0xFF, 0x2F, 0x00]);
+        assert!(track.events[1].running_status);
+        assert_eq!(track.events[1].event, {
+            let mut message = crate::channel_voice1::ProgramChange::<[u8; 3]>::new();
+            message.set_channel(u4::new(0x5));
+            message.set_program(u7::new(0x08));
+            Event::from(ChannelVoice1::from(message))
+        });
+    }
+
+    #[test]
+    fn running_status_not_applicable() {
+        let mut track = Track::default();
+        let mut note_on = NoteOn::<[u8; 3]>::new();
+        note_on.set_note_number(u7::new(0x3C));
+        track
+            .events
+            .push(TrackEvent::new(0, Meta::EndOfTrack.into()));
+        track.events.insert(
+            0,
+            TrackEvent {
+                delta: 0,
+                event: ChannelVoice1::from(note_on).into(),
+                running_status: true,
+            },
+        );
+        let mut written = std::vec::Vec::new();
+        track.write(&mut written);
+        assert_eq!(
+            written,
+            std::vec![0x00, 0x90, 0x3C, 0x00, 0x00, 0xFF, 0x2F, 0x00]
+        );
+    }
+
+    #[test]
+    fn end_of_track_is_appended() {
+        let mut written = std::vec::Vec::new();
+        Track::default().write(&mut written);
+        assert_eq!(written, std::vec![0x00, 0xFF, 0x2F, 0x00]);
+    }
+
+    #[test]
+    fn sysex() {
+        let track = round_trip(&[
+            0x00, 0xF0, 0x05, 0x7E, 0x00, 0x09, 0x01, 0xF7, 0x00, 0xFF, 0x2F, 0x00,
+        ]);
+        let Event::Sysex(sysex) = &track.events[0].event else {
+            panic!();
+        };
+        assert_eq!(sysex.message.data(), &[0xF0, 0x7E, 0x00, 0x09, 0x01, 0xF7]);
+        assert!(sysex.terminated);
+        assert!(sysex.continuations.is_empty());
+    }
+
+    #[test]
+    fn divided_sysex() {
+        let track = round_trip(&[
+            0x00, 0xF0, 0x03, 0x43, 0x12, 0x00, //
+            0x81, 0x48, 0xF7, 0x02, 0x43, 0x12, //
+            0x64, 0xF7, 0x03, 0x00, 0x43, 0xF7, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ]);
+        assert_eq!(track.events.len(), 2);
+        let Event::Sysex(sysex) = &track.events[0].event else {
+            panic!();
+        };
+        assert_eq!(
+            sysex.message.data(),
+            &[0xF0, 0x43, 0x12, 0x00, 0x43, 0x12, 0x00, 0x43, 0xF7]
+        );
+        assert_eq!(
+            sysex.continuations,
+            std::vec![
+                Continuation {
+                    delta: 200,
+                    size: 2
+                },
+                Continuation {
+                    delta: 100,
+                    size: 2
+                },
+            ]
+        );
+        assert!(sysex.terminated);
+    }
+
+    #[test]
+    fn interrupted_sysex() {
+        let track = round_trip(&[
+            0x00, 0xF0, 0x02, 0x43, 0x12, //
+            0x00, 0xFF, 0x01, 0x00, //
+            0x00, 0xF7, 0x01, 0xF8, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ]);
+        let Event::Sysex(sysex) = &track.events[0].event else {
+            panic!();
+        };
+        assert!(!sysex.terminated);
+        assert_eq!(track.events[2].event, Event::Escape(std::vec![0xF8]));
+    }
+
+    #[test]
+    fn escape() {
+        let track = round_trip(&[0x00, 0xF7, 0x03, 0xF2, 0x00, 0x10, 0x00, 0xFF, 0x2F, 0x00]);
+        assert_eq!(
+            track.events[0].event,
+            Event::Escape(std::vec![0xF2, 0x00, 0x10])
+        );
+    }
+
+    #[test]
+    fn truncated_event() {
+        assert_eq!(
+            Track::read(&[0x00, 0x90, 0x3C]),
+            Err(InvalidData(ERR_TRUNCATED_EVENT)),
+        );
+    }
+
+    #[test]
+    fn non_7bit_sysex_data() {
+        assert_eq!(
+            Track::read(&[0x00, 0xF0, 0x02, 0x80, 0xF7]),
+            Err(InvalidData(ERR_INVALID_SYSEX_DATA)),
+        );
+    }
+}
@@ -2,6 +2,8 @@
 
 pub(crate) const UMP_MESSAGE_TYPE: u8 = 0x1;
 
+mod midi_clock;
+mod mtc;
 mod packet;
 mod song_position_pointer;
 mod song_select;
@@ -20,7 +22,7 @@ mod tune_request {
         Via(system_common::SystemCommon),
         FixedSize,
         MinSizeUmp(1),
-        MinSizeBytes(2)
+        MinSizeBytes(1)
     )]
     struct TuneRequest {
         #[property(common_properties::UmpMessageTypeProperty<UMP_MESSAGE_TYPE>)]
@@ -178,6 +180,10 @@ mod reset {
 
 pub use active_sensing::*;
 pub use cont::*;
+pub use midi_clock::{
+    ClockFollower, ClockGenerator, Transport, CLOCKS_PER_QUARTER_NOTE, CLOCKS_PER_SIXTEENTH_NOTE,
+};
+pub use mtc::{Direction, FrameRate, MtcEvent, MtcGenerator, MtcReceiver, SmpteTime, LOCK_TIMEOUT};
 pub use packet::Packet;
 pub use reset::*;
 pub use song_position_pointer::*;
@@ -200,6 +206,9 @@ pub use tune_request::*;
     midi2_proc::RebufferFrom,
     midi2_proc::RebufferFromArray,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -365,6 +374,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn tune_request_bytes_data() {
+        use crate::Data;
+        assert_eq!(
+            TuneRequest::try_from(&[0xF6_u8][..]).unwrap().data(),
+            &[0xF6_u8][..]
+        );
+    }
+
     #[test]
     fn from_byte_data() {
         assert_eq!(
@@ -0,0 +1,579 @@
+use crate::{
+    error::BufferOverflow,
+    system_common::{Continue, SongPositionPointer, Start, Stop, SystemCommon, TimingClock},
+    ux::u14,
+};
+
+/// The number of timing clock messages per quarter note.
+pub const CLOCKS_PER_QUARTER_NOTE: u64 = 24;
+
+/// The number of timing clock messages per sixteenth note,
+/// the unit of the song position pointer.
+pub const CLOCKS_PER_SIXTEENTH_NOTE: u64 = 6;
+
+// weight given to each new clock interval by the tempo estimate
+const SMOOTHING: f64 = 0.125;
+
+// a clock interval this many times longer than the estimate restarts the estimate
+const GAP_FACTOR: f64 = 4.0;
+
+const MICROSECONDS_PER_MINUTE: f64 = 60_000_000.0;
+
+/// The state of a MIDI clock transport.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Transport {
+    #[default]
+    Stopped,
+    Playing,
+}
+
+/// Follows a MIDI clock from its timing clock and transport messages.
+///
+/// The tempo is estimated from the interval between timing clock messages
+/// with an exponential smoothing filter, which evens out the jitter of the
+/// incoming clock. Timing clocks are used for the estimate whether or not the transport
+/// is playing. A long gap between clocks restarts the estimate.
+///
+/// Following a Start or Continue message, playback begins with the next timing clock,
+/// which marks the song position at which the transport started.
+/// Each further timing clock advances the song position.
+///
+/// Times are given in microseconds of the receiver's local monotonic clock.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::system_common::{ClockFollower, Transport};
+///
+/// let mut follower = ClockFollower::new();
+/// follower.receive(&system_common::Start::<[u8; 3]>::new().into(), 0);
+/// for i in 0..=24 {
+///     // 120 bpm
+///     follower.receive(&system_common::TimingClock::<[u8; 3]>::new().into(), i * 20_833);
+/// }
+///
+/// assert_eq!(follower.transport(), Transport::Playing);
+/// assert_eq!(follower.song_position(), 4);
+/// assert_eq!(follower.tempo().map(f64::round), Some(120.0));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClockFollower {
+    transport: Transport,
+    waiting_for_clock: bool,
+    clocks: u64,
+    last_clock: Option<u64>,
+    interval: Option<f64>,
+}
+
+impl ClockFollower {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a system message which arrived at the given local time.
+    ///
+    /// Messages other than timing clocks, transport messages
+    /// and song position pointers are ignored.
+    pub fn receive<B: crate::buffer::Buffer>(&mut self, message: &SystemCommon<B>, now: u64) {
+        match message {
+            SystemCommon::TimingClock(_) => self.clock(now),
+            SystemCommon::Start(_) => {
+                self.clocks = 0;
+                self.transport = Transport::Playing;
+                self.waiting_for_clock = true;
+            }
+            SystemCommon::Continue(_) => {
+                self.transport = Transport::Playing;
+                self.waiting_for_clock = true;
+            }
+            SystemCommon::Stop(_) => {
+                self.transport = Transport::Stopped;
+                self.waiting_for_clock = false;
+            }
+            SystemCommon::SongPositionPointer(m) => {
+                self.clocks = u64::from(u16::from(m.position())) * CLOCKS_PER_SIXTEENTH_NOTE;
+            }
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self, now: u64) {
+        if let Some(last) = self.last_clock {
+            let interval = now.saturating_sub(last) as f64;
+            self.interval = match self.interval {
+                Some(estimate) if interval <= estimate * GAP_FACTOR => {
+                    Some(estimate + (interval - estimate) * SMOOTHING)
+                }
+                Some(_) => None,
+                None => Some(interval),
+            };
+        }
+        self.last_clock = Some(now);
+
+        if self.transport == Transport::Playing {
+            if self.waiting_for_clock {
+                self.waiting_for_clock = false;
+            } else {
+                self.clocks += 1;
+            }
+        }
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// The estimated tempo in beats per minute.
+    ///
+    /// Returns `None` until two timing clocks have been received.
+    pub fn tempo(&self) -> Option<f64> {
+        let interval = self.interval?;
+        if interval <= 0.0 {
+            return None;
+        }
+        Some(MICROSECONDS_PER_MINUTE / (interval * CLOCKS_PER_QUARTER_NOTE as f64))
+    }
+
+    /// The number of timing clocks since the start of the song.
+    pub fn clocks(&self) -> u64 {
+        self.clocks
+    }
+
+    /// The song position in sixteenth notes.
+    pub fn song_position(&self) -> u64 {
+        self.clocks / CLOCKS_PER_SIXTEENTH_NOTE
+    }
+
+    /// The position within the current beat at the given local time,
+    /// from zero at the start of the beat up to one at its end.
+    ///
+    /// While playing, the phase is interpolated between timing clocks
+    /// using the estimated tempo, and holds at the position of the next clock
+    /// should it arrive late.
+    pub fn beat_phase(&self, now: u64) -> f64 {
+        let mut clocks = (self.clocks % CLOCKS_PER_QUARTER_NOTE) as f64;
+        if let (Transport::Playing, false, Some(last), Some(interval)) = (
+            self.transport,
+            self.waiting_for_clock,
+            self.last_clock,
+            self.interval,
+        ) {
+            if interval > 0.0 {
+                let fraction = now.saturating_sub(last) as f64 / interval;
+                clocks += fraction.min(1.0);
+            }
+        }
+        clocks / CLOCKS_PER_QUARTER_NOTE as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pending {
+    Start,
+    Stop,
+    Continue,
+    SongPositionPointer(u14),
+}
+
+/// Generates MIDI clock messages for a transport at a given tempo.
+///
+/// Timing clocks are sent at 24 per quarter note while the transport is playing.
+/// Transport and song position pointer messages are queued by the transport methods
+/// and sent ahead of any due timing clock.
+/// Up to three messages are queued. A transport method which finds no room
+/// for its messages fails with [BufferOverflow] and leaves the generator as it was,
+/// so a queued message is never lost.
+/// Times are given in microseconds of the sender's local monotonic clock,
+/// and [poll_bytes](ClockGenerator::poll_bytes) or [poll_ump](ClockGenerator::poll_ump)
+/// should be called until they return `None` at least as often as clocks are due.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::system_common::{ClockGenerator, SystemCommon};
+///
+/// let mut generator = ClockGenerator::new(120.0);
+/// generator.start(0).unwrap();
+///
+/// assert!(matches!(generator.poll_bytes(0), Some(SystemCommon::Start(_))));
+/// assert!(matches!(generator.poll_bytes(0), Some(SystemCommon::TimingClock(_))));
+/// // the next clock is due after a 24th of a beat
+/// assert_eq!(generator.poll_bytes(20_832), None);
+/// assert!(matches!(generator.poll_bytes(20_834), Some(SystemCommon::TimingClock(_))));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClockGenerator {
+    tempo: f64,
+    transport: Transport,
+    // clocks sent since the start of the song
+    clocks: u64,
+    // the time of the first clock since starting or changing tempo
+    // and the number of clocks sent since then
+    anchor: u64,
+    clocks_since_anchor: u64,
+    pending: [Option<Pending>; 3],
+}
+
+impl ClockGenerator {
+    /// Create a stopped generator with the given tempo in beats per minute.
+    pub fn new(tempo: f64) -> Self {
+        ClockGenerator {
+            tempo,
+            transport: Transport::Stopped,
+            clocks: 0,
+            anchor: 0,
+            clocks_since_anchor: 0,
+            pending: [None; 3],
+        }
+    }
+
+    pub fn tempo(&self) -> f64 {
+        self.tempo
+    }
+
+    /// Change the tempo in beats per minute, from the next due clock.
+    pub fn set_tempo(&mut self, tempo: f64, now: u64) {
+        if self.transport == Transport::Playing {
+            self.anchor = self.next_clock_time().max(now);
+            self.clocks_since_anchor = 0;
+        }
+        self.tempo = tempo;
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// The song position in sixteenth notes of the next clock.
+    pub fn song_position(&self) -> u64 {
+        self.clocks / CLOCKS_PER_SIXTEENTH_NOTE
+    }
+
+    /// Start playing from the beginning of the song.
+    pub fn start(&mut self, now: u64) -> Result<(), BufferOverflow> {
+        self.reserve(1)?;
+        self.clocks = 0;
+        self.play(Pending::Start, now);
+        Ok(())
+    }
+
+    /// Resume playing from the current song position.
+    pub fn resume(&mut self, now: u64) -> Result<(), BufferOverflow> {
+        self.reserve(1)?;
+        self.play(Pending::Continue, now);
+        Ok(())
+    }
+
+    /// Stop playing, keeping the current song position.
+    pub fn stop(&mut self) -> Result<(), BufferOverflow> {
+        if self.transport == Transport::Playing {
+            self.reserve(1)?;
+            self.transport = Transport::Stopped;
+            self.push(Pending::Stop);
+        }
+        Ok(())
+    }
+
+    /// Move to the given song position in sixteenth notes.
+    ///
+    /// Song position pointer messages should only be sent while stopped,
+    /// so a playing transport is stopped first.
+    /// Use [resume](ClockGenerator::resume) to continue playing from the new position.
+    pub fn locate(&mut self, song_position: u14) -> Result<(), BufferOverflow> {
+        self.reserve(1 + usize::from(self.transport == Transport::Playing))?;
+        self.stop()?;
+        self.clocks = u64::from(u16::from(song_position)) * CLOCKS_PER_SIXTEENTH_NOTE;
+        self.push(Pending::SongPositionPointer(song_position));
+        Ok(())
+    }
+
+    /// The next message as bytes, if one is due at the given time.
+    pub fn poll_bytes(&mut self, now: u64) -> Option<SystemCommon<[u8; 3]>> {
+        if let Some(message) = self.next_pending() {
+            return Some(match message {
+                Pending::Start => Start::<[u8; 3]>::new().into(),
+                Pending::Stop => Stop::<[u8; 3]>::new().into(),
+                Pending::Continue => Continue::<[u8; 3]>::new().into(),
+                Pending::SongPositionPointer(position) => {
+                    let mut message = SongPositionPointer::<[u8; 3]>::new();
+                    message.set_position(position);
+                    message.into()
+                }
+            });
+        }
+        self.clock(now)
+            .then(|| TimingClock::<[u8; 3]>::new().into())
+    }
+
+    /// The next message as ump, if one is due at the given time.
+    pub fn poll_ump(&mut self, now: u64) -> Option<SystemCommon<[u32; 4]>> {
+        if let Some(message) = self.next_pending() {
+            return Some(match message {
+                Pending::Start => Start::<[u32; 4]>::new().into(),
+                Pending::Stop => Stop::<[u32; 4]>::new().into(),
+                Pending::Continue => Continue::<[u32; 4]>::new().into(),
+                Pending::SongPositionPointer(position) => {
+                    let mut message = SongPositionPointer::<[u32; 4]>::new();
+                    message.set_position(position);
+                    message.into()
+                }
+            });
+        }
+        self.clock(now)
+            .then(|| TimingClock::<[u32; 4]>::new().into())
+    }
+
+    fn play(&mut self, message: Pending, now: u64) {
+        self.transport = Transport::Playing;
+        self.anchor = now;
+        self.clocks_since_anchor = 0;
+        self.push(message);
+    }
+
+    fn reserve(&self, count: usize) -> Result<(), BufferOverflow> {
+        if self.pending.iter().filter(|p| p.is_none()).count() < count {
+            return Err(BufferOverflow);
+        }
+        Ok(())
+    }
+
+    // room is reserved before the transport changes
+    fn push(&mut self, message: Pending) {
+        let slot = self
+            .pending
+            .iter_mut()
+            .find(|p| p.is_none())
+            .expect("Room for the message is reserved");
+        *slot = Some(message);
+    }
+
+    fn next_pending(&mut self) -> Option<Pending> {
+        let message = self.pending[0].take()?;
+        self.pending.rotate_left(1);
+        Some(message)
+    }
+
+    fn interval(&self) -> f64 {
+        MICROSECONDS_PER_MINUTE / (self.tempo * CLOCKS_PER_QUARTER_NOTE as f64)
+    }
+
+    fn next_clock_time(&self) -> u64 {
+        // rounded to the nearest microsecond without f64::round, which needs std
+        self.anchor + (self.clocks_since_anchor as f64 * self.interval() + 0.5) as u64
+    }
+
+    // whether a timing clock is due, advancing the clock if so
+    fn clock(&mut self, now: u64) -> bool {
+        if self.transport != Transport::Playing
+            || self.tempo.is_nan()
+            || self.tempo <= 0.0
+            || now < self.next_clock_time()
+        {
+            return false;
+        }
+        if self.clocks_since_anchor > 0 {
+            self.clocks += 1;
+        }
+        self.clocks_since_anchor += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+
+    fn clock() -> SystemCommon<[u8; 3]> {
+        TimingClock::<[u8; 3]>::new().into()
+    }
+
+    fn follow_clocks(follower: &mut ClockFollower, start: u64, count: u64, interval: u64) -> u64 {
+        for i in 0..count {
+            follower.receive(&clock(), start + i * interval);
+        }
+        start + count * interval
+    }
+
+    #[test]
+    fn tempo_estimate() {
+        let mut follower = ClockFollower::new();
+        assert_eq!(follower.tempo(), None);
+        follow_clocks(&mut follower, 0, 10, 25_000);
+        assert_eq!(follower.tempo(), Some(100.0));
+    }
+
+    #[test]
+    fn tempo_estimate_smooths_jitter() {
+        let mut follower = ClockFollower::new();
+        let jitter = [0, 2_000, 0, 1_000, 3_000, 0];
+        for i in 0..200_u64 {
+            follower.receive(&clock(), i * 25_000 + jitter[i as usize % 6]);
+        }
+        let tempo = follower.tempo().unwrap();
+        assert!((99.0..101.0).contains(&tempo), "tempo: {tempo}");
+    }
+
+    #[test]
+    fn tempo_estimate_restarts_after_gap() {
+        let mut follower = ClockFollower::new();
+        let end = follow_clocks(&mut follower, 0, 10, 25_000);
+        follow_clocks(&mut follower, end + 1_000_000, 10, 12_500);
+        assert_eq!(follower.tempo(), Some(200.0));
+    }
+
+    #[test]
+    fn song_position_after_start() {
+        let mut follower = ClockFollower::new();
+        follower.receive(&Start::<[u8; 3]>::new().into(), 0);
+        assert_eq!(follower.transport(), Transport::Playing);
+        follow_clocks(&mut follower, 0, 13, 25_000);
+        assert_eq!(follower.clocks(), 12);
+        assert_eq!(follower.song_position(), 2);
+    }
+
+    #[test]
+    fn clocks_while_stopped_do_not_advance() {
+        let mut follower = ClockFollower::new();
+        follow_clocks(&mut follower, 0, 13, 25_000);
+        assert_eq!(follower.clocks(), 0);
+    }
+
+    #[test]
+    fn stop_and_continue() {
+        let mut follower = ClockFollower::new();
+        follower.receive(&Start::<[u8; 3]>::new().into(), 0);
+        let end = follow_clocks(&mut follower, 0, 7, 25_000);
+        follower.receive(&Stop::<[u8; 3]>::new().into(), end);
+        let end = follow_clocks(&mut follower, end, 7, 25_000);
+        assert_eq!(follower.transport(), Transport::Stopped);
+        assert_eq!(follower.clocks(), 6);
+        follower.receive(&Continue::<[u8; 3]>::new().into(), end);
+        follow_clocks(&mut follower, end, 7, 25_000);
+        assert_eq!(follower.clocks(), 12);
+    }
+
+    #[test]
+    fn song_position_pointer() {
+        let mut follower = ClockFollower::new();
+        let mut message = SongPositionPointer::<[u8; 3]>::new();
+        message.set_position(u14::new(0x10));
+        follower.receive(&message.into(), 0);
+        assert_eq!(follower.song_position(), 0x10);
+        assert_eq!(follower.clocks(), 0x60);
+    }
+
+    #[test]
+    fn beat_phase() {
+        let mut follower = ClockFollower::new();
+        follower.receive(&Start::<[u8; 3]>::new().into(), 0);
+        follow_clocks(&mut follower, 0, 7, 25_000);
+        assert_eq!(follower.beat_phase(150_000), 0.25);
+        assert_eq!(follower.beat_phase(162_500), 6.5 / 24.0);
+        assert_eq!(follower.beat_phase(1_000_000), 7.0 / 24.0);
+    }
+
+    #[test]
+    fn beat_phase_while_stopped() {
+        let mut follower = ClockFollower::new();
+        follower.receive(&Start::<[u8; 3]>::new().into(), 0);
+        let end = follow_clocks(&mut follower, 0, 7, 25_000);
+        follower.receive(&Stop::<[u8; 3]>::new().into(), end);
+        assert_eq!(follower.beat_phase(end + 100_000), 0.25);
+    }
+
+    #[test]
+    fn generate_clocks() {
+        let mut generator = ClockGenerator::new(100.0);
+        generator.start(1_000).unwrap();
+        let mut times = std::vec::Vec::new();
+        for now in (1_000..101_000).step_by(100) {
+            while let Some(message) = generator.poll_bytes(now) {
+                times.push((now, message.data()[0]));
+            }
+        }
+        assert_eq!(
+            times,
+            std::vec![
+                (1_000, 0xFA),
+                (1_000, 0xF8),
+                (26_000, 0xF8),
+                (51_000, 0xF8),
+                (76_000, 0xF8),
+            ]
+        );
+        assert_eq!(generator.song_position(), 0);
+    }
+
+    #[test]
+    fn nothing_while_stopped() {
+        let mut generator = ClockGenerator::new(100.0);
+        assert_eq!(generator.poll_bytes(1_000_000), None);
+    }
+
+    #[test]
+    fn locate_while_playing() {
+        let mut generator = ClockGenerator::new(100.0);
+        generator.start(0).unwrap();
+        while generator.poll_ump(0).is_some() {}
+        generator.locate(u14::new(0x10)).unwrap();
+        assert_eq!(generator.transport(), Transport::Stopped);
+        assert_eq!(generator.poll_ump(0).unwrap().data(), &[0x10FC_0000]);
+        assert_eq!(generator.poll_ump(0).unwrap().data(), &[0x10F2_1000]);
+        assert_eq!(generator.poll_ump(1_000_000), None);
+        generator.resume(1_000_000).unwrap();
+        assert_eq!(
+            generator.poll_ump(1_000_000).unwrap().data(),
+            &[0x10FB_0000]
+        );
+        assert_eq!(
+            generator.poll_ump(1_000_000).unwrap().data(),
+            &[0x10F8_0000]
+        );
+        assert_eq!(generator.song_position(), 0x10);
+    }
+
+    #[test]
+    fn change_tempo() {
+        let mut generator = ClockGenerator::new(100.0);
+        generator.start(0).unwrap();
+        while generator.poll_bytes(0).is_some() {}
+        generator.set_tempo(200.0, 10_000);
+        assert_eq!(generator.poll_bytes(24_999), None);
+        assert!(generator.poll_bytes(25_000).is_some());
+        assert_eq!(generator.poll_bytes(37_499), None);
+        assert!(generator.poll_bytes(37_500).is_some());
+    }
+
+    #[test]
+    fn generator_and_follower() {
+        let mut generator = ClockGenerator::new(90.0);
+        let mut follower = ClockFollower::new();
+        generator.start(0).unwrap();
+        for now in (0..2_000_000).step_by(50) {
+            while let Some(message) = generator.poll_ump(now) {
+                follower.receive(&message, now);
+            }
+        }
+        assert_eq!(follower.tempo().map(f64::round), Some(90.0));
+        assert_eq!(follower.song_position(), generator.song_position());
+        assert_eq!(follower.transport(), Transport::Playing);
+    }
+
+    #[test]
+    fn full_queue_is_reported() {
+        let mut generator = ClockGenerator::new(100.0);
+        generator.start(0).unwrap();
+        generator.stop().unwrap();
+        generator.resume(0).unwrap();
+        assert_eq!(generator.stop(), Err(BufferOverflow));
+        assert_eq!(generator.locate(u14::new(0x10)), Err(BufferOverflow));
+        assert_eq!(generator.transport(), Transport::Playing);
+
+        // the queued messages are all sent
+        assert_eq!(generator.poll_ump(0).unwrap().data(), &[0x10FA_0000]);
+        assert_eq!(generator.poll_ump(0).unwrap().data(), &[0x10FC_0000]);
+        assert_eq!(generator.poll_ump(0).unwrap().data(), &[0x10FB_0000]);
+        generator.stop().unwrap();
+        assert_eq!(generator.poll_ump(0).unwrap().data(), &[0x10FC_0000]);
+    }
+}
@@ -0,0 +1,750 @@
+use crate::{system_common::TimeCode, ux::u7};
+
+/// Time without quarter frame messages after which an [MtcReceiver] loses lock,
+/// in microseconds.
+pub const LOCK_TIMEOUT: u64 = 100_000;
+
+/// The number of frames by which the time of a completed sequence of quarter frames
+/// lags the current time.
+const SEQUENCE_FRAMES: i64 = 2;
+
+/// The frame rate of a [SmpteTime].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FrameRate {
+    #[default]
+    Fps24,
+    Fps25,
+    /// 29.97 frames per second with drop frame numbering.
+    Fps2997DropFrame,
+    Fps30,
+}
+
+impl FrameRate {
+    /// The number of frames labelled in each second.
+    pub fn frames_per_second(&self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps2997DropFrame | FrameRate::Fps30 => 30,
+        }
+    }
+
+    // the frame duration as a fraction of a second
+    fn frame_duration(&self) -> (u64, u64) {
+        match self {
+            FrameRate::Fps24 => (1, 24),
+            FrameRate::Fps25 => (1, 25),
+            FrameRate::Fps2997DropFrame => (1001, 30_000),
+            FrameRate::Fps30 => (1, 30),
+        }
+    }
+
+    fn from_code(code: u8) -> Self {
+        match code & 0b11 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps2997DropFrame,
+            _ => FrameRate::Fps30,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 0,
+            FrameRate::Fps25 => 1,
+            FrameRate::Fps2997DropFrame => 2,
+            FrameRate::Fps30 => 3,
+        }
+    }
+}
+
+/// An SMPTE time code position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SmpteTime {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub frame_rate: FrameRate,
+}
+
+const FRAMES_PER_DAY_30: i64 = 24 * 60 * 60 * 30;
+const DROP_FRAMES_PER_10_MINUTES: i64 = 10 * 60 * 30 - 9 * 2;
+const DROP_FRAMES_PER_MINUTE: i64 = 60 * 30 - 2;
+
+impl SmpteTime {
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8, frame_rate: FrameRate) -> Self {
+        SmpteTime {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            frame_rate,
+        }
+    }
+
+    /// The number of frames since midnight.
+    ///
+    /// Drop frame time codes skip the frame labels 0 and 1
+    /// at the start of each minute, except for every tenth minute.
+    pub fn frame_count(&self) -> u32 {
+        let fps = u32::from(self.frame_rate.frames_per_second());
+        let minutes = 60 * u32::from(self.hours) + u32::from(self.minutes);
+        let count = (60 * minutes + u32::from(self.seconds)) * fps + u32::from(self.frames);
+        match self.frame_rate {
+            FrameRate::Fps2997DropFrame => count - 2 * (minutes - minutes / 10),
+            _ => count,
+        }
+    }
+
+    /// The time code of the given number of frames since midnight,
+    /// wrapping around after 24 hours.
+    pub fn from_frame_count(frame_count: u32, frame_rate: FrameRate) -> Self {
+        Self::from_signed_frame_count(i64::from(frame_count), frame_rate)
+    }
+
+    /// This time code moved by the given number of frames,
+    /// wrapping around at midnight.
+    pub fn add_frames(&self, frames: i64) -> Self {
+        Self::from_signed_frame_count(i64::from(self.frame_count()) + frames, self.frame_rate)
+    }
+
+    fn from_signed_frame_count(frame_count: i64, frame_rate: FrameRate) -> Self {
+        let fps = i64::from(frame_rate.frames_per_second());
+        let mut count = match frame_rate {
+            FrameRate::Fps2997DropFrame => {
+                let count = frame_count.rem_euclid(FRAMES_PER_DAY_30 - 24 * 6 * 9 * 2);
+                let tens = count / DROP_FRAMES_PER_10_MINUTES;
+                let remainder = count % DROP_FRAMES_PER_10_MINUTES;
+                let skipped = if remainder > 1 {
+                    18 * tens + 2 * ((remainder - 2) / DROP_FRAMES_PER_MINUTE)
+                } else {
+                    18 * tens
+                };
+                count + skipped
+            }
+            _ => frame_count.rem_euclid(FRAMES_PER_DAY_30 / 30 * fps),
+        };
+        let frames = count % fps;
+        count /= fps;
+        let seconds = count % 60;
+        count /= 60;
+        let minutes = count % 60;
+        let hours = count / 60;
+        SmpteTime::new(
+            hours as u8,
+            minutes as u8,
+            seconds as u8,
+            frames as u8,
+            frame_rate,
+        )
+    }
+
+    // the hours byte shared by the quarter frame and full frame encodings
+    fn rate_and_hours(&self) -> u8 {
+        (self.frame_rate.code() << 5) | (self.hours & 0x1F)
+    }
+
+    fn quarter_frame_value(&self, piece: u8) -> u8 {
+        match piece {
+            0 => self.frames & 0xF,
+            1 => (self.frames >> 4) & 0x1,
+            2 => self.seconds & 0xF,
+            3 => (self.seconds >> 4) & 0x3,
+            4 => self.minutes & 0xF,
+            5 => (self.minutes >> 4) & 0x3,
+            6 => self.hours & 0xF,
+            _ => (self.rate_and_hours() >> 4) & 0x7,
+        }
+    }
+
+    fn from_quarter_frame_values(values: &[u8; 8]) -> Self {
+        SmpteTime::new(
+            values[6] | ((values[7] & 0x1) << 4),
+            values[4] | ((values[5] & 0x3) << 4),
+            values[2] | ((values[3] & 0x3) << 4),
+            values[0] | ((values[1] & 0x1) << 4),
+            FrameRate::from_code(values[7] >> 1),
+        )
+    }
+
+    #[cfg(feature = "sysex7")]
+    fn full_frame_payload(&self, device_id: u7) -> [u7; 8] {
+        [
+            u7::new(0x7F),
+            device_id,
+            u7::new(0x01),
+            u7::new(0x01),
+            u7::new(self.rate_and_hours() & 0x7F),
+            u7::new(self.minutes & 0x7F),
+            u7::new(self.seconds & 0x7F),
+            u7::new(self.frames & 0x7F),
+        ]
+    }
+}
+
+/// The direction in which a time code source is travelling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+/// A change of state reported by an [MtcReceiver].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MtcEvent {
+    /// A full sequence of quarter frames was received after being unlocked.
+    Locked(SmpteTime),
+    /// A further sequence of quarter frames was received while locked.
+    Position(SmpteTime),
+    /// The quarter frames were interrupted or stopped arriving.
+    LockLost,
+    /// A full frame message located the receiver.
+    Located(SmpteTime),
+}
+
+/// Assembles MIDI Time Code quarter frame messages into [SmpteTime] positions.
+///
+/// Each quarter frame message carries one of eight pieces of the time code.
+/// The receiver locks once it has received all eight pieces in consecutive order,
+/// and then reports a new position after each complete sequence.
+/// The direction of travel is detected from the order of the pieces.
+///
+/// The time encoded in a sequence is that of the frame at which its first
+/// quarter frame was sent, so by the time the sequence completes,
+/// the source has moved on by two frames. The reported positions account for this.
+///
+/// Lock is lost when a quarter frame arrives out of order,
+/// or when no quarter frame arrives for longer than [LOCK_TIMEOUT].
+/// Times are given in microseconds of the receiver's local monotonic clock.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::system_common::{FrameRate, MtcEvent, MtcReceiver, SmpteTime};
+///
+/// let mut receiver = MtcReceiver::new();
+/// let mut event = None;
+/// // 01:02:03:04 at 25 fps
+/// for (i, data) in [0x04, 0x10, 0x23, 0x30, 0x42, 0x50, 0x61, 0x72].into_iter().enumerate() {
+///     let mut message = system_common::TimeCode::<[u8; 3]>::new();
+///     message.set_time_code(u7::new(data));
+///     event = receiver.receive(&message, i as u64 * 10_000);
+/// }
+///
+/// let expected = SmpteTime::new(1, 2, 3, 6, FrameRate::Fps25);
+/// assert_eq!(event, Some(MtcEvent::Locked(expected)));
+/// assert_eq!(receiver.position(), Some(expected));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MtcReceiver {
+    values: [u8; 8],
+    last_piece: Option<u8>,
+    // number of consecutive pieces received in order
+    run: usize,
+    direction: Option<Direction>,
+    locked: bool,
+    position: Option<SmpteTime>,
+    last_time: u64,
+}
+
+impl MtcReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// The direction of travel of the most recent quarter frames.
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    /// The most recently assembled or located position.
+    pub fn position(&self) -> Option<SmpteTime> {
+        self.position
+    }
+
+    /// Register a quarter frame message which arrived at the given local time.
+    pub fn receive<B: crate::buffer::Buffer>(
+        &mut self,
+        message: &TimeCode<B>,
+        now: u64,
+    ) -> Option<MtcEvent> {
+        self.receive_quarter_frame(message.time_code(), now)
+    }
+
+    /// Register the data of a quarter frame message which arrived at the given local time.
+    pub fn receive_quarter_frame(&mut self, time_code: u7, now: u64) -> Option<MtcEvent> {
+        let time_code = u8::from(time_code);
+        let piece = time_code >> 4;
+        self.values[usize::from(piece)] = time_code & 0xF;
+        self.last_time = now;
+
+        let direction = match self.last_piece {
+            Some(last) if piece == (last + 1) % 8 => Some(Direction::Forward),
+            Some(last) if piece == (last + 7) % 8 => Some(Direction::Reverse),
+            _ => None,
+        };
+        self.last_piece = Some(piece);
+
+        if direction.is_none() || (self.run > 1 && direction != self.direction) {
+            self.run = 1;
+            self.direction = direction;
+            return self.lose_lock();
+        }
+
+        self.run += 1;
+        self.direction = direction;
+
+        let (last_piece, frames) = match direction {
+            Some(Direction::Forward) => (7, SEQUENCE_FRAMES),
+            _ => (0, -SEQUENCE_FRAMES),
+        };
+        if piece != last_piece || self.run < 8 {
+            return None;
+        }
+
+        let position = SmpteTime::from_quarter_frame_values(&self.values).add_frames(frames);
+        self.position = Some(position);
+        if self.locked {
+            Some(MtcEvent::Position(position))
+        } else {
+            self.locked = true;
+            Some(MtcEvent::Locked(position))
+        }
+    }
+
+    /// Check for loss of lock when no quarter frames have arrived.
+    ///
+    /// Should be called regularly while locked.
+    pub fn poll(&mut self, now: u64) -> Option<MtcEvent> {
+        if now.saturating_sub(self.last_time) > LOCK_TIMEOUT {
+            self.run = 0;
+            self.last_piece = None;
+            self.lose_lock()
+        } else {
+            None
+        }
+    }
+
+    /// Register an MTC Full Frame universal system exclusive message.
+    ///
+    /// Returns `None` when the message is not a full frame message.
+    #[cfg(feature = "sysex7")]
+    pub fn receive_full_frame<B: crate::buffer::Buffer>(
+        &mut self,
+        message: &crate::sysex7::Sysex7<B>,
+    ) -> Option<MtcEvent> {
+        use crate::traits::Sysex;
+
+        let mut payload = [0x0_u8; 8];
+        let mut len = 0;
+        for byte in message.payload() {
+            if len == payload.len() {
+                return None;
+            }
+            payload[len] = u8::from(byte);
+            len += 1;
+        }
+        let [0x7F, _, 0x01, 0x01, hours, minutes, seconds, frames] = payload else {
+            return None;
+        };
+        if len != payload.len() {
+            return None;
+        }
+
+        let position = SmpteTime::new(
+            hours & 0x1F,
+            minutes,
+            seconds,
+            frames,
+            FrameRate::from_code(hours >> 5),
+        );
+        self.position = Some(position);
+        self.run = 0;
+        self.last_piece = None;
+        self.locked = false;
+        Some(MtcEvent::Located(position))
+    }
+
+    fn lose_lock(&mut self) -> Option<MtcEvent> {
+        if self.locked {
+            self.locked = false;
+            Some(MtcEvent::LockLost)
+        } else {
+            None
+        }
+    }
+}
+
+/// Generates MIDI Time Code quarter frame messages for a running transport.
+///
+/// Quarter frames are sent four times per frame, so that a full
+/// sequence of eight quarter frames spans two frames.
+/// Times are given in microseconds of the sender's local monotonic clock,
+/// and [poll_bytes](MtcGenerator::poll_bytes) or [poll_ump](MtcGenerator::poll_ump)
+/// should be called at least as often as quarter frames are due.
+///
+/// To locate a receiver without running the transport,
+/// send a Full Frame message.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::system_common::{FrameRate, MtcGenerator, SmpteTime};
+///
+/// let mut generator = MtcGenerator::new(SmpteTime::new(1, 2, 3, 4, FrameRate::Fps25));
+/// generator.start(0);
+///
+/// assert_eq!(generator.poll_bytes(0).unwrap().time_code(), u7::new(0x04));
+/// // the next quarter frame is due 10ms later
+/// assert_eq!(generator.poll_bytes(9_999), None);
+/// assert_eq!(generator.poll_bytes(10_000).unwrap().time_code(), u7::new(0x10));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MtcGenerator {
+    position: SmpteTime,
+    running: Option<Running>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Running {
+    start_time: u64,
+    quarter_frames: u64,
+}
+
+impl MtcGenerator {
+    pub fn new(position: SmpteTime) -> Self {
+        MtcGenerator {
+            position,
+            running: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// The position at which the transport was started or located.
+    pub fn position(&self) -> SmpteTime {
+        self.position
+    }
+
+    /// Move the transport to the given position and stop.
+    pub fn locate(&mut self, position: SmpteTime) {
+        self.position = position;
+        self.running = None;
+    }
+
+    /// Start the transport at the given time.
+    ///
+    /// The first quarter frame is due immediately.
+    pub fn start(&mut self, now: u64) {
+        self.running = Some(Running {
+            start_time: now,
+            quarter_frames: 0,
+        });
+    }
+
+    /// Stop the transport, keeping the position of the last frame reached.
+    pub fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            let frames = running.quarter_frames / 4;
+            self.position = self.position.add_frames(frames as i64);
+        }
+    }
+
+    /// The next quarter frame message as bytes, if one is due at the given time.
+    pub fn poll_bytes(&mut self, now: u64) -> Option<TimeCode<[u8; 3]>> {
+        let time_code = self.next_quarter_frame(now)?;
+        let mut message = TimeCode::<[u8; 3]>::new();
+        message.set_time_code(time_code);
+        Some(message)
+    }
+
+    /// The next quarter frame message as ump, if one is due at the given time.
+    pub fn poll_ump(&mut self, now: u64) -> Option<TimeCode<[u32; 4]>> {
+        let time_code = self.next_quarter_frame(now)?;
+        let mut message = TimeCode::<[u32; 4]>::new();
+        message.set_time_code(time_code);
+        Some(message)
+    }
+
+    /// An MTC Full Frame universal system exclusive message for the current position,
+    /// as bytes.
+    #[cfg(feature = "sysex7")]
+    pub fn full_frame_bytes(&self, device_id: u7) -> crate::sysex7::Sysex7<[u8; 10]> {
+        use crate::traits::Sysex;
+        let mut message = crate::sysex7::Sysex7::<[u8; 10]>::new();
+        message
+            .try_set_payload(self.position.full_frame_payload(device_id).into_iter())
+            .expect("Buffer is large enough");
+        message
+    }
+
+    /// An MTC Full Frame universal system exclusive message for the current position,
+    /// as ump.
+    #[cfg(feature = "sysex7")]
+    pub fn full_frame_ump(&self, device_id: u7) -> crate::sysex7::Sysex7<[u32; 4]> {
+        use crate::traits::Sysex;
+        let mut message = crate::sysex7::Sysex7::<[u32; 4]>::new();
+        message
+            .try_set_payload(self.position.full_frame_payload(device_id).into_iter())
+            .expect("Buffer is large enough");
+        message
+    }
+
+    fn next_quarter_frame(&mut self, now: u64) -> Option<u7> {
+        let running = self.running.as_mut()?;
+        let (numerator, denominator) = self.position.frame_rate.frame_duration();
+        let due =
+            running.start_time + running.quarter_frames * 1_000_000 * numerator / (4 * denominator);
+        if now < due {
+            return None;
+        }
+
+        let piece = (running.quarter_frames % 8) as u8;
+        let sequence = running.quarter_frames / 8;
+        running.quarter_frames += 1;
+
+        let time = self.position.add_frames(sequence as i64 * SEQUENCE_FRAMES);
+        Some(u7::new((piece << 4) | time.quarter_frame_value(piece)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn receive_all(receiver: &mut MtcReceiver, data: &[u8]) -> std::vec::Vec<MtcEvent> {
+        data.iter()
+            .enumerate()
+            .filter_map(|(i, d)| receiver.receive_quarter_frame(u7::new(*d), i as u64 * 10_000))
+            .collect()
+    }
+
+    const SEQUENCE: [u8; 8] = [0x04, 0x10, 0x23, 0x30, 0x42, 0x50, 0x61, 0x72];
+
+    #[test]
+    fn frame_count() {
+        assert_eq!(
+            SmpteTime::new(1, 2, 3, 4, FrameRate::Fps25).frame_count(),
+            ((3600 + 2 * 60) + 3) * 25 + 4
+        );
+    }
+
+    #[test]
+    fn drop_frame_count() {
+        let time = SmpteTime::new(0, 1, 0, 2, FrameRate::Fps2997DropFrame);
+        assert_eq!(time.frame_count(), 1800);
+        assert_eq!(
+            time.add_frames(-1),
+            SmpteTime::new(0, 0, 59, 29, FrameRate::Fps2997DropFrame)
+        );
+        let time = SmpteTime::new(0, 10, 0, 0, FrameRate::Fps2997DropFrame);
+        assert_eq!(time.frame_count(), 17982);
+        assert_eq!(
+            time.add_frames(-1),
+            SmpteTime::new(0, 9, 59, 29, FrameRate::Fps2997DropFrame)
+        );
+    }
+
+    #[test]
+    fn drop_frame_round_trip() {
+        for count in (0..200_000).step_by(7) {
+            let time = SmpteTime::from_frame_count(count, FrameRate::Fps2997DropFrame);
+            assert_eq!(time.frame_count(), count);
+        }
+    }
+
+    #[test]
+    fn wrap_at_midnight() {
+        assert_eq!(
+            SmpteTime::new(23, 59, 59, 23, FrameRate::Fps24).add_frames(1),
+            SmpteTime::new(0, 0, 0, 0, FrameRate::Fps24)
+        );
+        assert_eq!(
+            SmpteTime::new(0, 0, 0, 0, FrameRate::Fps2997DropFrame).add_frames(-1),
+            SmpteTime::new(23, 59, 59, 29, FrameRate::Fps2997DropFrame)
+        );
+    }
+
+    #[test]
+    fn lock_forward() {
+        let mut receiver = MtcReceiver::new();
+        let events = receive_all(&mut receiver, &SEQUENCE);
+        assert_eq!(
+            events,
+            std::vec![MtcEvent::Locked(SmpteTime::new(
+                1,
+                2,
+                3,
+                6,
+                FrameRate::Fps25
+            ))]
+        );
+        assert_eq!(receiver.direction(), Some(Direction::Forward));
+        assert!(receiver.is_locked());
+    }
+
+    #[test]
+    fn lock_reverse() {
+        let mut receiver = MtcReceiver::new();
+        let mut reversed = SEQUENCE;
+        reversed.reverse();
+        let events = receive_all(&mut receiver, &reversed);
+        assert_eq!(
+            events,
+            std::vec![MtcEvent::Locked(SmpteTime::new(
+                1,
+                2,
+                3,
+                2,
+                FrameRate::Fps25
+            ))]
+        );
+        assert_eq!(receiver.direction(), Some(Direction::Reverse));
+    }
+
+    #[test]
+    fn lock_requires_all_pieces() {
+        let mut receiver = MtcReceiver::new();
+        assert_eq!(receive_all(&mut receiver, &SEQUENCE[3..]), std::vec![]);
+        assert!(!receiver.is_locked());
+    }
+
+    #[test]
+    fn position_updates() {
+        let mut receiver = MtcReceiver::new();
+        let mut data = std::vec::Vec::from(SEQUENCE);
+        data.extend_from_slice(&[0x06, 0x10, 0x23, 0x30, 0x42, 0x50, 0x61, 0x72]);
+        let events = receive_all(&mut receiver, &data);
+        assert_eq!(
+            events[1],
+            MtcEvent::Position(SmpteTime::new(1, 2, 3, 8, FrameRate::Fps25))
+        );
+    }
+
+    #[test]
+    fn lose_lock_on_out_of_order_piece() {
+        let mut receiver = MtcReceiver::new();
+        let mut data = std::vec::Vec::from(SEQUENCE);
+        data.extend_from_slice(&[0x06, 0x23]);
+        let events = receive_all(&mut receiver, &data);
+        assert_eq!(events.last(), Some(&MtcEvent::LockLost));
+        assert!(!receiver.is_locked());
+    }
+
+    #[test]
+    fn lose_lock_on_timeout() {
+        let mut receiver = MtcReceiver::new();
+        receive_all(&mut receiver, &SEQUENCE);
+        assert_eq!(receiver.poll(70_000 + LOCK_TIMEOUT), None);
+        assert_eq!(
+            receiver.poll(70_001 + LOCK_TIMEOUT),
+            Some(MtcEvent::LockLost)
+        );
+        assert_eq!(receiver.poll(80_001 + LOCK_TIMEOUT), None);
+    }
+
+    #[test]
+    fn generate_sequence() {
+        let mut generator = MtcGenerator::new(SmpteTime::new(1, 2, 3, 4, FrameRate::Fps25));
+        generator.start(1_000);
+        let mut data = std::vec::Vec::new();
+        for i in 0..8 {
+            data.push(u8::from(
+                generator.poll_ump(1_000 + i * 10_000).unwrap().time_code(),
+            ));
+        }
+        assert_eq!(data, std::vec::Vec::from(SEQUENCE));
+    }
+
+    #[test]
+    fn generate_drop_frame_timing() {
+        let mut generator =
+            MtcGenerator::new(SmpteTime::new(0, 0, 0, 0, FrameRate::Fps2997DropFrame));
+        generator.start(0);
+        let mut count = 0;
+        for now in (0..1_001_000).step_by(100) {
+            if generator.poll_bytes(now).is_some() {
+                count += 1;
+            }
+        }
+        // 4 quarter frames per frame over 30 frames of 1001/30000 s
+        assert_eq!(count, 120);
+    }
+
+    #[test]
+    fn generator_and_receiver() {
+        let start = SmpteTime::new(10, 0, 59, 28, FrameRate::Fps2997DropFrame);
+        let mut generator = MtcGenerator::new(start);
+        let mut receiver = MtcReceiver::new();
+        generator.start(0);
+        let mut events = std::vec::Vec::new();
+        for now in (0..200_000).step_by(1_000) {
+            if let Some(message) = generator.poll_ump(now) {
+                events.extend(receiver.receive(&message, now));
+            }
+        }
+        assert_eq!(
+            &events[..2],
+            &[
+                MtcEvent::Locked(SmpteTime::new(10, 1, 0, 2, FrameRate::Fps2997DropFrame)),
+                MtcEvent::Position(SmpteTime::new(10, 1, 0, 4, FrameRate::Fps2997DropFrame)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_keeps_position() {
+        let mut generator = MtcGenerator::new(SmpteTime::new(0, 0, 0, 0, FrameRate::Fps24));
+        generator.start(0);
+        for i in 0..9 {
+            assert!(generator.poll_bytes(i * 10_417).is_some());
+        }
+        generator.stop();
+        assert!(!generator.is_running());
+        assert_eq!(
+            generator.position(),
+            SmpteTime::new(0, 0, 0, 2, FrameRate::Fps24)
+        );
+    }
+
+    #[cfg(feature = "sysex7")]
+    #[test]
+    fn full_frame_bytes() {
+        use crate::traits::Data;
+        let generator = MtcGenerator::new(SmpteTime::new(1, 2, 3, 4, FrameRate::Fps2997DropFrame));
+        assert_eq!(
+            generator.full_frame_bytes(u7::new(0x7F)).data(),
+            &[0xF0, 0x7F, 0x7F, 0x01, 0x01, 0x41, 0x02, 0x03, 0x04, 0xF7]
+        );
+    }
+
+    #[cfg(feature = "sysex7")]
+    #[test]
+    fn full_frame_round_trip() {
+        let position = SmpteTime::new(23, 2, 3, 4, FrameRate::Fps30);
+        let generator = MtcGenerator::new(position);
+        let mut receiver = MtcReceiver::new();
+        assert_eq!(
+            receiver.receive_full_frame(&generator.full_frame_ump(u7::new(0x10))),
+            Some(MtcEvent::Located(position))
+        );
+        assert_eq!(receiver.position(), Some(position));
+    }
+
+    #[cfg(feature = "sysex7")]
+    #[test]
+    fn not_full_frame() {
+        use crate::traits::Sysex;
+        let mut message = crate::sysex7::Sysex7::<[u8; 10]>::new();
+        message
+            .try_set_payload([0x7E, 0x7F, 0x06, 0x01].into_iter().map(u7::new))
+            .unwrap();
+        assert_eq!(MtcReceiver::new().receive_full_frame(&message), None);
+    }
+}
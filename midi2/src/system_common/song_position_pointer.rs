@@ -12,7 +12,7 @@ pub const STATUS: u8 = 0xF2;
     Via(system_common::SystemCommon),
     FixedSize,
     MinSizeUmp(1),
-    MinSizeBytes(2)
+    MinSizeBytes(3)
 )]
 struct SongPositionPointer {
     #[property(common_properties::UmpMessageTypeProperty<UMP_MESSAGE_TYPE>)]
@@ -90,4 +90,20 @@ mod tests {
         assert_eq!(&*packets.next().unwrap(), &[0x1AF2_7D6C][..]);
         assert_eq!(packets.next(), None);
     }
+
+    #[test]
+    fn bytes_data() {
+        use crate::Data;
+        assert_eq!(
+            SongPositionPointer::try_from(&[0xF2_u8, 0x7D, 0x6C][..])
+                .unwrap()
+                .data(),
+            &[0xF2_u8, 0x7D, 0x6C][..],
+        );
+    }
+
+    #[test]
+    fn missing_byte() {
+        assert!(SongPositionPointer::try_from(&[0xF2_u8, 0x7D][..]).is_err());
+    }
 }
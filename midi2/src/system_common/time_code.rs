@@ -12,7 +12,7 @@ pub const STATUS: u8 = 0xF1;
     Via(system_common::SystemCommon),
     FixedSize,
     MinSizeUmp(1),
-    MinSizeBytes(3)
+    MinSizeBytes(2)
 )]
 struct TimeCode {
     #[property(common_properties::UmpMessageTypeProperty<UMP_MESSAGE_TYPE>)]
@@ -67,4 +67,13 @@ mod tests {
             u7::new(0x5F),
         );
     }
+
+    #[test]
+    fn bytes_data() {
+        use crate::Data;
+        assert_eq!(
+            TimeCode::try_from(&[0xF1_u8, 0x5F][..]).unwrap().data(),
+            &[0xF1_u8, 0x5F][..],
+        );
+    }
 }
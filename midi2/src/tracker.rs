@@ -0,0 +1,23 @@
+//! Follow the state of a stream of channel voice messages.
+//!
+//! A [NoteTracker] keeps the set of sounding notes, so that hanging notes
+//! can be silenced when a connection drops.
+//! A [ChannelState] keeps the controllers, pitch bend, pressure and program
+//! of each channel, so that a receiving device can be brought into the
+//! same state after a transport locate or when it is plugged in.
+//!
+//! Both the MIDI 1.0 and MIDI 2.0 channel voice messages are understood,
+//! whether they arrive as ump or, for MIDI 1.0, as bytes.
+
+mod channels;
+mod notes;
+
+pub use channels::{ChannelControllers, ChannelState};
+pub use notes::{ActiveNote, NoteTracker};
+
+/// The protocol of the channel voice message which set a piece of state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Midi1,
+    Midi2,
+}
@@ -0,0 +1,573 @@
+use crate::{
+    buffer::{Bytes, Ump},
+    channel_voice1::ChannelVoice1,
+    channel_voice2::{self as cv2, ChannelVoice2},
+    detail::translate::{Downgrader, Upgrader},
+    message::{BytesMessage, UmpMessage},
+    tracker::Protocol,
+    traits::{ArrayRebufferInto, Channeled, Data, Grouped, TryIntoBytes, TryIntoUmp},
+    ux::{u14, u4, u7},
+};
+use std::{collections::BTreeMap, vec::Vec};
+
+const MODULATION: u8 = 1;
+const EXPRESSION: u8 = 11;
+const SUSTAIN: u8 = 64;
+const DATA_ENTRY_MSB: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+const SOFT: u8 = 67;
+const DATA_INCREMENT: u8 = 96;
+const DATA_DECREMENT: u8 = 97;
+const RPN_MSB: u8 = 101;
+const ALL_SOUND_OFF: u8 = 120;
+const RESET_ALL_CONTROLLERS: u8 = 121;
+
+const PITCH_BEND_CENTER: u32 = 0x8000_0000;
+// the least significant bit of 14 bit MIDI 1.0 parameter data
+const DATA_STEP: i32 = 1 << 18;
+
+/// The controller state of a single channel.
+///
+/// Values are held at MIDI 2.0 resolution. Values received as MIDI 1.0
+/// are scaled up with the default translation of the UMP specification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelControllers {
+    pub group: u4,
+    pub channel: u4,
+    /// Control change values by controller number.
+    /// Channel mode messages are not included.
+    pub controllers: BTreeMap<u7, u32>,
+    /// Registered controller (RPN) values by bank and index.
+    pub registered: BTreeMap<(u7, u7), u32>,
+    /// Assignable controller (NRPN) values by bank and index.
+    pub assignable: BTreeMap<(u7, u7), u32>,
+    pub pitch_bend: Option<u32>,
+    pub channel_pressure: Option<u32>,
+    pub program: Option<u7>,
+    /// The bank of the most recent banked program change.
+    pub bank: Option<u14>,
+}
+
+impl ChannelControllers {
+    fn new(group: u4, channel: u4) -> Self {
+        ChannelControllers {
+            group,
+            channel,
+            controllers: BTreeMap::new(),
+            registered: BTreeMap::new(),
+            assignable: BTreeMap::new(),
+            pitch_bend: None,
+            channel_pressure: None,
+            program: None,
+            bank: None,
+        }
+    }
+
+    /// The MIDI 2.0 messages which put a receiving channel into this state.
+    ///
+    /// One message is given for each known value: the program change first,
+    /// followed by the registered, assignable and control change controllers,
+    /// the pitch bend and the channel pressure.
+    pub fn messages(&self) -> Vec<ChannelVoice2<[u32; 4]>> {
+        let mut messages: Vec<ChannelVoice2<[u32; 4]>> = Vec::new();
+
+        if let Some(program) = self.program {
+            let mut message = cv2::ProgramChange::<[u32; 4]>::new();
+            message.set_program(program);
+            message.set_bank(self.bank);
+            messages.push(message.into());
+        }
+        for (&(bank, index), &data) in self.registered.iter() {
+            let mut message = cv2::RegisteredController::<[u32; 4]>::new();
+            message.set_bank(bank);
+            message.set_index(index);
+            message.set_controller_data(data);
+            messages.push(message.into());
+        }
+        for (&(bank, index), &data) in self.assignable.iter() {
+            let mut message = cv2::AssignableController::<[u32; 4]>::new();
+            message.set_bank(bank);
+            message.set_index(index);
+            message.set_controller_data(data);
+            messages.push(message.into());
+        }
+        for (&control, &data) in self.controllers.iter() {
+            let mut message = cv2::ControlChange::<[u32; 4]>::new();
+            message.set_control(control);
+            message.set_control_change_data(data);
+            messages.push(message.into());
+        }
+        if let Some(data) = self.pitch_bend {
+            let mut message = cv2::ChannelPitchBend::<[u32; 4]>::new();
+            message.set_pitch_bend_data(data);
+            messages.push(message.into());
+        }
+        if let Some(data) = self.channel_pressure {
+            let mut message = cv2::ChannelPressure::<[u32; 4]>::new();
+            message.set_channel_pressure_data(data);
+            messages.push(message.into());
+        }
+
+        for message in messages.iter_mut() {
+            message.set_group(self.group);
+            message.set_channel(self.channel);
+        }
+        messages
+    }
+
+    fn is_empty(&self) -> bool {
+        self.controllers.is_empty()
+            && self.registered.is_empty()
+            && self.assignable.is_empty()
+            && self.pitch_bend.is_none()
+            && self.channel_pressure.is_none()
+            && self.program.is_none()
+    }
+
+    fn control_change(&mut self, control: u7, data: u32) {
+        match u8::from(control) {
+            RESET_ALL_CONTROLLERS => self.reset_all_controllers(),
+            // the remaining channel mode messages carry no controller state
+            ALL_SOUND_OFF.. => {}
+            // parameter selection and data entry address no controller of their own,
+            // and replaying them would change whichever parameter the receiver has selected
+            DATA_ENTRY_MSB | DATA_ENTRY_LSB | DATA_INCREMENT..=RPN_MSB => {}
+            _ => {
+                self.controllers.insert(control, data);
+            }
+        }
+    }
+
+    // follows the recommended practice RP-015, which leaves the volume,
+    // pan, sound and effect controllers, and the program untouched
+    fn reset_all_controllers(&mut self) {
+        self.controllers.insert(u7::new(MODULATION), 0);
+        self.controllers.insert(u7::new(EXPRESSION), u32::MAX);
+        for pedal in SUSTAIN..=SOFT {
+            self.controllers.insert(u7::new(pedal), 0);
+        }
+        self.pitch_bend = Some(PITCH_BEND_CENTER);
+        self.channel_pressure = Some(0);
+    }
+}
+
+/// Keeps track of the controller state of each group and channel.
+///
+/// Messages are fed in with [handle_ump](Self::handle_ump) or
+/// [handle_bytes](Self::handle_bytes).
+/// MIDI 1.0 messages are translated to MIDI 2.0 before they are applied,
+/// so that bank select and (N)RPN data entry sequences end up as
+/// the program bank and the registered and assignable controllers.
+/// Relative controllers, and the MIDI 1.0 data increment and decrement
+/// of the selected parameter, are applied to the last known value of their
+/// controller, and are ignored while that value is unknown.
+///
+/// A clone of the tracker is a snapshot of the state.
+/// [chase](Self::chase) gives the messages which put a receiving device
+/// into the state, as is needed after a transport locate or
+/// when a device is plugged in.
+///
+/// ```rust
+/// use midi2::{prelude::*, tracker::{ChannelState, Protocol}};
+///
+/// let mut state = ChannelState::new();
+///
+/// // volume, as MIDI 1.0 on group 2 and channel 1
+/// let volume = UmpMessage::try_from(&[0x22B0_0764][..]).unwrap();
+/// state.handle_ump(&volume);
+///
+/// let chase = state.chase(Protocol::Midi1);
+/// assert_eq!(chase[0].data(), &[0x22B0_0764]);
+///
+/// let chase = state.chase(Protocol::Midi2);
+/// assert_eq!(chase[0].data(), &[0x42B0_0700, 0xC924_9249]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChannelState {
+    channels: BTreeMap<(u4, u4), ChannelControllers>,
+    upgraders: [Upgrader; 16],
+}
+
+impl ChannelState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow a ump message. Messages other than channel voice messages are ignored.
+    pub fn handle_ump<B: Ump>(&mut self, message: &UmpMessage<B>) {
+        match message {
+            UmpMessage::ChannelVoice1(m) => {
+                let group = m.group();
+                let Ok(m) = ChannelVoice1::try_from(m.data()) else {
+                    return;
+                };
+                if let Ok(bytes) = m.try_into_bytes() {
+                    self.handle_midi1(group, bytes);
+                }
+            }
+            UmpMessage::ChannelVoice2(m) => self.handle_channel_voice2(m),
+            _ => {}
+        }
+    }
+
+    /// Follow a MIDI 1.0 message received on the given group.
+    /// Messages other than channel voice messages are ignored.
+    pub fn handle_bytes<B: Bytes>(&mut self, group: u4, message: &BytesMessage<B>) {
+        // irrefutable when the other bytes message features are disabled
+        #[allow(irrefutable_let_patterns)]
+        if let BytesMessage::ChannelVoice1(m) = message {
+            if let Ok(m) = ChannelVoice1::try_from(m.data()) {
+                self.handle_midi1(group, m.array_rebuffer_into());
+            }
+        }
+    }
+
+    /// Follow a MIDI 2.0 channel voice message.
+    pub fn handle_channel_voice2<B: Ump>(&mut self, message: &ChannelVoice2<B>) {
+        let group = message.group();
+        let channel = message.channel();
+        let state = self
+            .channels
+            .entry((group, channel))
+            .or_insert_with(|| ChannelControllers::new(group, channel));
+        match message {
+            ChannelVoice2::ControlChange(m) => {
+                state.control_change(m.control(), m.control_change_data())
+            }
+            ChannelVoice2::RegisteredController(m) => {
+                state
+                    .registered
+                    .insert((m.bank(), m.index()), m.controller_data());
+            }
+            ChannelVoice2::AssignableController(m) => {
+                state
+                    .assignable
+                    .insert((m.bank(), m.index()), m.controller_data());
+            }
+            ChannelVoice2::RelativeRegisteredController(m) => {
+                if let Some(data) = state.registered.get_mut(&(m.bank(), m.index())) {
+                    *data = data.saturating_add_signed(m.controller_data() as i32);
+                }
+            }
+            ChannelVoice2::RelativeAssignableController(m) => {
+                if let Some(data) = state.assignable.get_mut(&(m.bank(), m.index())) {
+                    *data = data.saturating_add_signed(m.controller_data() as i32);
+                }
+            }
+            ChannelVoice2::ChannelPitchBend(m) => state.pitch_bend = Some(m.pitch_bend_data()),
+            ChannelVoice2::ChannelPressure(m) => {
+                state.channel_pressure = Some(m.channel_pressure_data())
+            }
+            ChannelVoice2::ProgramChange(m) => {
+                state.program = Some(m.program());
+                if let Some(bank) = m.bank() {
+                    state.bank = Some(bank);
+                }
+            }
+            _ => {}
+        }
+        if state.is_empty() {
+            self.channels.remove(&(group, channel));
+        }
+    }
+
+    /// The state of the group and channel, if any of it is known.
+    pub fn channel(&self, group: u4, channel: u4) -> Option<&ChannelControllers> {
+        self.channels.get(&(group, channel))
+    }
+
+    /// The channels with known state, ordered by group and channel.
+    pub fn channels(&self) -> impl Iterator<Item = &ChannelControllers> {
+        self.channels.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// The messages which put a receiving device into this state,
+    /// ordered by group and channel.
+    ///
+    /// With [Protocol::Midi1] the messages are translated to MIDI 1.0,
+    /// and each controller is selected only once.
+    pub fn chase(&self, protocol: Protocol) -> Vec<UmpMessage<[u32; 4]>> {
+        let mut messages = Vec::new();
+        // the parameter selection of a channel is separate on each group
+        let mut downgraders: [Downgrader; 16] = Default::default();
+        let mut downgraded = Vec::new();
+
+        for state in self.channels.values() {
+            for message in state.messages() {
+                match protocol {
+                    Protocol::Midi1 => {
+                        downgraders[usize::from(u8::from(state.group))]
+                            .downgrade(&message, &mut downgraded);
+                        messages.extend(downgraded.drain(..).map(|m| {
+                            let mut m: ChannelVoice1<[u32; 4]> =
+                                m.try_into_ump().expect("Buffer is large enough");
+                            m.set_group(state.group);
+                            UmpMessage::from(m)
+                        }));
+                    }
+                    Protocol::Midi2 => messages.push(message.into()),
+                }
+            }
+        }
+        messages
+    }
+
+    /// Forget the state of every channel.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn handle_midi1(&mut self, group: u4, message: ChannelVoice1<[u8; 3]>) {
+        let upgrader = &mut self.upgraders[usize::from(u8::from(group))];
+        let channel = message.channel();
+        let upgraded = match &message {
+            // data increment and decrement step the selected parameter by one
+            ChannelVoice1::ControlChange(m) if u8::from(m.control()) == DATA_INCREMENT => {
+                upgrader.relative_parameter(channel, DATA_STEP)
+            }
+            ChannelVoice1::ControlChange(m) if u8::from(m.control()) == DATA_DECREMENT => {
+                upgrader.relative_parameter(channel, -DATA_STEP)
+            }
+            _ => upgrader.upgrade(message),
+        };
+        if let Some(mut upgraded) = upgraded {
+            upgraded.set_group(group);
+            self.handle_channel_voice2(&upgraded);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn handle(state: &mut ChannelState, data: &[u32]) {
+        state.handle_ump(&UmpMessage::try_from(data).unwrap());
+    }
+
+    fn chase(state: &ChannelState, protocol: Protocol) -> Vec<Vec<u32>> {
+        state
+            .chase(protocol)
+            .iter()
+            .map(|m| m.data().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn control_change() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B3_0740]);
+        handle(&mut state, &[0x20B3_0750]);
+        handle(&mut state, &[0x40B3_0A00, 0x1234_5678]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x3)).unwrap();
+        assert_eq!(channel.controllers[&u7::new(0x07)], 0xA082_0820);
+        assert_eq!(channel.controllers[&u7::new(0x0A)], 0x1234_5678);
+    }
+
+    #[test]
+    fn registered_parameter_from_midi1() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_6500]);
+        handle(&mut state, &[0x20B0_6400]);
+        handle(&mut state, &[0x20B0_0602]);
+        handle(&mut state, &[0x20B0_2600]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x0)).unwrap();
+        assert_eq!(
+            channel.registered[&(u7::new(0x0), u7::new(0x0))],
+            0x0400_0000
+        );
+        // the selection and data entry controllers are not kept
+        assert!(channel.controllers.is_empty());
+    }
+
+    #[test]
+    fn relative_controllers() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x4021_0102, 0x0000_1000]);
+        handle(&mut state, &[0x4041_0102, 0xFFFF_FC00]);
+        handle(&mut state, &[0x4051_0304, 0x0000_0010]);
+        handle(&mut state, &[0x4041_0102, 0x0000_0800]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x1)).unwrap();
+        assert_eq!(
+            channel.registered[&(u7::new(0x1), u7::new(0x2))],
+            0x0000_1400
+        );
+        // relative to an unknown value
+        assert!(channel.assignable.is_empty());
+    }
+
+    #[test]
+    fn relative_controller_saturates() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x4031_0102, 0xFFFF_FF00]);
+        handle(&mut state, &[0x4051_0102, 0x0000_1000]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x1)).unwrap();
+        assert_eq!(channel.assignable[&(u7::new(0x1), u7::new(0x2))], u32::MAX);
+    }
+
+    #[test]
+    fn banked_program_change() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_0001]);
+        handle(&mut state, &[0x20B0_2002]);
+        handle(&mut state, &[0x20C0_0500]);
+        // a program change without a bank stays in the current bank
+        handle(&mut state, &[0x40C0_0000, 0x0600_0000]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x0)).unwrap();
+        assert_eq!(channel.program, Some(u7::new(0x06)));
+        assert_eq!(channel.bank, Some(u14::new(0x0082)));
+    }
+
+    #[test]
+    fn pitch_bend_and_pressure() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20E2_0040]);
+        handle(&mut state, &[0x40D2_0000, 0x1234_5678]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x2)).unwrap();
+        assert_eq!(channel.pitch_bend, Some(0x8000_0000));
+        assert_eq!(channel.channel_pressure, Some(0x1234_5678));
+    }
+
+    #[test]
+    fn reset_all_controllers() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_0140]);
+        handle(&mut state, &[0x20B0_0764]);
+        handle(&mut state, &[0x20B0_407F]);
+        handle(&mut state, &[0x20E0_0000]);
+        handle(&mut state, &[0x20B0_7900]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x0)).unwrap();
+        assert_eq!(channel.controllers[&u7::new(MODULATION)], 0);
+        assert_eq!(channel.controllers[&u7::new(EXPRESSION)], u32::MAX);
+        assert_eq!(channel.controllers[&u7::new(SUSTAIN)], 0);
+        assert_eq!(channel.controllers[&u7::new(0x07)], 0xC924_9249);
+        assert_eq!(channel.pitch_bend, Some(PITCH_BEND_CENTER));
+    }
+
+    #[test]
+    fn data_increment_and_decrement() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_6500]);
+        handle(&mut state, &[0x20B0_6400]);
+        handle(&mut state, &[0x20B0_0602]);
+        handle(&mut state, &[0x20B0_2600]);
+        handle(&mut state, &[0x20B0_6000]);
+        handle(&mut state, &[0x20B0_6000]);
+        handle(&mut state, &[0x20B0_6100]);
+        let channel = state.channel(u4::new(0x0), u4::new(0x0)).unwrap();
+        assert_eq!(
+            channel.registered[&(u7::new(0x0), u7::new(0x0))],
+            0x0404_0000
+        );
+        assert!(channel.controllers.is_empty());
+    }
+
+    #[test]
+    fn data_increment_without_parameter() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_6000]);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn midi2_data_entry_is_not_kept() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x40B0_0600, 0x1000_0000]);
+        handle(&mut state, &[0x40B0_2600, 0x1000_0000]);
+        handle(&mut state, &[0x40B0_6000, 0x0]);
+        handle(&mut state, &[0x40B0_6100, 0x0]);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn channel_mode_messages_are_not_kept() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_7B00]);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn bytes() {
+        let mut state = ChannelState::new();
+        let message = BytesMessage::try_from(&[0xB5_u8, 0x07, 0x64][..]).unwrap();
+        state.handle_bytes(u4::new(0x7), &message);
+        assert_eq!(chase(&state, Protocol::Midi1), [[0x27B5_0764]]);
+    }
+
+    #[test]
+    fn chase_midi2() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x40E1_0000, 0x1234_5678]);
+        handle(&mut state, &[0x41B0_0700, 0x8000_0000]);
+        handle(&mut state, &[0x40C1_0001, 0x0500_0201]);
+        handle(&mut state, &[0x4021_0000, 0x0400_0000]);
+        assert_eq!(
+            chase(&state, Protocol::Midi2),
+            [
+                [0x40C1_0001, 0x0500_0201],
+                [0x4021_0000, 0x0400_0000],
+                [0x40E1_0000, 0x1234_5678],
+                [0x41B0_0700, 0x8000_0000],
+            ]
+        );
+    }
+
+    #[test]
+    fn chase_midi1() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x40C1_0001, 0x0500_0201]);
+        handle(&mut state, &[0x4021_0000, 0x0400_0000]);
+        handle(&mut state, &[0x4021_0001, 0x8000_0000]);
+        handle(&mut state, &[0x40B1_0700, 0xC924_9249]);
+        assert_eq!(
+            chase(&state, Protocol::Midi1),
+            [
+                [0x20B1_0001],
+                [0x20B1_2002],
+                [0x20C1_0500],
+                [0x20B1_6500],
+                [0x20B1_6400],
+                [0x20B1_0602],
+                [0x20B1_2600],
+                [0x20B1_6500],
+                [0x20B1_6401],
+                [0x20B1_0640],
+                [0x20B1_2600],
+                [0x20B1_0764],
+            ]
+        );
+    }
+
+    #[test]
+    fn chase_midi1_selects_parameters_on_each_group() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x4020_0000, 0x0400_0000]);
+        handle(&mut state, &[0x4120_0000, 0x0400_0000]);
+        assert_eq!(
+            chase(&state, Protocol::Midi1),
+            [
+                [0x20B0_6500],
+                [0x20B0_6400],
+                [0x20B0_0602],
+                [0x20B0_2600],
+                [0x21B0_6500],
+                [0x21B0_6400],
+                [0x21B0_0602],
+                [0x21B0_2600],
+            ]
+        );
+    }
+
+    #[test]
+    fn clear() {
+        let mut state = ChannelState::new();
+        handle(&mut state, &[0x20B0_0764]);
+        state.clear();
+        assert!(state.is_empty());
+    }
+}
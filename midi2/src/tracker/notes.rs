@@ -0,0 +1,407 @@
+use crate::{
+    buffer::{Buffer, Bytes, Ump},
+    channel_voice1::{self as cv1, ChannelVoice1},
+    channel_voice2::{self as cv2, ChannelVoice2, NoteAttribute},
+    message::{BytesMessage, UmpMessage},
+    tracker::Protocol,
+    traits::{Channeled, Grouped},
+    ux::{u4, u7},
+};
+use std::collections::BTreeMap;
+
+const ALL_SOUND_OFF: u8 = 120;
+// all notes off, and the mode messages which imply it
+const ALL_NOTES_OFF: u8 = 123;
+
+const MIDI1_NOTE_OFF_VELOCITY: u8 = 0x40;
+const MIDI2_NOTE_OFF_VELOCITY: u16 = 0x8000;
+
+/// A note which has been turned on and not yet off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActiveNote {
+    pub group: u4,
+    pub channel: u4,
+    pub note_number: u7,
+    /// The protocol of the most recent note on.
+    pub protocol: Protocol,
+    /// The velocity of the most recent note on,
+    /// with 7 bits for MIDI 1.0 and 16 bits for MIDI 2.0.
+    pub velocity: u16,
+    /// The attribute of the most recent MIDI 2.0 note on.
+    pub attribute: Option<NoteAttribute>,
+    /// The most recent per-note pitch bend, while attached to the note.
+    pub pitch_bend: Option<u32>,
+    /// Whether per-note controllers have been detached from the note
+    /// with a Per-Note Management message.
+    pub detached: bool,
+    /// The number of note ons which have not yet been matched by a note off.
+    pub count: u8,
+}
+
+/// Keeps track of the notes which are sounding on each group, channel and note number.
+///
+/// Messages are fed in with [handle_ump](Self::handle_ump) or
+/// [handle_bytes](Self::handle_bytes).
+/// A MIDI 1.0 note on with zero velocity is taken as a note off.
+/// A note on for a note which is already sounding is counted,
+/// and the note stays active until it has had as many note offs.
+/// All Sound Off, All Notes Off and the channel mode messages end
+/// every note on their channel.
+///
+/// When the connection drops, [note_offs](Self::note_offs) gives the messages
+/// which silence the notes left hanging.
+///
+/// ```rust
+/// use midi2::{prelude::*, tracker::NoteTracker};
+///
+/// let mut tracker = NoteTracker::new();
+///
+/// let note_on = UmpMessage::try_from(&[0x2391_3C40][..]).unwrap();
+/// tracker.handle_ump(&note_on);
+/// assert_eq!(tracker.len(), 1);
+///
+/// let note_offs: Vec<_> = tracker.note_offs().collect();
+/// assert_eq!(note_offs[0].data(), &[0x2381_3C40]);
+///
+/// tracker.clear();
+/// assert!(tracker.is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoteTracker {
+    notes: BTreeMap<(u4, u4, u7), ActiveNote>,
+}
+
+impl NoteTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow a ump message. Messages other than channel voice messages are ignored.
+    pub fn handle_ump<B: Ump>(&mut self, message: &UmpMessage<B>) {
+        match message {
+            UmpMessage::ChannelVoice1(m) => self.handle_channel_voice1(m.group(), m),
+            UmpMessage::ChannelVoice2(m) => self.handle_channel_voice2(m),
+            _ => {}
+        }
+    }
+
+    /// Follow a MIDI 1.0 message received on the given group.
+    /// Messages other than channel voice messages are ignored.
+    pub fn handle_bytes<B: Bytes>(&mut self, group: u4, message: &BytesMessage<B>) {
+        // irrefutable when the other bytes message features are disabled
+        #[allow(irrefutable_let_patterns)]
+        if let BytesMessage::ChannelVoice1(m) = message {
+            self.handle_channel_voice1(group, m);
+        }
+    }
+
+    /// Follow a MIDI 1.0 channel voice message received on the given group.
+    pub fn handle_channel_voice1<B: Buffer>(&mut self, group: u4, message: &ChannelVoice1<B>) {
+        let channel = message.channel();
+        match message {
+            ChannelVoice1::NoteOn(m) if m.velocity() != u7::new(0) => self.note_on(
+                (group, channel, m.note_number()),
+                Protocol::Midi1,
+                u16::from(u8::from(m.velocity())),
+                None,
+            ),
+            ChannelVoice1::NoteOn(m) => self.note_off((group, channel, m.note_number())),
+            ChannelVoice1::NoteOff(m) => self.note_off((group, channel, m.note_number())),
+            ChannelVoice1::ControlChange(m) => self.control_change(group, channel, m.control()),
+            _ => {}
+        }
+    }
+
+    /// Follow a MIDI 2.0 channel voice message.
+    pub fn handle_channel_voice2<B: Ump>(&mut self, message: &ChannelVoice2<B>) {
+        let group = message.group();
+        let channel = message.channel();
+        match message {
+            ChannelVoice2::NoteOn(m) => self.note_on(
+                (group, channel, m.note_number()),
+                Protocol::Midi2,
+                m.velocity(),
+                m.attribute(),
+            ),
+            ChannelVoice2::NoteOff(m) => self.note_off((group, channel, m.note_number())),
+            ChannelVoice2::ControlChange(m) => self.control_change(group, channel, m.control()),
+            ChannelVoice2::PerNotePitchBend(m) => {
+                if let Some(note) = self.notes.get_mut(&(group, channel, m.note_number())) {
+                    if !note.detached {
+                        note.pitch_bend = Some(m.pitch_bend_data());
+                    }
+                }
+            }
+            ChannelVoice2::PerNoteManagement(m) => {
+                if let Some(note) = self.notes.get_mut(&(group, channel, m.note_number())) {
+                    if m.reset() {
+                        note.pitch_bend = None;
+                    }
+                    if m.detach() {
+                        note.detached = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The note sounding on the group, channel and note number, if there is one.
+    pub fn note(&self, group: u4, channel: u4, note_number: u7) -> Option<&ActiveNote> {
+        self.notes.get(&(group, channel, note_number))
+    }
+
+    pub fn is_active(&self, group: u4, channel: u4, note_number: u7) -> bool {
+        self.notes.contains_key(&(group, channel, note_number))
+    }
+
+    /// The sounding notes, ordered by group, channel and note number.
+    pub fn active_notes(&self) -> impl Iterator<Item = &ActiveNote> {
+        self.notes.values()
+    }
+
+    /// The number of sounding notes, not counting repeated note ons.
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// The messages which turn off every sounding note.
+    ///
+    /// Each note is turned off with the protocol of its most recent note on,
+    /// once for every note on which is still outstanding.
+    /// Follow up with [clear](Self::clear) once the messages have been sent.
+    pub fn note_offs(&self) -> impl Iterator<Item = UmpMessage<[u32; 4]>> + '_ {
+        self.notes
+            .values()
+            .flat_map(|note| core::iter::repeat_n(note_off(note), usize::from(note.count)))
+    }
+
+    /// Forget every sounding note.
+    pub fn clear(&mut self) {
+        self.notes.clear();
+    }
+
+    fn note_on(
+        &mut self,
+        key: (u4, u4, u7),
+        protocol: Protocol,
+        velocity: u16,
+        attribute: Option<NoteAttribute>,
+    ) {
+        let note = self.notes.entry(key).or_insert(ActiveNote {
+            group: key.0,
+            channel: key.1,
+            note_number: key.2,
+            protocol,
+            velocity,
+            attribute,
+            pitch_bend: None,
+            detached: false,
+            count: 0,
+        });
+        if note.detached {
+            // the new note starts with fresh per-note controllers
+            note.detached = false;
+            note.pitch_bend = None;
+        }
+        note.protocol = protocol;
+        note.velocity = velocity;
+        note.attribute = attribute;
+        note.count = note.count.saturating_add(1);
+    }
+
+    fn note_off(&mut self, key: (u4, u4, u7)) {
+        if let Some(note) = self.notes.get_mut(&key) {
+            note.count -= 1;
+            if note.count == 0 {
+                self.notes.remove(&key);
+            }
+        }
+    }
+
+    fn control_change(&mut self, group: u4, channel: u4, control: u7) {
+        let control = u8::from(control);
+        if control == ALL_SOUND_OFF || control >= ALL_NOTES_OFF {
+            self.notes
+                .retain(|&(g, c, _), _| (g, c) != (group, channel));
+        }
+    }
+}
+
+fn note_off(note: &ActiveNote) -> UmpMessage<[u32; 4]> {
+    match note.protocol {
+        Protocol::Midi1 => {
+            let mut message = cv1::NoteOff::<[u32; 4]>::new();
+            message.set_group(note.group);
+            message.set_channel(note.channel);
+            message.set_note_number(note.note_number);
+            message.set_velocity(u7::new(MIDI1_NOTE_OFF_VELOCITY));
+            ChannelVoice1::from(message).into()
+        }
+        Protocol::Midi2 => {
+            let mut message = cv2::NoteOff::<[u32; 4]>::new();
+            message.set_group(note.group);
+            message.set_channel(note.channel);
+            message.set_note_number(note.note_number);
+            message.set_velocity(MIDI2_NOTE_OFF_VELOCITY);
+            ChannelVoice2::from(message).into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+    use std::vec::Vec;
+
+    fn handle(tracker: &mut NoteTracker, data: &[u32]) {
+        tracker.handle_ump(&UmpMessage::try_from(data).unwrap());
+    }
+
+    fn note_offs(tracker: &NoteTracker) -> Vec<Vec<u32>> {
+        tracker.note_offs().map(|m| m.data().to_vec()).collect()
+    }
+
+    #[test]
+    fn note_on_and_off() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x2090_3C40]);
+        handle(&mut tracker, &[0x4091_3E00, 0xFFFF_0000]);
+        assert!(tracker.is_active(u4::new(0x0), u4::new(0x0), u7::new(0x3C)));
+        assert!(tracker.is_active(u4::new(0x0), u4::new(0x1), u7::new(0x3E)));
+        handle(&mut tracker, &[0x2080_3C40]);
+        handle(&mut tracker, &[0x4081_3E00, 0x0]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn velocity_zero_note_off() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x2090_3C40]);
+        handle(&mut tracker, &[0x2090_3C00]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn midi2_velocity_zero_is_a_note_on() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x4090_3C00, 0x0]);
+        assert_eq!(tracker.len(), 1);
+    }
+
+    #[test]
+    fn duplicate_note_ons() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x2090_3C40]);
+        handle(&mut tracker, &[0x2090_3C50]);
+        let note = tracker
+            .note(u4::new(0x0), u4::new(0x0), u7::new(0x3C))
+            .unwrap();
+        assert_eq!(note.count, 2);
+        assert_eq!(note.velocity, 0x50);
+        assert_eq!(note_offs(&tracker), [[0x2080_3C40], [0x2080_3C40]]);
+        handle(&mut tracker, &[0x2080_3C40]);
+        assert_eq!(tracker.len(), 1);
+        handle(&mut tracker, &[0x2080_3C40]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn unmatched_note_off() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x2080_3C40]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn bytes() {
+        let mut tracker = NoteTracker::new();
+        let message = BytesMessage::try_from(&[0x95_u8, 0x3C, 0x40][..]).unwrap();
+        tracker.handle_bytes(u4::new(0x7), &message);
+        assert_eq!(note_offs(&tracker), [[0x2785_3C40]]);
+    }
+
+    #[test]
+    fn attributes() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x4A93_3C03, 0x1234_0200]);
+        assert_eq!(
+            tracker.active_notes().collect::<Vec<_>>(),
+            [&ActiveNote {
+                group: u4::new(0xA),
+                channel: u4::new(0x3),
+                note_number: u7::new(0x3C),
+                protocol: Protocol::Midi2,
+                velocity: 0x1234,
+                attribute: Some(NoteAttribute::Pitch7_9(crate::num::Fixed7_9::from_bits(
+                    0x0200
+                ))),
+                pitch_bend: None,
+                detached: false,
+                count: 1,
+            }]
+        );
+        assert_eq!(note_offs(&tracker), [[0x4A83_3C00, 0x8000_0000]]);
+    }
+
+    #[test]
+    fn per_note_management() {
+        let key = (u4::new(0x0), u4::new(0x0), u7::new(0x3C));
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x4090_3C00, 0xFFFF_0000]);
+        handle(&mut tracker, &[0x4060_3C00, 0x8000_1000]);
+        assert_eq!(
+            tracker.note(key.0, key.1, key.2).unwrap().pitch_bend,
+            Some(0x8000_1000)
+        );
+
+        // reset
+        handle(&mut tracker, &[0x40F0_3C02]);
+        assert_eq!(tracker.note(key.0, key.1, key.2).unwrap().pitch_bend, None);
+
+        // detach
+        handle(&mut tracker, &[0x40F0_3C01]);
+        handle(&mut tracker, &[0x4060_3C00, 0x9000_0000]);
+        let note = tracker.note(key.0, key.1, key.2).unwrap();
+        assert!(note.detached);
+        assert_eq!(note.pitch_bend, None);
+
+        // a new note on the detached note number
+        handle(&mut tracker, &[0x4090_3C00, 0xFFFF_0000]);
+        handle(&mut tracker, &[0x4060_3C00, 0x9000_0000]);
+        let note = tracker.note(key.0, key.1, key.2).unwrap();
+        assert!(!note.detached);
+        assert_eq!(note.pitch_bend, Some(0x9000_0000));
+        assert_eq!(note.count, 2);
+    }
+
+    #[test]
+    fn all_notes_off() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x2090_3C40]);
+        handle(&mut tracker, &[0x2090_3E40]);
+        handle(&mut tracker, &[0x2091_3C40]);
+        handle(&mut tracker, &[0x20B0_7B00]);
+        assert_eq!(note_offs(&tracker), [[0x2081_3C40]]);
+        handle(&mut tracker, &[0x40B1_7800, 0x0]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn note_offs_are_ordered() {
+        let mut tracker = NoteTracker::new();
+        handle(&mut tracker, &[0x2190_3C40]);
+        handle(&mut tracker, &[0x2090_3E40]);
+        handle(&mut tracker, &[0x2090_3C40]);
+        assert_eq!(
+            note_offs(&tracker),
+            [[0x2080_3C40], [0x2080_3E40], [0x2180_3C40]]
+        );
+    }
+}
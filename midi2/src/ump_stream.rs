@@ -16,6 +16,7 @@ mod product_instance_id;
 mod start_of_clip;
 mod stream_configuration_notification;
 mod stream_configuration_request;
+mod unknown;
 
 pub use device_identity::*;
 pub use end_of_clip::*;
@@ -30,6 +31,7 @@ pub use product_instance_id::*;
 pub use start_of_clip::*;
 pub use stream_configuration_notification::*;
 pub use stream_configuration_request::*;
+pub use unknown::*;
 
 pub(crate) const UMP_MESSAGE_TYPE: u8 = 0xF;
 const COMPLETE_FORMAT: u8 = 0x0;
@@ -43,6 +45,9 @@ const END_FORMAT: u8 = 0x3;
     midi2_proc::Packets,
     midi2_proc::RebufferFrom,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -65,6 +70,7 @@ pub enum UmpStream<B: crate::buffer::Ump> {
         stream_configuration_notification::StreamConfigurationNotification<B>,
     ),
     StreamConfigurationRequest(stream_configuration_request::StreamConfigurationRequest<B>),
+    Unknown(unknown::UnknownUmpStream<B>),
 }
 
 impl<'a> TryFrom<&'a [u32]> for UmpStream<&'a [u32]> {
@@ -114,6 +120,23 @@ impl<'a> TryFrom<&'a [u32]> for UmpStream<&'a [u32]> {
     }
 }
 
+impl<'a> UmpStream<&'a [u32]> {
+    /// Parses the message at the front of the buffer without rejecting
+    /// messages which don't strictly follow the specification.
+    ///
+    /// Reserved bits which have been set are reported through the returned warnings.
+    /// Packets with unassigned statuses or invalid fields come back unmodified
+    /// as [UmpStream::Unknown].
+    pub fn try_from_lenient(
+        buffer: &'a [u32],
+    ) -> Result<(Self, crate::error::Warnings<'a>), crate::error::Diagnostic> {
+        crate::detail::diagnose::lenient(
+            buffer,
+            Some(|packet| Ok(unknown::UnknownUmpStream::try_from(packet)?.into())),
+        )
+    }
+}
+
 struct StatusProperty<const STATUS: u16>;
 
 impl<const STATUS: u16, B: Ump> property::Property<B> for StatusProperty<STATUS> {
@@ -125,7 +148,7 @@ impl<'a, const STATUS: u16, B: Ump> property::ReadProperty<'a, B> for StatusProp
     fn validate(buffer: &B) -> Result<(), crate::error::InvalidData> {
         if buffer
             .buffer()
-            .chunks_exact(4)
+            .chunks(4)
             .all(|packet| status_from_buffer(packet) == STATUS)
         {
             Ok(())
@@ -140,7 +163,7 @@ impl<const STATUS: u16, B: Ump + BufferMut> property::WriteProperty<B> for Statu
         Ok(())
     }
     fn write(buffer: &mut B, _v: Self::Type) {
-        for packet in buffer.buffer_mut().chunks_exact_mut(4) {
+        for packet in buffer.buffer_mut().chunks_mut(4) {
             packet[0] &= !0x03FF_0000;
             packet[0] |= (STATUS as u32) << 16;
         }
@@ -344,13 +367,14 @@ fn set_format_fields(buffer: &mut [u32]) {
     use crate::detail::BitOps;
     use crate::ux::u2;
 
+    // single packet messages may be backed by less than a whole packet
     let mut packets = buffer
-        .chunks_exact_mut(4)
+        .chunks_mut(4)
         .take_while(|packet| u8::from(packet[0].nibble(0)) == UMP_MESSAGE_TYPE)
         .peekable();
 
     let Some(first) = packets.next() else {
-        panic!("Can't be called with empty buffer");
+        return;
     };
 
     if packets.peek().is_some() {
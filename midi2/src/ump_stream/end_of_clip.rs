@@ -25,6 +25,15 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn new_with_vec() {
+        assert_eq!(
+            EndOfClip::<std::vec::Vec<u32>>::new(),
+            EndOfClip(std::vec![0xF021_0000])
+        );
+    }
+
     #[test]
     fn from_data() {
         assert_eq!(
@@ -36,6 +36,8 @@ struct FunctionBlockInfo {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum UiHint {
     Undeclared,
     Sender,
@@ -101,6 +103,8 @@ impl core::default::Default for UiHint {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Midi1Port {
     RestrictBandwidth,
     DontRestrictBandwidth,
@@ -164,6 +168,8 @@ impl<B: crate::buffer::Ump + crate::buffer::BufferMut> property::WriteProperty<B
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Direction {
     Input,
     Output,
@@ -25,6 +25,15 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn new_with_vec() {
+        assert_eq!(
+            StartOfClip::<std::vec::Vec<u32>>::new(),
+            StartOfClip(std::vec![0xF020_0000])
+        );
+    }
+
     #[test]
     fn from_data() {
         assert_eq!(
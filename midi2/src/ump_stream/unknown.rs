@@ -0,0 +1,30 @@
+use crate::{detail::common_properties, ump_stream, ump_stream::UMP_MESSAGE_TYPE};
+
+/// MIDI 2.0 UMP Stream Message with an unassigned status
+///
+/// Only produced by the lenient parsing path, which uses it
+/// to carry packets it can't interpret without modifying them.
+/// Multi-packet messages are carried one packet at a time.
+/// The format, status and data bits are kept as they were found in `raw_data`.
+///
+/// See the [module docs](crate::ump_stream) for more info.
+#[midi2_proc::generate_message(Via(ump_stream::UmpStream), FixedSize, MinSizeUmp(4))]
+struct UnknownUmpStream {
+    #[property(common_properties::UmpMessageTypeProperty<UMP_MESSAGE_TYPE>)]
+    ump_type: (),
+    #[property(common_properties::UnknownDataProperty<4, 0x0FFF_FFFF>)]
+    raw_data: [u32; 4],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn setter() {
+        let mut message = UnknownUmpStream::<[u32; 4]>::new();
+        message.set_raw_data([0x0333_0000, 0x1, 0x2, 0x3]);
+        assert_eq!(message, UnknownUmpStream([0xF333_0000, 0x1, 0x2, 0x3]));
+    }
+}
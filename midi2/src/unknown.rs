@@ -0,0 +1,279 @@
+use crate::{
+    buffer::{BufferMut, BufferResize, BufferTryResize, Ump},
+    detail::{
+        common_properties,
+        property::{Property, ReadProperty, ResizeProperty, WriteProperty},
+        BitOps,
+    },
+    error, packet,
+    ux::u4,
+};
+
+/// A packet with a ump message type which isn't interpreted:
+/// one of the types (0x6 to 0xE) which the specification reserves for future use,
+/// or a type whose feature is disabled.
+///
+/// The specification already fixes the packet size of each message type,
+/// so these messages can be carried unmodified without losing sync with the stream.
+/// The data bits of the packet are kept as they were found in `raw_data`.
+/// Each packet of a multi-packet message is carried as a message of its own.
+///
+/// ```rust
+/// use midi2::prelude::*;
+///
+/// let message = UmpMessage::try_from(&[0xB512_3456, 0x789A_BCDE, 0xF012_3456][..]).unwrap();
+/// let UmpMessage::Unknown(unknown) = message else {
+///     panic!();
+/// };
+/// assert_eq!(unknown.message_type(), u4::new(0xB));
+/// assert_eq!(unknown.group(), u4::new(0x5));
+/// assert_eq!(unknown.data(), &[0xB512_3456, 0x789A_BCDE, 0xF012_3456]);
+/// ```
+///
+/// Owned messages are resized to fit the packet of their message type.
+///
+/// ```rust
+/// use midi2::prelude::*;
+///
+/// let mut message = UnknownMessage::<std::vec::Vec<u32>>::new();
+/// message.set_message_type(u4::new(0x8));
+/// message.set_raw_data([0x0012_3456, 0x789A_BCDE, 0x0, 0x0]);
+/// assert_eq!(message.data(), &[0x8012_3456, 0x789A_BCDE]);
+/// ```
+#[midi2_proc::generate_message(MinSizeUmp(1))]
+struct UnknownMessage {
+    #[property(MessageTypeProperty)]
+    #[resize]
+    message_type: u4,
+    #[property(common_properties::GroupProperty)]
+    group: u4,
+    #[property(RawDataProperty)]
+    raw_data: [u32; 4],
+}
+
+impl<B: Ump> crate::traits::Size<B> for UnknownMessage<B> {
+    fn size(&self) -> usize {
+        packet::size(self.0.buffer()[0].nibble(0).into())
+    }
+}
+
+const ERR_INTERPRETED: &str = "Ump message type is interpreted by an enabled feature";
+
+fn is_unknown(message_type: u4) -> bool {
+    !packet::is_interpreted(message_type.into())
+}
+
+struct MessageTypeProperty;
+
+impl<B: Ump> Property<B> for MessageTypeProperty {
+    type Type = u4;
+}
+
+impl<'a, B: Ump> ReadProperty<'a, B> for MessageTypeProperty {
+    fn read(buffer: &'a B) -> Self::Type {
+        buffer.buffer()[0].nibble(0)
+    }
+    fn validate(buffer: &B) -> Result<(), error::InvalidData> {
+        let message_type = buffer.buffer()[0].nibble(0);
+        if !is_unknown(message_type) {
+            return Err(error::InvalidData(ERR_INTERPRETED));
+        }
+        if buffer.buffer().len() < packet::size(message_type.into()) {
+            return Err(error::InvalidData(
+                crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<B: Ump + BufferMut> WriteProperty<B> for MessageTypeProperty {
+    fn write(buffer: &mut B, v: Self::Type) {
+        buffer.buffer_mut()[0].set_nibble(0, v);
+    }
+    fn validate(v: &Self::Type) -> Result<(), error::InvalidData> {
+        if !is_unknown(*v) {
+            return Err(error::InvalidData(ERR_INTERPRETED));
+        }
+        Ok(())
+    }
+    fn default() -> Self::Type {
+        u4::new(0x6)
+    }
+}
+
+impl<B: Ump + BufferMut> ResizeProperty<B> for MessageTypeProperty {
+    fn resize(buffer: &mut B, value: &Self::Type)
+    where
+        B: BufferResize,
+    {
+        buffer.resize(packet::size((*value).into()));
+    }
+    fn try_resize(buffer: &mut B, value: &Self::Type) -> Result<(), error::BufferOverflow>
+    where
+        B: BufferTryResize,
+    {
+        buffer.try_resize(packet::size((*value).into()))?;
+        Ok(())
+    }
+}
+
+/// The words of the packet without the message type and group.
+///
+/// Words beyond the packet size of the message type read as zero
+/// and are ignored when written.
+struct RawDataProperty;
+
+impl<B: Ump> Property<B> for RawDataProperty {
+    type Type = [u32; 4];
+}
+
+impl<'a, B: Ump> ReadProperty<'a, B> for RawDataProperty {
+    fn read(buffer: &'a B) -> Self::Type {
+        let buffer = buffer.buffer();
+        let size = packet::size(buffer[0].nibble(0).into()).min(buffer.len());
+        let mut data = [0x0; 4];
+        data[..size].copy_from_slice(&buffer[..size]);
+        data[0] &= 0x00FF_FFFF;
+        data
+    }
+    fn validate(_buffer: &B) -> Result<(), error::InvalidData> {
+        Ok(())
+    }
+}
+
+impl<B: Ump + BufferMut> WriteProperty<B> for RawDataProperty {
+    fn write(buffer: &mut B, v: Self::Type) {
+        let buffer = buffer.buffer_mut();
+        let size = packet::size(buffer[0].nibble(0).into()).min(buffer.len());
+        let header = buffer[0] & 0xFF00_0000;
+        buffer[..size].copy_from_slice(&v[..size]);
+        buffer[0] = header | (v[0] & 0x00FF_FFFF);
+    }
+    fn validate(v: &Self::Type) -> Result<(), error::InvalidData> {
+        if v[0] & 0xFF00_0000 != 0 {
+            return Err(error::InvalidData(
+                "Unknown message data overlaps the message header",
+            ));
+        }
+        Ok(())
+    }
+    fn default() -> Self::Type {
+        [0x0; 4]
+    }
+}
+
+/// A packet of a ump message type which isn't interpreted.
+#[derive(Eq, PartialEq, Clone)]
+pub struct Packet([u32; 4]);
+
+impl<'a> core::convert::TryFrom<&'a [u32]> for Packet {
+    type Error = error::InvalidData;
+    fn try_from(data: &'a [u32]) -> Result<Self, Self::Error> {
+        if data.is_empty() {
+            return Err(error::InvalidData(
+                crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT,
+            ));
+        }
+        if !is_unknown(data[0].nibble(0)) {
+            return Err(error::InvalidData(
+                crate::detail::common_err_strings::ERR_INCORRECT_UMP_MESSAGE_TYPE,
+            ));
+        }
+        let size = packet::size(data[0].nibble(0).into());
+        if data.len() < size {
+            return Err(error::InvalidData(
+                crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT,
+            ));
+        }
+
+        Ok(Packet({
+            let mut buffer = [0x0; 4];
+            buffer[..size].copy_from_slice(&data[..size]);
+            buffer
+        }))
+    }
+}
+
+impl core::ops::Deref for Packet {
+    type Target = [u32];
+    fn deref(&self) -> &Self::Target {
+        &self.0[..packet::size(self.0[0].nibble(0).into())]
+    }
+}
+
+impl core::fmt::Debug for Packet {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt.write_str("Packet([")?;
+        let mut iter = self.iter().peekable();
+        while let Some(v) = iter.next() {
+            fmt.write_fmt(format_args!("{v:#010X}"))?;
+            if iter.peek().is_some() {
+                fmt.write_str(", ")?;
+            }
+        }
+        fmt.write_str("])")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        traits::{Data, Grouped},
+        Packets,
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn try_from_slice() {
+        let message = UnknownMessage::try_from(&[0x6312_3456, 0x0][..]).unwrap();
+        assert_eq!(message.data(), &[0x6312_3456]);
+        assert_eq!(message.group(), u4::new(0x3));
+        assert_eq!(message.raw_data(), [0x0012_3456, 0x0, 0x0, 0x0]);
+    }
+
+    #[test]
+    fn try_from_short_slice() {
+        assert_eq!(
+            UnknownMessage::try_from(&[0xE000_0000, 0x0][..]),
+            Err(error::InvalidData(
+                crate::detail::common_err_strings::ERR_SLICE_TOO_SHORT
+            )),
+        );
+    }
+
+    #[test]
+    fn try_from_assigned_message_type() {
+        assert_eq!(
+            UnknownMessage::try_from(&[0x4000_0000, 0x0][..]),
+            Err(error::InvalidData(ERR_INTERPRETED)),
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "flex-data")]
+    fn try_from_flex_data() {
+        assert_eq!(
+            UnknownMessage::try_from(&[0xD010_0001, 0x0, 0x0, 0x0][..]),
+            Err(error::InvalidData(ERR_INTERPRETED)),
+        );
+    }
+
+    #[test]
+    fn try_set_message_type() {
+        let mut message = UnknownMessage::<[u32; 4]>::new();
+        message.set_group(u4::new(0x2));
+        message.try_set_message_type(u4::new(0xC)).unwrap();
+        message.set_raw_data([0x0012_3456, 0x1, 0x2, 0x3]);
+        assert_eq!(message.data(), &[0xC212_3456, 0x1, 0x2]);
+    }
+
+    #[test]
+    fn packets() {
+        let message = UnknownMessage::try_from(&[0xA000_0000, 0x1][..]).unwrap();
+        let mut packets = message.packets();
+        assert_eq!(&*packets.next().unwrap(), &[0xA000_0000, 0x1][..]);
+        assert_eq!(packets.next(), None);
+    }
+}
@@ -0,0 +1,597 @@
+//! Event packets of the USB Device Class Definition for MIDI Devices 1.0.
+//!
+//! USB-MIDI 1.0 devices exchange MIDI 1.0 messages in 4-byte event packets.
+//! The high nibble of the first byte is the virtual cable number and the low
+//! nibble is the Code Index Number (CIN) which tells how many of the
+//! following three bytes are used.
+//!
+//! - [EventPackets] splits a [BytesMessage] into event packets.
+//! - [UsbMidiDecoder] reassembles event packets into [BytesMessage] values.
+//! - [CableMap] translates between cable numbers and UMP groups.
+//!
+//! Nothing here allocates, so the module is suitable for USB gadget firmware.
+//!
+//! ```rust
+//! use midi2::{prelude::*, usb_midi::{EventPackets, UsbMidiDecoder}};
+//!
+//! let message = BytesMessage::try_from(&[0xF0, 0x01, 0x02, 0x03, 0x04, 0xF7][..]).unwrap();
+//! let packets = EventPackets::new(u4::new(0x2), &message);
+//!
+//! let mut decoder = UsbMidiDecoder::<16>::new();
+//! let mut decoded = None;
+//! for packet in packets {
+//!     if let Some((cable, message)) = decoder.decode(packet).unwrap() {
+//!         decoded = Some((cable, message.data().to_vec()));
+//!     }
+//! }
+//! assert_eq!(
+//!     decoded,
+//!     Some((u4::new(0x2), vec![0xF0, 0x01, 0x02, 0x03, 0x04, 0xF7])),
+//! );
+//! ```
+
+use crate::{
+    error::{BufferOverflow, Error, InvalidData},
+    message::BytesMessage,
+    traits::Data,
+    ux::u4,
+};
+
+const ERR_UNEXPECTED_CONTINUATION: &str =
+    "System exclusive continuation found outside of a sysex message";
+const ERR_INVALID_STATUS: &str = "Event packet status does not match its code index number";
+
+/// An iterator over the USB-MIDI event packets carrying one message.
+///
+/// Channel voice and system common messages fit in a single packet.
+/// System exclusive messages are split into three byte chunks sent with
+/// CIN `0x4`, finishing with a packet of CIN `0x5`, `0x6` or `0x7`
+/// depending on how many bytes remain.
+/// System real time messages are sent with CIN `0xF`.
+///
+/// ```rust
+/// use midi2::{prelude::*, usb_midi::EventPackets};
+///
+/// let message = BytesMessage::try_from(&[0x91, 0x3C, 0x40][..]).unwrap();
+/// let mut packets = EventPackets::new(u4::new(0x1), &message);
+///
+/// assert_eq!(packets.next(), Some([0x19, 0x91, 0x3C, 0x40]));
+/// assert_eq!(packets.next(), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct EventPackets<'a> {
+    cable: u8,
+    data: &'a [u8],
+}
+
+impl<'a> EventPackets<'a> {
+    pub fn new<B: crate::buffer::Bytes, M: Data<B>>(cable: u4, message: &'a M) -> Self {
+        Self::from_data(cable, message.data())
+    }
+
+    /// Packets for the raw bytes of a complete MIDI 1.0 message.
+    pub fn from_data(cable: u4, data: &'a [u8]) -> Self {
+        EventPackets {
+            cable: u8::from(cable) << 4,
+            data,
+        }
+    }
+}
+
+impl Iterator for EventPackets<'_> {
+    type Item = [u8; 4];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &status = self.data.first()?;
+        let (cin, size) = if self.data.len() > 3 && status != 0xF7 && status < 0xF8 {
+            // sysex start or continue
+            (0x4, 3)
+        } else if status == 0xF0 || self.data.last() == Some(&0xF7) {
+            // sysex end
+            let size = self.data.len().min(3);
+            (0x4 + size as u8, size)
+        } else {
+            match status {
+                0x80..=0xEF => (status >> 4, self.data.len()),
+                0xF1 | 0xF3 => (0x2, 2),
+                0xF2 => (0x3, 3),
+                0xF6 => (0x5, 1),
+                _ => (0xF, 1),
+            }
+        };
+        let mut packet = [self.cable | cin, 0x0, 0x0, 0x0];
+        packet[1..=size].copy_from_slice(&self.data[..size]);
+        self.data = &self.data[size..];
+        Some(packet)
+    }
+}
+
+impl core::iter::FusedIterator for EventPackets<'_> {}
+
+/// The number of midi bytes carried by a packet with the given code index number.
+///
+/// Reserved code index numbers carry nothing.
+pub fn packet_size(cin: u4) -> usize {
+    match u8::from(cin) {
+        0x5 | 0xF => 1,
+        0x2 | 0x6 | 0xC | 0xD => 2,
+        0x3 | 0x4 | 0x7 | 0x8 | 0x9 | 0xA | 0xB | 0xE => 3,
+        _ => 0,
+    }
+}
+
+/// A decoded message with the cable it was received on.
+pub type CableMessage<'a> = (u4, BytesMessage<&'a [u8]>);
+
+/// Reassembles MIDI 1.0 messages from USB-MIDI event packets without allocating.
+///
+/// Short messages are handed out as soon as their packet arrives,
+/// together with the cable they were received on.
+/// System exclusive messages are collected into an internal buffer of `SIZE` bytes
+/// kept for each cable, so the virtual cables are independent streams
+/// and their sysex messages may be interleaved.
+/// The decoder holds sixteen of these buffers, one per cable.
+/// Any message other than system real time on the same cable
+/// abandons a partially received sysex message.
+/// Packets with the reserved code index numbers `0x0` and `0x1` are ignored.
+///
+/// ```rust
+/// use midi2::{prelude::*, usb_midi::UsbMidiDecoder};
+///
+/// let mut decoder = UsbMidiDecoder::<16>::new();
+///
+/// let (cable, message) = decoder.decode([0x3B, 0xB0, 0x07, 0x64]).unwrap().unwrap();
+/// assert_eq!(cable, u4::new(0x3));
+/// assert_eq!(message.data(), &[0xB0, 0x07, 0x64]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct UsbMidiDecoder<const SIZE: usize = 64> {
+    cables: [CableSysex<SIZE>; 16],
+    short: [u8; 3],
+    // the cable of the sysex message handed out by the last call
+    complete: Option<u4>,
+}
+
+/// The system exclusive message being received on one cable.
+#[derive(Clone, Copy, Debug)]
+struct CableSysex<const SIZE: usize> {
+    buffer: [u8; SIZE],
+    len: usize,
+    state: Sysex,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Sysex {
+    Idle,
+    Receiving,
+    // continuation packets are dropped until the end of an overflowing message
+    Skipping,
+}
+
+impl<const SIZE: usize> Default for UsbMidiDecoder<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> UsbMidiDecoder<SIZE> {
+    pub fn new() -> Self {
+        UsbMidiDecoder {
+            cables: [CableSysex::new(); 16],
+            short: [0x0; 3],
+            complete: None,
+        }
+    }
+
+    /// Decode the next event packet.
+    ///
+    /// Returns `Ok(None)` while a system exclusive message is incomplete
+    /// and for packets which carry no message.
+    ///
+    /// Errors are reported as soon as they're found and the offending data is dropped,
+    /// so decoding can carry on with the next packet.
+    /// System exclusive messages longer than the internal buffer
+    /// fail with [Error::BufferOverflow].
+    pub fn decode(&mut self, packet: [u8; 4]) -> Result<Option<CableMessage<'_>>, Error> {
+        if let Some(cable) = self.complete.take() {
+            self.cables[usize::from(u8::from(cable))].abandon();
+        }
+
+        let cable = u4::new(packet[0] >> 4);
+        let cin = u4::new(packet[0] & 0xF);
+        let data = &packet[1..=packet_size(cin).max(1)];
+        let status = data[0];
+
+        let is_sysex = match u8::from(cin) {
+            0x0 | 0x1 => return Ok(None),
+            0x4 | 0x6 | 0x7 => true,
+            0x5 => status == 0xF7,
+            _ => false,
+        };
+        let sysex = &mut self.cables[usize::from(u8::from(cable))];
+        if !is_sysex {
+            if status < 0xF8 {
+                sysex.abandon();
+            }
+            return self.short(cable, cin, data);
+        }
+
+        if status == 0xF0 {
+            sysex.abandon();
+            sysex.state = Sysex::Receiving;
+        }
+        match sysex.state {
+            Sysex::Receiving => {}
+            Sysex::Skipping => {
+                if u8::from(cin) != 0x4 {
+                    sysex.abandon();
+                }
+                return Ok(None);
+            }
+            Sysex::Idle => return Err(InvalidData(ERR_UNEXPECTED_CONTINUATION).into()),
+        }
+
+        for &byte in data {
+            if let Err(e) = sysex.push(byte) {
+                sysex.state = if u8::from(cin) == 0x4 {
+                    Sysex::Skipping
+                } else {
+                    Sysex::Idle
+                };
+                return Err(e.into());
+            }
+        }
+        if u8::from(cin) == 0x4 {
+            return Ok(None);
+        }
+
+        self.complete = Some(cable);
+        let sysex = &self.cables[usize::from(u8::from(cable))];
+        Ok(Some((
+            cable,
+            BytesMessage::try_from(&sysex.buffer[..sysex.len])?,
+        )))
+    }
+
+    /// Drop any partially received system exclusive messages.
+    pub fn reset(&mut self) {
+        for sysex in self.cables.iter_mut() {
+            sysex.abandon();
+        }
+        self.complete = None;
+    }
+
+    fn short(
+        &mut self,
+        cable: u4,
+        cin: u4,
+        data: &[u8],
+    ) -> Result<Option<CableMessage<'_>>, Error> {
+        let status = data[0];
+        let valid = match u8::from(cin) {
+            0x8..=0xE => status >> 4 == u8::from(cin),
+            0x2 => matches!(status, 0xF1 | 0xF3),
+            0x3 => status == 0xF2,
+            _ => matches!(status, 0xF6 | 0xF8..=0xFF),
+        };
+        if !valid {
+            return Err(InvalidData(ERR_INVALID_STATUS).into());
+        }
+        self.short[..data.len()].copy_from_slice(data);
+        Ok(Some((
+            cable,
+            BytesMessage::try_from(&self.short[..data.len()])?,
+        )))
+    }
+}
+
+impl<const SIZE: usize> CableSysex<SIZE> {
+    fn new() -> Self {
+        CableSysex {
+            buffer: [0x0; SIZE],
+            len: 0,
+            state: Sysex::Idle,
+        }
+    }
+
+    fn abandon(&mut self) {
+        self.state = Sysex::Idle;
+        self.len = 0;
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), BufferOverflow> {
+        if self.len == SIZE {
+            self.len = 0;
+            return Err(BufferOverflow);
+        }
+        self.buffer[self.len] = byte;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+/// Translates between USB-MIDI cable numbers and UMP groups.
+///
+/// Each cable maps to at most one group.
+/// The default map takes each cable to the group of the same number.
+///
+/// ```rust
+/// use midi2::{prelude::*, channel_voice1::NoteOn, usb_midi::CableMap};
+///
+/// let mut map = CableMap::default();
+/// map.map(u4::new(0x0), Some(u4::new(0x5)));
+///
+/// let message = NoteOn::try_from(&[0x90_u8, 0x3C, 0x40][..]).unwrap();
+/// let mut ump = NoteOn::<[u32; 4]>::from_bytes(message);
+/// ump.set_group(map.group(u4::new(0x0)).unwrap());
+///
+/// assert_eq!(ump.data(), &[0x2590_3C40]);
+/// assert_eq!(map.cable(u4::new(0x5)), Some(u4::new(0x0)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CableMap {
+    groups: [Option<u4>; 16],
+}
+
+impl Default for CableMap {
+    fn default() -> Self {
+        let mut groups = [None; 16];
+        for (i, group) in groups.iter_mut().enumerate() {
+            *group = Some(u4::new(i as u8));
+        }
+        CableMap { groups }
+    }
+}
+
+impl CableMap {
+    /// A map with no cables mapped.
+    pub fn empty() -> Self {
+        CableMap { groups: [None; 16] }
+    }
+
+    /// Map the cable to a group, or unmap it with `None`.
+    pub fn map(&mut self, cable: u4, group: Option<u4>) {
+        self.groups[usize::from(u8::from(cable))] = group;
+    }
+
+    /// The group of the cable.
+    pub fn group(&self, cable: u4) -> Option<u4> {
+        self.groups[usize::from(u8::from(cable))]
+    }
+
+    /// The lowest numbered cable mapped to the group.
+    pub fn cable(&self, group: u4) -> Option<u4> {
+        self.groups
+            .iter()
+            .position(|g| *g == Some(group))
+            .map(|i| u4::new(i as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn packets(cable: u8, data: &[u8]) -> std::vec::Vec<[u8; 4]> {
+        EventPackets::from_data(u4::new(cable), data).collect()
+    }
+
+    fn decode_all<const SIZE: usize>(
+        decoder: &mut UsbMidiDecoder<SIZE>,
+        packets: &[[u8; 4]],
+    ) -> std::vec::Vec<Result<(u8, std::vec::Vec<u8>), Error>> {
+        packets
+            .iter()
+            .filter_map(|packet| match decoder.decode(*packet) {
+                Ok(Some((cable, message))) => Some(Ok((u8::from(cable), message.data().to_vec()))),
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encode_short_messages() {
+        assert_eq!(
+            packets(0x0, &[0x80, 0x3C, 0x00]),
+            [[0x08, 0x80, 0x3C, 0x00]]
+        );
+        assert_eq!(packets(0x1, &[0xC5, 0x07]), [[0x1C, 0xC5, 0x07, 0x00]]);
+        assert_eq!(packets(0x2, &[0xD0, 0x20]), [[0x2D, 0xD0, 0x20, 0x00]]);
+        assert_eq!(
+            packets(0x3, &[0xEF, 0x00, 0x40]),
+            [[0x3E, 0xEF, 0x00, 0x40]]
+        );
+        assert_eq!(packets(0x0, &[0xF1, 0x12]), [[0x02, 0xF1, 0x12, 0x00]]);
+        assert_eq!(
+            packets(0x0, &[0xF2, 0x01, 0x02]),
+            [[0x03, 0xF2, 0x01, 0x02]]
+        );
+        assert_eq!(packets(0x0, &[0xF6]), [[0x05, 0xF6, 0x00, 0x00]]);
+        assert_eq!(packets(0xF, &[0xF8]), [[0xFF, 0xF8, 0x00, 0x00]]);
+    }
+
+    #[test]
+    fn encode_sysex() {
+        assert_eq!(packets(0x0, &[0xF0, 0xF7]), [[0x06, 0xF0, 0xF7, 0x00]]);
+        assert_eq!(
+            packets(0x0, &[0xF0, 0x01, 0xF7]),
+            [[0x07, 0xF0, 0x01, 0xF7]]
+        );
+        assert_eq!(
+            packets(0x0, &[0xF0, 0x01, 0x02, 0xF7]),
+            [[0x04, 0xF0, 0x01, 0x02], [0x05, 0xF7, 0x00, 0x00]]
+        );
+        assert_eq!(
+            packets(0x0, &[0xF0, 0x01, 0x02, 0x03, 0xF7]),
+            [[0x04, 0xF0, 0x01, 0x02], [0x06, 0x03, 0xF7, 0x00]]
+        );
+        assert_eq!(
+            packets(0x1, &[0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0xF7]),
+            [
+                [0x14, 0xF0, 0x01, 0x02],
+                [0x14, 0x03, 0x04, 0x05],
+                [0x16, 0x06, 0xF7, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let messages: [&[u8]; 8] = [
+            &[0x90, 0x3C, 0x40],
+            &[0xC0, 0x05],
+            &[0xF3, 0x01],
+            &[0xFE],
+            &[0xF0, 0xF7],
+            &[0xF0, 0x01, 0x02, 0xF7],
+            &[0xF0, 0x01, 0x02, 0x03, 0x04, 0xF7],
+            &[0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0xF7],
+        ];
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        for message in messages {
+            assert_eq!(
+                decode_all(&mut decoder, &packets(0x7, message)),
+                [Ok((0x7, message.to_vec()))]
+            );
+        }
+    }
+
+    #[test]
+    fn real_time_within_sysex() {
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    [0x04, 0xF0, 0x01, 0x02],
+                    [0x0F, 0xF8, 0x00, 0x00],
+                    [0x05, 0xF7, 0x00, 0x00],
+                ]
+            ),
+            [
+                Ok((0x0, std::vec![0xF8])),
+                Ok((0x0, std::vec![0xF0, 0x01, 0x02, 0xF7]))
+            ]
+        );
+    }
+
+    #[test]
+    fn other_cables_within_sysex() {
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    [0x04, 0xF0, 0x01, 0x02],
+                    [0x19, 0x90, 0x3C, 0x40],
+                    [0x16, 0x03, 0xF7, 0x00],
+                    [0x06, 0x03, 0xF7, 0x00],
+                ]
+            ),
+            [
+                Ok((0x1, std::vec![0x90, 0x3C, 0x40])),
+                Err(InvalidData(ERR_UNEXPECTED_CONTINUATION).into()),
+                Ok((0x0, std::vec![0xF0, 0x01, 0x02, 0x03, 0xF7])),
+            ]
+        );
+    }
+
+    #[test]
+    fn interleaved_sysex_on_two_cables() {
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    [0x04, 0xF0, 0x01, 0x02],
+                    [0x14, 0xF0, 0x11, 0x12],
+                    [0x04, 0x03, 0x04, 0x05],
+                    [0x16, 0x13, 0xF7, 0x00],
+                    [0x06, 0x06, 0xF7, 0x00],
+                ]
+            ),
+            [
+                Ok((0x1, std::vec![0xF0, 0x11, 0x12, 0x13, 0xF7])),
+                Ok((
+                    0x0,
+                    std::vec![0xF0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0xF7]
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn interrupted_sysex() {
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    [0x04, 0xF0, 0x01, 0x02],
+                    [0x09, 0x90, 0x3C, 0x40],
+                    [0x06, 0x03, 0xF7, 0x00],
+                ]
+            ),
+            [
+                Ok((0x0, std::vec![0x90, 0x3C, 0x40])),
+                Err(InvalidData(ERR_UNEXPECTED_CONTINUATION).into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn sysex_overflow() {
+        let mut decoder = UsbMidiDecoder::<4>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[
+                    [0x04, 0xF0, 0x01, 0x02],
+                    [0x04, 0x03, 0x04, 0x05],
+                    [0x04, 0x06, 0x07, 0x08],
+                    [0x05, 0xF7, 0x00, 0x00],
+                    [0x07, 0xF0, 0x01, 0xF7],
+                ]
+            ),
+            [
+                Err(Error::BufferOverflow),
+                Ok((0x0, std::vec![0xF0, 0x01, 0xF7])),
+            ]
+        );
+    }
+
+    #[test]
+    fn reserved_packets_are_ignored() {
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[[0x00, 0x00, 0x00, 0x00], [0x01, 0x90, 0x00, 0x00]]
+            ),
+            []
+        );
+    }
+
+    #[test]
+    fn mismatched_status() {
+        let mut decoder = UsbMidiDecoder::<16>::new();
+        assert_eq!(
+            decode_all(&mut decoder, &[[0x08, 0x90, 0x3C, 0x40]]),
+            [Err(InvalidData(ERR_INVALID_STATUS).into())]
+        );
+    }
+
+    #[test]
+    fn cable_map() {
+        let mut map = CableMap::default();
+        assert_eq!(map.group(u4::new(0x3)), Some(u4::new(0x3)));
+        map.map(u4::new(0x3), Some(u4::new(0x9)));
+        map.map(u4::new(0x9), None);
+        assert_eq!(map.group(u4::new(0x3)), Some(u4::new(0x9)));
+        assert_eq!(map.cable(u4::new(0x9)), Some(u4::new(0x3)));
+        assert_eq!(map.cable(u4::new(0x3)), None);
+        assert_eq!(CableMap::empty().group(u4::new(0x0)), None);
+    }
+}
@@ -104,8 +104,31 @@ mod delta_clockstamp {
         ump_type: (),
         #[property(common_properties::ChannelVoiceStatusProperty<STATUS>)]
         status: (),
-        #[property(utility::DataProperty)]
-        time_data: u16,
+        #[property(utility::DeltaClockstampDataProperty)]
+        time_data: crate::ux::u20,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn time_data() {
+            assert_eq!(
+                DeltaClockstamp::try_from(&[0x004A_BCDE][..])
+                    .unwrap()
+                    .time_data(),
+                crate::ux::u20::new(0xA_BCDE),
+            );
+        }
+
+        #[test]
+        fn set_time_data() {
+            let mut message = DeltaClockstamp::<[u32; 1]>::new();
+            message.set_time_data(crate::ux::u20::new(0xF_FFFF));
+            assert_eq!(message, DeltaClockstamp([0x004F_FFFF]));
+        }
     }
 }
 mod delta_clockstamp_tpq {
@@ -123,14 +146,41 @@ mod delta_clockstamp_tpq {
     }
 }
 
+mod unknown {
+    use crate::detail::common_properties;
+    use crate::utility;
+    /// MIDI 2.0 Utility Message with an unassigned status
+    ///
+    /// Only produced by the lenient parsing path, which uses it
+    /// to carry messages it can't interpret without modifying them.
+    /// The status and data bits are kept as they were found in `raw_data`.
+    #[midi2_proc::generate_message(Via(crate::utility::Utility), FixedSize, MinSizeUmp(1))]
+    struct UnknownUtility {
+        #[property(common_properties::UmpMessageTypeProperty<{utility::UMP_MESSAGE_TYPE}>)]
+        ump_type: (),
+        #[property(common_properties::UnknownDataProperty<1, 0x0FFF_FFFF>)]
+        raw_data: [u32; 1],
+    }
+}
+
 pub(crate) const UMP_MESSAGE_TYPE: u8 = 0x0;
 
 pub use clock::Clock;
 pub use delta_clockstamp::DeltaClockstamp;
 pub use delta_clockstamp_tpq::DeltaClockstampTpq;
+pub use jitter_reduction::{
+    JrClockTracker, JrStamp, JrStamper, CLOCK_INTERVAL, MICROSECONDS_PER_TICK,
+};
 pub use no_op::NoOp;
 pub use packet::Packet;
+#[cfg(feature = "std")]
+pub use timeline::{Timeline, TimelineEvent};
 pub use timestamp::Timestamp;
+pub use timestamped::{Time, Timestamped, TimestampedIterator};
+pub use unknown::UnknownUtility;
+
+#[cfg(feature = "clip")]
+pub(crate) use timeline::delta_clockstamps;
 
 struct DataProperty;
 
@@ -163,6 +213,38 @@ impl<B: crate::buffer::Ump + crate::buffer::BufferMut> crate::detail::property::
     }
 }
 
+struct DeltaClockstampDataProperty;
+
+impl<B: crate::buffer::Ump> crate::detail::property::Property<B> for DeltaClockstampDataProperty {
+    type Type = crate::ux::u20;
+}
+
+impl<'a, B: crate::buffer::Ump> crate::detail::property::ReadProperty<'a, B>
+    for DeltaClockstampDataProperty
+{
+    fn read(buffer: &'a B) -> Self::Type {
+        crate::ux::u20::new(buffer.buffer()[0] & 0x000F_FFFF)
+    }
+    fn validate(_buffer: &B) -> Result<(), crate::error::InvalidData> {
+        Ok(())
+    }
+}
+
+impl<B: crate::buffer::Ump + crate::buffer::BufferMut> crate::detail::property::WriteProperty<B>
+    for DeltaClockstampDataProperty
+{
+    fn write(buffer: &mut B, value: Self::Type) {
+        let word = &mut buffer.buffer_mut()[0];
+        *word = (*word & 0xFFF0_0000) | u32::from(value);
+    }
+    fn validate(_v: &Self::Type) -> Result<(), crate::error::InvalidData> {
+        Ok(())
+    }
+    fn default() -> Self::Type {
+        Default::default()
+    }
+}
+
 #[derive(
     derive_more::From,
     midi2_proc::Data,
@@ -170,6 +252,9 @@ impl<B: crate::buffer::Ump + crate::buffer::BufferMut> crate::detail::property::
     midi2_proc::RebufferFrom,
     midi2_proc::RebufferFromArray,
     midi2_proc::TryRebufferFrom,
+    midi2_proc::Serde,
+    midi2_proc::Text,
+    midi2_proc::Defmt,
     Clone,
     Copy,
     Debug,
@@ -183,6 +268,7 @@ pub enum Utility<B: crate::buffer::Ump> {
     Timestamp(timestamp::Timestamp<B>),
     DeltaClockstamp(delta_clockstamp::DeltaClockstamp<B>),
     DeltaClockstampTpq(delta_clockstamp_tpq::DeltaClockstampTpq<B>),
+    Unknown(unknown::UnknownUtility<B>),
 }
 
 impl<'a> core::convert::TryFrom<&'a [u32]> for Utility<&'a [u32]> {
@@ -206,6 +292,23 @@ impl<'a> core::convert::TryFrom<&'a [u32]> for Utility<&'a [u32]> {
     }
 }
 
+impl<'a> Utility<&'a [u32]> {
+    /// Parses the message at the front of the buffer without rejecting
+    /// messages which don't strictly follow the specification.
+    ///
+    /// Reserved bits which have been set are reported through the returned warnings.
+    /// Packets with unassigned statuses or invalid fields come back unmodified
+    /// as [Utility::Unknown].
+    pub fn try_from_lenient(
+        buffer: &'a [u32],
+    ) -> Result<(Self, crate::error::Warnings<'a>), crate::error::Diagnostic> {
+        crate::detail::diagnose::lenient(
+            buffer,
+            Some(|packet| Ok(unknown::UnknownUtility::try_from(packet)?.into())),
+        )
+    }
+}
+
 fn status<U: crate::buffer::Unit>(buffer: &[U]) -> u8 {
     use crate::detail::BitOps;
     match <U as crate::buffer::UnitPrivate>::UNIT_ID {
@@ -219,7 +322,11 @@ fn status<U: crate::buffer::Unit>(buffer: &[U]) -> u8 {
     }
     .into()
 }
+mod jitter_reduction;
 mod packet;
+#[cfg(feature = "std")]
+mod timeline;
+mod timestamped;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,471 @@
+use crate::{
+    traits::Data,
+    utility::{Clock, Timestamp},
+};
+
+/// The duration of one unit of jitter reduction time data in microseconds.
+///
+/// Jitter reduction time data counts in units of 1/31250 seconds.
+pub const MICROSECONDS_PER_TICK: u64 = 32;
+
+/// The maximum interval between JR Clock messages sent by a [JrStamper] in microseconds.
+///
+/// The specification requires senders which use jitter reduction timestamps
+/// to send JR Clock messages at least every 250 milliseconds.
+pub const CLOCK_INTERVAL: u64 = 250_000;
+
+// a clock which jumps by more than this is assumed to have been reset by the sender
+const RESYNC_THRESHOLD: f64 = 100_000.0;
+
+// weight given to earlier clock messages each time a new one arrives
+const FORGETTING_FACTOR: f64 = 0.98;
+
+fn time_data(microseconds: u64) -> u16 {
+    (microseconds / MICROSECONDS_PER_TICK) as u16
+}
+
+/// Generates jitter reduction messages for an outgoing stream.
+///
+/// Times are given in microseconds of the sender's local monotonic clock.
+/// The [stamp](JrStamper::stamp) method provides the messages which
+/// should be sent before each outgoing message: a JR Clock message whenever one is due
+/// followed by a JR Timestamp message.
+/// When there is no other traffic, [poll](JrStamper::poll) should be called regularly
+/// so that JR Clock messages continue to be sent at the required interval.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::utility::JrStamper;
+///
+/// let mut stamper = JrStamper::new();
+/// let note_on = channel_voice2::NoteOn::<[u32; 4]>::new();
+///
+/// let mut stream = Vec::new();
+/// stream.extend_from_slice(stamper.stamp(320).data());
+/// stream.extend_from_slice(note_on.data());
+///
+/// assert_eq!(
+///     &stream[..],
+///     &[
+///         0x0010_000A,              // JR Clock: 10 ticks
+///         0x0020_000A,              // JR Timestamp: 10 ticks
+///         0x4090_0000, 0x0000_0000, // note on
+///     ],
+/// );
+///
+/// // no clock is due yet
+/// assert_eq!(stamper.poll(100_000), None);
+/// assert!(stamper.poll(250_320).is_some());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JrStamper {
+    last_clock: Option<u64>,
+}
+
+/// The jitter reduction messages to send before an outgoing message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JrStamp {
+    data: [u32; 2],
+    len: usize,
+}
+
+impl JrStamp {
+    /// The ump data of the messages.
+    pub fn data(&self) -> &[u32] {
+        &self.data[..self.len]
+    }
+}
+
+impl JrStamper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A JR Clock message if one is due at the given time.
+    pub fn poll(&mut self, now: u64) -> Option<Clock<[u32; 4]>> {
+        if let Some(last) = self.last_clock {
+            if now.saturating_sub(last) < CLOCK_INTERVAL {
+                return None;
+            }
+        }
+        self.last_clock = Some(now);
+        let mut clock = Clock::<[u32; 4]>::new();
+        clock.set_time_data(time_data(now));
+        Some(clock)
+    }
+
+    /// A JR Timestamp message for an event at the given time.
+    ///
+    /// The event time may be earlier than the time at which the message is sent,
+    /// for example when an input was sampled before being forwarded.
+    pub fn timestamp(&self, time: u64) -> Timestamp<[u32; 4]> {
+        let mut timestamp = Timestamp::<[u32; 4]>::new();
+        timestamp.set_time_data(time_data(time));
+        timestamp
+    }
+
+    /// The messages to send before a message which is sent at the given time.
+    pub fn stamp(&mut self, now: u64) -> JrStamp {
+        let mut stamp = JrStamp {
+            data: [0x0; 2],
+            len: 0,
+        };
+        if let Some(clock) = self.poll(now) {
+            stamp.data[0] = clock.data()[0];
+            stamp.len = 1;
+        }
+        stamp.data[stamp.len] = self.timestamp(now).data()[0];
+        stamp.len += 1;
+        stamp
+    }
+}
+
+/// Synchronises to the jitter reduction clock of a remote sender.
+///
+/// Each received JR Clock message is registered along with the time at which
+/// it arrived on the receiver's local monotonic clock, in microseconds.
+/// The 16 bit time data of the sender, which wraps roughly every two seconds,
+/// is unwrapped onto a continuous time line.
+/// The offset and drift of the sender's clock relative to the local clock
+/// are estimated with a least squares fit which gradually forgets older clock messages,
+/// smoothing out the transport jitter of the clock messages themselves.
+///
+/// Once synchronised, the time data of incoming JR Timestamp messages
+/// can be mapped onto the local time base.
+/// Timestamps are unwrapped relative to the most recent JR Clock message,
+/// so must lie within about one second of it.
+///
+/// A JR Clock message which disagrees with the estimate by more than 100 milliseconds
+/// is taken to mean the sender's clock was reset and the tracker synchronises afresh.
+///
+/// ```rust
+/// use midi2::utility::{Clock, JrClockTracker, Timestamp};
+///
+/// let mut tracker = JrClockTracker::new();
+///
+/// let mut clock = Clock::<[u32; 4]>::new();
+/// clock.set_time_data(1000);
+/// tracker.receive_clock(&clock, 5_000_000);
+///
+/// let mut timestamp = Timestamp::<[u32; 4]>::new();
+/// timestamp.set_time_data(1010);
+/// assert_eq!(tracker.local_time(&timestamp), Some(5_000_320));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JrClockTracker {
+    state: Option<Synchronised>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Synchronised {
+    // the most recent clock message,
+    // in unwrapped sender ticks and local microseconds
+    sender_ticks: u64,
+    local: u64,
+    // weighted sums of earlier clock messages.
+    // x is the sender time relative to the latest clock message
+    // and y is the deviation of the local time from a perfect match of the clocks,
+    // both in microseconds.
+    w: f64,
+    x: f64,
+    y: f64,
+    xx: f64,
+    xy: f64,
+}
+
+impl Synchronised {
+    fn new(sender_ticks: u64, local: u64) -> Self {
+        Synchronised {
+            sender_ticks,
+            local,
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            xx: 0.0,
+            xy: 0.0,
+        }
+    }
+
+    fn drift(&self) -> f64 {
+        let denominator = self.w * self.xx - self.x * self.x;
+        if denominator <= f64::EPSILON * self.w * self.xx {
+            return 0.0;
+        }
+        (self.w * self.xy - self.x * self.y) / denominator
+    }
+
+    fn deviation(&self) -> f64 {
+        (self.y - self.drift() * self.x) / self.w
+    }
+
+    // local time minus sender time, in microseconds
+    fn offset(&self) -> f64 {
+        self.local as f64 - (self.sender_ticks * MICROSECONDS_PER_TICK) as f64 + self.deviation()
+    }
+
+    // the local time of a sender time given relative to the latest clock message
+    fn map(&self, sender_microseconds: f64) -> f64 {
+        self.local as f64 + self.deviation() + sender_microseconds * (1.0 + self.drift())
+    }
+
+    // unwrap the time data to the sender tick nearest to the given estimate
+    fn unwrap(estimate: u64, time_data: u16) -> u64 {
+        let difference = time_data.wrapping_sub(estimate as u16) as i16;
+        estimate.saturating_add_signed(i64::from(difference))
+    }
+
+    fn add(&mut self, sender_ticks: u64, local: u64) {
+        let dx = ((sender_ticks - self.sender_ticks) * MICROSECONDS_PER_TICK) as f64;
+        let dy = (local as f64 - self.local as f64) - dx;
+
+        // recentre the sums on the new clock message
+        let Synchronised {
+            w, x, y, xx, xy, ..
+        } = *self;
+        self.x = x - dx * w;
+        self.y = y - dy * w;
+        self.xx = xx - 2.0 * dx * x + dx * dx * w;
+        self.xy = xy - dy * x - dx * y + dx * dy * w;
+
+        self.w *= FORGETTING_FACTOR;
+        self.x *= FORGETTING_FACTOR;
+        self.y *= FORGETTING_FACTOR;
+        self.xx *= FORGETTING_FACTOR;
+        self.xy *= FORGETTING_FACTOR;
+
+        // the new clock message is at the origin
+        self.w += 1.0;
+
+        self.sender_ticks = sender_ticks;
+        self.local = local;
+    }
+}
+
+impl JrClockTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget the sender's clock.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Whether a JR Clock message has been received since creation or the last reset.
+    pub fn is_synchronised(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Register a JR Clock message which arrived at the given local time.
+    pub fn receive_clock<B: crate::buffer::Ump>(&mut self, clock: &Clock<B>, now: u64) {
+        self.receive_clock_time_data(clock.time_data(), now);
+    }
+
+    /// Register the time data of a JR Clock message which arrived at the given local time.
+    pub fn receive_clock_time_data(&mut self, time_data: u16, now: u64) {
+        let Some(state) = self.state.as_mut() else {
+            self.state = Some(Synchronised::new(u64::from(time_data), now));
+            return;
+        };
+
+        let elapsed = now.saturating_sub(state.local) as f64 / (1.0 + state.drift());
+        let estimate = state.sender_ticks + elapsed as u64 / MICROSECONDS_PER_TICK;
+        let sender_ticks = Synchronised::unwrap(estimate, time_data);
+
+        let relative =
+            (sender_ticks as f64 - state.sender_ticks as f64) * MICROSECONDS_PER_TICK as f64;
+        let error = now as f64 - state.map(relative);
+        if sender_ticks < state.sender_ticks
+            || !(-RESYNC_THRESHOLD..=RESYNC_THRESHOLD).contains(&error)
+        {
+            // the sender's clock went backwards or jumped
+            *state = Synchronised::new(u64::from(time_data), now);
+            return;
+        }
+
+        state.add(sender_ticks, now);
+    }
+
+    /// The local time of a JR Timestamp message.
+    ///
+    /// Returns `None` until a JR Clock message has been received.
+    pub fn local_time<B: crate::buffer::Ump>(&self, timestamp: &Timestamp<B>) -> Option<u64> {
+        self.local_time_of_time_data(timestamp.time_data())
+    }
+
+    /// The local time of the time data of a JR Timestamp message.
+    ///
+    /// Returns `None` until a JR Clock message has been received.
+    pub fn local_time_of_time_data(&self, time_data: u16) -> Option<u64> {
+        let state = self.state.as_ref()?;
+        let sender_ticks = Synchronised::unwrap(state.sender_ticks, time_data);
+        let relative =
+            (sender_ticks as f64 - state.sender_ticks as f64) * MICROSECONDS_PER_TICK as f64;
+        let local = state.map(relative) + 0.5;
+        if local < 0.0 {
+            Some(0)
+        } else {
+            Some(local as u64)
+        }
+    }
+
+    /// The estimated local time minus the unwrapped sender time at the most recent JR Clock message,
+    /// in microseconds.
+    ///
+    /// The sender time is counted from the first JR Clock message received
+    /// since synchronisation, taken as the full value of its time data.
+    pub fn offset(&self) -> Option<f64> {
+        self.state.as_ref().map(Synchronised::offset)
+    }
+
+    /// The estimated rate of the local clock relative to the sender's clock,
+    /// in parts per million.
+    ///
+    /// A positive drift means that the local clock runs faster than the sender's clock.
+    pub fn drift_ppm(&self) -> Option<f64> {
+        self.state.as_ref().map(|s| s.drift() * 1_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn clock(time_data: u16) -> Clock<[u32; 4]> {
+        let mut clock = Clock::<[u32; 4]>::new();
+        clock.set_time_data(time_data);
+        clock
+    }
+
+    fn timestamp(time_data: u16) -> Timestamp<[u32; 4]> {
+        let mut timestamp = Timestamp::<[u32; 4]>::new();
+        timestamp.set_time_data(time_data);
+        timestamp
+    }
+
+    #[test]
+    fn stamp_with_clock() {
+        let mut stamper = JrStamper::new();
+        assert_eq!(
+            stamper.stamp(32 * 0x1234).data(),
+            &[0x0010_1234, 0x0020_1234]
+        );
+    }
+
+    #[test]
+    fn stamp_without_clock() {
+        let mut stamper = JrStamper::new();
+        stamper.stamp(0);
+        assert_eq!(stamper.stamp(32 * 0x1234).data(), &[0x0020_1234]);
+    }
+
+    #[test]
+    fn clock_interval() {
+        let mut stamper = JrStamper::new();
+        assert_eq!(stamper.poll(1_000), Some(clock(31)));
+        assert_eq!(stamper.poll(250_999), None);
+        assert_eq!(stamper.poll(251_000), Some(clock(7843)));
+        assert_eq!(stamper.poll(251_000), None);
+    }
+
+    #[test]
+    fn stamper_time_data_wraps() {
+        let stamper = JrStamper::new();
+        assert_eq!(stamper.timestamp(32 * 0x1_0005), timestamp(0x0005));
+    }
+
+    #[test]
+    fn not_synchronised() {
+        let tracker = JrClockTracker::new();
+        assert!(!tracker.is_synchronised());
+        assert_eq!(tracker.local_time(&timestamp(0)), None);
+        assert_eq!(tracker.drift_ppm(), None);
+    }
+
+    #[test]
+    fn timestamp_before_clock() {
+        let mut tracker = JrClockTracker::new();
+        tracker.receive_clock(&clock(100), 1_000_000);
+        assert_eq!(tracker.local_time(&timestamp(90)), Some(999_680));
+    }
+
+    #[test]
+    fn timestamp_across_wraparound() {
+        let mut tracker = JrClockTracker::new();
+        tracker.receive_clock(&clock(0xFFF0), 1_000_000);
+        assert_eq!(tracker.local_time(&timestamp(0x0010)), Some(1_001_024));
+        assert_eq!(tracker.local_time(&timestamp(0xFFE0)), Some(999_488));
+    }
+
+    #[test]
+    fn clocks_across_wraparound() {
+        let mut tracker = JrClockTracker::new();
+        let mut sender = 0xF000_u64;
+        let mut local = 3_000_000_u64;
+        for _ in 0..40 {
+            tracker.receive_clock(&clock(sender as u16), local);
+            sender += 7812;
+            local += 7812 * 32;
+        }
+        assert_eq!(
+            tracker.local_time(&timestamp((sender - 7812 + 10) as u16)),
+            Some(local - 7812 * 32 + 320)
+        );
+        assert_eq!(tracker.drift_ppm(), Some(0.0));
+        assert_eq!(tracker.offset(), Some(3_000_000.0 - (0xF000 * 32) as f64));
+    }
+
+    #[test]
+    fn long_gap_between_clocks() {
+        let mut tracker = JrClockTracker::new();
+        tracker.receive_clock(&clock(0), 0);
+        // more than one full wrap of the time data
+        let ticks = 0x1_0000 + 0x4000_u64;
+        tracker.receive_clock(&clock(ticks as u16), ticks * 32);
+        assert_eq!(
+            tracker.local_time(&timestamp(ticks as u16)),
+            Some(ticks * 32)
+        );
+        assert_eq!(tracker.drift_ppm(), Some(0.0));
+    }
+
+    #[test]
+    fn estimates_drift() {
+        let mut tracker = JrClockTracker::new();
+        // the local clock runs 100ppm faster than the sender's
+        // and clock messages arrive with some jitter
+        let jitter = [0, 300, 120, 50, 410, 0, 90, 200];
+        for i in 0..400_u64 {
+            let sender = i * 7812;
+            let local = 10_000_000 + sender * 32 + sender * 32 / 10_000;
+            tracker.receive_clock(&clock(sender as u16), local + jitter[i as usize % 8]);
+        }
+        let drift = tracker.drift_ppm().unwrap();
+        assert!((95.0..105.0).contains(&drift), "drift: {drift}");
+
+        let sender = 399 * 7812 + 100;
+        let expected = 10_000_000 + sender * 32 + sender * 32 / 10_000;
+        let local = tracker.local_time(&timestamp(sender as u16)).unwrap();
+        assert!(local.abs_diff(expected) < 300, "{local} != {expected}");
+    }
+
+    #[test]
+    fn resynchronise_after_reset() {
+        let mut tracker = JrClockTracker::new();
+        tracker.receive_clock(&clock(5000), 1_000_000);
+        tracker.receive_clock(&clock(5000 + 7812), 1_250_000);
+        // the sender restarts
+        tracker.receive_clock(&clock(10), 1_500_000);
+        assert_eq!(tracker.local_time(&timestamp(20)), Some(1_500_320));
+        assert_eq!(tracker.drift_ppm(), Some(0.0));
+    }
+
+    #[test]
+    fn reset() {
+        let mut tracker = JrClockTracker::new();
+        tracker.receive_clock(&clock(0), 0);
+        tracker.reset();
+        assert!(!tracker.is_synchronised());
+    }
+}
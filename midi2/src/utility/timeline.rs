@@ -0,0 +1,348 @@
+use crate::{
+    error::InvalidData,
+    message::{UmpMessage, UmpMessageIterator},
+    traits::RebufferInto,
+    utility::{timestamped::delta_chunks, DeltaClockstamp, DeltaClockstampTpq, Utility},
+};
+
+/// A sequence of ump messages positioned at absolute tick times.
+///
+/// Converts between streams where each message is preceded by
+/// [DeltaClockstamp] messages and a simple list of events
+/// for editing and playback.
+///
+/// The absolute ticks of the events are all expressed in the resolution of the timeline.
+/// [DeltaClockstampTpq] messages which change the resolution in the middle of a stream
+/// are honoured when reading, and the following deltas are rescaled to the timeline resolution.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::utility::Timeline;
+///
+/// let stream = [
+///     0x0030_0060,              // ticks per quarter note: 96
+///     0x0040_0000,              // delta: 0
+///     0x4090_3C00, 0xFFFF_0000, // note on
+///     0x0040_0060,              // delta: 96
+///     0x4080_3C00, 0x0000_0000, // note off
+/// ];
+///
+/// let timeline = Timeline::try_from_ump(96, &stream[..]).expect("Valid data");
+/// assert_eq!(timeline.events[0].tick, 0);
+/// assert_eq!(timeline.events[1].tick, 96);
+///
+/// let rewritten: Vec<u32> = timeline
+///     .to_messages()
+///     .iter()
+///     .flat_map(|m| m.data().iter().copied())
+///     .collect();
+/// assert_eq!(&rewritten[..], &stream[..]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timeline {
+    pub ticks_per_quarter_note: u16,
+    /// Events in ascending tick order.
+    pub events: std::vec::Vec<TimelineEvent>,
+}
+
+/// A message at an absolute tick time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimelineEvent {
+    pub tick: u64,
+    pub message: UmpMessage<std::vec::Vec<u32>>,
+}
+
+impl TimelineEvent {
+    pub fn new(tick: u64, message: UmpMessage<std::vec::Vec<u32>>) -> Self {
+        TimelineEvent { tick, message }
+    }
+}
+
+impl Timeline {
+    pub fn new(ticks_per_quarter_note: u16) -> Self {
+        Timeline {
+            ticks_per_quarter_note,
+            events: std::vec::Vec::new(),
+        }
+    }
+
+    /// Position a stream of messages on a timeline with the given resolution.
+    ///
+    /// Deltas which precede any [DeltaClockstampTpq] message
+    /// are assumed to be in the resolution of the timeline.
+    /// Delta clockstamp and no-op messages are consumed.
+    pub fn from_messages<'a, I>(ticks_per_quarter_note: u16, messages: I) -> Self
+    where
+        I: core::iter::IntoIterator<Item = UmpMessage<&'a [u32]>>,
+    {
+        let mut timeline = Timeline::new(ticks_per_quarter_note);
+        let mut clock = Clock::new(ticks_per_quarter_note);
+
+        for message in messages {
+            match message {
+                UmpMessage::Utility(Utility::DeltaClockstampTpq(m)) => {
+                    clock.set_ticks_per_quarter_note(m.time_data());
+                }
+                UmpMessage::Utility(Utility::DeltaClockstamp(m)) => {
+                    clock.advance(u32::from(m.time_data()));
+                }
+                UmpMessage::Utility(Utility::NoOp(_)) => {}
+                message => timeline
+                    .events
+                    .push(TimelineEvent::new(clock.tick(), message.rebuffer_into())),
+            }
+        }
+
+        timeline
+    }
+
+    /// Position a buffer of ump data on a timeline with the given resolution.
+    ///
+    /// See [Timeline::from_messages].
+    pub fn try_from_ump(ticks_per_quarter_note: u16, data: &[u32]) -> Result<Self, InvalidData> {
+        let messages = UmpMessageIterator::new(data).collect::<Result<std::vec::Vec<_>, _>>()?;
+        Ok(Self::from_messages(ticks_per_quarter_note, messages))
+    }
+
+    /// Insert a message at the given tick, after any events already at that tick.
+    pub fn insert(&mut self, tick: u64, message: UmpMessage<std::vec::Vec<u32>>) {
+        let index = self.events.partition_point(|event| event.tick <= tick);
+        self.events.insert(index, TimelineEvent::new(tick, message));
+    }
+
+    /// Convert the timeline back into a stream of messages.
+    ///
+    /// The stream begins with a [DeltaClockstampTpq] message and each event
+    /// is preceded by [DeltaClockstamp] messages. Deltas which do not fit into
+    /// the 20 bit field of a single message are split over several messages.
+    pub fn to_messages(&self) -> std::vec::Vec<UmpMessage<std::vec::Vec<u32>>> {
+        let mut messages = std::vec::Vec::new();
+
+        let mut tpq = DeltaClockstampTpq::<std::vec::Vec<u32>>::new();
+        tpq.set_time_data(self.ticks_per_quarter_note);
+        messages.push(Utility::from(tpq).into());
+
+        let mut previous = 0_u64;
+        for event in self.events.iter() {
+            let delta = event.tick.saturating_sub(previous);
+            for stamp in delta_clockstamps(delta) {
+                messages.push(Utility::from(stamp).into());
+            }
+            messages.push(event.message.clone());
+            previous = previous.max(event.tick);
+        }
+
+        messages
+    }
+}
+
+/// The delta clockstamp messages which represent the given delta.
+///
+/// A zero delta is represented with a single message.
+pub(crate) fn delta_clockstamps(
+    delta: u64,
+) -> impl core::iter::Iterator<Item = DeltaClockstamp<std::vec::Vec<u32>>> {
+    delta_chunks(delta).map(|chunk| {
+        let mut message = DeltaClockstamp::<std::vec::Vec<u32>>::new();
+        message.set_time_data(chunk);
+        message
+    })
+}
+
+// Accumulates deltas which may change resolution,
+// rescaling them to the resolution of the timeline.
+struct Clock {
+    ticks_per_quarter_note: u16,
+    segment_start: u64,
+    segment_ticks: u64,
+    segment_ticks_per_quarter_note: u16,
+}
+
+impl Clock {
+    fn new(ticks_per_quarter_note: u16) -> Self {
+        Clock {
+            ticks_per_quarter_note,
+            segment_start: 0,
+            segment_ticks: 0,
+            segment_ticks_per_quarter_note: ticks_per_quarter_note,
+        }
+    }
+
+    fn set_ticks_per_quarter_note(&mut self, ticks_per_quarter_note: u16) {
+        if ticks_per_quarter_note == 0
+            || ticks_per_quarter_note == self.segment_ticks_per_quarter_note
+        {
+            return;
+        }
+        self.segment_start = self.tick();
+        self.segment_ticks = 0;
+        self.segment_ticks_per_quarter_note = ticks_per_quarter_note;
+    }
+
+    fn advance(&mut self, delta: u32) {
+        self.segment_ticks += u64::from(delta);
+    }
+
+    fn tick(&self) -> u64 {
+        // rescaling the whole segment at once avoids accumulating rounding errors
+        let from = u128::from(self.segment_ticks_per_quarter_note.max(1));
+        let to = u128::from(self.ticks_per_quarter_note);
+        let rescaled = (u128::from(self.segment_ticks) * to + from / 2) / from;
+        self.segment_start + rescaled as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Data;
+    use pretty_assertions::assert_eq;
+
+    fn words(messages: &[UmpMessage<std::vec::Vec<u32>>]) -> std::vec::Vec<u32> {
+        messages
+            .iter()
+            .flat_map(|m| m.data().iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn absolute_ticks() {
+        let timeline = Timeline::try_from_ump(
+            96,
+            &[
+                0x0030_0060,
+                0x0040_0010,
+                0x0010_0000,
+                0x0040_0010,
+                0x0040_0010,
+                0x0020_0000,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            timeline
+                .events
+                .iter()
+                .map(|e| e.tick)
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![0x10, 0x30],
+        );
+    }
+
+    #[test]
+    fn messages_before_any_delta() {
+        let timeline = Timeline::try_from_ump(96, &[0x0010_0000]).unwrap();
+        assert_eq!(timeline.events[0].tick, 0);
+    }
+
+    #[test]
+    fn no_ops_are_consumed() {
+        let timeline = Timeline::try_from_ump(96, &[0x0000_0000, 0x0010_0000]).unwrap();
+        assert_eq!(timeline.events.len(), 1);
+    }
+
+    #[test]
+    fn change_of_resolution() {
+        let timeline = Timeline::try_from_ump(
+            96,
+            &[
+                0x0030_0060,
+                0x0040_0060,
+                0x0010_0000,
+                0x0030_01E0,
+                0x0040_00F0,
+                0x0010_0000,
+                0x0040_0001,
+                0x0010_0000,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            timeline
+                .events
+                .iter()
+                .map(|e| e.tick)
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![96, 144, 144],
+        );
+    }
+
+    #[test]
+    fn rescaling_does_not_drift() {
+        let mut data = std::vec![0x0030_01E0];
+        data.extend(core::iter::repeat_n(0x0040_0001, 480));
+        data.push(0x0010_0000);
+        let timeline = Timeline::try_from_ump(96, &data[..]).unwrap();
+        assert_eq!(timeline.events[0].tick, 96);
+    }
+
+    #[test]
+    fn to_messages() {
+        let mut timeline = Timeline::new(96);
+        let clock: UmpMessage<std::vec::Vec<u32>> = UmpMessage::try_from(&[0x0010_0000][..])
+            .unwrap()
+            .rebuffer_into();
+        timeline.insert(0x10, clock.clone());
+        timeline.insert(0x30, clock.clone());
+        assert_eq!(
+            words(&timeline.to_messages()),
+            std::vec![
+                0x0030_0060,
+                0x0040_0010,
+                0x0010_0000,
+                0x0040_0020,
+                0x0010_0000,
+            ],
+        );
+    }
+
+    #[test]
+    fn split_large_delta() {
+        let mut timeline = Timeline::new(96);
+        let clock: UmpMessage<std::vec::Vec<u32>> = UmpMessage::try_from(&[0x0010_0000][..])
+            .unwrap()
+            .rebuffer_into();
+        timeline.insert(0x20_0001, clock);
+        let messages = timeline.to_messages();
+        assert_eq!(
+            words(&messages),
+            std::vec![
+                0x0030_0060,
+                0x004F_FFFF,
+                0x004F_FFFF,
+                0x0040_0003,
+                0x0010_0000,
+            ],
+        );
+        let round_trip = Timeline::try_from_ump(96, &words(&messages)[..]).unwrap();
+        assert_eq!(round_trip, timeline);
+    }
+
+    #[test]
+    fn insert_after_simultaneous_events() {
+        let mut timeline = Timeline::new(96);
+        let first: UmpMessage<std::vec::Vec<u32>> = UmpMessage::try_from(&[0x0010_0001][..])
+            .unwrap()
+            .rebuffer_into();
+        let second: UmpMessage<std::vec::Vec<u32>> = UmpMessage::try_from(&[0x0010_0002][..])
+            .unwrap()
+            .rebuffer_into();
+        timeline.insert(10, first.clone());
+        timeline.insert(10, second.clone());
+        timeline.insert(5, second.clone());
+        assert_eq!(
+            timeline.events,
+            std::vec![
+                TimelineEvent::new(5, second.clone()),
+                TimelineEvent::new(10, first),
+                TimelineEvent::new(10, second),
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sysex7")]
+    fn invalid_data() {
+        assert!(Timeline::try_from_ump(96, &[0x3026_0001, 0x0]).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    error::BufferOverflow,
+    message::UmpMessage,
+    traits::Data,
+    utility::{DeltaClockstamp, Timestamp, Utility},
+    ux::u20,
+};
+
+/// The time attached to a [Timestamped] message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Time {
+    /// The time data of a preceding JR Timestamp message.
+    Jr(u16),
+    /// The sum of the time data of the preceding Delta Clockstamp messages.
+    Delta(u32),
+}
+
+/// A message paired with the time of the utility message which preceded it.
+///
+/// Use a [TimestampedIterator] to pair up the messages of a ump stream
+/// and [write_ump](Timestamped::write_ump) to write them back out
+/// with their utility prefix.
+///
+/// ```rust
+/// use midi2::prelude::*;
+/// use midi2::utility::{Time, Timestamped, TimestampedIterator};
+///
+/// let stream = [
+///     0x0020_1234,              // JR Timestamp
+///     0x4090_3C00, 0xFFFF_0000, // note on
+///     0x4080_3C00, 0x0000_0000, // note off
+/// ];
+///
+/// let messages: Vec<Timestamped<UmpMessage<&[u32]>>> = TimestampedIterator::new(
+///     UmpMessageIterator::new(&stream[..]).map(|m| m.expect("Valid data")),
+/// )
+/// .collect();
+///
+/// assert_eq!(messages[0].time, Some(Time::Jr(0x1234)));
+/// assert_eq!(messages[0].message.data(), &[0x4090_3C00, 0xFFFF_0000]);
+/// assert_eq!(messages[1].time, None);
+///
+/// let mut buffer = [0x0; 8];
+/// assert_eq!(
+///     messages[0].write_ump(&mut buffer[..]),
+///     Ok(&[0x0020_1234, 0x4090_3C00, 0xFFFF_0000][..]),
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Timestamped<M> {
+    pub time: Option<Time>,
+    pub message: M,
+}
+
+impl<M> Timestamped<M> {
+    pub fn new(time: Option<Time>, message: M) -> Self {
+        Timestamped { time, message }
+    }
+
+    /// The number of words which [write_ump](Timestamped::write_ump) writes.
+    pub fn size_ump<B: crate::buffer::Ump>(&self) -> usize
+    where
+        M: Data<B>,
+    {
+        prefix_size(self.time) + self.message.data().len()
+    }
+
+    /// Write the utility prefix followed by the message into the buffer.
+    ///
+    /// A delta which does not fit into the 20 bit field of a single
+    /// Delta Clockstamp message is split over several messages.
+    /// Returns the written portion of the buffer.
+    pub fn write_ump<'b, B: crate::buffer::Ump>(
+        &self,
+        buffer: &'b mut [u32],
+    ) -> Result<&'b [u32], BufferOverflow>
+    where
+        M: Data<B>,
+    {
+        let size = self.size_ump();
+        if buffer.len() < size {
+            return Err(BufferOverflow);
+        }
+
+        let mut written = 0;
+        match self.time {
+            Some(Time::Jr(time_data)) => {
+                let mut timestamp = Timestamp::<[u32; 4]>::new();
+                timestamp.set_time_data(time_data);
+                buffer[written] = timestamp.data()[0];
+                written += 1;
+            }
+            Some(Time::Delta(delta)) => {
+                for chunk in delta_chunks(u64::from(delta)) {
+                    let mut delta_clockstamp = DeltaClockstamp::<[u32; 4]>::new();
+                    delta_clockstamp.set_time_data(chunk);
+                    buffer[written] = delta_clockstamp.data()[0];
+                    written += 1;
+                }
+            }
+            None => {}
+        }
+
+        let data = self.message.data();
+        buffer[written..written + data.len()].copy_from_slice(data);
+
+        Ok(&buffer[..size])
+    }
+}
+
+fn prefix_size(time: Option<Time>) -> usize {
+    match time {
+        Some(Time::Jr(_)) => 1,
+        Some(Time::Delta(delta)) => delta_chunks(u64::from(delta)).count(),
+        None => 0,
+    }
+}
+
+/// Splits a delta into the time data of as many Delta Clockstamp messages as it needs.
+///
+/// A zero delta is represented with a single message.
+pub(crate) fn delta_chunks(mut delta: u64) -> impl core::iter::Iterator<Item = u20> {
+    let mut first = true;
+    core::iter::from_fn(move || {
+        if delta == 0 && !first {
+            return None;
+        }
+        first = false;
+        let chunk = delta.min(u64::from(u32::from(u20::MAX)));
+        delta -= chunk;
+        Some(u20::new(chunk as u32))
+    })
+}
+
+/// Pairs the messages of a ump stream with their preceding
+/// JR Timestamp or Delta Clockstamp messages.
+///
+/// The timing messages are consumed.
+/// Consecutive Delta Clockstamp messages are summed,
+/// and a JR Timestamp replaces any pending Delta Clockstamps, and vice versa.
+/// All other messages are yielded, including JR Clock messages.
+/// Timing messages at the end of the stream which precede no message are dropped.
+///
+/// See [Timestamped] for an example.
+#[derive(Clone, Debug)]
+pub struct TimestampedIterator<I> {
+    messages: I,
+}
+
+impl<I> TimestampedIterator<I> {
+    pub fn new(messages: I) -> Self {
+        TimestampedIterator { messages }
+    }
+}
+
+impl<B, I> core::iter::Iterator for TimestampedIterator<I>
+where
+    B: crate::buffer::Ump,
+    I: core::iter::Iterator<Item = UmpMessage<B>>,
+{
+    type Item = Timestamped<UmpMessage<B>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut time = None;
+        for message in self.messages.by_ref() {
+            match message {
+                UmpMessage::Utility(Utility::Timestamp(m)) => {
+                    time = Some(Time::Jr(m.time_data()));
+                }
+                UmpMessage::Utility(Utility::DeltaClockstamp(m)) => {
+                    let delta = u32::from(m.time_data());
+                    time = Some(Time::Delta(match time {
+                        Some(Time::Delta(d)) => d.saturating_add(delta),
+                        _ => delta,
+                    }));
+                }
+                message => return Some(Timestamped::new(time, message)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::UmpMessageIterator;
+    use pretty_assertions::assert_eq;
+
+    fn timestamped(data: &[u32]) -> std::vec::Vec<Timestamped<UmpMessage<&[u32]>>> {
+        TimestampedIterator::new(UmpMessageIterator::new(data).map(|m| m.unwrap())).collect()
+    }
+
+    #[test]
+    fn untimed_messages() {
+        let messages = timestamped(&[0x0010_0000, 0x0010_0001]);
+        assert_eq!(
+            messages
+                .iter()
+                .map(|m| (m.time, m.message.data()))
+                .collect::<std::vec::Vec<_>>(),
+            std::vec![(None, &[0x0010_0000][..]), (None, &[0x0010_0001][..])],
+        );
+    }
+
+    #[test]
+    fn jr_timestamp() {
+        let messages = timestamped(&[0x0020_0010, 0x2090_3C40]);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].time, Some(Time::Jr(0x10)));
+        assert_eq!(messages[0].message.data(), &[0x2090_3C40]);
+    }
+
+    #[test]
+    fn delta_clockstamps_are_summed() {
+        let messages = timestamped(&[0x004F_FFFF, 0x0040_0002, 0x2090_3C40]);
+        assert_eq!(messages[0].time, Some(Time::Delta(0x10_0001)));
+    }
+
+    #[test]
+    fn latest_kind_of_time_wins() {
+        let messages = timestamped(&[0x0040_0002, 0x0020_0010, 0x2090_3C40]);
+        assert_eq!(messages[0].time, Some(Time::Jr(0x10)));
+        let messages = timestamped(&[0x0020_0010, 0x0040_0002, 0x2090_3C40]);
+        assert_eq!(messages[0].time, Some(Time::Delta(0x2)));
+    }
+
+    #[test]
+    fn trailing_time_is_dropped() {
+        assert_eq!(timestamped(&[0x2090_3C40, 0x0020_0010]).len(), 1);
+    }
+
+    #[test]
+    fn write_without_time() {
+        let messages = timestamped(&[0x2090_3C40]);
+        let mut buffer = [0x0; 4];
+        assert_eq!(
+            messages[0].write_ump(&mut buffer[..]),
+            Ok(&[0x2090_3C40][..])
+        );
+    }
+
+    #[test]
+    fn write_delta() {
+        let messages = timestamped(&[0x0040_0060, 0x2090_3C40]);
+        let mut buffer = [0x0; 4];
+        assert_eq!(
+            messages[0].write_ump(&mut buffer[..]),
+            Ok(&[0x0040_0060, 0x2090_3C40][..])
+        );
+    }
+
+    #[test]
+    fn write_large_delta() {
+        let message = Timestamped::new(
+            Some(Time::Delta(0x20_0001)),
+            UmpMessage::try_from(&[0x2090_3C40][..]).unwrap(),
+        );
+        let mut buffer = [0x0; 8];
+        assert_eq!(message.size_ump(), 4);
+        assert_eq!(
+            message.write_ump(&mut buffer[..]),
+            Ok(&[0x004F_FFFF, 0x004F_FFFF, 0x0040_0003, 0x2090_3C40][..])
+        );
+    }
+
+    #[test]
+    fn write_zero_delta() {
+        let message = Timestamped::new(
+            Some(Time::Delta(0)),
+            UmpMessage::try_from(&[0x2090_3C40][..]).unwrap(),
+        );
+        let mut buffer = [0x0; 4];
+        assert_eq!(
+            message.write_ump(&mut buffer[..]),
+            Ok(&[0x0040_0000, 0x2090_3C40][..])
+        );
+    }
+
+    #[test]
+    fn write_overflow() {
+        let messages = timestamped(&[0x0020_0010, 0x4090_3C00, 0xFFFF_0000]);
+        let mut buffer = [0x0; 2];
+        assert_eq!(messages[0].write_ump(&mut buffer[..]), Err(BufferOverflow));
+    }
+
+    #[test]
+    fn round_trip() {
+        let stream = [
+            0x0020_0010,
+            0x4090_3C00,
+            0xFFFF_0000,
+            0x0010_0100,
+            0x0040_0005,
+            0x2080_3C00,
+        ];
+        let mut buffer = [0x0; 8];
+        let mut written = std::vec::Vec::new();
+        for message in timestamped(&stream) {
+            written.extend_from_slice(message.write_ump(&mut buffer[..]).unwrap());
+        }
+        assert_eq!(&written[..], &stream[..]);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{buffer::Ump, error::BufferOverflow, traits::Data, Packets};
+
+/// Writes ump messages into a pre-allocated buffer of words without allocating.
+///
+/// The buffer is used as a ring: words are appended at the back with the `write`
+/// methods and taken from the front with [as_slices](UmpWriter::as_slices)
+/// and [consume](UmpWriter::consume).
+///
+/// Messages are written atomically, either the whole message fits
+/// into the remaining capacity or nothing is written.
+///
+/// ```rust
+/// use midi2::prelude::*;
+///
+/// let mut buffer = [0x0; 4];
+/// let mut writer = UmpWriter::new(&mut buffer[..]);
+///
+/// let mut note_on = channel_voice2::NoteOn::<[u32; 4]>::new();
+/// note_on.set_note_number(u7::new(0x3C));
+/// note_on.set_velocity(0xFFFF);
+///
+/// writer.write(&note_on).unwrap();
+/// writer.write(&note_on).unwrap();
+/// assert_eq!(writer.remaining(), 0);
+/// assert_eq!(writer.write(&note_on), Err(error::BufferOverflow));
+///
+/// assert_eq!(writer.as_slices().0, &[0x4090_3C00, 0xFFFF_0000, 0x4090_3C00, 0xFFFF_0000]);
+/// writer.consume(2);
+/// assert_eq!(writer.remaining(), 2);
+/// ```
+#[derive(Debug)]
+pub struct UmpWriter<'a> {
+    buffer: &'a mut [u32],
+    start: usize,
+    len: usize,
+}
+
+/// Tracks the packets of a message which have been written by
+/// [UmpWriter::write_packets] so that the rest can be written by a later call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacketProgress {
+    written: usize,
+}
+
+impl PacketProgress {
+    /// The number of packets of the message written so far.
+    pub fn packets_written(&self) -> usize {
+        self.written
+    }
+}
+
+impl<'a> UmpWriter<'a> {
+    /// Create an empty writer over the buffer.
+    pub fn new(buffer: &'a mut [u32]) -> Self {
+        UmpWriter {
+            buffer,
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// The total number of words which the writer can hold.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The number of words which have been written and not yet consumed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of words which can still be written.
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.len
+    }
+
+    /// Write the whole message or nothing at all.
+    ///
+    /// Fails with [BufferOverflow] when the message doesn't fit
+    /// into the remaining capacity.
+    pub fn write<B: Ump, M: Data<B>>(&mut self, message: &M) -> Result<(), BufferOverflow> {
+        self.write_words(message.data())
+    }
+
+    /// Write as many whole packets of the message as fit
+    /// into the remaining capacity.
+    ///
+    /// This allows long messages like [Sysex7](crate::sysex7::Sysex7)
+    /// and [Sysex8](crate::sysex8::Sysex8) to be split
+    /// at packet boundaries across several writes.
+    /// The progress should start as [Default::default] for each new message
+    /// and be passed back unchanged to continue writing that message.
+    /// Fails with [BufferOverflow] until all the packets have been written.
+    ///
+    /// ```rust
+    /// use midi2::prelude::*;
+    ///
+    /// let mut sysex = sysex7::Sysex7::<Vec<u32>>::new();
+    /// sysex.set_payload((0..14).map(u7::new));
+    ///
+    /// let mut buffer = [0x0; 4];
+    /// let mut writer = UmpWriter::new(&mut buffer[..]);
+    /// let mut progress = PacketProgress::default();
+    ///
+    /// assert_eq!(writer.write_packets(&sysex, &mut progress), Err(error::BufferOverflow));
+    /// assert_eq!(progress.packets_written(), 2);
+    ///
+    /// writer.consume(4);
+    /// assert_eq!(writer.write_packets(&sysex, &mut progress), Ok(()));
+    /// assert_eq!(writer.as_slices(), (&[0x3032_0C0D, 0x0][..], &[][..]));
+    /// ```
+    pub fn write_packets<M: Packets>(
+        &mut self,
+        message: &M,
+        progress: &mut PacketProgress,
+    ) -> Result<(), BufferOverflow> {
+        for packet in message.packets().skip(progress.written) {
+            self.write_words(&packet)?;
+            progress.written += 1;
+        }
+        Ok(())
+    }
+
+    /// The written words which haven't been consumed, in order.
+    ///
+    /// The second slice is non-empty when the written words wrap around
+    /// the end of the buffer.
+    pub fn as_slices(&self) -> (&[u32], &[u32]) {
+        let end = self.start + self.len;
+        if end <= self.capacity() {
+            (&self.buffer[self.start..end], &[])
+        } else {
+            (
+                &self.buffer[self.start..],
+                &self.buffer[..end - self.capacity()],
+            )
+        }
+    }
+
+    /// Release the oldest written words, making room for more messages.
+    ///
+    /// Consuming more words than have been written empties the writer.
+    pub fn consume(&mut self, words: usize) {
+        let words = words.min(self.len);
+        self.len -= words;
+        self.start = if self.len == 0 {
+            0
+        } else {
+            (self.start + words) % self.capacity()
+        };
+    }
+
+    /// Release all the written words.
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
+    fn write_words(&mut self, words: &[u32]) -> Result<(), BufferOverflow> {
+        if words.len() > self.remaining() {
+            return Err(BufferOverflow);
+        }
+        let capacity = self.capacity();
+        let back = (self.start + self.len) % capacity.max(1);
+        let contiguous = words.len().min(capacity - back);
+        self.buffer[back..back + contiguous].copy_from_slice(&words[..contiguous]);
+        self.buffer[..words.len() - contiguous].copy_from_slice(&words[contiguous..]);
+        self.len += words.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn write_wraps_around() {
+        let mut buffer = [0x0; 5];
+        let mut writer = UmpWriter::new(&mut buffer[..]);
+
+        let message = crate::UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        writer.write(&message).unwrap();
+        writer.write(&message).unwrap();
+        writer.consume(3);
+        writer.write(&message).unwrap();
+
+        assert_eq!(writer.len(), 3);
+        assert_eq!(
+            writer.as_slices(),
+            (&[0xFFFF_0000, 0x4090_3C00][..], &[0xFFFF_0000][..])
+        );
+    }
+
+    #[test]
+    fn write_is_atomic() {
+        let mut buffer = [0x0; 3];
+        let mut writer = UmpWriter::new(&mut buffer[..]);
+
+        let message = crate::UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        writer.write(&message).unwrap();
+        assert_eq!(writer.write(&message), Err(BufferOverflow));
+        assert_eq!(writer.len(), 2);
+        assert_eq!(writer.remaining(), 1);
+    }
+
+    #[test]
+    fn consume_everything_resets() {
+        let mut buffer = [0x0; 3];
+        let mut writer = UmpWriter::new(&mut buffer[..]);
+
+        let message = crate::UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        writer.write(&message).unwrap();
+        writer.consume(10);
+        assert!(writer.is_empty());
+        writer.write(&message).unwrap();
+        assert_eq!(
+            writer.as_slices(),
+            (&[0x4090_3C00, 0xFFFF_0000][..], &[][..])
+        );
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let mut writer = UmpWriter::new(&mut []);
+        let message = crate::UmpMessage::try_from(&[0x4090_3C00, 0xFFFF_0000][..]).unwrap();
+        assert_eq!(writer.write(&message), Err(BufferOverflow));
+        assert_eq!(writer.as_slices(), (&[][..], &[][..]));
+    }
+
+    #[test]
+    #[cfg(feature = "sysex8")]
+    fn write_packets_across_writes() {
+        use crate::traits::Sysex;
+
+        let mut sysex = crate::sysex8::Sysex8::<std::vec::Vec<u32>>::new();
+        sysex.set_payload(0..30);
+
+        let mut buffer = [0x0; 6];
+        let mut writer = UmpWriter::new(&mut buffer[..]);
+        let mut progress = PacketProgress::default();
+
+        let mut written = std::vec::Vec::new();
+        while writer.write_packets(&sysex, &mut progress).is_err() {
+            let (front, back) = writer.as_slices();
+            written.extend_from_slice(front);
+            written.extend_from_slice(back);
+            writer.consume(writer.len());
+        }
+        let (front, back) = writer.as_slices();
+        written.extend_from_slice(front);
+        written.extend_from_slice(back);
+
+        assert_eq!(progress.packets_written(), 3);
+        assert_eq!(written, crate::traits::Data::data(&sysex));
+    }
+}
@@ -467,3 +467,209 @@ pub fn debug(item: TokenStream1) -> TokenStream1 {
     }
     .into()
 }
+
+pub fn serde(item: TokenStream1) -> TokenStream1 {
+    let input = parse_macro_input!(item as ItemEnum);
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let expecting = format!("a {ident} message");
+    let buffer_id = common::buffer_generic(&input.generics)
+        .expect("Expected buffer generic")
+        .ident();
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+    let params = &input.generics.params;
+
+    let mut variant_names = Vec::new();
+    let mut variant_types = Vec::new();
+    let mut serialize_arms = TokenStream::new();
+    let mut deserialize_arms = TokenStream::new();
+    for (index, variant) in input.variants.iter().enumerate() {
+        let variant_ident = &variant.ident;
+        let variant_name = variant_ident.to_string();
+        let syn::Fields::Unnamed(fields) = &variant.fields else {
+            panic!("Expected enum variant with unnamed fields");
+        };
+        let ty = &fields.unnamed.last().expect("Expected unnamed field").ty;
+        let variant_index = index as u32;
+        serialize_arms.extend(quote! {
+            #ident::#variant_ident(m) => serializer.serialize_newtype_variant(#name, #variant_index, #variant_name, m),
+        });
+        deserialize_arms.extend(quote! {
+            Some(#index) => ::serde::de::VariantAccess::newtype_variant::<#ty>(variant).map(#ident::#variant_ident),
+        });
+        variant_names.push(variant_name);
+        variant_types.push(ty.clone());
+    }
+    let variants = quote! { &[#(#variant_names),*] };
+    let unit_type = quote! { <#buffer_id as crate::buffer::Buffer>::Unit };
+    let deserialize_bounds = quote! {
+        #buffer_id: crate::buffer::BufferMut
+            + crate::buffer::BufferDefault
+            + crate::buffer::BufferTryResize,
+        #(#variant_types: ::serde::Deserialize<'de>,)*
+    };
+
+    quote! {
+        #[cfg(feature = "serde")]
+        impl #impl_generics ::serde::Serialize for #ident #ty_generics
+        where
+            #(#variant_types: ::serde::Serialize,)*
+        {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+                match self {
+                    #serialize_arms
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl #impl_generics crate::serde_support::SerializeRaw for #ident #ty_generics {
+            fn serialize_raw<S: ::serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+                crate::detail::serde::serialize_units(crate::traits::Data::data(self), serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, #params> ::serde::de::Visitor<'de> for crate::detail::serde::MessageVisitor<#ident #ty_generics>
+        where
+            #deserialize_bounds
+        {
+            type Value = #ident #ty_generics;
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+            fn visit_enum<A: ::serde::de::EnumAccess<'de>>(self, data: A) -> core::result::Result<Self::Value, A::Error> {
+                let (index, variant) = data.variant_seed(crate::detail::serde::FieldIndex(#variants))?;
+                match index {
+                    #deserialize_arms
+                    _ => Err(crate::detail::serde::unknown_variant()),
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, #params> ::serde::Deserialize<'de> for #ident #ty_generics
+        where
+            #deserialize_bounds
+        {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+                deserializer.deserialize_enum(
+                    #name,
+                    #variants,
+                    crate::detail::serde::MessageVisitor::<#ident #ty_generics>::new(),
+                )
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, #params> crate::serde_support::DeserializeRaw<'de> for #ident #ty_generics
+        where
+            #buffer_id: crate::buffer::BufferMut
+                + crate::buffer::BufferDefault
+                + crate::buffer::BufferTryResize
+                + for<'a> crate::buffer::TryFromBuffer<&'a [#unit_type]>,
+        {
+            fn deserialize_raw<D: ::serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+                let (buffer, size) = crate::detail::serde::deserialize_units::<#buffer_id, D>(deserializer)?;
+                let message = #ident::<&[#unit_type]>::try_from(&buffer.buffer()[..size])
+                    .map_err(crate::detail::serde::invalid_data)?;
+                crate::traits::TryRebufferFrom::try_rebuffer_from(message)
+                    .map_err(crate::detail::serde::buffer_overflow)
+            }
+        }
+    }
+    .into()
+}
+
+pub fn text(item: TokenStream1) -> TokenStream1 {
+    let input = parse_macro_input!(item as ItemEnum);
+    let ident = &input.ident;
+    let buffer_id = common::buffer_generic(&input.generics)
+        .expect("Expected buffer generic")
+        .ident();
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+
+    let mut variant_types = Vec::new();
+    let mut display_arms = TokenStream::new();
+    let mut parse_variants = TokenStream::new();
+    for variant in input.variants.iter() {
+        let variant_ident = &variant.ident;
+        let syn::Fields::Unnamed(fields) = &variant.fields else {
+            panic!("Expected enum variant with unnamed fields");
+        };
+        let ty = &fields.unnamed.last().expect("Expected unnamed field").ty;
+        display_arms.extend(quote! {
+            #ident::#variant_ident(m) => core::fmt::Display::fmt(m, f),
+        });
+        parse_variants.extend(quote! {
+            if let Some(m) = crate::detail::text::parse_variant::<#ty>(s, &mut error) {
+                return Ok(#ident::#variant_ident(m));
+            }
+        });
+        variant_types.push(ty.clone());
+    }
+
+    quote! {
+        impl #impl_generics core::fmt::Display for #ident #ty_generics
+        where
+            #(#variant_types: core::fmt::Display,)*
+        {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    #display_arms
+                }
+            }
+        }
+
+        impl #impl_generics core::str::FromStr for #ident #ty_generics
+        where
+            #buffer_id: crate::buffer::BufferMut
+                + crate::buffer::BufferDefault
+                + crate::buffer::BufferTryResize,
+            #(#variant_types: core::str::FromStr<Err = crate::error::InvalidData>,)*
+        {
+            type Err = crate::error::InvalidData;
+            fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+                let mut error = crate::error::InvalidData(crate::detail::text::ERR_UNKNOWN_MESSAGE);
+                #parse_variants
+                Err(error)
+            }
+        }
+    }
+    .into()
+}
+
+pub fn defmt(item: TokenStream1) -> TokenStream1 {
+    let input = parse_macro_input!(item as ItemEnum);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+
+    let mut variant_types = Vec::new();
+    let mut format_arms = TokenStream::new();
+    for variant in input.variants.iter() {
+        let variant_ident = &variant.ident;
+        let syn::Fields::Unnamed(fields) = &variant.fields else {
+            panic!("Expected enum variant with unnamed fields");
+        };
+        let ty = &fields.unnamed.last().expect("Expected unnamed field").ty;
+        format_arms.extend(quote! {
+            #ident::#variant_ident(m) => ::defmt::Format::format(m, fmt),
+        });
+        variant_types.push(ty.clone());
+    }
+
+    quote! {
+        #[cfg(feature = "defmt")]
+        impl #impl_generics ::defmt::Format for #ident #ty_generics
+        where
+            #(#variant_types: ::defmt::Format,)*
+        {
+            fn format(&self, fmt: ::defmt::Formatter) {
+                match self {
+                    #format_arms
+                }
+            }
+        }
+    }
+    .into()
+}
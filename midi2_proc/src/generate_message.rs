@@ -96,6 +96,9 @@ impl Property {
     pub fn is_sysex_payload(&self) -> bool {
         self.ident == "sysex_payload"
     }
+    pub fn is_str(&self) -> bool {
+        matches!(self.ty, syn::Type::Reference(_))
+    }
 }
 
 pub fn properties(input: &syn::ItemStruct) -> Vec<Property> {
@@ -340,10 +343,11 @@ fn packets_impl(root_ident: &syn::Ident) -> TokenStream {
     quote! {
         impl<B: crate::buffer::Ump> crate::Packets for #root_ident<B> {
             fn packets(&self) -> crate::PacketsIterator {
-                crate::PacketsIterator(self
-                    .data()
-                    .chunks_exact(<Self as crate::traits::MinSize<B>>::MIN_SIZE)
-                )
+                // the packet size follows from the message type.
+                // messages which don't fill their packet are a single short packet
+                let data = self.data();
+                let packet_size = crate::packet::size((data[0] >> 28) as u8).min(data.len());
+                crate::PacketsIterator(data.chunks_exact(packet_size))
             }
         }
     }
@@ -796,6 +800,427 @@ fn bytes_message_via(root_ident: &syn::Ident, via_type: &syn::Type) -> TokenStre
     }
 }
 
+fn unit_type(args: &GenerateMessageArgs) -> TokenStream {
+    match args.representation() {
+        Representation::Ump => quote! { u32 },
+        Representation::Bytes => quote! { u8 },
+        Representation::UmpOrBytes => quote! { <B as crate::buffer::Buffer>::Unit },
+    }
+}
+
+fn std_only_cfg(is_std_only: bool) -> TokenStream {
+    if is_std_only {
+        quote! { #[cfg(feature = "std")] }
+    } else {
+        TokenStream::new()
+    }
+}
+
+fn serialize_impl(
+    root_ident: &syn::Ident,
+    args: &GenerateMessageArgs,
+    properties: &[Property],
+) -> TokenStream {
+    let constraint = generic_buffer_constraint(args);
+    let mut len = quote! { 0 };
+    let mut serialize_fields = TokenStream::new();
+    for property in properties
+        .iter()
+        .filter(|p| !p.constant && !p.writeonly && (!p.readonly || p.std))
+    {
+        let meta_type = &property.meta_type;
+        let key = property.ident.to_string();
+        let std_only_attribute = std_only_cfg(property.std);
+        let condition = match (property.std, property.is_group(), args.representation()) {
+            (true, _, _) => quote! { cfg!(feature = "std") },
+            // bytes messages carry no group
+            (_, true, Representation::UmpOrBytes) => quote! {
+                <B::Unit as UnitPrivateGenMessage>::UNIT_ID == crate::buffer::UNIT_ID_U32
+            },
+            _ => quote! { true },
+        };
+        len.extend(quote! { + usize::from(#condition) });
+        serialize_fields.extend(quote! {
+            #std_only_attribute
+            if #condition {
+                crate::detail::serde::serialize_field::<#meta_type, B, _>(&mut state, #key, &self.0)?;
+            }
+        });
+    }
+    if properties.iter().any(|p| p.is_sysex_payload()) {
+        len.extend(quote! { + 1 });
+        serialize_fields.extend(quote! {
+            ::serde::ser::SerializeStruct::serialize_field(
+                &mut state,
+                "payload",
+                &crate::detail::serde::Payload::<Self, B>(self, core::marker::PhantomData),
+            )?;
+        });
+    }
+    let name = root_ident.to_string();
+    quote! {
+        #[cfg(feature = "serde")]
+        impl<B: #constraint> ::serde::Serialize for #root_ident<B> {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+                let mut state = serializer.serialize_struct(#name, #len)?;
+                #serialize_fields
+                ::serde::ser::SerializeStruct::end(state)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<B: #constraint> crate::serde_support::SerializeRaw for #root_ident<B> {
+            fn serialize_raw<S: ::serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+                crate::detail::serde::serialize_units(self.data(), serializer)
+            }
+        }
+    }
+}
+
+fn deserialize_impl(
+    root_ident: &syn::Ident,
+    args: &GenerateMessageArgs,
+    properties: &[Property],
+) -> TokenStream {
+    let constraint = generic_buffer_constraint(args);
+    let unit_type = unit_type(args);
+    let mut field_names = Vec::new();
+    let mut match_arms = TokenStream::new();
+    for property in properties.iter().filter(|p| !p.constant && !p.readonly) {
+        let index = field_names.len();
+        let meta_type = &property.meta_type;
+        field_names.push(property.ident.to_string());
+        if property.resize {
+            // resize properties are written with the fallible setter
+            // text properties are read into an owned string
+            let setter = syn::Ident::new(
+                format!("try_set_{}", &property.ident).as_str(),
+                proc_macro2::Span::call_site(),
+            );
+            if property.is_str() {
+                match_arms.extend(quote! {
+                    #[cfg(feature = "std")]
+                    Some(#index) => {
+                        let value = map.next_value::<std::string::String>()?;
+                        message.#setter(&value).map_err(crate::detail::serde::buffer_overflow)?;
+                    }
+                });
+            } else {
+                let ty = &property.ty;
+                match_arms.extend(quote! {
+                    Some(#index) => {
+                        let repr = map.next_value::<<#ty as crate::detail::serde::Field>::Repr>()?;
+                        let value = <#ty as crate::detail::serde::Field>::from_repr(repr)
+                            .map_err(crate::detail::serde::invalid_data)?;
+                        <#meta_type as crate::detail::property::WriteProperty<B>>::validate(&value)
+                            .map_err(crate::detail::serde::invalid_data)?;
+                        message.#setter(value).map_err(crate::detail::serde::buffer_overflow)?;
+                    }
+                });
+            }
+        } else {
+            let std_only_attribute = std_only_cfg(property.std);
+            match_arms.extend(quote! {
+                #std_only_attribute
+                Some(#index) => crate::detail::serde::deserialize_field::<#meta_type, B, A>(
+                    &mut map,
+                    &mut message.0,
+                )?,
+            });
+        }
+    }
+    if properties.iter().any(|p| p.is_sysex_payload()) {
+        let index = field_names.len();
+        field_names.push("payload".to_string());
+        match_arms.extend(quote! {
+            Some(#index) => map.next_value_seed(
+                crate::detail::serde::PayloadSeed::<_, B>(&mut message, core::marker::PhantomData),
+            )?,
+        });
+    }
+    let name = root_ident.to_string();
+    let expecting = format!("a {root_ident} message");
+    let fields = quote! { &[#(#field_names),*] };
+    let bounds = quote! {
+        #constraint
+            + crate::buffer::BufferMut
+            + crate::buffer::BufferDefault
+            + crate::buffer::BufferTryResize
+    };
+    quote! {
+        #[cfg(feature = "serde")]
+        impl<'de, B: #bounds> ::serde::de::Visitor<'de> for crate::detail::serde::MessageVisitor<#root_ident<B>> {
+            type Value = #root_ident<B>;
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+            fn visit_map<A: ::serde::de::MapAccess<'de>>(self, mut map: A) -> core::result::Result<Self::Value, A::Error> {
+                let mut message = #root_ident::<B>::try_new()
+                    .map_err(crate::detail::serde::buffer_overflow)?;
+                while let Some(index) = map.next_key_seed(crate::detail::serde::FieldIndex(#fields))? {
+                    match index {
+                        #match_arms
+                        _ => {
+                            map.next_value::<::serde::de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                #root_ident::<&[#unit_type]>::try_from(message.data())
+                    .map_err(crate::detail::serde::invalid_data)?;
+                Ok(message)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, B: #bounds> ::serde::Deserialize<'de> for #root_ident<B> {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+                deserializer.deserialize_struct(
+                    #name,
+                    #fields,
+                    crate::detail::serde::MessageVisitor::<#root_ident<B>>::new(),
+                )
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, B: #bounds> crate::serde_support::DeserializeRaw<'de> for #root_ident<B> {
+            fn deserialize_raw<D: ::serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+                let (buffer, size) = crate::detail::serde::deserialize_units::<B, D>(deserializer)?;
+                #root_ident::<&[#unit_type]>::try_from(&buffer.buffer()[..size])
+                    .map_err(crate::detail::serde::invalid_data)?;
+                Ok(#root_ident(buffer))
+            }
+        }
+    }
+}
+
+// the key of a property in the text form
+// common fields are abbreviated, their full names are still accepted when parsing
+fn text_key(property: &Property) -> String {
+    let name = property.ident.to_string();
+    match name.as_str() {
+        "note_number" => "note".to_string(),
+        "velocity" => "vel".to_string(),
+        "attribute" => "attr".to_string(),
+        _ => name,
+    }
+}
+
+fn display_impl(
+    root_ident: &syn::Ident,
+    args: &GenerateMessageArgs,
+    properties: &[Property],
+) -> TokenStream {
+    let constraint = generic_buffer_constraint(args);
+    let name = root_ident.to_string();
+    let displayed = || {
+        properties
+            .iter()
+            .filter(|p| !p.constant && !p.writeonly && (!p.readonly || p.std))
+    };
+    let mut write_fields = TokenStream::new();
+    for property in displayed()
+        .filter(|p| p.is_group())
+        .chain(displayed().filter(|p| p.is_channel()))
+        .chain(displayed().filter(|p| !p.is_group() && !p.is_channel()))
+    {
+        let meta_type = &property.meta_type;
+        let std_only_attribute = std_only_cfg(property.std);
+        let prefix = if property.is_group() {
+            " g".to_string()
+        } else if property.is_channel() {
+            " ch".to_string()
+        } else {
+            format!(" {}=", text_key(property))
+        };
+        let condition = match (property.is_group(), args.representation()) {
+            // bytes messages carry no group
+            (true, Representation::UmpOrBytes) => quote! {
+                <B::Unit as UnitPrivateGenMessage>::UNIT_ID == crate::buffer::UNIT_ID_U32
+            },
+            _ => quote! { true },
+        };
+        write_fields.extend(quote! {
+            #std_only_attribute
+            if #condition {
+                f.write_str(#prefix)?;
+                crate::detail::text::write_property::<#meta_type, B>(&self.0, f)?;
+            }
+        });
+    }
+    if properties.iter().any(|p| p.is_sysex_payload()) {
+        write_fields.extend(quote! {
+            f.write_str(" ")?;
+            crate::detail::text::write_payload(crate::traits::Sysex::payload(self), f)?;
+        });
+    }
+    quote! {
+        impl<B: #constraint> core::fmt::Display for #root_ident<B> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str(#name)?;
+                #write_fields
+                Ok(())
+            }
+        }
+    }
+}
+
+fn from_str_impl(
+    root_ident: &syn::Ident,
+    args: &GenerateMessageArgs,
+    properties: &[Property],
+) -> TokenStream {
+    let constraint = generic_buffer_constraint(args);
+    let unit_type = unit_type(args);
+    let name = root_ident.to_string();
+    let mut match_arms = TokenStream::new();
+    for property in properties.iter().filter(|p| !p.constant && !p.readonly) {
+        let meta_type = &property.meta_type;
+        let std_only_attribute = std_only_cfg(property.std);
+        let pattern = if property.is_group() {
+            quote! { crate::detail::text::Token::Group(value) }
+        } else if property.is_channel() {
+            quote! { crate::detail::text::Token::Channel(value) }
+        } else {
+            let key = text_key(property);
+            let full_name = property.ident.to_string();
+            if key == full_name {
+                quote! { crate::detail::text::Token::Field(#key, value) }
+            } else {
+                quote! { crate::detail::text::Token::Field(#key | #full_name, value) }
+            }
+        };
+        if property.resize {
+            // resize properties are written with the fallible setter
+            // text properties are read into an owned string
+            let setter = syn::Ident::new(
+                format!("try_set_{}", &property.ident).as_str(),
+                proc_macro2::Span::call_site(),
+            );
+            if property.is_str() {
+                match_arms.extend(quote! {
+                    #[cfg(feature = "std")]
+                    #pattern => {
+                        let value = <std::string::String as crate::detail::text::Text>::parse_text(value)?;
+                        message.#setter(&value).map_err(crate::detail::text::buffer_overflow)?;
+                    }
+                });
+            } else {
+                let ty = &property.ty;
+                match_arms.extend(quote! {
+                    #pattern => {
+                        let value = <#ty as crate::detail::text::Text>::parse_text(value)?;
+                        <#meta_type as crate::detail::property::WriteProperty<B>>::validate(&value)?;
+                        message.#setter(value).map_err(crate::detail::text::buffer_overflow)?;
+                    }
+                });
+            }
+        } else {
+            match_arms.extend(quote! {
+                #std_only_attribute
+                #pattern => crate::detail::text::parse_property::<#meta_type, B>(value, &mut message.0)?,
+            });
+        }
+    }
+    if properties.iter().any(|p| p.is_sysex_payload()) {
+        match_arms.extend(quote! {
+            crate::detail::text::Token::Payload(value) => {
+                crate::traits::Sysex::try_set_payload(&mut message, core::iter::empty())
+                    .map_err(crate::detail::text::buffer_overflow)?;
+                for byte in crate::detail::text::parse_payload(value) {
+                    crate::traits::Sysex::try_append_byte(&mut message, byte?)
+                        .map_err(crate::detail::text::buffer_overflow)?;
+                }
+            }
+        });
+    }
+    quote! {
+        impl<B: #constraint
+            + crate::buffer::BufferMut
+            + crate::buffer::BufferDefault
+            + crate::buffer::BufferTryResize
+        > core::str::FromStr for #root_ident<B> {
+            type Err = crate::error::InvalidData;
+            fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+                // the name is checked before a message is built
+                let tokens = crate::detail::text::tokens(s, #name)?;
+                let mut message = #root_ident::<B>::try_new()
+                    .map_err(crate::detail::text::buffer_overflow)?;
+                for token in tokens {
+                    match token? {
+                        #match_arms
+                        _ => return crate::detail::text::unknown_field(),
+                    }
+                }
+                #root_ident::<&[#unit_type]>::try_from(message.data())?;
+                Ok(message)
+            }
+        }
+    }
+}
+
+fn format_impl(
+    root_ident: &syn::Ident,
+    args: &GenerateMessageArgs,
+    properties: &[Property],
+) -> TokenStream {
+    let constraint = generic_buffer_constraint(args);
+    let name = root_ident.to_string();
+    // text properties are only readable with std
+    // so they are left out of the logged form
+    let logged = || {
+        properties
+            .iter()
+            .filter(|p| !p.constant && !p.writeonly && !p.readonly)
+    };
+    let mut write_fields = TokenStream::new();
+    for property in logged()
+        .filter(|p| p.is_group())
+        .chain(logged().filter(|p| p.is_channel()))
+        .chain(logged().filter(|p| !p.is_group() && !p.is_channel()))
+    {
+        let meta_type = &property.meta_type;
+        let format_string = if property.is_group() {
+            " g{}".to_string()
+        } else if property.is_channel() {
+            " ch{}".to_string()
+        } else {
+            format!(" {}={{}}", text_key(property))
+        };
+        let condition = match (property.is_group(), args.representation()) {
+            // bytes messages carry no group
+            (true, Representation::UmpOrBytes) => quote! {
+                <B::Unit as UnitPrivateGenMessage>::UNIT_ID == crate::buffer::UNIT_ID_U32
+            },
+            _ => quote! { true },
+        };
+        write_fields.extend(quote! {
+            if #condition {
+                ::defmt::write!(
+                    fmt,
+                    #format_string,
+                    crate::detail::defmt::field::<#meta_type, B>(&self.0),
+                );
+            }
+        });
+    }
+    if properties.iter().any(|p| p.is_sysex_payload()) {
+        write_fields.extend(quote! {
+            ::defmt::write!(fmt, " ");
+            crate::detail::defmt::write_payload(crate::traits::Sysex::payload(self), fmt);
+        });
+    }
+    quote! {
+        #[cfg(feature = "defmt")]
+        impl<B: #constraint> ::defmt::Format for #root_ident<B> {
+            fn format(&self, fmt: ::defmt::Formatter) {
+                ::defmt::write!(fmt, #name);
+                #write_fields
+            }
+        }
+    }
+}
+
 pub fn generate_message(attrs: TokenStream1, item: TokenStream1) -> TokenStream1 {
     let input = syn::parse_macro_input!(item as syn::ItemStruct);
     let args = syn::parse_macro_input!(attrs as GenerateMessageArgs);
@@ -817,6 +1242,11 @@ pub fn generate_message(attrs: TokenStream1, item: TokenStream1) -> TokenStream1
     let try_new_with_buffer_impl = try_new_with_buffer_impl(root_ident, &args, &properties);
     let new_array_impl = new_array_impl(root_ident, &args, &properties);
     let try_new_impl = try_new_impl(root_ident, &args, &properties);
+    let serialize_impl = serialize_impl(root_ident, &args, &properties);
+    let deserialize_impl = deserialize_impl(root_ident, &args, &properties);
+    let display_impl = display_impl(root_ident, &args, &properties);
+    let from_str_impl = from_str_impl(root_ident, &args, &properties);
+    let format_impl = format_impl(root_ident, &args, &properties);
 
     let mut tokens = TokenStream::new();
 
@@ -836,6 +1266,11 @@ pub fn generate_message(attrs: TokenStream1, item: TokenStream1) -> TokenStream1
         #try_new_with_buffer_impl
         #new_array_impl
         #try_new_impl
+        #serialize_impl
+        #deserialize_impl
+        #display_impl
+        #from_str_impl
+        #format_impl
     });
 
     if args.fixed_size {
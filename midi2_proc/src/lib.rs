@@ -74,3 +74,18 @@ pub fn derive_rebuffer_from_array(item: TokenStream1) -> TokenStream1 {
 pub fn derive_try_rebuffer_from(item: TokenStream1) -> TokenStream1 {
     derives::try_rebuffer_from(item)
 }
+
+#[proc_macro_derive(Serde)]
+pub fn derive_serde(item: TokenStream1) -> TokenStream1 {
+    derives::serde(item)
+}
+
+#[proc_macro_derive(Text)]
+pub fn derive_text(item: TokenStream1) -> TokenStream1 {
+    derives::text(item)
+}
+
+#[proc_macro_derive(Defmt)]
+pub fn derive_defmt(item: TokenStream1) -> TokenStream1 {
+    derives::defmt(item)
+}